@@ -0,0 +1,73 @@
+//! `apropos`: keyword search over commands, their arguments and settings.
+
+use crate::args::dbg::DbgArgs;
+use crate::settings::Settings;
+use clap::{Command, CommandFactory as _};
+
+/// Returns `path -- summary` for every command whose name, description or arguments
+/// mention `keyword`, and for every such setting (case-insensitively).
+pub(crate) fn search(keyword: &str, settings: &Settings) -> Vec<String> {
+    let keyword = keyword.to_lowercase();
+    let mut matches = Vec::new();
+    for command in DbgArgs::command().get_subcommands() {
+        collect(command, "", &keyword, &mut matches);
+    }
+    matches.extend(
+        settings
+            .iter()
+            .filter(|setting| {
+                setting.name.to_lowercase().contains(&keyword)
+                    || setting.doc.to_lowercase().contains(&keyword)
+            })
+            .map(|setting| format!("set {} -- {}", setting.name, setting.doc)),
+    );
+    matches
+}
+
+fn collect(command: &Command, parent: &str, keyword: &str, matches: &mut Vec<String>) {
+    let path = if parent.is_empty() {
+        command.get_name().to_string()
+    } else {
+        format!("{parent} {}", command.get_name())
+    };
+    if command.get_name() == "help" {
+        return; // clap's generated `help` subcommands.
+    }
+    let about = command
+        .get_about()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    let mentions = |text: &str| text.to_lowercase().contains(keyword);
+    let args_mention = command.get_arguments().any(|arg| {
+        mentions(arg.get_id().as_str())
+            || arg.get_long().is_some_and(mentions)
+            || arg
+                .get_help()
+                .is_some_and(|help| mentions(&help.to_string()))
+    });
+    if mentions(&path) || mentions(&about) || args_mention {
+        matches.push(format!("{path} -- {about}"));
+    }
+    for subcommand in command.get_subcommands() {
+        collect(subcommand, &path, keyword, matches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_commands_and_settings() {
+        let settings = Settings::default();
+        let search = |keyword| search(keyword, &settings);
+        assert!(search("PAGINATION")
+            .iter()
+            .any(|line| line.starts_with("set pagination -- ")));
+        // Matched through an argument description.
+        assert!(search("regex")
+            .iter()
+            .any(|line| line.starts_with("catch syscall -- ")));
+        assert!(search("no such thing").is_empty());
+    }
+}
@@ -12,7 +12,7 @@ use crate::logger::LogLevel;
 #[clap(group(
     ArgGroup::new("input")
         .required(true)
-        .args(&["pid", "program_path"]),
+        .args(&["pid", "program_path", "replay"]),
 ))]
 #[cfg_attr(feature = "color", clap(styles=get_styles()))]
 #[clap(arg_required_else_help = true, args_conflicts_with_subcommands = true)]
@@ -24,6 +24,42 @@ pub(crate) struct AppArgs {
     #[clap(short)]
     pub pid: Option<i32>,
 
+    /// Interpret `-p` as a pid inside the pid namespace of this host pid
+    #[clap(long, requires = "pid", conflicts_with = "container")]
+    pub pidns: Option<i32>,
+
+    /// Interpret `-p` as a pid inside the container with this (possibly abbreviated) id
+    #[clap(long, requires = "pid")]
+    pub container: Option<String>,
+
+    /// Launch the program again and run the commands of a `record session` transcript
+    #[clap(long, value_name = "FILE", conflicts_with = "pid")]
+    pub replay: Option<PathBuf>,
+
+    /// Library to load before the program's own, e.g. a sanitizer runtime (LD_PRELOAD;
+    /// repeatable)
+    #[clap(long, value_name = "LIB", conflicts_with = "pid")]
+    pub preload: Vec<PathBuf>,
+
+    /// Directory searched for libraries first (LD_LIBRARY_PATH; repeatable)
+    #[clap(long, value_name = "DIR", conflicts_with = "pid")]
+    pub ld_library_path: Vec<PathBuf>,
+
+    /// Continue the program right after launching it, instead of stopping before its first
+    /// instruction
+    #[clap(long, conflicts_with = "pid")]
+    pub run: bool,
+
+    /// Read the program's stdout and stderr through pipes and print them live, prefixed with
+    /// `[stdout]` or `[stderr]`
+    #[clap(long, conflicts_with = "pid")]
+    pub capture_output: bool,
+
+    /// Whether the inferior dies with the debugger (ptrace EXITKILL) or is detached from and
+    /// keeps running [default: true when launched, false when attached]
+    #[clap(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub kill_on_exit: Option<bool>,
+
     // --logger (Global options)
     #[cfg(feature = "tracing")]
     #[clap(global = true, long, display_order = 101)]
@@ -1,9 +1,18 @@
+use crate::settings;
 use clap::{Parser, Subcommand};
+use nix::sys::mman::ProtFlags;
+use sdb::scan::ValueType;
+use sdb::sched::parse_cpu_list;
+use sdb::symbol_list::SymbolOrder;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// A command-line debugger argument parser
 #[derive(Debug, PartialEq, Eq, Parser)]
 #[command(name = "Debugger")]
 #[command(about = "A command-line debugger", long_about = None)]
+#[command(override_usage = "<COMMAND> [ARGS]...")]
+#[command(after_help = "Type `help <command>` for details, e.g. `help catch syscall`.")]
 pub struct DbgArgs {
     #[command(subcommand)]
     pub sub_command: SubCommand,
@@ -13,6 +22,868 @@ pub struct DbgArgs {
 pub enum SubCommand {
     /// Continue the process execution
     Continue,
+    /// Start the program again from the beginning
+    Run,
+    /// Interrupt the program if the next `continue` runs for too long, and show where it is
+    #[command(
+        after_help = "Examples:\n  interrupt --after 10s\n  set hang-detector 30s  # every `continue`"
+    )]
+    Interrupt {
+        /// How long to let it run, e.g. 500ms, 10s or 2m
+        #[arg(long, value_parser = parse_duration)]
+        after: Duration,
+    },
+    /// Search commands and settings for a keyword
+    #[command(after_help = "Examples:\n  apropos syscall\n  apropos history")]
+    Apropos {
+        /// Word to look for in command names, descriptions and arguments
+        keyword: String,
+    },
+    /// Run a shell command, also available as `!<command>`
+    Shell {
+        /// Command line passed to `sh -c`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Evaluate an integer expression and add it to the value history
+    #[command(
+        alias = "p",
+        after_help = "Examples:\n  print $base + 0x1234\n  print ($rsp + 15) & ~15\n  mem dump $ 0x100 stack.bin\n\nOperators: + - * / % & | ^ << >> ~ and parentheses. `$name` is a convenience variable\nor register, `$N` the Nth printed value, `$` the last and `$$N` the one N before it. Any\nnumeric argument may use `$` values, e.g. `mem dump $base+0x10 8 out.bin`."
+    )]
+    Print {
+        /// Expression, e.g. `$rip - $base`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expression: Vec<String>,
+    },
+    /// Print values formatted like C's printf, e.g. `printf "%s = %d\n" $rdi $rsi`
+    #[command(
+        after_help = "Examples:\n  printf \"rdi=%#x rsi=%d\\n\" $rdi $rsi\n  printf \"%-16s|%08x\\n\", $rdi, $rax & 0xff\n  hook stop 'printf \"at %p\\n\" $rip'\n\nConversions: %d %i %u %o %x %X %c %p, and %s for the C string at an address, with flags,\nwidth, precision and the lengths hh and h. Separate arguments with spaces, or with commas\nto use spaces inside them."
+    )]
+    Printf {
+        /// Format, with escapes such as `\n`
+        #[arg(allow_hyphen_values = true)]
+        format: String,
+        /// Expressions giving the values
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Send the output of a command through a shell command
+    #[command(after_help = "Examples:\n  pipe bt --all | grep main\n  pipe show env | sort")]
+    Pipe {
+        /// `<command> | <shell command>`; the `|` must be a separate word
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
+    /// Change a setting or how `run` launches the program
+    Set {
+        #[command(subcommand)]
+        command: SetCommand,
+    },
+    /// Undo a `set`
+    Unset {
+        #[command(subcommand)]
+        command: UnsetCommand,
+    },
+    /// Show settings (all of them without arguments) or how `run` launches the program
+    Show {
+        #[command(subcommand)]
+        command: Option<ShowCommand>,
+    },
+    /// Manage the config file
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand,
+    },
+    /// Crash triage
+    Crash {
+        #[command(subcommand)]
+        command: CrashCommand,
+    },
+    /// Print the call stack, following frame pointers
+    #[command(alias = "bt")]
+    #[command(after_help = "Examples:\n  backtrace\n  bt --all")]
+    Backtrace {
+        /// Print every thread, grouping threads with identical stacks
+        #[arg(short, long)]
+        all: bool,
+        /// Print at most this many frames per thread
+        #[arg(long, default_value_t = 64)]
+        limit: usize,
+    },
+    /// Show information about the inferior
+    Info {
+        #[command(subcommand)]
+        command: InfoCommand,
+    },
+    /// Look up symbols
+    Sym {
+        #[command(subcommand)]
+        command: SymCommand,
+    },
+    /// Find threads waiting on each other's locks
+    Deadlock {
+        #[command(subcommand)]
+        command: DeadlockCommand,
+    },
+    /// Inspect threads
+    Thread {
+        #[command(subcommand)]
+        command: ThreadCommand,
+    },
+    /// Inspect the stack
+    Stack {
+        #[command(subcommand)]
+        command: StackCommand,
+    },
+    /// Manage catchpoints
+    Catch {
+        #[command(subcommand)]
+        command: CatchCommand,
+    },
+    /// Manage watchpoints, which stop the program when it writes watched memory or runs code
+    Watch {
+        #[command(subcommand)]
+        command: WatchCommand,
+    },
+    /// Print what the program does while it runs
+    Trace {
+        #[command(subcommand)]
+        command: TraceCommand,
+    },
+    /// Sample the CPU's last branch records, to see how the program got where it stopped
+    Lbr {
+        #[command(subcommand)]
+        command: LbrCommand,
+    },
+    /// Record the program's branches with Intel PT, to see the path that led to a stop
+    #[cfg(feature = "intel-pt")]
+    Pt {
+        #[command(subcommand)]
+        command: PtCommand,
+    },
+    /// Change inferior memory
+    Mem {
+        #[command(subcommand)]
+        command: MemCommand,
+    },
+    /// Overwrite code and undo it again
+    Patch {
+        #[command(subcommand)]
+        command: PatchCommand,
+    },
+    /// Move the program counter past the current instruction without running it
+    Skip,
+    /// Record the session to a transcript that `sdb --replay` can run again
+    Record {
+        #[command(subcommand)]
+        command: RecordCommand,
+    },
+    /// Define a command running other commands, typed on the following lines until `end`
+    #[command(
+        after_help = "Examples:\n  define where\n  > bt --limit $arg0\n  > info threads\n  > end\n  where 5\n\n$arg0..$argN stand for the arguments and $argc for their number. An empty definition\nremoves the command; `settings save` keeps definitions in the config file."
+    )]
+    Define {
+        /// Name of the new command
+        name: String,
+    },
+    /// Run commands before every use of a command (none remove the hook)
+    #[command(
+        after_help = "Examples:\n  hook-pre continue 'info threads'\n  hook-pre continue  # remove the hook"
+    )]
+    HookPre {
+        /// Command to hook, e.g. `continue`
+        command: String,
+        /// Command lines to run, each quoted
+        lines: Vec<String>,
+    },
+    /// Run commands after every successful use of a command (none remove the hook)
+    #[command(after_help = "Examples:\n  hook-post continue context 'bt --limit 5'")]
+    HookPost {
+        /// Command to hook, e.g. `continue`
+        command: String,
+        /// Command lines to run, each quoted
+        lines: Vec<String>,
+    },
+    /// Run commands whenever the program stops, or list hooks
+    Hook {
+        #[command(subcommand)]
+        command: HookCommand,
+    },
+    /// Show registers, the next instructions and the top of the stack (`set context off`
+    /// stops showing them at every stop)
+    Context,
+    /// Search executable memory for instruction sequences
+    Gadget {
+        #[command(subcommand)]
+        command: GadgetCommand,
+    },
+    /// Find where a value lives by how it changes between stops
+    Scan {
+        #[command(subcommand)]
+        command: ScanCommand,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum SetCommand {
+    /// Set an environment variable of the program
+    #[command(after_help = "Examples:\n  set env LD_BIND_NOW 1\n  set env GREETING 'hello world'")]
+    Env {
+        /// Variable name
+        name: String,
+        /// Value (may be empty)
+        #[arg(default_value = "")]
+        value: String,
+    },
+    /// Set the working directory of the program
+    Cwd {
+        /// Directory to start the program in
+        path: PathBuf,
+    },
+    /// Restrict every thread of the program to some CPUs, like `taskset`
+    #[command(
+        after_help = "Examples:\n  set affinity 0\n  set affinity 0-3,8\n  set affinity 0x5\n\nPinning to one CPU serializes the threads, which makes many races far more reproducible.\nThreads created later inherit the affinity of their creator."
+    )]
+    Affinity {
+        /// CPU list, e.g. `0-3,8`, or hex mask, e.g. `0x5`
+        #[arg(value_parser = parse_cpus)]
+        cpus: ::std::vec::Vec<usize>,
+    },
+    /// Change a setting, e.g. `set print max-elements 16` (`show` lists them), or a
+    /// convenience variable, e.g. `set $base = 0x555555554000`
+    #[command(external_subcommand)]
+    Setting(Vec<String>),
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum UnsetCommand {
+    /// Remove an environment variable of the program
+    Env {
+        /// Variable name
+        name: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum ShowCommand {
+    /// Print the environment of the program, or a single variable
+    Env {
+        /// Variable name
+        name: Option<String>,
+    },
+    /// Print the working directory of the program
+    Cwd,
+    /// Print the definition of a user command, or of all of them
+    User {
+        /// Command name
+        name: Option<String>,
+    },
+    /// Print the convenience variables made with `set $name = ...`
+    Convenience,
+    /// Print a setting, e.g. `show disassembly-flavor`
+    #[command(external_subcommand)]
+    Setting(Vec<String>),
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum SettingsCommand {
+    /// Write all settings to the config file ($XDG_CONFIG_HOME/sdb/config)
+    Save,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum CrashCommand {
+    /// Print siginfo, disassembly, registers, threads and mappings of the current stop
+    Report {
+        /// Write the report to this file instead of the terminal
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum InfoCommand {
+    /// List threads blocked on futexes and which thread holds each lock
+    Locks,
+    /// Describe a stack frame: its CFA, return address, the slots its caller's registers were
+    /// saved in, and how the unwinder found it
+    #[command(after_help = "Examples:\n  info frame\n  info frame 2")]
+    Frame {
+        /// Frame number as `backtrace` shows it
+        #[arg(default_value_t = 0)]
+        index: usize,
+    },
+    /// List the open file descriptors with their targets, offsets and open flags
+    #[command(
+        after_help = "Examples:\n  info fds\n\nSockets show as socket:[inode] and pipes as pipe:[inode]. Pair with `catch fd` to\nstop when one of them is used."
+    )]
+    Fds,
+    /// List the socket descriptors with their protocol, state and local and remote addresses
+    #[command(
+        after_help = "Examples:\n  info sockets\n\nAddresses come from the TCP, UDP and Unix socket tables of the inferior's network\nnamespace. Sockets missing from them, e.g. netlink or unbound UDP sockets, only show\ntheir protocol."
+    )]
+    Sockets,
+    /// Report RELRO, stack canary, NX, PIE and Fortify for the program and loaded libraries
+    Security,
+    /// List GOT slots with their targets, flagging slots that point outside library code
+    #[command(after_help = "Examples:\n  info got\n  info got libc.so.6")]
+    Got {
+        /// Library file name or path (default: the program)
+        lib: Option<String>,
+    },
+    /// Describe the DWARF compile unit of the current code, or find which units have line
+    /// info for a source file
+    #[command(
+        after_help = "Examples:\n  info source\n  info source parser.c\n  info source src/net/socket.c --lib libnet.so"
+    )]
+    Source {
+        /// Source file path or trailing path components
+        file: Option<String>,
+        /// Library file name or path (default: the program)
+        #[arg(long)]
+        lib: Option<String>,
+    },
+    /// List the DWARF compile units with their language, compiler and flags
+    #[command(
+        after_help = "Examples:\n  info compile-units\n  info compile-units parse --files\n  info compile-units --lib libssl.so.3\n\nCompiler flags are only recorded by GCC, and by clang with -grecord-gcc-switches."
+    )]
+    CompileUnits {
+        /// Only list units whose name contains this
+        filter: Option<String>,
+        /// Also list the source files of each unit's line table
+        #[arg(long)]
+        files: bool,
+        /// Library file name or path (default: the program)
+        #[arg(long)]
+        lib: Option<String>,
+    },
+    /// Show the scheduling policy, priority, nice value, CPU affinity and context switches of
+    /// every thread
+    Sched,
+    /// Same as `thread list`
+    #[command(after_help = "Examples:\n  info threads\n  info threads --sample")]
+    Threads {
+        /// Interrupt running threads for an instant to show where they are
+        #[arg(long)]
+        sample: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum SymCommand {
+    /// List the function and data symbols of the program or a library, with their runtime
+    /// address, module offset, size, table and section
+    #[command(
+        after_help = "Examples:\n  sym list --regex '^str'\n  sym list --dynamic --lib libc.so.6 --sort name\n  sym list --debug --section .text --limit 20 --skip 40\n\nWithout --symtab, --dynamic or --debug, both ELF symbol tables are listed. A symbol in\nseveral of the chosen tables is listed once. --debug lists the functions of the DWARF,\nwhich a stripped file keeping its debug info still has."
+    )]
+    List {
+        /// Only list symbols whose name contains a match of this regex
+        #[arg(long, value_name = "REGEX")]
+        regex: Option<String>,
+        /// List the `.symtab` symbols
+        #[arg(long)]
+        symtab: bool,
+        /// List the `.dynsym` symbols
+        #[arg(long)]
+        dynamic: bool,
+        /// List the functions of the DWARF debug info
+        #[arg(long)]
+        debug: bool,
+        /// Only list symbols in this section, e.g. `.text` or `.bss`
+        #[arg(long)]
+        section: Option<String>,
+        /// `address` or `name`
+        #[arg(long, value_parser = parse_symbol_order, default_value = "address")]
+        sort: SymbolOrder,
+        /// Library file name or path (default: the program)
+        #[arg(long)]
+        lib: Option<String>,
+        /// Number of matching symbols to leave out first
+        #[arg(long, default_value_t = 0)]
+        skip: usize,
+        /// Print at most this many symbols
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum DeadlockCommand {
+    /// Report cycles in the wait-for graph of mutexes with the owners' backtraces
+    Check,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum ThreadCommand {
+    /// List threads with their name, state, location and last stop reason
+    #[command(after_help = "Examples:\n  thread list\n  thread list --sample")]
+    List {
+        /// Interrupt running threads for an instant to show where they are
+        #[arg(long)]
+        sample: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum StackCommand {
+    /// Dump stack slots from rsp upward, marking frames, return addresses and the canary
+    #[command(after_help = "Examples:\n  stack view\n  stack view 64")]
+    View {
+        /// Number of 8-byte words to show
+        #[arg(default_value_t = 16)]
+        nwords: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum MemCommand {
+    /// Change the protection of pages by making the inferior call `mprotect`
+    #[command(
+        after_help = "Examples:\n  mem protect 0x401000 0x1000 rwx\n  mem protect 0x7ffff7fc3000 4096 r--"
+    )]
+    Protect {
+        /// Page aligned start address
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length in bytes
+        #[arg(value_parser = parse_number)]
+        len: u64,
+        /// Any of `r`, `w` and `x`, e.g. `rw-`, or `---` for no access
+        #[arg(value_parser = parse_protection)]
+        perms: ProtFlags,
+    },
+    /// Map zeroed scratch memory into the inferior and print its address
+    #[command(after_help = "Examples:\n  mem alloc 4096\n  mem alloc 0x2000 rwx")]
+    Alloc {
+        /// Length in bytes, rounded up to whole pages
+        #[arg(value_parser = parse_number)]
+        len: u64,
+        /// Protection of the new pages
+        #[arg(value_parser = parse_protection, default_value = "rw-")]
+        perms: ProtFlags,
+    },
+    /// Unmap memory, e.g. from `mem alloc`
+    Free {
+        /// Page aligned start address
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length in bytes
+        #[arg(value_parser = parse_number)]
+        len: u64,
+    },
+    /// Print inferior memory as a hex dump
+    #[command(after_help = "Examples:\n  mem read $rsp\n  mem read 0x404028 0x100")]
+    Read {
+        /// Start address
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length in bytes
+        #[arg(value_parser = parse_number, default_value = "64")]
+        len: u64,
+    },
+    /// Save inferior memory to a file
+    #[command(after_help = "Examples:\n  mem dump 0x7ffff7dd0000 0x1000 page.bin")]
+    Dump {
+        /// Start address
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length in bytes
+        #[arg(value_parser = parse_number)]
+        len: u64,
+        /// Output file
+        file: PathBuf,
+    },
+    /// Save every mapping of a file, or a pseudo mapping such as `[heap]`, to a file
+    #[command(
+        after_help = "Examples:\n  mem dump-mapping heap heap.bin\n  mem dump-mapping libc.so.6 libc.bin"
+    )]
+    DumpMapping {
+        /// File name, path or `[name]` of the mapping (brackets optional)
+        name: String,
+        /// Output file
+        file: PathBuf,
+    },
+    /// Write the contents of a file into inferior memory
+    #[command(after_help = "Examples:\n  mem restore heap.bin 0x555555559000")]
+    Restore {
+        /// File saved by `mem dump`
+        file: PathBuf,
+        /// Address to write to
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+    },
+    /// Save a copy of inferior memory under a name, to compare with `mem diff` later
+    #[command(after_help = "Examples:\n  mem snapshot heap1 0x555555559000 0x100")]
+    Snapshot {
+        /// Name to refer to the snapshot by; an existing one is replaced
+        name: String,
+        /// Start address
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length in bytes
+        #[arg(value_parser = parse_number)]
+        len: u64,
+    },
+    /// Find words in readable memory that point into a range, e.g. at a heap object
+    #[command(
+        after_help = "Examples:\n  mem scan-pointers 0x5555555592a0\n  mem scan-pointers 0x5555555592a0 0x40"
+    )]
+    ScanPointers {
+        /// Start of the range
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Length of the range in bytes; without it only pointers to exactly `addr` count
+        #[arg(value_parser = parse_number, default_value = "1")]
+        len: u64,
+        /// Print at most this many pointers
+        #[arg(long, default_value_t = 256)]
+        limit: usize,
+    },
+    /// Print the bytes that changed between two snapshots, or since a snapshot
+    #[command(after_help = "Examples:\n  mem diff heap1\n  mem diff heap1 heap2")]
+    Diff {
+        /// Earlier snapshot
+        old: String,
+        /// Later snapshot (default: the memory as it is now)
+        new: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum GadgetCommand {
+    /// Find every address, aligned to an instruction or not, where the instructions decode
+    #[command(
+        after_help = "Examples:\n  gadget search \"pop rdi; ret\"\n  gadget search mov rax, [rsp+0x10]; ret"
+    )]
+    Search {
+        /// Intel syntax instructions separated by `;`
+        #[arg(required = true, allow_negative_numbers = true)]
+        query: Vec<String>,
+        /// Print at most this many addresses
+        #[arg(long, default_value_t = 32)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum ScanCommand {
+    /// Find every address holding a value, replacing any scan in progress
+    #[command(after_help = "Examples:\n  scan start i32 100\n  scan start f32 -1.5")]
+    Start {
+        /// One of u8, u16, u32, u64, i8, i16, i32, i64, f32 and f64
+        #[arg(value_parser = parse_value_type)]
+        ty: ValueType,
+        /// Value to look for
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Keep the addresses whose value changed as described since the last scan
+    #[command(
+        after_help = "Examples:\n  scan next changed\n  scan next decreased\n  scan next ==99"
+    )]
+    Next {
+        /// `changed`, `unchanged`, `increased`, `decreased`, or `==<value>`
+        #[arg(allow_hyphen_values = true)]
+        filter: String,
+    },
+    /// Print the remaining addresses and their current values
+    List {
+        /// Print at most this many addresses
+        #[arg(long, default_value_t = 32)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum PatchCommand {
+    /// Assemble Intel syntax instructions and write them at an address
+    #[command(
+        after_help = "Examples:\n  patch asm 0x401136 \"mov eax, 1; ret\"\n  patch asm 0x401150 jmp 0x401170"
+    )]
+    Asm {
+        /// Address to write to
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Instructions separated by `;`. Branch targets are absolute addresses.
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        code: Vec<String>,
+    },
+    /// Overwrite instructions with `nop`s
+    #[command(after_help = "Examples:\n  patch nop 0x401136\n  patch nop 0x401136 5")]
+    Nop {
+        /// Address to write to
+        #[arg(value_parser = parse_number)]
+        addr: u64,
+        /// Number of bytes (default: the length of the instruction at the address)
+        #[arg(value_parser = parse_number)]
+        len: Option<u64>,
+    },
+    /// List applied patches
+    List,
+    /// Restore the original bytes of a patch
+    Revert {
+        /// Patch id (default: the latest patch)
+        id: Option<usize>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum RecordCommand {
+    /// Log every command and its output, with timestamps, to a file
+    #[command(after_help = "Examples:\n  record session crash.sdb\n  sdb --replay crash.sdb")]
+    Session {
+        /// Transcript file, overwritten if it exists
+        file: PathBuf,
+    },
+    /// Stop recording
+    Stop,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum CatchCommand {
+    /// Stop when the inferior reads, writes or closes a file descriptor
+    Fd {
+        /// File descriptor to watch
+        fd: i32,
+        /// Print the syscall and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// Stop on syscalls
+    #[command(
+        after_help = "Examples:\n  catch syscall openat close\n  catch syscall --enter-only --match 'open.*|stat'\n  catch syscall --exclude 'futex|nanosleep' --log"
+    )]
+    Syscall {
+        /// Syscall names or numbers (all syscalls if neither these nor `--match` are given)
+        syscalls: Vec<String>,
+        /// Only catch syscalls whose name fully matches this regex
+        #[arg(long = "match", value_name = "REGEX")]
+        include: Option<String>,
+        /// Ignore syscalls whose name fully matches this regex
+        #[arg(long, value_name = "REGEX")]
+        exclude: Option<String>,
+        /// Only stop on syscall entry
+        #[arg(long, conflicts_with = "exit_only")]
+        enter_only: bool,
+        /// Only stop on syscall exit
+        #[arg(long)]
+        exit_only: bool,
+        /// Print the syscall and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// Stop when the inferior's resident memory grows past a size, checked every 10 ms
+    #[command(after_help = "Examples:\n  catch rss > 512")]
+    Rss {
+        /// `>`, for readability
+        #[arg(value_parser = [">"])]
+        comparison: String,
+        /// Threshold in MiB
+        mib: u64,
+        /// Print the growth and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// Stop when a shared object is loaded, before its initializers run
+    #[command(after_help = "Examples:\n  catch load\n  catch load 'libssl|libcrypto'")]
+    Load {
+        /// Only catch objects whose path contains a match of this regex
+        regex: Option<String>,
+        /// Print the object and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// Stop when a shared object is unloaded
+    #[command(after_help = "Examples:\n  catch unload plugin")]
+    Unload {
+        /// Only catch objects whose path contains a match of this regex
+        regex: Option<String>,
+        /// Print the object and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// Stop when AddressSanitizer or ThreadSanitizer reports an error
+    #[command(
+        after_help = "Examples:\n  catch sanitizer\n  catch sanitizer --log\n\nThe program stops in the runtime's report hook, before ASan prints the report and after\nTSan does, and before either aborts.\nThe kind of error and the bad access are read by calling the runtime's report functions.\nOnly reports made by the main thread are caught."
+    )]
+    Sanitizer {
+        /// Print the report summary and keep running instead of stopping
+        #[arg(long)]
+        log: bool,
+    },
+    /// List catchpoints
+    List,
+    /// Delete a catchpoint
+    Delete {
+        /// Catchpoint id
+        id: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum HookCommand {
+    /// Run commands whenever the program stops after `continue` or `run` (none remove the
+    /// hook)
+    #[command(after_help = "Examples:\n  hook stop 'bt --limit 3' 'info threads'")]
+    Stop {
+        /// Command lines to run, each quoted
+        lines: Vec<String>,
+    },
+    /// List hooks
+    List,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum WatchCommand {
+    /// Stop when the program's GOT slot of a symbol is written, e.g. by a hijack
+    #[command(after_help = "Examples:\n  watch got puts")]
+    Got {
+        /// Symbol name
+        symbol: String,
+    },
+    /// Stop before the instruction at a function or address executes, using a debug register
+    #[command(after_help = "Examples:\n  watch exec main\n  watch exec 0x401136")]
+    Exec {
+        /// Function name, or address of an instruction
+        location: String,
+    },
+    /// Stop when any code in a module, function or page is first executed
+    #[command(
+        after_help = "Examples:\n  watch enter libssl.so.3\n  watch enter parse_header\n  watch enter 0x7ffff7fc3000\n\nThe code's pages are made non-executable until then, so another thread running it\ncrashes the program."
+    )]
+    Enter {
+        /// Mapping name (as in `dump`), function name, or an address in the page to watch
+        target: String,
+    },
+    /// Stop before every write to a whole mapping, such as the heap or a library's data
+    #[command(
+        after_help = "Examples:\n  watch region heap\n  watch region libfoo.so\n  watch region 0x404028\n\nThe writable pages are made read-only, so unlike `watch got` any number of bytes can\nbe watched, at the cost of a stop per write. The kernel does not stop for its own\nwrites: syscalls such as read(2) into the region fail with EFAULT. Another thread\nwriting to it crashes the program."
+    )]
+    Region {
+        /// Mapping name (as in `dump`), or an address in the mapping to watch
+        target: String,
+    },
+    /// List watchpoints
+    List,
+    /// Delete a watchpoint
+    Delete {
+        /// Watchpoint id
+        id: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum TraceCommand {
+    /// Continue, printing calls of matching functions as an indented tree with arguments,
+    /// return values and durations, until the program stops
+    #[command(
+        after_help = "Examples:\n  trace functions\n  trace functions '^(parse|eval)_' --args 2\n  trace functions malloc --args 1\n\nWithout a regex, every function of the program itself is traced. Functions get a\nbreakpoint at entry and their return address is redirected while they run. Only\nsingle-threaded programs can be traced, and durations include the debugger's overhead."
+    )]
+    Functions {
+        /// Regex searched for in the function names of the program and its libraries
+        pattern: Option<String>,
+        /// Number of integer arguments to print for each call
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(0..=6))]
+        args: u8,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum LbrCommand {
+    /// Start sampling the last branches of the main thread's user-space code
+    #[command(
+        after_help = "Examples:\n  lbr start\n  lbr start --period 1000\n\nThe branch records reach the debugger in samples, taken every `--period` branches,\nso the newest sample ends up to that many branches before the stop. A period of 1\nis exact but slows the program down. Needs a CPU with LBR, which most VMs hide."
+    )]
+    Start {
+        /// Number of branches between samples
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        period: u64,
+    },
+    /// Stop sampling, keeping the last sample for `lbr show`
+    Stop,
+    /// Print the branches of the newest sample, oldest first
+    Show {
+        /// Number of branches to print
+        #[arg(long, default_value_t = 32)]
+        last: usize,
+    },
+}
+
+#[cfg(feature = "intel-pt")]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum PtCommand {
+    /// Start recording the branches of the main thread's user-space code
+    #[command(
+        after_help = "Examples:\n  pt start\n  pt start --size 65536\n\nThe trace is a ring buffer: when it is full, the oldest branches are overwritten,\nso after a crash it holds the path leading to it. Needs an Intel CPU with PT and\n/proc/sys/kernel/perf_event_paranoid at 2 or less."
+    )]
+    Start {
+        /// Buffer size in KiB, rounded up to a power of two
+        #[arg(long, default_value_t = 4096)]
+        size: usize,
+    },
+    /// Stop recording, keeping the trace for `pt decode`
+    Stop,
+    /// Print the last blocks of code the program ran, oldest first
+    #[command(
+        after_help = "Examples:\n  pt decode\n  pt decode --last 100\n\nThe path is rebuilt from the code in memory now, so code changed since it ran is\nfollowed wrongly. Gaps show where tracing paused, e.g. in syscalls, or lost sync."
+    )]
+    Decode {
+        /// Number of blocks to print
+        #[arg(long, default_value_t = 32)]
+        last: usize,
+    },
+}
+
+fn parse_duration(input: &str) -> Result<Duration, String> {
+    settings::parse_duration(input)
+        .ok_or_else(|| format!("`{input}` is not a duration such as 500ms, 10s or 2m"))
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal number.
+pub(crate) fn parse_number(input: &str) -> Result<u64, String> {
+    let parsed = input
+        .strip_prefix("0x")
+        .map_or_else(|| input.parse(), |hex| u64::from_str_radix(hex, 16));
+    parsed.map_err(|err| format!("{err}: `{input}`"))
+}
+
+/// Parses `rwx`-style permissions, where `-` stands for an absent permission.
+fn parse_value_type(input: &str) -> Result<ValueType, String> {
+    ValueType::from_name(input).ok_or_else(|| {
+        let names: Vec<_> = ValueType::ALL.iter().map(|(name, _)| *name).collect();
+        format!("unknown type `{input}`; use one of {}", names.join(", "))
+    })
+}
+
+fn parse_symbol_order(input: &str) -> Result<SymbolOrder, String> {
+    match input {
+        "address" => Ok(SymbolOrder::Address),
+        "name" => Ok(SymbolOrder::Name),
+        _ => Err(format!("unknown order `{input}`; use address or name")),
+    }
+}
+
+fn parse_cpus(input: &str) -> Result<Vec<usize>, String> {
+    match parse_cpu_list(input) {
+        Some(cpus) if !cpus.is_empty() => Ok(cpus),
+        Some(_) => Err("the mask has no CPU".into()),
+        None => Err(format!(
+            "`{input}` is neither a CPU list like 0-3,8 nor a hex mask like 0x5"
+        )),
+    }
+}
+
+fn parse_protection(input: &str) -> Result<ProtFlags, String> {
+    input.chars().try_fold(ProtFlags::PROT_NONE, |prot, char| {
+        let flag = match char {
+            'r' => ProtFlags::PROT_READ,
+            'w' => ProtFlags::PROT_WRITE,
+            'x' => ProtFlags::PROT_EXEC,
+            '-' => ProtFlags::PROT_NONE,
+            _ => return Err(format!("unknown permission `{char}`; use r, w, x or -")),
+        };
+        Ok(prot | flag)
+    })
 }
 
 #[cfg(test)]
@@ -37,4 +908,44 @@ mod tests {
         };
         Ok(())
     }
+
+    #[test]
+    fn should_render_help_for_subcommands() {
+        let err = DbgArgs::try_parse_from(["", "help", "catch", "syscall"]).err();
+        assert_eq!(
+            err.as_ref().map(clap::Error::kind),
+            Some(clap::error::ErrorKind::DisplayHelp)
+        );
+        assert!(err.is_some_and(|err| err.to_string().contains("Examples:")));
+    }
+
+    #[test]
+    fn should_parse_mem_protect() {
+        let args = DbgArgs::try_parse_from(["", "mem", "protect", "0x401000", "4096", "rw-"]);
+        let expected = DbgArgs {
+            sub_command: SubCommand::Mem {
+                command: MemCommand::Protect {
+                    addr: 0x40_1000,
+                    len: 4096,
+                    perms: ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                },
+            },
+        };
+        assert_eq!(args.ok(), Some(expected));
+        assert!(DbgArgs::try_parse_from(["", "mem", "protect", "0", "1", "rq"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_set_affinity() {
+        let args = DbgArgs::try_parse_from(["", "set", "affinity", "0-1,4"]);
+        let expected = DbgArgs {
+            sub_command: SubCommand::Set {
+                command: SetCommand::Affinity {
+                    cpus: vec![0, 1, 4],
+                },
+            },
+        };
+        assert_eq!(args.ok(), Some(expected));
+        assert!(DbgArgs::try_parse_from(["", "set", "affinity", "0x0"]).is_err());
+    }
 }
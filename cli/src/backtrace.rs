@@ -0,0 +1,98 @@
+//! `backtrace`: frame-pointer backtraces of one or all threads.
+
+use nix::unistd::Pid;
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelRefIterator as _, ParallelIterator as _,
+};
+use sdb::annotate::Annotator;
+use sdb::error::Result;
+use sdb::output::Backtrace;
+use sdb::process::Process;
+use sdb::procfs::{read_maps, tids, TaskStat};
+
+/// Deepest backtrace printed by default.
+const MAX_FRAMES: usize = 64;
+/// Fewest threads worth handing to a worker thread of their own.
+const MIN_THREADS_PER_WORKER: usize = 16;
+
+/// Renders the backtrace of the traced thread, at most `limit` frames deep.
+pub(crate) fn current(process: &Process, limit: usize) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let regs = process.read_registers()?;
+    let annotator = Annotator::new(&maps).with_jit(process.jit_objects());
+    Ok(lines(&Backtrace::capture(process, annotator, &regs, limit)))
+}
+
+/// Renders the backtrace of thread `tid`.
+pub(crate) fn thread(process: &Process, tid: Pid) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let regs = process.thread_registers(tid)?;
+    let annotator = Annotator::new(&maps).with_jit(process.jit_objects());
+    Ok(lines(&Backtrace::capture(
+        process, annotator, &regs, MAX_FRAMES,
+    )))
+}
+
+/// Renders the backtraces of every thread, at most `limit` frames deep, printing threads with
+/// identical stacks once.
+///
+/// Every thread is stopped first and held until all stacks are walked, so no stack changes
+/// under the walk. Threads have to be stopped one at a time, as only the tracer may ptrace,
+/// but their stacks are then walked on several worker threads.
+pub(crate) fn all(process: &Process, limit: usize) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let stopped = process.stop_threads(tids(process.pid)?);
+    let memory = process.remote_memory();
+    let stacks: Vec<_> = stopped
+        .registers()
+        .par_iter()
+        .with_min_len(MIN_THREADS_PER_WORKER)
+        .map(|(tid, regs)| {
+            let pcs = regs
+                .as_ref()
+                .map(|regs| Backtrace::walk(&memory, &maps, regs, limit))
+                .map_err(ToString::to_string);
+            (*tid, pcs)
+        })
+        .collect();
+    drop(stopped);
+
+    let annotator = Annotator::new(&maps).with_jit(process.jit_objects());
+    let mut groups: Vec<(Vec<u64>, Vec<Pid>)> = Vec::new();
+    let mut lines = Vec::new();
+    for (tid, stack) in stacks {
+        match stack {
+            Ok(pcs) => match groups.iter_mut().find(|(stack, _)| *stack == pcs) {
+                Some((_, members)) => members.push(tid),
+                None => groups.push((pcs, vec![tid])),
+            },
+            Err(err) => lines.push(format!("Thread {tid}: {err}")),
+        }
+    }
+
+    for (stack, members) in groups {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(if let [tid] = members.as_slice() {
+            let name = TaskStat::read(process.pid, *tid)
+                .map(|stat| stat.comm)
+                .unwrap_or_default();
+            format!("Thread {tid} ({name}):")
+        } else {
+            let tids: Vec<_> = members.iter().map(ToString::to_string).collect();
+            let top = stack
+                .first()
+                .and_then(|&pc| annotator.symbol(pc))
+                .map(|location| format!(" in {}", location.name))
+                .unwrap_or_default();
+            format!("{} threads ({}){top}:", members.len(), tids.join(", "))
+        });
+        lines.extend(self::lines(&Backtrace::from_pcs(annotator, &stack)));
+    }
+    Ok(lines)
+}
+
+fn lines(backtrace: &Backtrace) -> Vec<String> {
+    backtrace.frames.iter().map(ToString::to_string).collect()
+}
@@ -0,0 +1,218 @@
+//! `info source` and `info compile-units`: the DWARF compile units of the program or a
+//! library, for finding out why a source file has no line info.
+
+use crate::dump::mapping_matches;
+use crate::error::{NoMappingSnafu, Result};
+use sdb::compile_units::{self, CompileUnit};
+use sdb::process::Process;
+use sdb::procfs::{find_region, read_maps, MemoryRegion};
+use sdb::symbols::SymbolTable;
+use snafu::OptionExt as _;
+use std::path::Path;
+
+/// Lists the compile units of `lib`, or of the program, whose name contains `filter`, with
+/// their producer and, if `files`, the files of their line tables.
+pub(crate) fn list(
+    process: &Process,
+    lib: Option<&str>,
+    filter: Option<&str>,
+    files: bool,
+) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid)?;
+    let module = find_module(&maps, lib)?;
+    let units = compile_units::load(Path::new(&module.path))?;
+    if units.is_empty() {
+        return Ok(vec![no_dwarf(&module.path)]);
+    }
+    let mut lines = Vec::new();
+    for unit in &units {
+        let name = unit.name.as_deref().unwrap_or("<unnamed>");
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+        let line_table = unit.files.as_ref().map_or_else(
+            || "no line table".to_string(),
+            |files| format!("{} files", files.len()),
+        );
+        lines.push(format!(
+            "{:#010x}  {name}  [{}, DWARF {}, {line_table}]  {}",
+            unit.offset,
+            unit.language.as_deref().unwrap_or("unknown language"),
+            unit.version,
+            unit.producer.as_deref().unwrap_or("")
+        ));
+        if files {
+            lines.extend(
+                unit.files
+                    .iter()
+                    .flatten()
+                    .map(|file| format!("    {file}")),
+            );
+        }
+    }
+    Ok(lines)
+}
+
+/// Describes the compile unit of the code at the program counter, or, given `file`, which
+/// units of `lib` (default: the program) have line info for it and why none might.
+pub(crate) fn source(
+    process: &Process,
+    file: Option<&str>,
+    lib: Option<&str>,
+) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid)?;
+    let Some(file) = file else {
+        return current_unit(process, &maps);
+    };
+    let module = find_module(&maps, lib)?;
+    let units = compile_units::load(Path::new(&module.path))?;
+    if units.is_empty() {
+        return Ok(vec![no_dwarf(&module.path)]);
+    }
+    let mut lines = Vec::new();
+    for unit in &units {
+        let paths = unit.matching_files(file);
+        if !paths.is_empty() {
+            lines.push(format!(
+                "{} (unit {:#x}): {}",
+                unit.name.as_deref().unwrap_or("<unnamed>"),
+                unit.offset,
+                paths.join(", ")
+            ));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(format!(
+            "No compile unit of {} lists {file} in its line table.",
+            module.path
+        ));
+        lines.push(
+            "It was compiled without -g, its code was discarded, or it belongs to another library (see --lib)."
+                .to_string(),
+        );
+        let without_lines = units.iter().filter(|unit| unit.files.is_none()).count();
+        if without_lines > 0 {
+            lines.push(format!("{without_lines} units have no line table at all."));
+        }
+    } else {
+        lines.insert(0, format!("{file} has line info in:"));
+    }
+    Ok(lines)
+}
+
+/// Describes the unit containing the program counter.
+fn current_unit(process: &Process, maps: &[MemoryRegion]) -> Result<Vec<String>> {
+    let pc = process.read_registers()?.rip;
+    let module = find_region(maps, pc)
+        .filter(|region| region.path.starts_with('/'))
+        .context(NoMappingSnafu {
+            name: format!("{pc:#x}"),
+        })?;
+    let path = Path::new(&module.path);
+    let bias = SymbolTable::cached(path)?
+        .load_bias(module)
+        .unwrap_or_default();
+    let units = compile_units::load(path)?;
+    if units.is_empty() {
+        return Ok(vec![no_dwarf(&module.path)]);
+    }
+    let Some(unit) = units
+        .iter()
+        .find(|unit| unit.contains(pc.wrapping_sub(bias)))
+    else {
+        return Ok(vec![format!(
+            "No compile unit of {} covers {pc:#x}: it was built without debug info.",
+            module.path
+        )]);
+    };
+    Ok(describe(unit, &module.path))
+}
+
+/// Lists the attributes of `unit` of the file at `path`.
+fn describe(unit: &CompileUnit, path: &str) -> Vec<String> {
+    let field =
+        |name: &str, value: Option<&str>| value.map(|value| format!("  {name:<10} {value}"));
+    let ranges = unit
+        .ranges
+        .iter()
+        .map(|range| format!("{:#x}..{:#x}", range.start, range.end))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let files = unit.files.as_ref().map_or_else(
+        || "none: the unit has no line table".to_string(),
+        |files| format!("{} in the line table", files.len()),
+    );
+    let split = unit
+        .dwo_name
+        .as_ref()
+        .map(|dwo| format!("{dwo} holds the rest of the unit"));
+    std::iter::once(Some(format!(
+        "Compile unit at .debug_info+{:#x} of {path}:",
+        unit.offset
+    )))
+    .chain([
+        field("name", unit.name.as_deref()),
+        field("directory", unit.comp_dir.as_deref()),
+        field("producer", unit.producer.as_deref()),
+        field("language", unit.language.as_deref()),
+        field("DWARF", Some(unit.version.to_string().as_str())),
+        field("split", split.as_deref()),
+        field(
+            "code",
+            Some(ranges.as_str()).filter(|ranges| !ranges.is_empty()),
+        ),
+        field("files", Some(files.as_str())),
+    ])
+    .flatten()
+    .collect()
+}
+
+/// Finds the module named `lib`, or the program.
+pub(crate) fn find_module<'a>(
+    maps: &'a [MemoryRegion],
+    lib: Option<&str>,
+) -> Result<&'a MemoryRegion> {
+    Ok(match lib {
+        Some(name) => maps
+            .iter()
+            .find(|region| mapping_matches(region, name))
+            .context(NoMappingSnafu { name })?,
+        None => maps
+            .iter()
+            .find(|region| region.path.starts_with('/'))
+            .context(NoMappingSnafu { name: "program" })?,
+    })
+}
+
+fn no_dwarf(path: &str) -> String {
+    format!("{path} has no DWARF: it was built without -g, or stripped.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_unit() {
+        let unit = CompileUnit {
+            offset: 0x2a,
+            version: 5,
+            name: Some("main.c".into()),
+            producer: Some("GNU C17 12.2.0 -g -O2".into()),
+            ranges: vec![0x1130..0x1180, 0x1200..0x1210],
+            files: None,
+            ..CompileUnit::default()
+        };
+        assert_eq!(
+            describe(&unit, "/tmp/app"),
+            [
+                "Compile unit at .debug_info+0x2a of /tmp/app:",
+                "  name       main.c",
+                "  producer   GNU C17 12.2.0 -g -O2",
+                "  DWARF      5",
+                "  code       0x1130..0x1180, 0x1200..0x1210",
+                "  files      none: the unit has no line table",
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! `context`: registers, code and stack at a glance, shown at every stop.
+
+use crate::stack_view;
+use crate::telescope::telescope;
+use sdb::annotate::Annotator;
+use sdb::disassembler::{disassemble, Syntax};
+use sdb::error::Result;
+use sdb::output::RegisterDump;
+use sdb::process::Process;
+use sdb::procfs::read_maps;
+
+/// Registers shown, in order.
+const REGISTERS: [&str; 18] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "eflags",
+];
+/// Instructions shown from the program counter on.
+const CODE_INSTRUCTIONS: usize = 6;
+/// Stack slots shown from `rsp` upward.
+const STACK_WORDS: usize = 8;
+
+/// Renders the registers, the next instructions and the top of the stack, following
+/// pointers up to `depth` hops.
+pub(crate) fn render(process: &Process, syntax: Syntax, depth: usize) -> Result<Vec<String>> {
+    let regs = process.read_registers()?;
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let annotator = Annotator::new(&maps).with_jit(process.jit_objects());
+    let dump = RegisterDump::new(&regs).only(&REGISTERS);
+
+    let mut lines = vec!["--- Registers ---".to_string()];
+    let raw: Vec<_> = dump
+        .registers
+        .iter()
+        .map(|register| register.value)
+        .collect();
+    let pointees = telescope(process, &maps, &raw, depth);
+    for (register, pointee) in dump.registers.iter().zip(pointees) {
+        let line = format!("{register}  {pointee}");
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines.push("--- Code ---".to_string());
+    for instruction in disassemble(process, regs.rip, CODE_INSTRUCTIONS, syntax)? {
+        let marker = if instruction.address == regs.rip {
+            "=>"
+        } else {
+            "  "
+        };
+        let location = annotator
+            .annotate(instruction.address)
+            .place()
+            .map(|place| format!(" <{place}>"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{marker} {:#x}{location}: {}",
+            instruction.address, instruction.text
+        ));
+    }
+
+    lines.push("--- Stack ---".to_string());
+    lines.extend(stack_view::render(process, STACK_WORDS, depth)?);
+    Ok(lines)
+}
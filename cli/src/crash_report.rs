@@ -0,0 +1,160 @@
+//! `crash report`: everything needed to file a bug about a crashed inferior.
+
+use crate::threads;
+use nix::sys::signal::Signal;
+use sdb::annotate::Annotator;
+use sdb::disassembler::{disassemble, Instruction, Syntax};
+use sdb::output::RegisterDump;
+use sdb::process::{Process, SignalInfo};
+use sdb::procfs::{read_maps, MemoryRegion};
+use sdb::stack::detect_stack_overflow;
+use std::fmt::Write as _;
+
+/// Instructions shown before the faulting one, when the function start is known.
+const INSTRUCTIONS_BEFORE_PC: usize = 5;
+/// Instructions shown from the faulting one on.
+const INSTRUCTIONS_FROM_PC: usize = 6;
+/// Farthest distance from the function start we decode from to find earlier instructions.
+const MAX_DECODE_BACK: u64 = 0x400;
+
+/// Builds the report for the current stop.
+pub(crate) fn build(process: &Process, syntax: Syntax) -> String {
+    let mut report = String::new();
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let _ = writeln!(report, "=== sdb crash report: process {} ===", process.pid);
+
+    let regs = process.read_registers();
+
+    let _ = writeln!(report, "\n--- Signal ---");
+    match process.signal_info() {
+        Ok(info) => {
+            let _ = writeln!(report, "{info}");
+            if let Some(fault_addr) = info.fault_addr {
+                let _ = writeln!(
+                    report,
+                    "fault address {}",
+                    Annotator::new(&maps).annotate(fault_addr)
+                );
+            }
+            let note = regs
+                .as_ref()
+                .ok()
+                .and_then(|regs| stack_overflow_note(process, &maps, &info, regs.rsp));
+            if let Some(note) = note {
+                let _ = writeln!(report, "{note}");
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(report, "unavailable: {err}");
+        }
+    }
+
+    let _ = writeln!(report, "\n--- Disassembly ---");
+    match &regs {
+        Ok(regs) => {
+            for instruction in instructions_around(process, &maps, regs.rip, syntax) {
+                let marker = if instruction.address == regs.rip {
+                    "=>"
+                } else {
+                    "  "
+                };
+                let location = Annotator::new(&maps)
+                    .annotate(instruction.address)
+                    .place()
+                    .map(|place| format!(" <{place}>"))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    report,
+                    "{marker} {:#x}{location}: {}",
+                    instruction.address, instruction.text
+                );
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(report, "unavailable: {err}");
+        }
+    }
+
+    let _ = writeln!(report, "\n--- Registers ---");
+    match &regs {
+        Ok(regs) => {
+            let _ = writeln!(report, "{}", RegisterDump::new(regs));
+        }
+        Err(err) => {
+            let _ = writeln!(report, "unavailable: {err}");
+        }
+    }
+
+    let _ = writeln!(report, "\n--- Threads ---");
+    match threads::list(process, false) {
+        Ok(lines) => {
+            for line in lines {
+                let _ = writeln!(report, "{line}");
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(report, "unavailable: {err}");
+        }
+    }
+
+    let _ = writeln!(report, "\n--- Memory mappings ---");
+    for region in &maps {
+        let _ = writeln!(
+            report,
+            "{:#014x}-{:#014x} {} {:08x} {}",
+            region.start,
+            region.end,
+            region.perms(),
+            region.offset,
+            region.path
+        );
+    }
+    report
+}
+
+/// Explains a `SIGSEGV` caused by running off the end of a stack, if that is what happened.
+///
+/// Only the main thread is traced, so it is the only one whose guard-page faults are seen.
+fn stack_overflow_note(
+    process: &Process,
+    maps: &[MemoryRegion],
+    info: &SignalInfo,
+    sp: u64,
+) -> Option<String> {
+    if info.signal() != Some(Signal::SIGSEGV) {
+        return None;
+    }
+    let overflow = detect_stack_overflow(maps, info.fault_addr?, sp)?;
+    Some(format!(
+        "likely stack overflow in the main thread ({}): {overflow}",
+        process.pid
+    ))
+}
+
+/// Disassembles a few instructions before and after `pc`.
+///
+/// x86 cannot be decoded backwards, so earlier instructions are only shown when the
+/// containing function start is known and we can decode forward from there.
+fn instructions_around(
+    process: &Process,
+    maps: &[MemoryRegion],
+    pc: u64,
+    syntax: Syntax,
+) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if let Some(location) = Annotator::new(maps)
+        .symbol(pc)
+        .filter(|location| location.offset <= MAX_DECODE_BACK)
+    {
+        let start = pc - location.offset;
+        let before: Vec<_> = disassemble(process, start, location.offset as usize, syntax)
+            .unwrap_or_default()
+            .into_iter()
+            .take_while(|instruction| instruction.address < pc)
+            .collect();
+        let skip = before.len().saturating_sub(INSTRUCTIONS_BEFORE_PC);
+        instructions.extend(before.into_iter().skip(skip));
+    }
+    instructions.extend(disassemble(process, pc, INSTRUCTIONS_FROM_PC, syntax).unwrap_or_default());
+    instructions
+}
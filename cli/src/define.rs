@@ -0,0 +1,124 @@
+//! `define`: user commands made of other commands, with `$arg0`..`$argN` replaced by their
+//! arguments and `$argc` by how many there are. `settings save` keeps them in the config file.
+
+use crate::error::{BuiltinCommandSnafu, MissingUserArgumentSnafu, Result};
+use crate::hooks::command_name;
+use snafu::OptionExt as _;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// How deeply user commands may run each other, which stops runaway recursion.
+pub(crate) const MAX_DEPTH: usize = 16;
+
+/// User commands by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct UserCommands {
+    commands: BTreeMap<String, Vec<String>>,
+}
+
+impl UserCommands {
+    /// Defines `name` to run `body`, replacing any previous definition. An empty body
+    /// removes the command.
+    ///
+    /// # Returns
+    /// `true` if `name` was defined before.
+    pub(crate) fn define(&mut self, name: String, body: Vec<String>) -> bool {
+        if body.is_empty() {
+            self.commands.remove(&name).is_some()
+        } else {
+            self.commands.insert(name, body).is_some()
+        }
+    }
+
+    /// Returns the lines run by `name`.
+    pub(crate) fn get(&self, name: &str) -> Option<&[String]> {
+        self.commands.get(name).map(Vec::as_slice)
+    }
+
+    /// Renders the definitions of `name`, or of all commands, as `define ... end` blocks.
+    pub(crate) fn render(&self, name: Option<&str>) -> String {
+        let mut text = String::new();
+        for (command, body) in &self.commands {
+            if name.is_some_and(|name| name != command) {
+                continue;
+            }
+            let _ = writeln!(text, "define {command}");
+            for line in body {
+                let _ = writeln!(text, "  {line}");
+            }
+            text.push_str("end\n");
+        }
+        text
+    }
+}
+
+/// A definition being typed, until a line saying just `end`.
+#[derive(Debug)]
+pub(crate) struct Definition {
+    pub name: String,
+    pub body: Vec<String>,
+}
+
+/// Checks that `name` can be defined, that is, does not name a built-in command.
+///
+/// # Errors
+/// Returns an error if `name` is a built-in command or one of its aliases.
+pub(crate) fn check_name(name: &str) -> Result<()> {
+    if command_name(name).is_ok() {
+        return BuiltinCommandSnafu { name }.fail();
+    }
+    Ok(())
+}
+
+/// Replaces `$argc` and `$arg0`..`$argN` in a `line` of user command `name` run with `args`.
+///
+/// # Errors
+/// Returns an error if `line` uses an argument that was not given.
+pub(crate) fn substitute(name: &str, line: &str, args: &[String]) -> Result<String> {
+    let mut expanded = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find("$arg") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + "$arg".len()..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if let Some(tail) = after.strip_prefix('c') {
+            expanded.push_str(&args.len().to_string());
+            rest = tail;
+        } else if let Ok(index) = after[..digits].parse::<usize>() {
+            let arg = args.get(index).context(MissingUserArgumentSnafu {
+                name,
+                index,
+                count: args.len(),
+            })?;
+            expanded.push_str(arg);
+            rest = &after[digits..];
+        } else {
+            expanded.push_str("$arg");
+            rest = after;
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_substitute_arguments() -> Result<()> {
+        let args = ["0x10".to_string(), "main".to_string()];
+        assert_eq!(
+            substitute("peek", "mem view $arg1 $arg0 # $argc", &args)?,
+            "mem view main 0x10 # 2"
+        );
+        assert_eq!(
+            substitute("peek", "echo $argument", &args)?,
+            "echo $argument"
+        );
+        assert!(substitute("peek", "bt --limit $arg2", &args).is_err());
+        assert!(check_name("bt").is_err());
+        assert!(check_name("peek").is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! `mem dump`/`mem restore`: copying inferior memory to and from files.
+
+use crate::error::{IoErrWithPathSnafu, NoMappingSnafu, Result};
+use sdb::process::Process;
+use sdb::procfs::{read_maps, MemoryRegion};
+use snafu::ResultExt as _;
+use std::path::Path;
+
+/// Saves `len` bytes at `addr` to `file`.
+///
+/// # Returns
+/// The number of bytes saved, fewer than `len` if the range runs into unmapped memory.
+pub(crate) fn dump_range(process: &Process, addr: u64, len: u64, file: &Path) -> Result<usize> {
+    let bytes = process.read_memory(addr, len as usize)?;
+    std::fs::write(file, &bytes).context(IoErrWithPathSnafu { path: file })?;
+    Ok(bytes.len())
+}
+
+/// Saves the span covering every mapping of `name` to `file`, with unreadable parts
+/// (e.g. guard pages between them) zero-filled.
+///
+/// # Returns
+/// The start address and length of the span.
+pub(crate) fn dump_mapping(process: &Process, name: &str, file: &Path) -> Result<(u64, usize)> {
+    let maps = read_maps(process.pid)?;
+    let regions: Vec<_> = maps
+        .iter()
+        .filter(|region| mapping_matches(region, name))
+        .collect();
+    let (Some(first), Some(last)) = (regions.first(), regions.last()) else {
+        return NoMappingSnafu { name }.fail();
+    };
+    let start = first.start;
+    let mut bytes = vec![0; (last.end - start) as usize];
+    for region in regions.iter().filter(|region| region.read) {
+        let data = process
+            .read_memory(region.start, (region.end - region.start) as usize)
+            .unwrap_or_default();
+        let offset = (region.start - start) as usize;
+        bytes[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    std::fs::write(file, &bytes).context(IoErrWithPathSnafu { path: file })?;
+    Ok((start, bytes.len()))
+}
+
+/// Writes the contents of `file` at `addr`.
+///
+/// # Returns
+/// The number of bytes written.
+pub(crate) fn restore(process: &Process, file: &Path, addr: u64) -> Result<usize> {
+    let bytes = std::fs::read(file).context(IoErrWithPathSnafu { path: file })?;
+    process.write_memory(addr, &bytes)?;
+    Ok(bytes.len())
+}
+
+/// Whether `region` is a mapping of the file or pseudo mapping called `name`.
+///
+/// `heap` matches `[heap]`, and `libc.so.6` matches `/usr/lib/x86_64-linux-gnu/libc.so.6`.
+pub(crate) fn mapping_matches(region: &MemoryRegion, name: &str) -> bool {
+    !region.path.is_empty()
+        && (region.path == name
+            || region.path == format!("[{name}]")
+            || Path::new(&region.path)
+                .file_name()
+                .is_some_and(|file| file == name))
+}
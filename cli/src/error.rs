@@ -13,6 +13,103 @@ pub enum Error {
     #[snafu(display("{source}: {}", path.display()))]
     IoErrWithPath { source: io::Error, path: PathBuf },
 
+    /// The program is not being run.
+    NoProcessError,
+
+    /// The attached process cannot be restarted. Only launched programs support `run`.
+    NotLaunchedError,
+
+    /// Unbalanced quotes in command line.
+    UnbalancedQuotesError,
+
+    /// Failed to run `{command}`: {source}
+    ShellError { command: String, source: io::Error },
+
+    /// Usage: pipe <command> | <shell command>
+    PipeSyntaxError,
+
+    /// !{event}: event not found
+    HistoryEventNotFoundError { event: String },
+
+    /// Cannot locate the config file: neither $XDG_CONFIG_HOME nor $HOME is set.
+    NoConfigPathError,
+
+    /// No command named `{name}`. Type `help` to list commands.
+    UnknownCommandError { name: String },
+
+    /// `{name}` is a built-in command and cannot be redefined.
+    BuiltinCommandError { name: String },
+
+    /// `{name}` uses $arg{index}, but was given {count} arguments.
+    MissingUserArgumentError {
+        name: String,
+        index: usize,
+        count: usize,
+    },
+
+    /// `{name}` runs user commands more than 16 levels deep; does it run itself?
+    UserCommandDepthError { name: String },
+
+    /// Cannot evaluate `{text}`: {msg}.
+    ExpressionError { text: String, msg: String },
+
+    /// `${name}` cannot be a convenience variable: names are letters, digits and `_`, and not registers.
+    InvalidVariableNameError { name: String },
+
+    /// Bad format `{format}`: {msg}.
+    PrintfFormatError { format: String, msg: String },
+
+    /// `{format}` takes {needed} arguments, but was given {given}.
+    PrintfArgumentsError {
+        format: String,
+        needed: usize,
+        given: usize,
+    },
+
+    /// No setting named `{name}`. Type `show` to list settings.
+    UnknownSettingError { name: String },
+
+    /// Invalid value `{value}` for `{name}`: expected {expected}.
+    InvalidSettingValueError {
+        name: String,
+        value: String,
+        expected: String,
+    },
+
+    /// Failed to record the session output: {source}
+    RecordError { source: io::Error },
+
+    /// {} does not name a program to launch; pass one after `--replay <FILE>`.
+    #[snafu(display("{} does not name a program to launch; pass one after `--replay <FILE>`.", path.display()))]
+    NoReplayProgramError { path: PathBuf },
+
+    /// Cannot decode an instruction at {addr:#x}.
+    UndecodableError { addr: u64 },
+
+    /// No mapping named `{name}`. Type `!cat /proc/<pid>/maps` to list them.
+    NoMappingError { name: String },
+
+    /// No snapshot named `{name}`. Take one with `mem snapshot`.
+    NoSnapshotError { name: String },
+
+    /// No scan in progress. Start one with `scan start`.
+    NoScanError,
+
+    /// `{text}` is neither a valid {ty} value nor a `scan next` filter.
+    InvalidScanValueError { text: String, ty: String },
+
+    /// No catchpoint number {id}.
+    NoCatchpointError { id: usize },
+
+    /// No function named `{name}` in the loaded files.
+    NoFunctionError { name: String },
+
+    /// The program has no GOT slot for `{symbol}`. Type `info got` to list them.
+    NoGotEntryError { symbol: String },
+
+    /// No frame #{index}: following frame pointers found {count}. Type `backtrace` to list them.
+    NoFrameError { index: usize, count: usize },
+
     #[snafu(transparent)]
     ReadlineError {
         source: rustyline::error::ReadlineError,
@@ -24,6 +121,13 @@ pub enum Error {
     #[snafu(transparent)]
     SdbError { source: sdb::error::SdbError },
 
+    /// No branches are being sampled; start with `lbr start`.
+    NoLbrError,
+
+    /// No Intel PT trace; start one with `pt start`.
+    #[cfg(feature = "intel-pt")]
+    NoPtTraceError,
+
     /// Tracing log error
     #[cfg(feature = "tracing")]
     #[snafu(transparent)]
@@ -32,5 +136,15 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Suggests what to do about the error, see [`sdb::error::SdbError::hint`].
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::SdbError { source } => source.hint(),
+            _ => None,
+        }
+    }
+}
+
 /// `Result` for `sdb`(CLI) wrapper crate.
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -0,0 +1,251 @@
+//! Integer expressions for `print` and numeric arguments, such as `$base + 0x1234`.
+//!
+//! `$name` refers to a convenience variable made with `set $name = ...`, or else to a
+//! register of the stopped program. `$N` is the `N`th value printed, `$` the last one, `$$`
+//! the one before and `$$N` the one `N` before the last.
+
+use crate::error::{ExpressionSnafu, InvalidVariableNameSnafu, Result};
+use nix::libc::user_regs_struct;
+use sdb::process::register_values;
+use std::collections::BTreeMap;
+
+/// Convenience variables and value history that expressions refer to.
+#[derive(Debug, Default)]
+pub(crate) struct Environment {
+    /// Variables by name, without the `$`.
+    variables: BTreeMap<String, u64>,
+    /// Values printed by `print`; `$1` is the first.
+    history: Vec<u64>,
+}
+
+impl Environment {
+    /// Sets the variable `$name`.
+    ///
+    /// # Errors
+    /// Returns an error if `name` is not an identifier, or is the name of a register.
+    pub(crate) fn assign(&mut self, name: &str, value: u64) -> Result<()> {
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let is_register = register_values(&empty_registers())
+            .iter()
+            .any(|(register, _)| *register == name);
+        if !is_identifier || is_register {
+            return InvalidVariableNameSnafu { name }.fail();
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Appends `value` to the value history.
+    ///
+    /// # Returns
+    /// Its number, for `$N`.
+    pub(crate) fn record(&mut self, value: u64) -> usize {
+        self.history.push(value);
+        self.history.len()
+    }
+
+    /// Returns the variables in name order.
+    pub(crate) fn variables(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Evaluates `text`, reading `$rip` and the like from `registers` if given.
+    ///
+    /// # Errors
+    /// Returns an error if `text` is malformed, divides by zero or uses an unknown `$` name.
+    pub(crate) fn evaluate(&self, text: &str, registers: Option<&user_regs_struct>) -> Result<u64> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            environment: self,
+            registers,
+        };
+        let value = parser.expression(0).and_then(|value| {
+            parser.skip_spaces();
+            parser
+                .rest()
+                .chars()
+                .next()
+                .map_or(Ok(value), |c| Err(format!("unexpected `{c}`")))
+        });
+        value.map_err(|msg| ExpressionSnafu { text, msg }.build())
+    }
+
+    /// Resolves `$name`, where `name` is what follows the `$`.
+    fn lookup(&self, name: &str, registers: Option<&user_regs_struct>) -> Result<u64, String> {
+        let (back, number) = name
+            .strip_prefix('$')
+            .map_or((false, name), |number| (true, number));
+        if number.is_empty() || number.chars().all(|c| c.is_ascii_digit()) {
+            let index = match (back, number.parse::<usize>()) {
+                (false, Ok(n)) => n.checked_sub(1),
+                (false, Err(_)) => self.history.len().checked_sub(1),
+                (true, Ok(n)) => self.history.len().checked_sub(n + 1),
+                (true, Err(_)) => self.history.len().checked_sub(2),
+            };
+            return index
+                .and_then(|index| self.history.get(index).copied())
+                .ok_or_else(|| format!("history has no value ${name}"));
+        }
+        if let Some(value) = self.variables.get(name) {
+            return Ok(*value);
+        }
+        registers
+            .and_then(|regs| {
+                register_values(regs)
+                    .into_iter()
+                    .find(|(register, _)| *register == name)
+            })
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("no variable or register ${name}"))
+    }
+}
+
+/// Binary operators from loosest to tightest binding.
+const OPERATORS: [(&str, u8); 10] = [
+    ("|", 0),
+    ("^", 1),
+    ("&", 2),
+    ("<<", 3),
+    (">>", 3),
+    ("+", 4),
+    ("-", 4),
+    ("*", 5),
+    ("/", 5),
+    ("%", 5),
+];
+
+/// Recursive descent over `text`, with wrapping 64-bit arithmetic.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    environment: &'a Environment,
+    registers: Option<&'a user_regs_struct>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Parses operators binding at least as tightly as `precedence`.
+    fn expression(&mut self, precedence: u8) -> Result<u64, String> {
+        let mut left = self.unary()?;
+        loop {
+            self.skip_spaces();
+            let Some(&(operator, binding)) = OPERATORS.iter().find(|(operator, binding)| {
+                *binding >= precedence && self.rest().starts_with(operator)
+            }) else {
+                return Ok(left);
+            };
+            self.pos += operator.len();
+            let right = self.expression(binding + 1)?;
+            left = match operator {
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "<<" => left.wrapping_shl(right as u32),
+                ">>" => left.wrapping_shr(right as u32),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" => left.checked_div(right).ok_or("division by zero")?,
+                _ => left.checked_rem(right).ok_or("division by zero")?,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<u64, String> {
+        self.skip_spaces();
+        let Some(first) = self.rest().chars().next() else {
+            return Err("missing value".to_string());
+        };
+        self.pos += first.len_utf8();
+        match first {
+            '-' => Ok(self.unary()?.wrapping_neg()),
+            '~' => Ok(!self.unary()?),
+            '(' => {
+                let value = self.expression(0)?;
+                self.skip_spaces();
+                if !self.rest().starts_with(')') {
+                    return Err("missing `)`".to_string());
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            '$' => {
+                let rest = self.rest();
+                let back = usize::from(rest.starts_with('$'));
+                let len = back
+                    + rest[back..]
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len() - back);
+                let name = &rest[..len];
+                self.pos += len;
+                self.environment.lookup(name, self.registers)
+            }
+            '0'..='9' => {
+                self.pos -= 1;
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                let literal = &rest[..len];
+                self.pos += len;
+                let parsed = literal
+                    .strip_prefix("0x")
+                    .map_or_else(|| literal.parse(), |hex| u64::from_str_radix(hex, 16));
+                parsed.map_err(|err| format!("{err}: `{literal}`"))
+            }
+            c => Err(format!("unexpected `{c}`")),
+        }
+    }
+}
+
+const fn empty_registers() -> user_regs_struct {
+    // SAFETY: `user_regs_struct` is plain integers, for which all zeroes is valid.
+    unsafe { std::mem::zeroed() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_evaluate_with_variables_and_history() -> Result<()> {
+        let mut environment = Environment::default();
+        environment.assign("base", 0x5555_5555_4000)?;
+        assert_eq!(
+            environment.evaluate("$base + 0x1234", None)?,
+            0x5555_5555_5234
+        );
+        assert_eq!(environment.evaluate("2 + 3 * (4 - 1) << 1", None)?, 22);
+        assert_eq!(environment.evaluate("-1 & 0xff", None)?, 0xff);
+
+        environment.record(10);
+        environment.record(20);
+        assert_eq!(environment.evaluate("$ + $$ + $1 + $$1", None)?, 50);
+
+        let mut registers = empty_registers();
+        registers.rsp = 0x7ff0;
+        assert_eq!(environment.evaluate("$rsp+8", Some(&registers))?, 0x7ff8);
+
+        assert!(environment.evaluate("$nope", None).is_err());
+        assert!(environment.evaluate("1 / 0", None).is_err());
+        assert!(environment.evaluate("open$", None).is_err());
+        assert!(environment.assign("rip", 0).is_err());
+        assert!(environment.assign("1x", 0).is_err());
+        Ok(())
+    }
+}
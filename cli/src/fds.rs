@@ -0,0 +1,25 @@
+//! `info fds`: the open file descriptors of the inferior, with their offsets and flags.
+
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::read_fds;
+
+/// Renders a header and one line per open file descriptor.
+pub(crate) fn info(process: &Process) -> Result<Vec<String>> {
+    let fds = read_fds(process.pid)?;
+    if fds.is_empty() {
+        return Ok(vec!["No file descriptor is open.".to_string()]);
+    }
+    let mut lines = vec![format!(
+        "{:>4} {:>10}  {:<32} Target",
+        "Fd", "Offset", "Flags"
+    )];
+    lines.extend(fds.iter().map(|fd| {
+        let (offset, flags) = fd.info.map_or_else(
+            || ("?".to_string(), "?".to_string()),
+            |info| (info.pos.to_string(), info.flag_names().join("|")),
+        );
+        format!("{:>4} {offset:>10}  {flags:<32} {}", fd.fd, fd.target)
+    }));
+    Ok(lines)
+}
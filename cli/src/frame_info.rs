@@ -0,0 +1,74 @@
+//! `info frame`: how a frame of the backtrace was found and where its caller's registers are.
+
+use crate::error::{NoFrameSnafu, Result};
+use sdb::annotate::Annotator;
+use sdb::process::Process;
+use sdb::procfs::read_maps;
+use sdb::stack::{frame_pointer_chain, FrameKind};
+use snafu::OptionExt as _;
+
+/// Deepest frame `info frame` looks for.
+const MAX_FRAMES: usize = 256;
+/// Callee-saved registers only CFI tells the slots of.
+const CALLEE_SAVED: [&str; 5] = ["rbx", "r12", "r13", "r14", "r15"];
+
+/// Describes frame `index` of the traced thread: its CFA, the slots its caller's registers
+/// were saved in and how the unwinder found it.
+pub(crate) fn info(process: &Process, index: usize) -> Result<Vec<String>> {
+    let regs = process.read_registers()?;
+    let frames = frame_pointer_chain(process, &regs, MAX_FRAMES);
+    let frame = frames.get(index).context(NoFrameSnafu {
+        index,
+        count: frames.len(),
+    })?;
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let annotator = Annotator::new(&maps).with_jit(process.jit_objects());
+    let read = |slot: u64| {
+        process
+            .read_words(slot, 1)
+            .ok()
+            .and_then(|words| words.first().copied())
+            .map_or_else(|| "unreadable".to_string(), |value| format!("{value:#x}"))
+    };
+
+    let mut lines = vec![
+        format!("Stack frame #{index} at {}", annotator.annotate(frame.pc)),
+        format!("  Found by: {}", frame.kind),
+        format!("  CFA: {:#x}", frame.cfa()),
+    ];
+    let (return_label, saved_label) = match frame.kind {
+        FrameKind::FramePointer => (
+            "Return address",
+            "Saved registers of the caller:".to_string(),
+        ),
+        FrameKind::Signal { .. } => (
+            "Interrupted at",
+            format!(
+                "Registers of the interrupted frame, in the ucontext at {:#x}:",
+                frame.frame_pointer
+            ),
+        ),
+    };
+    lines.push(format!(
+        "  {return_label}: {} in slot {:#x}",
+        read(frame.return_address_slot()),
+        frame.return_address_slot()
+    ));
+    if let Some(caller) = frames.get(index + 1) {
+        lines.push(format!(
+            "  Caller: frame #{} at {:#x}",
+            caller.index, caller.pc
+        ));
+    }
+    lines.push(format!("  {saved_label}"));
+    for (name, slot) in frame.saved_registers() {
+        lines.push(format!("    {name:<6} at {slot:#x} = {}", read(slot)));
+    }
+    if frame.kind == FrameKind::FramePointer {
+        lines.push(format!(
+            "  {}: not recovered; where a function saved them is only in CFI.",
+            CALLEE_SAVED.join(", ")
+        ));
+    }
+    Ok(lines)
+}
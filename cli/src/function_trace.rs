@@ -0,0 +1,139 @@
+//! `trace functions`: printing the program's calls as an indented tree while it runs.
+
+use crate::error::Result;
+use sdb::function_trace::{FunctionTracer, TraceEvent};
+use sdb::process::Process;
+use std::time::Duration;
+
+/// A running trace and the tree printed so far.
+pub(crate) struct FunctionTrace {
+    tracer: FunctionTracer,
+    tree: CallTree,
+}
+
+impl FunctionTrace {
+    pub(crate) const fn new(tracer: FunctionTracer) -> Self {
+        Self {
+            tracer,
+            tree: CallTree { pending: None },
+        }
+    }
+
+    /// Resumes the process like [`Process::resume`], printing the calls and returns on the way.
+    pub(crate) fn resume(&mut self, process: &mut Process) -> Result<()> {
+        let tree = &mut self.tree;
+        self.tracer.resume(process, |event| {
+            for line in tree.event(event) {
+                println!("{line}");
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Prints the call still open, and removes the instrumentation.
+    pub(crate) fn finish(mut self, process: &mut Process) -> Result<()> {
+        if let Some(line) = self.tree.finish() {
+            println!("{line}");
+        }
+        self.tracer.finish(process)?;
+        Ok(())
+    }
+}
+
+/// Turns trace events into lines, printing a call that returns before calling anything on a
+/// single line.
+#[derive(Debug, Default)]
+struct CallTree {
+    /// Line of the last call, until it is known whether it calls anything.
+    pending: Option<String>,
+}
+
+impl CallTree {
+    /// Returns the lines that `event` completes.
+    fn event(&mut self, event: TraceEvent) -> Vec<String> {
+        match event {
+            TraceEvent::Entry { depth, name, args } => {
+                let args: Vec<_> = args.iter().map(|arg| format!("{arg:#x}")).collect();
+                let line = format!("{}{name}({})", indent(depth), args.join(", "));
+                let lines = self.finish().into_iter().collect();
+                self.pending = Some(line);
+                lines
+            }
+            TraceEvent::Return {
+                depth,
+                value,
+                duration,
+                ..
+            } => {
+                let result = value.map_or_else(
+                    || " (left without returning)".to_string(),
+                    |value| format!(" = {value:#x}"),
+                );
+                // A call returning before calling anything stays on its line.
+                let head = self
+                    .pending
+                    .take()
+                    .unwrap_or_else(|| format!("{}}}", indent(depth)));
+                vec![format!("{head}{result}  {}", format_time(duration))]
+            }
+        }
+    }
+
+    /// Returns the line of a call whose callees follow, opening its block.
+    fn finish(&mut self) -> Option<String> {
+        self.pending.take().map(|call| format!("{call} {{"))
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+/// Formats e.g. `12.3 us` or `4.567 ms`.
+fn format_time(duration: Duration) -> String {
+    if duration < Duration::from_millis(1) {
+        format!("{:.1} us", duration.as_secs_f64() * 1e6)
+    } else {
+        format!("{:.3} ms", duration.as_secs_f64() * 1e3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_print_leaf_calls_on_one_line() {
+        let mut tree = CallTree::default();
+        let entry = |depth, name: &str| TraceEvent::Entry {
+            depth,
+            name: name.into(),
+            args: vec![depth as u64],
+        };
+        let exit = |depth, value| TraceEvent::Return {
+            depth,
+            name: String::new(),
+            value,
+            duration: Duration::from_micros(1500),
+        };
+        let mut lines = tree.event(entry(0, "main"));
+        lines.extend(tree.event(entry(1, "spin")));
+        lines.extend(tree.event(exit(1, Some(5))));
+        lines.extend(tree.event(entry(1, "jump")));
+        lines.extend(tree.event(entry(2, "deep")));
+        lines.extend(tree.event(exit(2, None)));
+        lines.extend(tree.event(exit(1, Some(0))));
+        lines.extend(tree.finish());
+        assert_eq!(
+            lines,
+            [
+                "main(0x0) {",
+                "  spin(0x1) = 0x5  1.500 ms",
+                "  jump(0x1) {",
+                "    deep(0x2) (left without returning)  1.500 ms",
+                "  } = 0x0  1.500 ms",
+            ]
+        );
+        assert_eq!(format_time(Duration::from_nanos(12_345)), "12.3 us");
+    }
+}
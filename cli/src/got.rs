@@ -0,0 +1,117 @@
+//! `info got` and `watch got`: the GOT slots of the program or a library at runtime.
+
+use crate::dump::mapping_matches;
+use crate::error::{NoGotEntrySnafu, NoMappingSnafu, Result};
+use sdb::annotate::Annotator;
+use sdb::got::{Got, GotEntry, GotKind};
+use sdb::process::Process;
+use sdb::procfs::{find_region, read_maps, MemoryRegion};
+use sdb::symbols::SymbolTable;
+use snafu::OptionExt as _;
+use std::ops::Range;
+use std::path::Path;
+
+/// The GOT of a loaded module.
+struct LoadedGot<'a> {
+    module: &'a MemoryRegion,
+    /// Slots with their runtime addresses.
+    entries: Vec<(u64, GotEntry)>,
+    /// The module's PLT, at link-time addresses.
+    plt: Vec<Range<u64>>,
+    bias: u64,
+}
+
+/// Lists the GOT of `lib`, or of the program, one slot per line with what it points to.
+///
+/// Function slots pointing outside the code of a mapped file, and data slots pointing outside
+/// any mapping, are flagged as possibly hijacked.
+pub(crate) fn info(process: &Process, lib: Option<&str>) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid)?;
+    let got = load(&maps, lib)?;
+    if got.entries.is_empty() {
+        return Ok(vec![format!("{} has no GOT slots.", got.module.path)]);
+    }
+    let addrs: Vec<_> = got.entries.iter().map(|(addr, _)| (*addr, 8)).collect();
+    let values = process.read_memory_batch(&addrs)?;
+    let mut lines = Vec::with_capacity(got.entries.len());
+    for ((addr, entry), bytes) in got.entries.iter().zip(values) {
+        let Ok(word) = <[u8; 8]>::try_from(bytes.as_slice()) else {
+            lines.push(format!("{addr:#x}  {:<24}  (unreadable)", entry.symbol));
+            continue;
+        };
+        let target = u64::from_le_bytes(word);
+        let note = check_target(&maps, &got, entry.kind, target)
+            .map(|note| format!("  {note}"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{addr:#x}  {:<24}  {}{note}",
+            entry.symbol,
+            Annotator::new(&maps).annotate(target)
+        ));
+    }
+    Ok(lines)
+}
+
+/// Finds the runtime address of the program's GOT slot for `symbol`.
+pub(crate) fn slot_of(process: &Process, symbol: &str) -> Result<u64> {
+    let maps = read_maps(process.pid)?;
+    let got = load(&maps, None)?;
+    got.entries
+        .iter()
+        .find(|(_, entry)| entry.symbol == symbol)
+        .map(|(addr, _)| *addr)
+        .context(NoGotEntrySnafu { symbol })
+}
+
+/// Reads the GOT of the module named `lib`, or of the first mapped file.
+fn load<'a>(maps: &'a [MemoryRegion], lib: Option<&str>) -> Result<LoadedGot<'a>> {
+    let module = match lib {
+        Some(name) => maps
+            .iter()
+            .find(|region| mapping_matches(region, name))
+            .context(NoMappingSnafu { name })?,
+        None => maps
+            .iter()
+            .find(|region| region.path.starts_with('/'))
+            .context(NoMappingSnafu { name: "program" })?,
+    };
+    let path = Path::new(&module.path);
+    let table = SymbolTable::cached(path)?;
+    let bias = table.load_bias(module).unwrap_or_default();
+    let got = Got::load(path)?;
+    let entries = got
+        .entries
+        .into_iter()
+        .map(|entry| (entry.slot.wrapping_add(bias), entry))
+        .collect();
+    Ok(LoadedGot {
+        module,
+        entries,
+        plt: got.plt,
+        bias,
+    })
+}
+
+/// Explains a surprising GOT slot value, if it is one.
+fn check_target(
+    maps: &[MemoryRegion],
+    got: &LoadedGot<'_>,
+    kind: GotKind,
+    target: u64,
+) -> Option<&'static str> {
+    // Lazy binding leaves the slot pointing back into the module's own PLT, at its link-time
+    // address until the dynamic loader has relocated the module.
+    let in_plt = |addr: u64| got.plt.iter().any(|range| range.contains(&addr));
+    let region = find_region(maps, target);
+    match kind {
+        _ if target == 0 => Some("(null: not bound yet, or a missing weak symbol)"),
+        GotKind::Function if in_plt(target) || in_plt(target.wrapping_sub(got.bias)) => {
+            Some("(not resolved yet)")
+        }
+        GotKind::Function => match region {
+            Some(region) if region.execute && region.path.starts_with('/') => None,
+            _ => Some("!! points outside library code"),
+        },
+        GotKind::Data => region.is_none().then_some("!! points outside any mapping"),
+    }
+}
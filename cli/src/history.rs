@@ -0,0 +1,72 @@
+//! Command history: `!!`/`!prefix` expansion and the history file location.
+
+use crate::error::{HistoryEventNotFoundSnafu, Result};
+use rustyline::history::{History, SearchDirection};
+
+/// Default number of remembered commands.
+pub(crate) const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// Expands `!!` to the previous command and `!prefix` to the latest command starting with `prefix`.
+///
+/// # Returns
+/// `None` if `line` is not a history reference.
+///
+/// # Errors
+/// Returns an error if no command matches.
+pub(crate) fn expand(line: &str, history: &dyn History) -> Result<Option<String>> {
+    let Some(event) = line.trim_start().strip_prefix('!') else {
+        return Ok(None);
+    };
+    let (prefix, rest) = event.split_once(' ').unwrap_or((event, ""));
+    let prefix = if prefix == "!" { "" } else { prefix };
+    let found = (0..history.len()).rev().find_map(|index| {
+        let entry = history.get(index, SearchDirection::Reverse).ok()??.entry;
+        entry.starts_with(prefix).then(|| entry.into_owned())
+    });
+    let Some(entry) = found else {
+        return HistoryEventNotFoundSnafu { event: prefix }.fail();
+    };
+    Ok(Some(if rest.is_empty() {
+        entry
+    } else {
+        format!("{entry} {rest}")
+    }))
+}
+
+/// Returns `$XDG_DATA_HOME/sdb/history`, falling back to `~/.local/share/sdb/history`.
+#[cfg(feature = "file-history")]
+pub(crate) fn history_path() -> Option<std::path::PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/share"))
+        })?;
+    Some(data_home.join("sdb").join("history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::MemHistory;
+
+    #[test]
+    fn should_expand_history_events() -> Result<()> {
+        let mut history = MemHistory::new();
+        for entry in ["catch syscall openat", "continue", "backtrace --all"] {
+            history.add(entry)?;
+        }
+        assert_eq!(expand("!!", &history)?.as_deref(), Some("backtrace --all"));
+        assert_eq!(
+            expand("!cat", &history)?.as_deref(),
+            Some("catch syscall openat")
+        );
+        assert_eq!(
+            expand("!back | head", &history)?.as_deref(),
+            Some("backtrace --all | head")
+        );
+        assert_eq!(expand("continue", &history)?, None);
+        assert!(expand("!step", &history).is_err());
+        Ok(())
+    }
+}
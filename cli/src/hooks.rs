@@ -0,0 +1,127 @@
+//! `hook-pre`, `hook-post` and `hook stop`: command lines run automatically around commands and
+//! whenever the program stops.
+
+use crate::args::dbg::DbgArgs;
+use crate::error::{Result, UnknownCommandSnafu};
+use clap::CommandFactory as _;
+use snafu::OptionExt as _;
+use std::collections::BTreeMap;
+
+/// When hook lines run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum HookPoint {
+    /// Before the named command.
+    Pre(String),
+    /// After the named command succeeds.
+    Post(String),
+    /// Whenever the program stops after running.
+    Stop,
+}
+
+/// Hook lines by [`HookPoint`].
+#[derive(Debug, Default)]
+pub(crate) struct Hooks {
+    pre: BTreeMap<String, Vec<String>>,
+    post: BTreeMap<String, Vec<String>>,
+    stop: Vec<String>,
+    /// Set while hook lines run, so that they do not trigger hooks themselves.
+    running: bool,
+}
+
+impl Hooks {
+    /// Replaces the lines run at `point`; no lines remove the hook.
+    pub(crate) fn set(&mut self, point: HookPoint, lines: Vec<String>) {
+        let (hooks, command) = match point {
+            HookPoint::Pre(command) => (&mut self.pre, command),
+            HookPoint::Post(command) => (&mut self.post, command),
+            HookPoint::Stop => {
+                self.stop = lines;
+                return;
+            }
+        };
+        if lines.is_empty() {
+            hooks.remove(&command);
+        } else {
+            hooks.insert(command, lines);
+        }
+    }
+
+    /// Takes the lines to run at `point`, unless hooks are already running.
+    ///
+    /// Call [`Self::finish`] once they have run.
+    pub(crate) fn start(&mut self, point: &HookPoint) -> Vec<String> {
+        if self.running {
+            return Vec::new();
+        }
+        let lines = match point {
+            HookPoint::Pre(command) => self.pre.get(command),
+            HookPoint::Post(command) => self.post.get(command),
+            HookPoint::Stop => Some(&self.stop),
+        };
+        let lines = lines.cloned().unwrap_or_default();
+        self.running = !lines.is_empty();
+        lines
+    }
+
+    /// Marks the hook lines taken by [`Self::start`] as done.
+    pub(crate) const fn finish(&mut self) {
+        self.running = false;
+    }
+
+    /// Describes every hook, one line each, as the command that defines it.
+    pub(crate) fn list(&self) -> Vec<String> {
+        let quoted = |lines: &[String]| {
+            lines
+                .iter()
+                .map(|line| format!("'{line}'"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let pre = self
+            .pre
+            .iter()
+            .map(|(command, lines)| format!("hook-pre {command} {}", quoted(lines)));
+        let post = self
+            .post
+            .iter()
+            .map(|(command, lines)| format!("hook-post {command} {}", quoted(lines)));
+        let stop = (!self.stop.is_empty()).then(|| format!("hook stop {}", quoted(&self.stop)));
+        pre.chain(post).chain(stop).collect()
+    }
+}
+
+/// Returns the name of the command called `name`, which may be an alias such as `bt`.
+///
+/// # Errors
+/// Returns an error if there is no such command.
+pub(crate) fn command_name(name: &str) -> Result<String> {
+    DbgArgs::command()
+        .find_subcommand(name)
+        .map(|command| command.get_name().to_string())
+        .context(UnknownCommandSnafu { name })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_run_hooks_once_at_a_time() -> Result<()> {
+        let mut hooks = Hooks::default();
+        let point = HookPoint::Post(command_name("bt")?);
+        hooks.set(point.clone(), vec!["context".into()]);
+        assert_eq!(hooks.list(), ["hook-post backtrace 'context'"]);
+
+        assert_eq!(hooks.start(&point), ["context"]);
+        // The hook's own commands do not trigger hooks.
+        assert!(hooks.start(&point).is_empty());
+        hooks.finish();
+        assert_eq!(hooks.start(&point), ["context"]);
+        hooks.finish();
+
+        hooks.set(point.clone(), Vec::new());
+        assert!(hooks.start(&point).is_empty() && hooks.list().is_empty());
+        assert!(command_name("frobnicate").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! `pt decode`: printing the blocks of code an Intel PT trace went through.
+
+use sdb::annotate::Annotator;
+use sdb::intel_pt::flow::FlowEvent;
+use sdb::procfs::MemoryRegion;
+
+/// Formats the last `last` events of `flow`, oldest first, with the symbols of the blocks.
+pub(crate) fn format_flow(flow: &[FlowEvent], maps: &[MemoryRegion], last: usize) -> Vec<String> {
+    let location = |addr| {
+        Annotator::new(maps)
+            .symbol(addr)
+            .map(|location| location.to_string())
+            .unwrap_or_default()
+    };
+    flow[flow.len().saturating_sub(last)..]
+        .iter()
+        .map(|event| match *event {
+            FlowEvent::Block { start, end } => {
+                let (from, to) = (location(start), location(end));
+                let symbols = if from == to {
+                    from
+                } else {
+                    format!("{from} .. {to}")
+                };
+                format!("{start:#x}..{end:#x}  {symbols}")
+                    .trim_end()
+                    .to_string()
+            }
+            FlowEvent::Paused => "-- tracing paused --".to_string(),
+            FlowEvent::Lost => "-- trace lost, path before is cut off --".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_format_last_events() {
+        let flow = [
+            FlowEvent::Lost,
+            FlowEvent::Block {
+                start: 0x1000,
+                end: 0x1004,
+            },
+            FlowEvent::Paused,
+        ];
+        assert_eq!(
+            format_flow(&flow, &[], 2),
+            ["0x1000..0x1004", "-- tracing paused --"]
+        );
+    }
+}
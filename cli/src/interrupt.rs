@@ -0,0 +1,35 @@
+//! Ctrl+C while a command runs: stops scans and searches early instead of killing sdb.
+
+use nix::libc::c_int;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use sdb::cancel::CancelToken;
+
+/// Cancelled by Ctrl+C, reset before each command.
+pub(crate) static CANCEL: CancelToken = CancelToken::new();
+
+extern "C" fn on_sigint(_: c_int) {
+    CANCEL.cancel();
+}
+
+/// Makes `SIGINT` cancel [`CANCEL`].
+///
+/// At the prompt the line editor reads Ctrl+C as a key, so this only affects running commands.
+/// Interrupted syscalls are restarted, so `continue` keeps waiting until the inferior, which
+/// gets the same Ctrl+C from the terminal, reports its `SIGINT` stop.
+pub(crate) fn install() {
+    let action = SigAction::new(
+        SigHandler::Handler(on_sigint),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    // SAFETY: The handler only stores to an atomic, which is async-signal-safe.
+    // Without the handler, Ctrl+C keeps its default action, so failing is harmless.
+    let _ = unsafe { sigaction(Signal::SIGINT, &action) };
+}
+
+/// Tells that the command was interrupted and printed partial results, if it was.
+pub(crate) fn note_partial() {
+    if CANCEL.is_cancelled() {
+        println!("Interrupted: the results above are partial.");
+    }
+}
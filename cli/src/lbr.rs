@@ -0,0 +1,65 @@
+//! `lbr show`: printing the last branches sampled before a stop.
+
+use sdb::annotate::Annotator;
+use sdb::lbr::Branch;
+use sdb::procfs::MemoryRegion;
+
+/// Formats the last `last` of `branches`, oldest first, e.g.
+/// `0x401136 main+0x26 -> 0x401000 parse`.
+pub(crate) fn format_branches(
+    branches: &[Branch],
+    maps: &[MemoryRegion],
+    last: usize,
+) -> Vec<String> {
+    let annotator = Annotator::new(maps);
+    let location = |addr: u64| {
+        let symbol = annotator.symbol(addr).map_or_else(String::new, |location| {
+            if location.offset == 0 {
+                format!(" {}", location.name)
+            } else {
+                format!(" {location}")
+            }
+        });
+        format!("{addr:#x}{symbol}")
+    };
+    branches[branches.len().saturating_sub(last)..]
+        .iter()
+        .map(|branch| {
+            let mispredicted = if branch.mispredicted {
+                "  (mispredicted)"
+            } else {
+                ""
+            };
+            format!(
+                "{} -> {}{mispredicted}",
+                location(branch.from),
+                location(branch.to)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_format_last_branches() {
+        let branches = [
+            Branch {
+                from: 0x10,
+                to: 0x20,
+                mispredicted: false,
+            },
+            Branch {
+                from: 0x30,
+                to: 0x40,
+                mispredicted: true,
+            },
+        ];
+        assert_eq!(
+            format_branches(&branches, &[], 1),
+            ["0x30 -> 0x40  (mispredicted)"]
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! Looking up functions of the inferior by name; addresses are described by
+//! [`sdb::annotate::Annotator`].
+
+use sdb::procfs::MemoryRegion;
+use sdb::symbols::SymbolTable;
+use std::ops::Range;
+use std::path::Path;
+
+/// Finds the runtime addresses of the function `name` in the first mapped file defining it.
+pub(crate) fn find_function(maps: &[MemoryRegion], name: &str) -> Option<Range<u64>> {
+    maps.iter()
+        .filter(|region| Path::new(&region.path).is_absolute())
+        .find_map(|region| {
+            let table = SymbolTable::cached(Path::new(&region.path)).ok()?;
+            let bias = table.load_bias(region)?;
+            let symbol = table
+                .symbols()
+                .iter()
+                .find(|symbol| symbol.is_function && symbol.name == name)?;
+            let start = symbol.address.wrapping_add(bias);
+            Some(start..start + symbol.size.max(1))
+        })
+}
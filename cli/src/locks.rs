@@ -0,0 +1,68 @@
+//! `info locks` and `deadlock check`: threads blocked on futexes and the threads holding them.
+
+use crate::backtrace;
+use nix::unistd::Pid;
+use sdb::error::Result;
+use sdb::locks::{find_deadlocks, futex_waits};
+use sdb::process::Process;
+use sdb::procfs::TaskStat;
+
+/// Renders one line per thread waiting on a futex.
+pub(crate) fn info(process: &Process) -> Result<Vec<String>> {
+    let waits = futex_waits(process)?;
+    if waits.is_empty() {
+        return Ok(vec!["No thread is blocked on a futex.".to_string()]);
+    }
+    Ok(waits
+        .iter()
+        .map(|wait| {
+            let owner = wait.owner.map_or_else(
+                || "no owning thread (condition variable, semaphore or unlocked)".to_string(),
+                |owner| format!("held by thread {}", thread_name(process, owner)),
+            );
+            format!(
+                "Thread {} waits on {:#x} ({}), {owner}",
+                thread_name(process, wait.tid),
+                wait.addr,
+                wait.op_name()
+            )
+        })
+        .collect())
+}
+
+/// Reports cycles of threads waiting on each other's mutexes, with the owners' backtraces.
+pub(crate) fn deadlock_check(process: &Process) -> Result<Vec<String>> {
+    let cycles = find_deadlocks(&futex_waits(process)?);
+    if cycles.is_empty() {
+        return Ok(vec!["No deadlock found.".to_string()]);
+    }
+    let mut lines = Vec::new();
+    for cycle in cycles {
+        lines.push(format!("Deadlock between {} threads:", cycle.len()));
+        for wait in &cycle {
+            if let Some(owner) = wait.owner {
+                lines.push(format!(
+                    "  thread {} waits on mutex {:#x} held by thread {}",
+                    thread_name(process, wait.tid),
+                    wait.addr,
+                    thread_name(process, owner)
+                ));
+            }
+        }
+        for wait in &cycle {
+            lines.push(String::new());
+            lines.push(format!("Thread {}:", thread_name(process, wait.tid)));
+            match backtrace::thread(process, wait.tid) {
+                Ok(frames) => lines.extend(frames),
+                Err(err) => lines.push(format!("  {err}")),
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// Formats `tid (comm)`.
+pub(crate) fn thread_name(process: &Process, tid: Pid) -> String {
+    TaskStat::read(process.pid, tid)
+        .map_or_else(|_| tid.to_string(), |stat| format!("{tid} ({})", stat.comm))
+}
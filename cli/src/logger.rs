@@ -1,4 +1,5 @@
 use crate::error::{IoErrWithPathSnafu, Result};
+use sdb::process::PTRACE_LOG_TARGET;
 use snafu::ResultExt;
 use std::fs::File;
 use std::path::Path;
@@ -40,20 +41,24 @@ where
     P: AsRef<Path>,
     L: Into<Level>,
 {
-    use tracing_subscriber::{fmt, layer::SubscriberExt};
+    use tracing_subscriber::{filter::Targets, fmt, layer::SubscriberExt};
 
     if let Some(log_parent) = log_path.as_ref().and_then(|p| p.as_ref().parent()) {
         std::fs::create_dir_all(log_parent).context(IoErrWithPathSnafu {
             path: log_parent.to_path_buf(),
-        });
+        })?;
     }
 
     let subscriber_builder = fmt::Subscriber::builder()
         .compact()
         .with_file(true)
         .with_line_number(true)
-        .with_max_level(filter.into())
+        .with_max_level(Level::TRACE)
         .with_target(false);
+    // `set debug ptrace on` is the switch for ptrace events, so they pass at any level.
+    let targets = Targets::new()
+        .with_default(filter.into())
+        .with_target(PTRACE_LOG_TARGET, Level::TRACE);
 
     if with_stdout {
         if let Some(log_path) = log_path {
@@ -66,7 +71,11 @@ where
                 .with_target(false)
                 .with_writer(log_file);
             tracing::subscriber::set_global_default(
-                subscriber_builder.pretty().finish().with(log_file_config),
+                subscriber_builder
+                    .pretty()
+                    .finish()
+                    .with(log_file_config)
+                    .with(targets),
             )?;
         } else {
             tracing::subscriber::set_global_default(
@@ -75,15 +84,19 @@ where
                     .with_ansi(true)
                     .with_line_number(true)
                     .with_target(false)
-                    .finish(),
+                    .finish()
+                    .with(targets),
             )?;
         }
     } else if let Some(log_path) = log_path {
         let log_file = create_log_file(log_path.as_ref())?;
-        subscriber_builder
-            .with_writer(log_file)
-            .with_ansi(false)
-            .init();
+        tracing::subscriber::set_global_default(
+            subscriber_builder
+                .with_writer(log_file)
+                .with_ansi(false)
+                .finish()
+                .with(targets),
+        )?;
     }
 
     Ok(())
@@ -1,59 +1,1578 @@
+mod apropos;
 mod args;
+mod backtrace;
+mod compile_units;
+mod context;
+mod crash_report;
+mod define;
+mod dump;
 mod error;
+mod expr;
+mod fds;
+mod frame_info;
+mod function_trace;
+mod got;
+mod history;
+mod hooks;
+#[cfg(feature = "intel-pt")]
+mod intel_pt;
+mod interrupt;
+mod lbr;
+mod location;
+mod locks;
 #[cfg(feature = "tracing")]
 mod logger;
+mod pager;
+mod printf;
+mod record;
+mod sched;
+mod settings;
+mod shell;
+mod snapshot;
+mod sockets;
+mod stack_view;
+mod symbol_list;
+mod telescope;
+mod threads;
+mod timing;
 
-use crate::error::Result;
+#[cfg(feature = "intel-pt")]
+use crate::error::NoPtTraceSnafu;
+use crate::error::{
+    ExpressionSnafu, InvalidScanValueSnafu, IoErrWithPathSnafu, NoCatchpointSnafu,
+    NoConfigPathSnafu, NoFunctionSnafu, NoLbrSnafu, NoMappingSnafu, NoProcessSnafu,
+    NoReplayProgramSnafu, NoScanSnafu, NoSnapshotSnafu, NotLaunchedSnafu, PipeSyntaxSnafu,
+    RecordSnafu, Result, UnbalancedQuotesSnafu, UndecodableSnafu, UserCommandDepthSnafu,
+};
 use args::app::AppArgs;
-use args::dbg::{DbgArgs, SubCommand};
-use clap::Parser;
-use nix::sys::wait::WaitStatus;
+#[cfg(feature = "intel-pt")]
+use args::dbg::PtCommand;
+use args::dbg::{
+    CatchCommand, CrashCommand, DbgArgs, DeadlockCommand, GadgetCommand, HookCommand, InfoCommand,
+    LbrCommand, MemCommand, PatchCommand, RecordCommand, ScanCommand, SetCommand, SettingsCommand,
+    ShowCommand, StackCommand, SubCommand, SymCommand, ThreadCommand, TraceCommand, UnsetCommand,
+    WatchCommand,
+};
+use clap::error::ErrorKind;
+use clap::{CommandFactory as _, FromArgMatches as _, Parser};
+use define::Definition;
+use expr::Environment;
+use function_trace::FunctionTrace;
+use hooks::{HookPoint, Hooks};
+use interrupt::CANCEL;
 use nix::unistd::Pid;
-use rustyline::{error::ReadlineError, DefaultEditor};
-use sdb::process::{wait_on_signal, Process};
+use record::{Capture, Recorder, Replay};
+use rustyline::config::Configurer as _;
+use rustyline::{error::ReadlineError, Config, DefaultEditor};
+use sdb::annotate::Annotator;
+use sdb::assembler::assemble;
+use sdb::catchpoint::{
+    CatchAction, Catchpoint, CatchpointKind, ModulePattern, SyscallFilter, SyscallPattern,
+};
+use sdb::disassembler::{decode, disassemble, Instruction, Syntax};
+use sdb::frame_recognizer::{register_frame_recognizer, Wasmtime};
+use sdb::function_trace::{find_functions, FunctionTracer};
+use sdb::gadget::{parse_query, search_gadgets};
+use sdb::hardening::Hardening;
+#[cfg(feature = "intel-pt")]
+use sdb::intel_pt::{flow::reconstruct, PtRecorder};
+use sdb::lbr::LbrRecorder;
+use sdb::output::{format_duration, MemoryDump, StopEvent};
+use sdb::process::{find_container_process, translate_ns_pid, LaunchSpec, Process};
+use sdb::procfs::{find_region, pid_path, read_maps};
+use sdb::sanitizer::SanitizerReport;
+use sdb::scan::{scan_pointers, ScanFilter, ValueScan, ValueType};
+use sdb::symbol_index::{unindexed_files, SymbolIndexer};
+use sdb::symbol_list::{SymbolQuery, SymbolSource};
+use sdb::syscall::describe_with_limit;
+use sdb::watchpoint::WatchKind;
+use settings::{Setting, Settings, Value};
+use snafu::{OptionExt as _, ResultExt as _};
+use snapshot::Snapshot;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write as _};
+use std::ops::Range;
+use std::path::Path;
 use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
+use timing::RunTimer;
 
-fn handle_command(process: &mut Process, line: &str) -> Result<()> {
-    let mut lines = vec![""]; // HACK: Push exe item as dummy.
-    lines.extend(line.split_whitespace());
-    let args = DbgArgs::try_parse_from(lines)?;
+/// One-byte `nop`.
+const NOP: u8 = 0x90;
+/// Frames shown when the watchdog interrupts the program.
+const BACKTRACE_FRAMES: usize = 16;
 
-    match args.sub_command {
-        SubCommand::Continue => {
-            process.resume()?;
-            let status = wait_on_signal(process.pid)?;
-            print_stop_reason(&process.pid, status);
+/// State of a debugging session.
+struct Session {
+    /// Debuggee. `None` once it has terminated.
+    process: Option<Process>,
+    /// What `run` launches. `None` when attached to an existing pid.
+    launch_spec: Option<LaunchSpec>,
+    /// `set`/`show` options.
+    settings: Settings,
+    /// Transcript being written by `record session`.
+    recorder: Option<Recorder>,
+    /// Output of the running command being recorded.
+    capture: Option<Capture>,
+    /// `mem snapshot`s by name.
+    snapshots: HashMap<String, Snapshot>,
+    /// Value scan being narrowed down with `scan next`.
+    scan: Option<ValueScan>,
+    /// Worker loading the symbols of newly mapped files.
+    indexer: Option<SymbolIndexer>,
+    /// Timeout set by `interrupt --after` for the next `continue`.
+    interrupt_after: Option<Duration>,
+    /// Commands run around other commands and at stops.
+    hooks: Hooks,
+    /// User command being typed after `define`.
+    definition: Option<Definition>,
+    /// Number of user commands running, one inside the other.
+    user_command_depth: usize,
+    /// Convenience variables and `print` history.
+    values: Environment,
+    /// Branch sampling of `lbr start`.
+    lbr: Option<LbrRecorder>,
+    /// Branch trace of `pt start`.
+    #[cfg(feature = "intel-pt")]
+    pt: Option<PtRecorder>,
+}
+
+impl Session {
+    /// Starts a session, loading settings from the config file.
+    fn new(process: Process, launch_spec: Option<LaunchSpec>) -> Self {
+        let mut settings = Settings::default();
+        if let Some(path) = settings::config_path() {
+            match settings.load(&path) {
+                Ok(warnings) => warnings.iter().for_each(|warning| eprintln!("{warning}")),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        #[cfg(feature = "tracing")]
+        sdb::process::set_ptrace_logging(settings.debug_ptrace());
+        let mut session = Self {
+            process: Some(process),
+            launch_spec,
+            settings,
+            recorder: None,
+            capture: None,
+            snapshots: HashMap::new(),
+            scan: None,
+            indexer: None,
+            interrupt_after: None,
+            hooks: Hooks::default(),
+            definition: None,
+            user_command_depth: 0,
+            values: Environment::default(),
+            lbr: None,
+            #[cfg(feature = "intel-pt")]
+            pt: None,
+        };
+        session.index_symbols();
+        session
+    }
+
+    fn process(&mut self) -> Result<&mut Process> {
+        self.process.as_mut().context(NoProcessSnafu)
+    }
+
+    fn launch_spec(&mut self) -> Result<&mut LaunchSpec> {
+        self.launch_spec.as_mut().context(NotLaunchedSnafu)
+    }
+
+    /// Prints command output, paging it if it is long.
+    fn print_lines<S: AsRef<str>>(&self, lines: &[S]) {
+        pager::print_lines(lines, self.settings.pagination());
+    }
+
+    /// Reports and forgets the inferior if it was killed while we sat at the prompt, and
+    /// otherwise warns about changes made to it behind our back.
+    fn check_liveness(&mut self) -> Result<()> {
+        let Some(process) = self.process.as_mut() else {
+            return Ok(());
+        };
+        if let Some(exit_status) = process.poll_exit()? {
+            println!("Process {} {exit_status} while stopped", process.pid);
+            self.on_exit();
+        } else if self.settings.check_consistency() {
+            for desync in process.check_consistency()? {
+                eprintln!("warning: {desync}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts loading the symbols of files mapped since the last time in the background,
+    /// unless a load is still going on.
+    fn index_symbols(&mut self) {
+        if self
+            .indexer
+            .as_ref()
+            .is_some_and(|indexer| !indexer.is_finished())
+        {
+            return;
+        }
+        let Some(process) = &self.process else {
+            return;
+        };
+        let paths = read_maps(process.pid)
+            .map(|maps| unindexed_files(&maps))
+            .unwrap_or_default();
+        self.indexer = (!paths.is_empty()).then(|| SymbolIndexer::spawn(paths));
+    }
+
+    /// Tells that `command` has to wait for symbols that are still loading.
+    fn note_symbol_loading(&self, command: &SubCommand) {
+        let Some(indexer) = self.indexer.as_ref() else {
+            return;
+        };
+        if indexer.is_finished() || !uses_symbols(command) {
+            return;
+        }
+        let (done, total) = indexer.progress();
+        println!("Symbols still loading: {}%", done * 100 / total.max(1));
+    }
+
+    /// Runs the hook lines for `point`, unless they are the ones running this command.
+    fn run_hooks(&mut self, point: &HookPoint) {
+        let lines = self.hooks.start(point);
+        if lines.is_empty() {
+            return;
+        }
+        for line in lines {
+            if let Err(err) = handle_command(self, &line) {
+                eprintln!("{err}");
+            }
+        }
+        self.hooks.finish();
+    }
+
+    /// Evaluates `text` with the registers of the stopped program, if there is one.
+    fn evaluate(&self, text: &str) -> Result<u64> {
+        let registers = self
+            .process
+            .as_ref()
+            .and_then(|process| process.read_registers().ok());
+        self.values.evaluate(text, registers.as_ref())
+    }
+
+    /// Replaces the words using `$` values with their hex value, so that any numeric argument
+    /// can take them. Words that do not evaluate, such as the regex `^open$`, stay as they are.
+    fn expand_values(&self, mut words: Vec<String>) -> Vec<String> {
+        let skipped = match words.first().map(String::as_str) {
+            // Their words run later, with the values of then.
+            Some("shell" | "define" | "hook-pre" | "hook-post" | "hook") => return words,
+            // The variable being assigned, or the format.
+            Some("set" | "printf") => 1,
+            _ => 0,
+        };
+        for (index, word) in words.iter_mut().enumerate().skip(1) {
+            if index == skipped || !word.contains('$') {
+                continue;
+            }
+            if let Ok(value) = self.evaluate(word) {
+                *word = format!("{value:#x}");
+            }
+        }
+        words
+    }
+
+    /// Runs `set $name = expression`, given the words after `set`.
+    fn assign_variable(&mut self, words: &[String]) -> Result<()> {
+        let assignment = words.join(" ");
+        let (name, expression) = assignment.split_once('=').context(ExpressionSnafu {
+            text: assignment.as_str(),
+            msg: "expected `$name = value`",
+        })?;
+        let value = self.evaluate(expression)?;
+        let name = name.trim().trim_start_matches('$');
+        self.values.assign(name, value)
+    }
+
+    /// Adds `line` to the user command being defined, or saves the command at `end`.
+    fn continue_definition(&mut self, line: &str) {
+        let line = line.trim();
+        if line != "end" {
+            if let Some(definition) = &mut self.definition {
+                definition.body.push(line.to_string());
+            }
+            return;
+        }
+        let Some(Definition { name, body }) = self.definition.take() else {
+            return;
+        };
+        let removed = body.is_empty();
+        if self.settings.user_commands.define(name.clone(), body) && removed {
+            println!("Removed `{name}`.");
+        }
+    }
+
+    /// Drops the terminated inferior.
+    fn on_exit(&mut self) {
+        self.process = None;
+        if self.launch_spec.is_some() {
+            println!("Type `run` to start the program again.");
+        }
+    }
+}
+
+/// Runs `line`, reporting errors and recording it when `record session` is active.
+fn execute(session: &mut Session, line: &str) {
+    if let Some(recorder) = &session.recorder {
+        match recorder.capture(line).context(RecordSnafu) {
+            Ok(capture) => session.capture = Some(capture),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    if let Err(err) = handle_command(session, line) {
+        eprintln!("{err}");
+        if let Some(hint) = err.hint() {
+            eprintln!("hint: {hint}");
+        }
+    }
+    if let Some(capture) = session.capture.take() {
+        if let Err(err) = capture.finish().context(RecordSnafu) {
+            eprintln!("{err}");
+        }
+    }
+}
+
+fn handle_command(session: &mut Session, line: &str) -> Result<()> {
+    if session.definition.is_some() {
+        session.continue_definition(line);
+        return Ok(());
+    }
+    session.check_liveness()?;
+    CANCEL.reset();
+
+    if let Some(command) = line.trim_start().strip_prefix('!') {
+        return shell::run(command);
+    }
+    let words = shlex::split(line).context(UnbalancedQuotesSnafu)?;
+    run_command(session, words)
+}
+
+/// Parses and runs one already split command line, with its hooks.
+fn run_command(session: &mut Session, words: Vec<String>) -> Result<()> {
+    let words = session.expand_values(words);
+    if let Some((name, args)) = words.split_first() {
+        if let Some(body) = session.settings.user_commands.get(name) {
+            return run_user_command(session, name, body.to_vec(), args);
+        }
+    }
+    let mut lines = vec![String::new()]; // HACK: Push exe item as dummy.
+    lines.extend(words);
+    let matches = match DbgArgs::command().try_get_matches_from(lines) {
+        Ok(matches) => matches,
+        Err(err) if err.kind() == ErrorKind::DisplayHelp => {
+            let help = err.render().to_string();
+            session.print_lines(&help.lines().collect::<Vec<_>>());
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let args = DbgArgs::from_arg_matches(&matches)?;
+    let name = matches.subcommand_name().unwrap_or_default().to_string();
+    let resumes = matches!(args.sub_command, SubCommand::Continue | SubCommand::Run);
+
+    session.run_hooks(&HookPoint::Pre(name.clone()));
+    dispatch(session, args.sub_command)?;
+    if resumes && session.process.is_some() {
+        session.run_hooks(&HookPoint::Stop);
+    }
+    session.run_hooks(&HookPoint::Post(name));
+    Ok(())
+}
+
+/// Runs the `body` of user command `name` with `args`, stopping at the first error.
+fn run_user_command(
+    session: &mut Session,
+    name: &str,
+    body: Vec<String>,
+    args: &[String],
+) -> Result<()> {
+    if session.user_command_depth >= define::MAX_DEPTH {
+        return UserCommandDepthSnafu { name }.fail();
+    }
+    session.user_command_depth += 1;
+    let result = body.iter().try_for_each(|line| {
+        let line = define::substitute(name, line, args)?;
+        handle_command(session, &line)
+    });
+    session.user_command_depth -= 1;
+    result
+}
+
+/// Runs one parsed command.
+fn dispatch(session: &mut Session, command: SubCommand) -> Result<()> {
+    session.note_symbol_loading(&command);
+    match command {
+        SubCommand::Apropos { keyword } => {
+            let matches = apropos::search(&keyword, &session.settings);
+            if matches.is_empty() {
+                println!("No command or setting mentions \"{keyword}\".");
+            }
+            session.print_lines(&matches);
+        }
+        SubCommand::Shell { command } => shell::run(&join_words(&command))?,
+        SubCommand::Printf { format, args } => printf_command(session, &format, &args)?,
+        SubCommand::Print { expression } => {
+            let value = session.evaluate(&expression.join(" "))?;
+            let number = session.values.record(value);
+            println!("${number} = {value:#x} ({value})");
+        }
+        SubCommand::Pipe { words } => {
+            let separator = words.iter().position(|word| word == "|");
+            let (command, shell_command) = match separator {
+                Some(index) if index > 0 && index + 1 < words.len() => words.split_at(index),
+                _ => return PipeSyntaxSnafu.fail(),
+            };
+            let shell_command = join_words(&shell_command[1..]);
+            shell::pipe_to(&shell_command, || run_command(session, command.to_vec()))?;
+        }
+        SubCommand::Continue => continue_program(session, None)?,
+        SubCommand::Trace {
+            command: TraceCommand::Functions { pattern, args },
+        } => trace_functions(session, pattern.as_deref(), args.into())?,
+        SubCommand::Lbr { command } => lbr_command(session, command)?,
+        #[cfg(feature = "intel-pt")]
+        SubCommand::Pt { command } => pt_command(session, command)?,
+        SubCommand::Run => run(session)?,
+        SubCommand::Define { name } => {
+            define::check_name(&name)?;
+            println!("Type commands for `{name}`, then a line saying just `end`.");
+            session.definition = Some(Definition {
+                name,
+                body: Vec::new(),
+            });
+        }
+        SubCommand::HookPre { command, lines } => {
+            let point = HookPoint::Pre(hooks::command_name(&command)?);
+            session.hooks.set(point, lines);
+        }
+        SubCommand::HookPost { command, lines } => {
+            let point = HookPoint::Post(hooks::command_name(&command)?);
+            session.hooks.set(point, lines);
+        }
+        SubCommand::Hook { command } => handle_hook(session, command),
+        SubCommand::Interrupt { after } => {
+            session.interrupt_after = Some(after);
+            println!(
+                "The next `continue` is interrupted after {}.",
+                format_duration(after)
+            );
+        }
+        SubCommand::Set { command } => handle_set(session, command)?,
+        SubCommand::Unset {
+            command: UnsetCommand::Env { name },
+        } => {
+            if session
+                .launch_spec()?
+                .env
+                .remove(OsStr::new(&name))
+                .is_none()
+            {
+                println!("Environment variable `{name}` is not set.");
+            }
+        }
+        SubCommand::Show { command: None } => {
+            let lines: Vec<_> = session.settings.iter().map(format_setting).collect();
+            session.print_lines(&lines);
+        }
+        SubCommand::Show {
+            command: Some(command),
+        } => match command {
+            ShowCommand::Env { name: Some(name) } => {
+                match session.launch_spec()?.env.get(OsStr::new(&name)) {
+                    Some(value) => println!("{name}={}", value.to_string_lossy()),
+                    None => println!("Environment variable `{name}` is not set."),
+                }
+            }
+            ShowCommand::Env { name: None } => {
+                let lines: Vec<_> = session
+                    .launch_spec()?
+                    .env
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("{}={}", name.to_string_lossy(), value.to_string_lossy())
+                    })
+                    .collect();
+                session.print_lines(&lines);
+            }
+            ShowCommand::User { name } => {
+                let text = session.settings.user_commands.render(name.as_deref());
+                if text.is_empty() {
+                    println!("No user commands.");
+                }
+                session.print_lines(&text.lines().collect::<Vec<_>>());
+            }
+            ShowCommand::Convenience => {
+                let lines: Vec<_> = session
+                    .values
+                    .variables()
+                    .map(|(name, value)| format!("${name} = {value:#x} ({value})"))
+                    .collect();
+                if lines.is_empty() {
+                    println!("No convenience variables. Make one with `set $name = value`.");
+                }
+                session.print_lines(&lines);
+            }
+            ShowCommand::Cwd => match &session.launch_spec()?.cwd {
+                Some(cwd) => println!("{}", cwd.display()),
+                None => println!("(the debugger's working directory)"),
+            },
+            ShowCommand::Setting(words) => {
+                println!(
+                    "{}",
+                    format_setting(session.settings.get(&words.join(" "))?)
+                );
+            }
+        },
+        SubCommand::Settings {
+            command: SettingsCommand::Save,
+        } => {
+            let path = settings::config_path().context(NoConfigPathSnafu)?;
+            session.settings.save(&path)?;
+            println!("Settings saved to {}.", path.display());
+        }
+        SubCommand::Crash {
+            command: CrashCommand::Report { output },
+        } => {
+            let syntax = session.settings.disassembly_flavor();
+            let report = crash_report::build(session.process()?, syntax);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, report).context(IoErrWithPathSnafu { path: &path })?;
+                    println!("Crash report written to {}", path.display());
+                }
+                None => session.print_lines(&report.lines().collect::<Vec<_>>()),
+            }
+        }
+        SubCommand::Backtrace { all, limit } => {
+            let process = session.process()?;
+            let lines = if all {
+                backtrace::all(process, limit)?
+            } else {
+                backtrace::current(process, limit)?
+            };
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Locks,
+        } => {
+            let lines = locks::info(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Frame { index },
+        } => {
+            let lines = frame_info::info(session.process()?, index)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Fds,
+        } => {
+            let lines = fds::info(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Sockets,
+        } => {
+            let lines = sockets::info(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Sched,
+        } => {
+            let lines = sched::info(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Security,
+        } => {
+            let lines = security_report(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Got { lib },
+        } => {
+            let lines = got::info(session.process()?, lib.as_deref())?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::Source { file, lib },
+        } => {
+            let lines = compile_units::source(session.process()?, file.as_deref(), lib.as_deref())?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Info {
+            command: InfoCommand::CompileUnits { filter, files, lib },
+        } => {
+            let lines =
+                compile_units::list(session.process()?, lib.as_deref(), filter.as_deref(), files)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Sym {
+            command:
+                SymCommand::List {
+                    regex,
+                    symtab,
+                    dynamic,
+                    debug,
+                    section,
+                    sort,
+                    lib,
+                    skip,
+                    limit,
+                },
+        } => {
+            let sources = [
+                (symtab, SymbolSource::Static),
+                (dynamic, SymbolSource::Dynamic),
+                (debug, SymbolSource::Debug),
+            ];
+            let query = SymbolQuery {
+                sources: sources
+                    .into_iter()
+                    .filter_map(|(wanted, source)| wanted.then_some(source))
+                    .collect(),
+                regex,
+                section,
+                order: sort,
+            };
+            let lines = symbol_list::list(session.process()?, lib.as_deref(), &query, skip, limit)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Deadlock {
+            command: DeadlockCommand::Check,
+        } => {
+            let lines = locks::deadlock_check(session.process()?)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Thread {
+            command: ThreadCommand::List { sample },
+        }
+        | SubCommand::Info {
+            command: InfoCommand::Threads { sample },
+        } => {
+            let lines = threads::list(session.process()?, sample)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Stack {
+            command: StackCommand::View { nwords },
+        } => {
+            let depth = session.settings.telescope_depth();
+            let lines = stack_view::render(session.process()?, nwords, depth)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Catch { command } => {
+            let lines = handle_catch(session.process()?, command)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Watch { command } => {
+            let lines = handle_watch(session.process()?, command)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Mem { command } => match command {
+            MemCommand::Protect { addr, len, perms } => {
+                session.process()?.protect_memory(addr, len, perms)?;
+                println!("Changed protection of {len:#x} bytes at {addr:#x}");
+            }
+            MemCommand::Alloc { len, perms } => {
+                let addr = session.process()?.allocate_memory(len, perms)?;
+                println!("Allocated {len:#x} bytes at {addr:#x}");
+            }
+            MemCommand::Free { addr, len } => {
+                session.process()?.free_memory(addr, len)?;
+                println!("Freed {len:#x} bytes at {addr:#x}");
+            }
+            MemCommand::Read { addr, len } => {
+                let dump = MemoryDump::read(session.process()?, addr, len as usize)?.to_string();
+                session.print_lines(&dump.lines().collect::<Vec<_>>());
+            }
+            MemCommand::Dump { addr, len, file } => {
+                let saved = dump::dump_range(session.process()?, addr, len, &file)?;
+                println!("Saved {saved:#x} bytes at {addr:#x} to {}", file.display());
+            }
+            MemCommand::DumpMapping { name, file } => {
+                let (addr, saved) = dump::dump_mapping(session.process()?, &name, &file)?;
+                println!("Saved {saved:#x} bytes at {addr:#x} to {}", file.display());
+            }
+            MemCommand::Restore { file, addr } => {
+                let written = dump::restore(session.process()?, &file, addr)?;
+                println!(
+                    "Wrote {written:#x} bytes from {} at {addr:#x}",
+                    file.display()
+                );
+            }
+            MemCommand::Snapshot { name, addr, len } => {
+                let snapshot = Snapshot::take(session.process()?, addr, len)?;
+                println!(
+                    "Saved {:#x} bytes at {addr:#x} as `{name}`",
+                    snapshot.bytes.len()
+                );
+                session.snapshots.insert(name, snapshot);
+            }
+            MemCommand::ScanPointers { addr, len, limit } => {
+                let process = session.process()?;
+                let maps = read_maps(process.pid)?;
+                let target = addr..addr.saturating_add(len);
+                let found = scan_pointers(process, &maps, &target, &CANCEL);
+                let mut lines: Vec<_> = found
+                    .iter()
+                    .take(limit)
+                    .map(|pointer| {
+                        let holder = find_region(&maps, pointer.addr)
+                            .map(|region| region.path.as_str())
+                            .filter(|path| !path.is_empty())
+                            .unwrap_or("anonymous");
+                        format!("{:#x} -> {:#x} ({holder})", pointer.addr, pointer.value)
+                    })
+                    .collect();
+                lines.push(match found.len() {
+                    0 => "No pointers found.".to_string(),
+                    total if total > limit => {
+                        format!("... {} more ({total} in total)", total - limit)
+                    }
+                    total => format!("{total} pointers"),
+                });
+                session.print_lines(&lines);
+                interrupt::note_partial();
+            }
+            MemCommand::Diff { old, new } => print_snapshot_diff(session, &old, new.as_deref())?,
+        },
+        SubCommand::Patch { command } => {
+            let syntax = session.settings.disassembly_flavor();
+            let lines = handle_patch(session.process()?, command, syntax)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Skip => {
+            let syntax = session.settings.disassembly_flavor();
+            let process = session.process()?;
+            let mut regs = process.read_registers()?;
+            let skipped = instruction_at(process, regs.rip, syntax)?;
+            regs.rip += skipped.bytes.len() as u64;
+            process.write_registers(&regs)?;
+            println!("Skipped {:#x}: {}", skipped.address, skipped.text);
+        }
+        SubCommand::Context => {
+            let syntax = session.settings.disassembly_flavor();
+            let depth = session.settings.telescope_depth();
+            let lines = context::render(session.process()?, syntax, depth)?;
+            session.print_lines(&lines);
+        }
+        SubCommand::Gadget {
+            command: GadgetCommand::Search { query, limit },
+        } => {
+            let process = session.process()?;
+            let maps = read_maps(process.pid)?;
+            let pattern = parse_query(&query.join(" "));
+            let gadgets = search_gadgets(process, &maps, &pattern, &CANCEL);
+            let text = pattern.join("; ");
+            let mut lines: Vec<_> = gadgets
+                .iter()
+                .take(limit)
+                .map(|gadget| {
+                    let module = Path::new(&gadget.module)
+                        .file_name()
+                        .map_or(gadget.module.as_str(), |name| {
+                            name.to_str().unwrap_or_default()
+                        });
+                    format!(
+                        "{:#x}: {text}  ({module}+{:#x})",
+                        gadget.address, gadget.offset
+                    )
+                })
+                .collect();
+            lines.push(match gadgets.len() {
+                0 => "No gadgets found.".to_string(),
+                total if total > limit => format!("... {} more ({total} in total)", total - limit),
+                total => format!("{total} gadgets"),
+            });
+            session.print_lines(&lines);
+            interrupt::note_partial();
+        }
+        SubCommand::Scan { command } => handle_scan(session, command)?,
+        SubCommand::Record { command } => match command {
+            RecordCommand::Session { file } => {
+                let program = session
+                    .launch_spec
+                    .as_ref()
+                    .map(|spec| spec.program.as_path());
+                session.recorder = Some(Recorder::create(&file, program)?);
+                println!("Recording session to {}", file.display());
+            }
+            RecordCommand::Stop => match session.recorder.take() {
+                Some(_) => println!("Recording stopped."),
+                None => println!("Not recording."),
+            },
+        },
+    }
+    Ok(())
+}
+
+/// Runs `printf`, evaluating `args` separated by commas if there are any, else by spaces.
+fn printf_command(session: &mut Session, format: &str, args: &[String]) -> Result<()> {
+    // Like gdb, a comma may follow the format.
+    let format = format
+        .strip_suffix(',')
+        .filter(|_| !args.is_empty())
+        .unwrap_or(format);
+    let joined = args.join(" ");
+    let expressions: Vec<_> = if joined.contains(',') {
+        joined.split(',').collect()
+    } else {
+        args.iter().map(String::as_str).collect()
+    };
+    let values = expressions
+        .iter()
+        .map(|expression| session.evaluate(expression))
+        .collect::<Result<Vec<_>>>()?;
+    let text = printf::format(format, &values, session.process()?)?;
+    print!("{text}");
+    let _ = io::stdout().flush();
+    Ok(())
+}
+
+/// Runs `set`: program environment, settings and convenience variables.
+fn handle_set(session: &mut Session, command: SetCommand) -> Result<()> {
+    match command {
+        SetCommand::Env { name, value } => {
+            session.launch_spec()?.env.insert(name.into(), value.into());
+        }
+        SetCommand::Cwd { path } => session.launch_spec()?.cwd = Some(path),
+        SetCommand::Affinity { cpus } => println!("{}", sched::pin(session.process()?, &cpus)?),
+        SetCommand::Setting(words) if words.first().is_some_and(|w| w.starts_with('$')) => {
+            session.assign_variable(&words)?;
+        }
+        SetCommand::Setting(words) => {
+            session.settings.set_words(&words)?;
+            #[cfg(feature = "tracing")]
+            sdb::process::set_ptrace_logging(session.settings.debug_ptrace());
+        }
+    }
+    Ok(())
+}
+
+fn handle_hook(session: &mut Session, command: HookCommand) {
+    match command {
+        HookCommand::Stop { lines } => session.hooks.set(HookPoint::Stop, lines),
+        HookCommand::List => {
+            let lines = session.hooks.list();
+            if lines.is_empty() {
+                println!("No hooks.");
+            }
+            session.print_lines(&lines);
+        }
+    }
+}
+
+/// Launches the program again, killing the current inferior and keeping its catchpoints.
+fn run(session: &mut Session) -> Result<()> {
+    let mut launch_spec = session.launch_spec.clone().context(NotLaunchedSnafu)?;
+    // Continued below, once the catchpoints are back.
+    let run_to_stop = std::mem::take(&mut launch_spec.run);
+    let catchpoints = session
+        .process
+        .take() // Kill the old inferior before starting a new one.
+        .map(|process| process.catchpoints().to_vec())
+        .unwrap_or_default();
+    if session.settings.catch_seccomp() {
+        launch_spec.traced_syscalls = seccomp_syscalls(&catchpoints);
+    }
+    // The inferior keeps its stdout after the command, so it must not get the capture's.
+    let mut process = match &session.capture {
+        Some(capture) => capture
+            .bypass(|| Process::launch_spec(&launch_spec, true))
+            .context(RecordSnafu)??,
+        None => Process::launch_spec(&launch_spec, true)?,
+    };
+    for catchpoint in catchpoints {
+        process.add_catchpoint(catchpoint.kind, catchpoint.action);
+    }
+    print_output(&mut process);
+    println!("Process {} launched", process.pid);
+    session.process = Some(process);
+    // Branch recorders followed the old process.
+    session.lbr = None;
+    #[cfg(feature = "intel-pt")]
+    {
+        session.pt = None;
+    }
+    session.index_symbols();
+    if run_to_stop {
+        continue_program(session, None)?;
+    }
+    Ok(())
+}
+
+/// Starts a session debugging a launch of `launch_spec`, continued to its first stop with
+/// `--run`.
+fn launch_session(launch_spec: LaunchSpec) -> Result<Session> {
+    let stopped = LaunchSpec {
+        run: false,
+        ..launch_spec.clone()
+    };
+    let mut process = Process::launch_spec(&stopped, true)?;
+    print_output(&mut process);
+    let run_to_stop = launch_spec.run;
+    let mut session = Session::new(process, Some(launch_spec));
+    if run_to_stop {
+        continue_program(&mut session, None)?;
+    }
+    Ok(session)
+}
+
+/// Prints the captured output of `process` as it comes, e.g. `[stdout] hello`.
+fn print_output(process: &mut Process) {
+    process.set_output_handler(Arc::new(|stream, line| println!("[{stream}] {line}")));
+}
+
+/// Whether `command` symbolizes addresses.
+const fn uses_symbols(command: &SubCommand) -> bool {
+    matches!(
+        command,
+        SubCommand::Backtrace { .. }
+            | SubCommand::Context
+            | SubCommand::Crash { .. }
+            | SubCommand::Info { .. }
+            | SubCommand::Stack { .. }
+            | SubCommand::Thread { .. }
+    )
+}
+
+/// Formats `name = value`, e.g. for `show`.
+fn format_setting(setting: &Setting) -> String {
+    match &setting.value {
+        Value::Path(None) => format!("{} = (default) -- {}", setting.name, setting.doc),
+        value => format!("{} = {value} -- {}", setting.name, setting.doc),
+    }
+}
+
+/// Quotes split words back into a shell command line.
+fn join_words(words: &[String]) -> String {
+    shlex::try_join(words.iter().map(String::as_str)).unwrap_or_else(|_| words.join(" "))
+}
+
+/// Decodes the instruction at `addr`.
+fn instruction_at(process: &Process, addr: u64, syntax: Syntax) -> Result<Instruction> {
+    let instruction = disassemble(process, addr, 1, syntax)?.into_iter().next();
+    instruction.context(UndecodableSnafu { addr })
+}
+
+/// Lists the mitigations of the program and each library mapped into it.
+fn security_report(process: &Process) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid)?;
+    let mut seen = HashSet::new();
+    let paths = maps
+        .iter()
+        .map(|region| region.path.as_str())
+        .filter(|path| path.starts_with('/') && seen.insert(*path));
+    let mut lines = vec![format!(
+        "{:<13}  {:<9}  {:<11}  {:<6}  {:<25}  File",
+        "RELRO", "Canary", "NX", "PIE", "Fortify"
+    )];
+    for path in paths {
+        lines.push(match Hardening::load(Path::new(path)) {
+            Ok(hardening) => format!("{:<72}  {path}", hardening.to_string()),
+            Err(err) => format!("{path}: {err}"),
+        });
+    }
+    Ok(lines)
+}
+
+/// Prints what changed between snapshot `old` and snapshot `new`, or the current memory.
+fn print_snapshot_diff(session: &Session, old: &str, new: Option<&str>) -> Result<()> {
+    let snapshot = |name: &str| {
+        session
+            .snapshots
+            .get(name)
+            .context(NoSnapshotSnafu { name })
+    };
+    let before = snapshot(old)?;
+    let after = if let Some(new) = new {
+        snapshot(new)?.clone()
+    } else {
+        let process = session.process.as_ref().context(NoProcessSnafu)?;
+        Snapshot::take(process, before.addr, before.bytes.len() as u64)?
+    };
+    let changes = snapshot::diff(&before.bytes, &after.bytes);
+    if changes.is_empty() {
+        println!("No changes.");
+    }
+    let lines: Vec<_> = changes
+        .iter()
+        .map(|change| {
+            format!(
+                "+{:#06x} {:#x}: {} -> {}",
+                change.offset,
+                before.addr + change.offset as u64,
+                hex_bytes(&change.old),
+                hex_bytes(&change.new)
+            )
+        })
+        .collect();
+    session.print_lines(&lines);
+    Ok(())
+}
+
+fn handle_scan(session: &mut Session, command: ScanCommand) -> Result<()> {
+    match command {
+        ScanCommand::Start { ty, value } => {
+            let value = parse_scan_value(ty, &value, ValueType::parse)?;
+            let process = session.process()?;
+            let maps = read_maps(process.pid)?;
+            let scan = ValueScan::start(process, &maps, ty, value, &CANCEL);
+            println!("{} candidates", scan.candidates().len());
+            interrupt::note_partial();
+            session.scan = Some(scan);
+        }
+        ScanCommand::Next { filter } => {
+            let process = session.process.as_ref().context(NoProcessSnafu)?;
+            let scan = session.scan.as_mut().context(NoScanSnafu)?;
+            let filter = parse_scan_value(scan.value_type(), &filter, |ty, text| {
+                ScanFilter::parse(text, ty)
+            })?;
+            scan.next(process, filter, &CANCEL);
+            println!("{} candidates", scan.candidates().len());
+            interrupt::note_partial();
+        }
+        ScanCommand::List { limit } => {
+            let scan = session.scan.as_ref().context(NoScanSnafu)?;
+            let candidates = scan.candidates();
+            let mut lines: Vec<_> = candidates
+                .iter()
+                .take(limit)
+                .map(|candidate| {
+                    let value = scan.value_type().format(candidate.value);
+                    format!("{:#x}: {value}", candidate.addr)
+                })
+                .collect();
+            if candidates.len() > limit {
+                lines.push(format!("... {} more", candidates.len() - limit));
+            }
+            session.print_lines(&lines);
         }
     }
     Ok(())
 }
 
-fn print_stop_reason(pid: &Pid, status: WaitStatus) {
-    println!("Process {} ", pid);
-    match status {
-        WaitStatus::Exited(_pid, info) => println!("exited with status {info}"),
-        WaitStatus::Stopped(_pid, signal) => println!("stopped with signal {signal}"),
-        other => println!("{other:?}"),
+/// Parses `text` for a scan of `ty` values with `parse`.
+fn parse_scan_value<T>(
+    ty: ValueType,
+    text: &str,
+    parse: impl FnOnce(ValueType, &str) -> Option<T>,
+) -> Result<T> {
+    parse(ty, text).context(InvalidScanValueSnafu {
+        text,
+        ty: ty.name(),
+    })
+}
+
+/// Formats `bytes` as space separated hex pairs.
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn handle_patch(
+    process: &mut Process,
+    command: PatchCommand,
+    syntax: Syntax,
+) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    match command {
+        PatchCommand::Asm { addr, code } => {
+            let bytes = assemble(&code.join(" "), addr)?;
+            let id = process.patch(addr, &bytes)?;
+            lines.push(format!(
+                "Patch {id}: wrote {} bytes at {addr:#x}",
+                bytes.len()
+            ));
+            for instruction in decode(&bytes, addr, usize::MAX, syntax) {
+                lines.push(format!(
+                    "  {:#x}: {}",
+                    instruction.address, instruction.text
+                ));
+            }
+        }
+        PatchCommand::Nop { addr, len } => {
+            let len = match len {
+                Some(len) => len,
+                None => instruction_at(process, addr, syntax)?.bytes.len() as u64,
+            };
+            let id = process.patch(addr, &vec![NOP; len as usize])?;
+            lines.push(format!("Patch {id}: wrote {len} nops at {addr:#x}"));
+        }
+        PatchCommand::List => {
+            for patch in process.patches() {
+                lines.push(format!(
+                    "{}: {:#x} {} (was {})",
+                    patch.id,
+                    patch.addr,
+                    hex_bytes(&patch.bytes),
+                    hex_bytes(&patch.original)
+                ));
+            }
+        }
+        PatchCommand::Revert { id } => {
+            let Some(id) = id.or_else(|| process.patches().last().map(|patch| patch.id)) else {
+                lines.push("No patches.".to_string());
+                return Ok(lines);
+            };
+            process.revert_patch(id)?;
+            lines.push(format!("Reverted patch {id}"));
+        }
     }
+    Ok(lines)
 }
 
-fn main_loop(mut process: Process) -> Result<()> {
+fn handle_catch(process: &mut Process, command: CatchCommand) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    match command {
+        CatchCommand::Fd { fd, log } => {
+            let id = process.add_catchpoint(CatchpointKind::Fd(fd), catch_action(log));
+            lines.push(format!("Catchpoint {id} ({})", CatchpointKind::Fd(fd)));
+        }
+        CatchCommand::Syscall {
+            syscalls,
+            include,
+            exclude,
+            enter_only,
+            exit_only,
+            log,
+        } => {
+            let kind = CatchpointKind::Syscall(SyscallFilter {
+                syscalls: SyscallFilter::resolve_syscalls(&syscalls)?,
+                include: include.as_deref().map(SyscallPattern::new).transpose()?,
+                exclude: exclude.as_deref().map(SyscallPattern::new).transpose()?,
+                on_entry: !exit_only,
+                on_exit: !enter_only,
+            });
+            let description = kind.to_string();
+            let id = process.add_catchpoint(kind, catch_action(log));
+            lines.push(format!("Catchpoint {id} ({description})"));
+        }
+        CatchCommand::Rss { mib, log, .. } => {
+            let kind = CatchpointKind::Rss(mib.saturating_mul(1 << 20));
+            let description = kind.to_string();
+            let id = process.add_catchpoint(kind, catch_action(log));
+            lines.push(format!("Catchpoint {id} ({description})"));
+        }
+        CatchCommand::Sanitizer { log } => {
+            let id = process.add_catchpoint(CatchpointKind::Sanitizer, catch_action(log));
+            lines.push(format!("Catchpoint {id} (sanitizer)"));
+        }
+        CatchCommand::Load { ref regex, log } | CatchCommand::Unload { ref regex, log } => {
+            let pattern = regex.as_deref().map(ModulePattern::new).transpose()?;
+            let kind = if matches!(command, CatchCommand::Load { .. }) {
+                CatchpointKind::Load(pattern)
+            } else {
+                CatchpointKind::Unload(pattern)
+            };
+            let description = kind.to_string();
+            let id = process.add_catchpoint(kind, catch_action(log));
+            lines.push(format!("Catchpoint {id} ({description})"));
+        }
+        CatchCommand::List => {
+            for catchpoint in process.catchpoints() {
+                let action = match catchpoint.action {
+                    CatchAction::Stop => "stop",
+                    CatchAction::Log => "log",
+                };
+                lines.push(format!("{}: {} [{action}]", catchpoint.id, catchpoint.kind));
+            }
+        }
+        CatchCommand::Delete { id } => {
+            if !process.remove_catchpoint(id) {
+                return NoCatchpointSnafu { id }.fail();
+            }
+        }
+    }
+    Ok(lines)
+}
+
+fn handle_watch(process: &mut Process, command: WatchCommand) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    match command {
+        WatchCommand::Got { symbol } => {
+            let slot = got::slot_of(process, &symbol)?;
+            let description = format!("GOT slot of {symbol}");
+            let id = process.add_watchpoint(slot, 8, WatchKind::Write, description.clone())?;
+            lines.push(format!("Watchpoint {id} ({description} at {slot:#x})"));
+        }
+        WatchCommand::Exec { location } => {
+            let maps = read_maps(process.pid)?;
+            let addr = match args::dbg::parse_number(&location) {
+                Ok(addr) => addr,
+                Err(_) => {
+                    location::find_function(&maps, &location)
+                        .context(NoFunctionSnafu { name: &location })?
+                        .start
+                }
+            };
+            let description = format!("execution of {location}");
+            let id = process.add_watchpoint(addr, 1, WatchKind::Execute, description)?;
+            lines.push(format!(
+                "Watchpoint {id} (execution of {})",
+                Annotator::new(&maps).annotate(addr)
+            ));
+        }
+        WatchCommand::Enter { target } => {
+            let range = watched_range(process, &target)?;
+            let description = format!("entry into {target}");
+            let id = process.add_range_watch(range, description)?;
+            if let Some(watch) = process.range_watches().last() {
+                lines.push(format!(
+                    "Watchpoint {id} (first execution in {:#x}..{:#x})",
+                    watch.range.start, watch.range.end
+                ));
+            }
+        }
+        WatchCommand::Region { target } => {
+            let range = watched_region(process, &target)?;
+            let description = format!("writes to {target}");
+            let id = process.add_write_watch(range, description)?;
+            if let Some(watch) = process.range_watches().last() {
+                lines.push(format!(
+                    "Watchpoint {id} (writes to {:#x}..{:#x})",
+                    watch.range.start, watch.range.end
+                ));
+            }
+        }
+        WatchCommand::List => {
+            for watchpoint in process.watchpoints() {
+                if watchpoint.kind == WatchKind::Execute {
+                    lines.push(format!("{}: {}", watchpoint.id, watchpoint.description));
+                    continue;
+                }
+                lines.push(format!(
+                    "{}: {} at {:#x}, {} bytes = {:#x}",
+                    watchpoint.id,
+                    watchpoint.description,
+                    watchpoint.addr,
+                    watchpoint.len,
+                    watchpoint.value
+                ));
+            }
+            for watch in process.range_watches() {
+                let state = if watch.is_armed() { "" } else { " [hit]" };
+                lines.push(format!(
+                    "{}: {} at {:#x}..{:#x}{state}",
+                    watch.id, watch.description, watch.range.start, watch.range.end
+                ));
+            }
+        }
+        WatchCommand::Delete { id } => process.remove_watchpoint(id)?,
+    }
+    Ok(lines)
+}
+
+/// Resolves the target of `watch enter`: the code of a mapped file, a function, or the page
+/// containing an address.
+fn watched_range(process: &Process, target: &str) -> Result<Range<u64>> {
+    let maps = read_maps(process.pid)?;
+    if let Ok(addr) = args::dbg::parse_number(target) {
+        return Ok(addr..addr + 1);
+    }
+    let code = maps
+        .iter()
+        .filter(|region| region.execute && dump::mapping_matches(region, target));
+    let module = code.fold(None, |range: Option<Range<u64>>, region| {
+        Some(range.map_or(region.start..region.end, |range| {
+            range.start.min(region.start)..range.end.max(region.end)
+        }))
+    });
+    if let Some(module) = module {
+        return Ok(module);
+    }
+    location::find_function(&maps, target).context(NoFunctionSnafu { name: target })
+}
+
+/// Resolves the target of `watch region`: the writable mappings of a file or pseudo mapping,
+/// or the mapping containing an address.
+fn watched_region(process: &Process, target: &str) -> Result<Range<u64>> {
+    let maps = read_maps(process.pid)?;
+    if let Ok(addr) = args::dbg::parse_number(target) {
+        return maps
+            .iter()
+            .find(|region| (region.start..region.end).contains(&addr))
+            .map(|region| region.start..region.end)
+            .context(NoMappingSnafu { name: target });
+    }
+    maps.iter()
+        .filter(|region| region.write && dump::mapping_matches(region, target))
+        .fold(None, |range: Option<Range<u64>>, region| {
+            Some(range.map_or(region.start..region.end, |range| {
+                range.start.min(region.start)..range.end.max(region.end)
+            }))
+        })
+        .context(NoMappingSnafu { name: target })
+}
+
+/// Collects the syscalls `catchpoints` can catch, for `catch seccomp`.
+///
+/// # Returns
+/// `None`, meaning every syscall must be traced, if a catchpoint can catch any syscall.
+fn seccomp_syscalls(catchpoints: &[Catchpoint]) -> Option<Vec<u64>> {
+    if catchpoints.is_empty() {
+        return None;
+    }
+    let mut syscalls = Vec::new();
+    for catchpoint in catchpoints {
+        let Some(numbers) = catchpoint.syscalls() else {
+            println!(
+                "Catchpoint {} can catch any syscall, so `catch seccomp` has no effect.",
+                catchpoint.id
+            );
+            return None;
+        };
+        syscalls.extend(numbers);
+    }
+    syscalls.sort_unstable();
+    syscalls.dedup();
+    Some(syscalls)
+}
+
+const fn catch_action(log: bool) -> CatchAction {
+    if log {
+        CatchAction::Log
+    } else {
+        CatchAction::Stop
+    }
+}
+
+/// Continues the program until a stop to report, tracing its calls with `trace`, and shows
+/// where it stopped.
+fn continue_program(session: &mut Session, mut trace: Option<FunctionTrace>) -> Result<()> {
+    let max_buffer_len = session.settings.print_max_elements();
+    let show_context = session.settings.context();
+    let syntax = session.settings.disassembly_flavor();
+    let depth = session.settings.telescope_depth();
+    let watchdog = session.interrupt_after.take();
+    let watchdog = watchdog.or_else(|| session.settings.hang_detector());
+    let show_timing = session.settings.show_timing();
+    let process = session.process()?;
+    process.set_watchdog(watchdog);
+    let timer = show_timing.then(|| RunTimer::start(process.pid));
+    let resumed = resume_until_stop(process, max_buffer_len, |process| match &mut trace {
+        Some(trace) => trace.resume(process),
+        None => Ok(process.resume()?),
+    });
+    if let Some(trace) = trace {
+        trace.finish(process)?;
+    }
+    resumed?;
+    print_stop_reason(process, max_buffer_len);
+    if let Some(timer) = timer {
+        println!("{}", timer.report());
+    }
+    if process.exit_status().is_some() {
+        session.on_exit();
+    } else if show_context {
+        print_context(session, syntax, depth);
+    }
+    session.index_symbols();
+    Ok(())
+}
+
+/// Runs `trace functions`.
+fn trace_functions(session: &mut Session, pattern: Option<&str>, args: usize) -> Result<()> {
+    let process = session.process()?;
+    let maps = read_maps(process.pid)?;
+    let path = pid_path(process.pid, "exe");
+    let program = fs::read_link(&path).context(IoErrWithPathSnafu { path })?;
+    let functions = find_functions(&maps, &program, pattern)?;
+    let tracer = FunctionTracer::start(process, &functions, args)?;
+    println!(
+        "Tracing {} functions until the program stops.",
+        tracer.len()
+    );
+    continue_program(session, Some(FunctionTrace::new(tracer)))
+}
+
+fn lbr_command(session: &mut Session, command: LbrCommand) -> Result<()> {
+    match command {
+        LbrCommand::Start { period } => {
+            let pid = session.process()?.pid;
+            session.lbr = Some(LbrRecorder::start(pid, period)?);
+            println!("Sampling the last branches of {pid} every {period} branches.");
+        }
+        LbrCommand::Stop => {
+            let recorder = session.lbr.as_mut().context(NoLbrSnafu)?;
+            if recorder.is_recording() {
+                recorder.disable()?;
+            }
+            println!("Stopped sampling; `lbr show` shows the last sample.");
+        }
+        LbrCommand::Show { last } => {
+            let recorder = session.lbr.as_ref().context(NoLbrSnafu)?;
+            let Some(branches) = recorder.latest() else {
+                println!("No branches sampled yet.");
+                return Ok(());
+            };
+            if recorder.period() > 1 {
+                println!(
+                    "Newest sample, up to {} branches before the stop:",
+                    recorder.period()
+                );
+            }
+            let maps = read_maps(session.process()?.pid)?;
+            session.print_lines(&lbr::format_branches(&branches, &maps, last));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "intel-pt")]
+fn pt_command(session: &mut Session, command: PtCommand) -> Result<()> {
+    match command {
+        PtCommand::Start { size } => {
+            let pid = session.process()?.pid;
+            let recorder = PtRecorder::start(pid, size.saturating_mul(1024))?;
+            println!(
+                "Recording branches of {pid} into {} KiB.",
+                recorder.size() / 1024
+            );
+            session.pt = Some(recorder);
+        }
+        PtCommand::Stop => {
+            let recorder = session.pt.as_mut().context(NoPtTraceSnafu)?;
+            if recorder.is_recording() {
+                recorder.disable()?;
+            }
+            println!("Stopped recording; `pt decode` shows the trace.");
+        }
+        PtCommand::Decode { last } => {
+            let trace = session.pt.as_mut().context(NoPtTraceSnafu)?.snapshot()?;
+            let process = session.process()?;
+            let maps = read_maps(process.pid)?;
+            let flow = reconstruct(&trace, &*process);
+            if flow.is_empty() {
+                println!("The trace has no complete block yet.");
+            }
+            session.print_lines(&intel_pt::format_flow(&flow, &maps, last));
+        }
+    }
+    Ok(())
+}
+
+/// Resumes the process with `resume`, printing and skipping over stops of logging
+/// catchpoints.
+fn resume_until_stop(
+    process: &mut Process,
+    max_buffer_len: usize,
+    mut resume: impl FnMut(&mut Process) -> Result<()>,
+) -> Result<()> {
+    loop {
+        resume(process)?;
+        let hits = process.hit_catchpoints();
+        if hits.is_empty() || hits.iter().any(|hit| hit.action == CatchAction::Stop) {
+            return Ok(());
+        }
+        if let Some(event) = &process.last_syscall {
+            println!(
+                "[catch] {}",
+                describe_with_limit(event, process, max_buffer_len)
+            );
+        }
+        if let Some(crossing) = process.last_rss {
+            println!("[catch] {crossing}");
+        }
+        for event in &process.last_modules {
+            println!("[catch] {event}");
+        }
+        if let Some(report) = &process.last_sanitizer_report {
+            println!("[catch] {}", describe_sanitizer_report(process, report));
+        }
+    }
+}
+
+/// Formats a sanitizer report with the code that made the bad access, e.g.
+/// `AddressSanitizer: heap-use-after-free, READ of size 4 at 0x602000000010 by 0x401196 in main+0x40 (...)`.
+fn describe_sanitizer_report(process: &Process, report: &SanitizerReport) -> String {
+    let Some(pc) = report.pc else {
+        return report.to_string();
+    };
+    let place = read_maps(process.pid).map_or_else(
+        |_| format!("{pc:#x}"),
+        |maps| Annotator::new(&maps).annotate(pc).to_string(),
+    );
+    format!("{report} by {place}")
+}
+
+fn print_stop_reason(process: &Process, max_buffer_len: usize) {
+    let event = StopEvent::capture(process, max_buffer_len, BACKTRACE_FRAMES);
+    println!("Process {} {event}", process.pid);
+}
+
+/// Prints the `context` view after a stop, or why it is unavailable.
+fn print_context(session: &Session, syntax: Syntax, depth: usize) {
+    let Some(process) = &session.process else {
+        return;
+    };
+    match context::render(process, syntax, depth) {
+        Ok(lines) => session.print_lines(&lines),
+        Err(err) => eprintln!("{err}"),
+    }
+}
+
+fn main_loop(mut session: Session) -> Result<()> {
+    let config = Config::builder()
+        .max_history_size(session.settings.history_size())?
+        .history_ignore_dups(true)?
+        .build();
     // `()` can be used when no completer is required
-    let mut rl = DefaultEditor::new()?;
+    let mut rl = DefaultEditor::with_config(config)?;
+    #[cfg(feature = "file-history")]
+    let history_path = session
+        .settings
+        .history_file()
+        .map(std::path::Path::to_path_buf)
+        .or_else(history::history_path);
     #[cfg(feature = "file-history")]
-    if rl.load_history("history.txt").is_err() {
+    if history_path
+        .as_ref()
+        .is_none_or(|path| rl.load_history(path).is_err())
+    {
         println!("No previous history.");
     }
     loop {
-        let readline = rl.readline("sdb> ");
+        let prompt = if session.definition.is_some() {
+            "> "
+        } else {
+            "sdb> "
+        };
+        let readline = rl.readline(prompt);
         match readline {
             Ok(line) => {
-                let _ = rl.add_history_entry(line.as_str())?;
-                if let Err(err) = handle_command(&mut process, &line) {
-                    eprintln!("{err}");
-                    continue;
+                let line = if session.settings.history_expansion() {
+                    match history::expand(&line, rl.history()) {
+                        Ok(Some(expanded)) => {
+                            println!("{expanded}");
+                            expanded
+                        }
+                        Ok(None) => line,
+                        Err(err) => {
+                            eprintln!("{err}");
+                            continue;
+                        }
+                    }
+                } else {
+                    line
                 };
+                let _ = rl.add_history_entry(line.as_str())?;
+                execute(&mut session, &line);
+                rl.set_max_history_size(session.settings.history_size())?;
+                if session.process.is_none() && session.launch_spec.is_none() {
+                    break; // The attached process is gone and cannot be restarted.
+                }
             }
             Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
             Err(err) => {
@@ -63,24 +1582,89 @@ fn main_loop(mut process: Process) -> Result<()> {
         }
     }
     #[cfg(feature = "file-history")]
-    rl.save_history("history.txt");
+    if let Some(path) = history_path {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(ReadlineError::from)
+            .and_then(|()| rl.save_history(&path));
+        if let Err(err) = saved {
+            eprintln!("Failed to save history to {}: {err}", path.display());
+        }
+    }
     Ok(())
 }
 
 fn main() {
     let args = AppArgs::parse();
+    interrupt::install();
+    register_frame_recognizer(Arc::new(Wasmtime));
+    #[cfg(feature = "tracing")]
+    map_err_exit(logger::init(args.log_file.as_ref(), args.log_level, false));
 
     if let Some(pid) = args.pid {
-        let process = map_err_exit(Process::attach(pid));
-        map_err_exit(main_loop(process));
+        let pid = map_err_exit(resolve_host_pid(pid, &args));
+        let mut process = map_err_exit(Process::attach(pid));
+        if let Some(kill) = args.kill_on_exit {
+            map_err_exit(process.set_kill_on_exit(kill));
+        }
+        map_err_exit(main_loop(Session::new(process, None)));
     }
 
-    if let Some(program_path) = args.program_path {
-        let process = map_err_exit(Process::launch(&program_path, false));
-        map_err_exit(main_loop(process));
+    if let Some(path) = &args.replay {
+        map_err_exit(replay(path, &args));
+    } else if let Some(program_path) = &args.program_path {
+        let session = map_err_exit(launch_session(new_launch_spec(program_path, &args)));
+        map_err_exit(main_loop(session));
     }
 }
 
+/// Creates the spec to launch `program_path` with, with the libraries, exit behavior, `--run`
+/// and `--capture-output` given on the command line.
+fn new_launch_spec(program_path: &std::path::Path, args: &AppArgs) -> LaunchSpec {
+    let mut launch_spec = LaunchSpec::new(program_path);
+    launch_spec.kill_on_exit = args.kill_on_exit.unwrap_or(true);
+    launch_spec.run = args.run;
+    launch_spec.capture_output = args.capture_output;
+    // Prepended in reverse, so the first one given comes first.
+    for library in args.preload.iter().rev() {
+        launch_spec.preload(library);
+    }
+    for dir in args.ld_library_path.iter().rev() {
+        launch_spec.add_library_path(dir);
+    }
+    launch_spec
+}
+
+/// Launches the program again and runs the commands of the transcript at `path`, then
+/// hands over to the prompt.
+fn replay(path: &std::path::Path, args: &AppArgs) -> Result<()> {
+    let replay = Replay::load(path)?;
+    let program_path = args
+        .program_path
+        .clone()
+        .or(replay.program)
+        .context(NoReplayProgramSnafu { path })?;
+    let mut session = launch_session(new_launch_spec(&program_path, args))?;
+    for command in &replay.commands {
+        println!("sdb> {command}");
+        execute(&mut session, command);
+    }
+    main_loop(session)
+}
+
+/// Translates `-p` from the namespace selected by `--pidns`/`--container` into a host pid.
+fn resolve_host_pid(pid: i32, args: &AppArgs) -> sdb::error::Result<i32> {
+    let ns_member = match (args.pidns, &args.container) {
+        (Some(ns_member), _) => Pid::from_raw(ns_member),
+        (None, Some(container_id)) => find_container_process(container_id)?,
+        (None, None) => return Ok(pid),
+    };
+    let host_pid = translate_ns_pid(pid, ns_member)?;
+    println!("Namespace pid {pid} is host pid {host_pid}");
+    Ok(host_pid.as_raw())
+}
+
 fn map_err_exit<T, Err: Display>(result: Result<T, Err>) -> T {
     match result {
         Ok(any) => any,
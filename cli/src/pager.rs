@@ -0,0 +1,86 @@
+//! Paging of long command output through `$PAGER` (default `less -FRX`).
+
+use std::io::{self, BufRead as _, IsTerminal as _, Write as _};
+use std::process::{Command, Stdio};
+
+/// Pager used when `$PAGER` is unset.
+const DEFAULT_PAGER: &str = "less -FRX";
+/// Screen height assumed when the terminal size is unknown.
+const DEFAULT_HEIGHT: usize = 24;
+
+/// Prints `lines`, through a pager if `enabled` and they do not fit on the terminal.
+pub(crate) fn print_lines<S: AsRef<str>>(lines: &[S], enabled: bool) {
+    let height = terminal_height();
+    if !enabled || !io::stdout().is_terminal() || lines.len() < height {
+        for line in lines {
+            println!("{}", line.as_ref());
+        }
+        return;
+    }
+    if external_pager(lines).is_err() {
+        internal_pager(lines, height);
+    }
+}
+
+/// Pipes `lines` into `$PAGER`.
+fn external_pager<S: AsRef<str>>(lines: &[S]) -> io::Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let mut child = Command::new("sh")
+        .args(["-c", &pager])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        for line in lines {
+            // The user quitting the pager early closes the pipe; that is not an error.
+            if writeln!(stdin, "{}", line.as_ref()).is_err() {
+                break;
+            }
+        }
+    }
+    let status = child.wait()?;
+    // `sh` reports 127 when the pager does not exist.
+    if status.code() == Some(127) {
+        return Err(io::Error::from(io::ErrorKind::NotFound));
+    }
+    Ok(())
+}
+
+/// Shows `lines` a screen at a time, waiting for Enter between screens.
+fn internal_pager<S: AsRef<str>>(lines: &[S], height: usize) {
+    let page = height.saturating_sub(1).max(1);
+    let mut stdin = io::stdin().lock();
+    for (index, chunk) in lines.chunks(page).enumerate() {
+        if index > 0 {
+            print!("--Type <RET> for more, q to quit--");
+            let _ = io::stdout().flush();
+            let mut answer = String::new();
+            if stdin.read_line(&mut answer).is_err() || answer.trim() == "q" {
+                return;
+            }
+        }
+        for line in chunk {
+            println!("{}", line.as_ref());
+        }
+    }
+}
+
+/// Returns the number of rows of the terminal on stdout.
+fn terminal_height() -> usize {
+    let mut size = nix::libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `TIOCGWINSZ` only writes a `winsize` into the pointed-to struct.
+    let result =
+        unsafe { nix::libc::ioctl(nix::libc::STDOUT_FILENO, nix::libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_row > 0 {
+        usize::from(size.ws_row)
+    } else {
+        std::env::var("LINES")
+            .ok()
+            .and_then(|lines| lines.parse().ok())
+            .unwrap_or(DEFAULT_HEIGHT)
+    }
+}
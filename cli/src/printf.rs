@@ -0,0 +1,276 @@
+//! `printf`: C-style formatted output of expression values, with `%s` reading C strings from
+//! inferior memory.
+//!
+//! Values are 64 bits wide. The length modifiers `hh` and `h` narrow them to 8 and 16 bits;
+//! `l`, `ll`, `z`, `j` and `t` are accepted and change nothing.
+
+use crate::error::{PrintfArgumentsSnafu, PrintfFormatSnafu, Result};
+use sdb::process::ReadMemory;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Longest string `%s` prints without a precision.
+const MAX_STRING_LEN: usize = 4096;
+
+/// A `%` conversion.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Spec {
+    /// `-`: pad on the right.
+    left: bool,
+    /// `0`: pad numbers with zeros.
+    zero: bool,
+    /// `+`: sign positive numbers.
+    plus: bool,
+    /// ` `: put a space before positive numbers.
+    space: bool,
+    /// `#`: `0x` before hex, `0` before octal.
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    /// Bits of the value used, fewer with `h` and `hh`.
+    bits: u32,
+    /// One of `diuoxXcsp`.
+    conversion: char,
+}
+
+/// Text between conversions, or a conversion.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Conversion(Spec),
+}
+
+/// Formats `args` by `format`, whose escapes such as `\n` have not been replaced yet.
+///
+/// # Errors
+/// Returns an error if `format` is malformed, takes another number of arguments than given,
+/// or a `%s` argument does not point to readable memory.
+pub(crate) fn format(format: &str, args: &[u64], memory: &impl ReadMemory) -> Result<String> {
+    let pieces = parse(format).map_err(|msg| PrintfFormatSnafu { format, msg }.build())?;
+    let needed = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Conversion(_)))
+        .count();
+    if needed != args.len() {
+        return PrintfArgumentsSnafu {
+            format,
+            needed,
+            given: args.len(),
+        }
+        .fail();
+    }
+    let mut output = String::new();
+    let mut args = args.iter();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => output.push_str(&text),
+            Piece::Conversion(spec) => {
+                let arg = args.next().copied().unwrap_or_default();
+                let value = if spec.bits < 64 {
+                    arg & ((1 << spec.bits) - 1)
+                } else {
+                    arg
+                };
+                convert(&mut output, &spec, value, memory)?;
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Appends `value` converted by `spec`.
+fn convert(output: &mut String, spec: &Spec, value: u64, memory: &impl ReadMemory) -> Result<()> {
+    let (sign, prefix, digits) = match spec.conversion {
+        's' => {
+            let len = spec.precision.unwrap_or(MAX_STRING_LEN);
+            let bytes = memory.read_memory(value, len)?;
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            pad(output, spec, "", &String::from_utf8_lossy(&bytes[..end]));
+            return Ok(());
+        }
+        'c' => {
+            pad(output, spec, "", &char::from(value as u8).to_string());
+            return Ok(());
+        }
+        'd' | 'i' => {
+            // Sign-extend from the width kept by `h` and `hh`.
+            let shift = 64 - spec.bits;
+            let signed = ((value << shift) as i64) >> shift;
+            let sign = if signed < 0 {
+                "-"
+            } else if spec.plus {
+                "+"
+            } else if spec.space {
+                " "
+            } else {
+                ""
+            };
+            (sign, "", signed.unsigned_abs().to_string())
+        }
+        'u' => ("", "", value.to_string()),
+        'o' => (
+            "",
+            if spec.alternate { "0" } else { "" },
+            format!("{value:o}"),
+        ),
+        'x' => (
+            "",
+            if spec.alternate && value != 0 {
+                "0x"
+            } else {
+                ""
+            },
+            format!("{value:x}"),
+        ),
+        'X' => (
+            "",
+            if spec.alternate && value != 0 {
+                "0X"
+            } else {
+                ""
+            },
+            format!("{value:X}"),
+        ),
+        _ => ("", "0x", format!("{value:x}")),
+    };
+    // A precision is the least number of digits.
+    let digits = match spec.precision {
+        Some(0) if value == 0 => String::new(),
+        Some(precision) => format!("{digits:0>precision$}"),
+        None => digits,
+    };
+    let head = format!("{sign}{prefix}");
+    if spec.zero && !spec.left && spec.precision.is_none() {
+        let width = spec.width.saturating_sub(head.len());
+        output.push_str(&head);
+        output.push_str(&format!("{digits:0>width$}"));
+    } else {
+        pad(output, spec, &head, &digits);
+    }
+    Ok(())
+}
+
+/// Appends `head` and `body`, padded with spaces to the width of `spec`.
+fn pad(output: &mut String, spec: &Spec, head: &str, body: &str) {
+    let len = head.chars().count() + body.chars().count();
+    let padding = " ".repeat(spec.width.saturating_sub(len));
+    if spec.left {
+        output.extend([head, body, &padding]);
+    } else {
+        output.extend([&padding, head, body]);
+    }
+}
+
+/// Splits `format` into text and conversions, replacing escapes in the text.
+fn parse(format: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('a') => '\x07',
+                Some('e') => '\x1b',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"' | '\'')) => c,
+                Some(c) => return Err(format!("unknown escape `\\{c}`")),
+                None => return Err("`\\` at the end".to_string()),
+            }),
+            '%' if chars.next_if_eq(&'%').is_some() => text.push('%'),
+            '%' => {
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Conversion(parse_spec(&mut chars)?));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Parses the conversion after a `%`: flags, width, precision, length and conversion.
+fn parse_spec(chars: &mut Peekable<Chars<'_>>) -> Result<Spec, String> {
+    let mut spec = Spec {
+        bits: 64,
+        ..Spec::default()
+    };
+    while let Some(flag) = chars.next_if(|c| "-0+ #".contains(*c)) {
+        match flag {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            ' ' => spec.space = true,
+            _ => spec.alternate = true,
+        }
+    }
+    spec.width = digits(chars);
+    if chars.next_if_eq(&'.').is_some() {
+        spec.precision = Some(digits(chars));
+    }
+    while let Some(length) = chars.next_if(|c| "hlzjt".contains(*c)) {
+        if length == 'h' {
+            spec.bits = if spec.bits == 16 { 8 } else { 16 };
+        }
+    }
+    match chars.next() {
+        Some(conversion @ ('d' | 'i' | 'u' | 'o' | 'x' | 'X' | 'c' | 's' | 'p')) => {
+            spec.conversion = conversion;
+            Ok(spec)
+        }
+        Some(c) => Err(format!("unknown conversion `%{c}`")),
+        None => Err("`%` at the end".to_string()),
+    }
+}
+
+/// Parses a run of decimal digits, 0 if there is none.
+fn digits(chars: &mut Peekable<Chars<'_>>) -> usize {
+    let mut number = 0_usize;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        let digit = digit.to_digit(10).unwrap_or_default() as usize;
+        number = number.saturating_mul(10).saturating_add(digit);
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory holding `"hello"` at 0x1000.
+    struct Hello;
+
+    impl ReadMemory for Hello {
+        fn read_memory(&self, addr: u64, len: usize) -> sdb::error::Result<Vec<u8>> {
+            let bytes = b"hello\0";
+            let start = (addr - 0x1000) as usize;
+            Ok(bytes[start..].iter().copied().take(len).collect())
+        }
+    }
+
+    #[test]
+    fn should_format_like_c() -> Result<()> {
+        let args = [0x1000, u64::MAX, 255, 0x41];
+        assert_eq!(
+            format(r"%s|%-4d|%#06x|%c\n", &args, &Hello)?,
+            "hello|-1  |0x00ff|A\n"
+        );
+        assert_eq!(
+            format(
+                "%.3s %5.3u %hhu %+d %p 100%%",
+                &[0x1000, 7, 0x1ff, 3, 0x10],
+                &Hello
+            )?,
+            "hel   007 255 +3 0x10 100%"
+        );
+        assert!(format("%d %d", &[1], &Hello).is_err());
+        assert!(format("%q", &[1], &Hello).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! `record session`: a timestamped transcript of commands and their output, and `--replay`.
+//!
+//! Transcript lines look like
+//! ```text
+//! [    1.042] > continue
+//! [    1.043] | Process 1234 stopped with SIGSEGV
+//! [    1.043] ! No process is running.
+//! ```
+//! where `>` marks a command, `|` stdout and `!` stderr.
+
+use crate::error::{IoErrWithPathSnafu, Result};
+use nix::fcntl::OFlag;
+use nix::unistd::{dup2, pipe2};
+use snafu::ResultExt as _;
+use std::fs::File;
+use std::io::{self, Read as _, Write as _};
+use std::os::fd::{AsRawFd as _, BorrowedFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Header line naming the launched program.
+const PROGRAM_HEADER: &str = "# program: ";
+/// Marks a command line.
+const COMMAND_MARKER: char = '>';
+
+/// The transcript file shared by the threads copying stdout and stderr.
+#[derive(Debug)]
+struct Transcript {
+    file: File,
+    start: Instant,
+}
+
+impl Transcript {
+    fn write_line(&mut self, marker: char, text: &str) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        // A full disk must not break the session being recorded.
+        let line = format!("[{elapsed:>9.3}] {marker} {text}");
+        let _ = writeln!(self.file, "{}", line.trim_end());
+    }
+}
+
+/// An active `record session`.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    transcript: Arc<Mutex<Transcript>>,
+}
+
+impl Recorder {
+    /// Creates the transcript at `path`, noting the launched `program` for `--replay`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be created.
+    pub(crate) fn create(path: &Path, program: Option<&Path>) -> Result<Self> {
+        let mut file = File::create(path).context(IoErrWithPathSnafu { path })?;
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut header = format!(
+            "# sdb session recorded at {} (seconds since the epoch)\n",
+            since_epoch.as_secs()
+        );
+        if let Some(program) = program {
+            header.push_str(&format!("{PROGRAM_HEADER}{}\n", program.display()));
+        }
+        file.write_all(header.as_bytes())
+            .context(IoErrWithPathSnafu { path })?;
+        Ok(Self {
+            transcript: Arc::new(Mutex::new(Transcript {
+                file,
+                start: Instant::now(),
+            })),
+        })
+    }
+
+    /// Records `line` and starts copying everything written to stdout and stderr into the
+    /// transcript until [`Capture::finish`].
+    ///
+    /// # Errors
+    /// Returns an error if the output cannot be redirected.
+    pub(crate) fn capture(&self, line: &str) -> io::Result<Capture> {
+        self.transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_line(COMMAND_MARKER, line);
+        let stdout = Tee::start(io::stdout().as_raw_fd(), '|', Arc::clone(&self.transcript))?;
+        let stderr = Tee::start(io::stderr().as_raw_fd(), '!', Arc::clone(&self.transcript));
+        match stderr {
+            Ok(stderr) => Ok(Capture { stdout, stderr }),
+            Err(err) => {
+                stdout.finish()?;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Output of one command being copied into the transcript.
+#[derive(Debug)]
+pub(crate) struct Capture {
+    stdout: Tee,
+    stderr: Tee,
+}
+
+impl Capture {
+    /// Runs `f` with the real stdout and stderr, e.g. to launch an inferior that must not
+    /// inherit the capture pipes.
+    pub(crate) fn bypass<T>(&self, f: impl FnOnce() -> T) -> io::Result<T> {
+        self.stdout.point_at(self.stdout.saved.as_raw_fd())?;
+        self.stderr.point_at(self.stderr.saved.as_raw_fd())?;
+        let value = f();
+        self.stdout.point_at(self.stdout.pipe.as_raw_fd())?;
+        self.stderr.point_at(self.stderr.pipe.as_raw_fd())?;
+        Ok(value)
+    }
+
+    /// Restores stdout and stderr and waits until all captured output is recorded.
+    ///
+    /// # Errors
+    /// Returns an error if the original file descriptors cannot be restored.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let stdout = self.stdout.finish();
+        let stderr = self.stderr.finish();
+        stdout.and(stderr)
+    }
+}
+
+/// Copies what is written to one file descriptor both to where it originally pointed and
+/// into the transcript.
+#[derive(Debug)]
+struct Tee {
+    fd: RawFd,
+    /// Where `fd` pointed before the capture.
+    saved: OwnedFd,
+    /// Write end of the pipe read by `thread`.
+    pipe: OwnedFd,
+    thread: JoinHandle<()>,
+}
+
+impl Tee {
+    fn start(fd: RawFd, marker: char, transcript: Arc<Mutex<Transcript>>) -> io::Result<Self> {
+        io::stdout().flush()?;
+        // SAFETY: stdout and stderr stay open for the life of the program.
+        let saved = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        let (read, pipe) = pipe2(OFlag::O_CLOEXEC)?;
+        let mut terminal = File::from(saved.try_clone()?);
+        let thread = std::thread::spawn(move || {
+            let mut reader = File::from(read);
+            let mut buffer = [0; 4096];
+            let mut pending = Vec::new();
+            while let Ok(len @ 1..) = reader.read(&mut buffer) {
+                let _ = terminal.write_all(&buffer[..len]);
+                pending.extend_from_slice(&buffer[..len]);
+                while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                    let line: Vec<_> = pending.drain(..=end).collect();
+                    transcript
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .write_line(marker, String::from_utf8_lossy(&line).trim_end());
+                }
+            }
+            if !pending.is_empty() {
+                transcript
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .write_line(marker, &String::from_utf8_lossy(&pending));
+            }
+        });
+        let tee = Self {
+            fd,
+            saved,
+            pipe,
+            thread,
+        };
+        tee.point_at(tee.pipe.as_raw_fd())?;
+        Ok(tee)
+    }
+
+    fn point_at(&self, target: RawFd) -> io::Result<()> {
+        io::stdout().flush()?;
+        dup2(target, self.fd)?;
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let restored = self.point_at(self.saved.as_raw_fd());
+        // Closing the last write end lets the copying thread see EOF.
+        drop(self.pipe);
+        let _ = self.thread.join();
+        restored
+    }
+}
+
+/// A transcript read back for `--replay`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Replay {
+    /// Program the session was recorded against, if it launched one.
+    pub program: Option<PathBuf>,
+    /// Commands in the order they were entered.
+    pub commands: Vec<String>,
+}
+
+impl Replay {
+    /// Reads the transcript at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read.
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).context(IoErrWithPathSnafu { path })?;
+        Ok(Self::parse(&contents))
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut replay = Self::default();
+        for line in contents.lines() {
+            if let Some(program) = line.strip_prefix(PROGRAM_HEADER) {
+                replay.program = Some(PathBuf::from(program));
+            } else if let Some((_, entry)) = line.split_once("] ") {
+                let command = entry.strip_prefix(COMMAND_MARKER).map(str::trim_start);
+                // Replaying must not start or stop a recording.
+                if let Some(command) = command.filter(|command| !command.starts_with("record ")) {
+                    replay.commands.push(command.to_string());
+                }
+            }
+        }
+        replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_recorded_commands() {
+        let transcript = "\
+# sdb session recorded at 1700000000 (seconds since the epoch)
+# program: /tmp/hello
+[    0.000] > set env GREETING 'hello world'
+[    1.042] > continue
+[    1.043] | > not a command
+[    1.043] ! No process is running.
+[    2.000] > record stop
+[    2.000] | Recording stopped.
+";
+        let replay = Replay::parse(transcript);
+        assert_eq!(replay.program, Some(PathBuf::from("/tmp/hello")));
+        assert_eq!(
+            replay.commands,
+            ["set env GREETING 'hello world'", "continue"]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! `info sched` and `set affinity`: how the inferior's threads are scheduled, and pinning them.
+
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::{tids, TaskStat};
+use sdb::sched::{format_cpu_list, set_affinity, SchedInfo};
+
+/// Renders a header and one line per thread; the traced thread is marked with `*`.
+pub(crate) fn info(process: &Process) -> Result<Vec<String>> {
+    let mut lines = vec![format!(
+        "  {:<7} {:<16} {:<14} {:>8} {:>4}  {:<12} Switches (voluntary/involuntary)",
+        "Tid", "Name", "Policy", "Priority", "Nice", "CPUs"
+    )];
+    for tid in tids(process.pid)? {
+        let marker = if tid == process.pid { '*' } else { ' ' };
+        let name =
+            TaskStat::read(process.pid, tid).map_or_else(|_| "?".to_string(), |stat| stat.comm);
+        // Threads may exit while they are listed.
+        let Ok(sched) = SchedInfo::read(process.pid, tid) else {
+            continue;
+        };
+        let count = |count: Option<u64>| count.map_or_else(|| "?".to_string(), |n| n.to_string());
+        lines.push(format!(
+            "{marker} {tid:<7} {name:<16} {:<14} {:>8} {:>4}  {:<12} {}/{}",
+            sched.policy_name(),
+            sched.priority,
+            sched.nice,
+            format_cpu_list(&sched.affinity),
+            count(sched.voluntary_switches),
+            count(sched.involuntary_switches)
+        ));
+    }
+    Ok(lines)
+}
+
+/// Pins every thread to `cpus` and says how many were pinned.
+pub(crate) fn pin(process: &Process, cpus: &[usize]) -> Result<String> {
+    let threads = set_affinity(process.pid, cpus)?;
+    let threads = if threads == 1 {
+        "1 thread".to_string()
+    } else {
+        format!("{threads} threads")
+    };
+    let cpus_word = if cpus.len() == 1 { "CPU" } else { "CPUs" };
+    Ok(format!(
+        "Pinned {threads} to {cpus_word} {}.",
+        format_cpu_list(cpus)
+    ))
+}
@@ -0,0 +1,469 @@
+//! Typed debugger settings behind `set`/`show`, persisted with `settings save`.
+
+use crate::define::{check_name, UserCommands};
+use crate::error::{InvalidSettingValueSnafu, IoErrWithPathSnafu, Result, UnknownSettingSnafu};
+use crate::history::DEFAULT_HISTORY_SIZE;
+use crate::telescope::DEFAULT_TELESCOPE_DEPTH;
+use sdb::disassembler::Syntax;
+use sdb::output::format_duration;
+use sdb::syscall::MAX_BUFFER_LEN;
+use snafu::{OptionExt as _, ResultExt as _};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Value of a setting; the variant fixes which values `set` accepts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    /// `on`/`off`.
+    Bool(bool),
+    /// A non-negative integer.
+    Int(usize),
+    /// One of a fixed set of words.
+    Enum {
+        value: &'static str,
+        choices: &'static [&'static str],
+    },
+    /// A file system path, unset by default.
+    Path(Option<PathBuf>),
+    /// A duration such as `30s`, or `off`.
+    Duration(Option<Duration>),
+}
+
+impl Value {
+    /// Parses `input` as a value of the same type as `self`.
+    fn parse(&self, input: &str) -> Option<Self> {
+        Some(match self {
+            Self::Bool(_) => Self::Bool(match input {
+                "on" | "true" | "yes" | "1" => true,
+                "off" | "false" | "no" | "0" => false,
+                _ => return None,
+            }),
+            Self::Int(_) => Self::Int(input.parse().ok()?),
+            Self::Enum { choices, .. } => Self::Enum {
+                value: choices.iter().find(|choice| **choice == input)?,
+                choices,
+            },
+            Self::Path(_) => Self::Path((!input.is_empty()).then(|| PathBuf::from(input))),
+            Self::Duration(_) => Self::Duration(match input {
+                "off" | "0" => None,
+                _ => Some(parse_duration(input)?),
+            }),
+        })
+    }
+
+    /// Describes the accepted values, e.g. `on or off`.
+    fn expected(&self) -> String {
+        match self {
+            Self::Bool(_) => "on or off".to_string(),
+            Self::Int(_) => "a non-negative integer".to_string(),
+            Self::Enum { choices, .. } => format!("one of {}", choices.join(", ")),
+            Self::Path(_) => "a path".to_string(),
+            Self::Duration(_) => "a duration such as 30s, 500ms or 2m, or off".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(value) => f.write_str(if *value { "on" } else { "off" }),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Enum { value, .. } => f.write_str(value),
+            Self::Path(Some(path)) => write!(f, "{}", path.display()),
+            Self::Path(None) => Ok(()),
+            Self::Duration(Some(duration)) => f.write_str(&format_duration(*duration)),
+            Self::Duration(None) => f.write_str("off"),
+        }
+    }
+}
+
+/// One named option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Setting {
+    /// Name, possibly several words such as `print max-elements`.
+    pub name: &'static str,
+    /// One-line description.
+    pub doc: &'static str,
+    /// Current value.
+    pub value: Value,
+}
+
+/// All settings with their current values, and the user commands saved along with them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Settings {
+    settings: Vec<Setting>,
+    /// Commands made with `define`.
+    pub user_commands: UserCommands,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let setting = |name, doc, value| Setting { name, doc, value };
+        #[cfg_attr(
+            not(feature = "tracing"),
+            allow(unused_mut, reason = "Debug settings need `tracing`.")
+        )]
+        let mut settings = vec![
+            setting(
+                "pagination",
+                "Page long output through $PAGER (default `less -FRX`)",
+                Value::Bool(true),
+            ),
+            setting(
+                "history size",
+                "Number of commands to remember",
+                Value::Int(DEFAULT_HISTORY_SIZE),
+            ),
+            setting(
+                "history expansion",
+                "Make `!!` and `!prefix` recall earlier commands (`!` then no longer runs shell commands)",
+                Value::Bool(false),
+            ),
+            setting(
+                "history file",
+                "Where history is saved (default $XDG_DATA_HOME/sdb/history)",
+                Value::Path(None),
+            ),
+            setting(
+                "disassembly-flavor",
+                "Assembly syntax of disassembly",
+                Value::Enum {
+                    value: "intel",
+                    choices: &["intel", "att"],
+                },
+            ),
+            setting(
+                "context",
+                "Show registers, code and stack (the `context` command) at every stop",
+                Value::Bool(true),
+            ),
+            setting(
+                "telescope depth",
+                "Pointers followed from each register and stack slot in `context` and `stack view`",
+                Value::Int(DEFAULT_TELESCOPE_DEPTH),
+            ),
+            setting(
+                "catch seccomp",
+                "Make only syscalls that catchpoints catch stop the program, with a seccomp filter installed at `run` (set-user-ID programs then run unprivileged)",
+                Value::Bool(false),
+            ),
+            setting(
+                "print max-elements",
+                "Bytes of each syscall buffer to print",
+                Value::Int(MAX_BUFFER_LEN),
+            ),
+            setting(
+                "hang-detector",
+                "Interrupt the program and show where it is when `continue` runs longer than this",
+                Value::Duration(None),
+            ),
+            setting(
+                "show-timing",
+                "Print the wall-clock and CPU time the program ran for at each stop",
+                Value::Bool(false),
+            ),
+            setting(
+                "check-consistency",
+                "Before each command, warn if the program was resumed, traced or had its patches or debug registers changed by something else, and repair what can be",
+                Value::Bool(true),
+            ),
+        ];
+        #[cfg(feature = "tracing")]
+        settings.push(setting(
+            "debug ptrace",
+            "Log every ptrace request, waitpid result and signal to --log-file",
+            Value::Bool(false),
+        ));
+        Self {
+            settings,
+            user_commands: UserCommands::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Returns all settings in display order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Setting> {
+        self.settings.iter()
+    }
+
+    /// Looks up a setting by its name.
+    ///
+    /// # Errors
+    /// Returns an error if there is no such setting.
+    pub(crate) fn get(&self, name: &str) -> Result<&Setting> {
+        self.settings
+            .iter()
+            .find(|setting| setting.name == name)
+            .context(UnknownSettingSnafu { name })
+    }
+
+    /// Parses `words` as `<name words>... <value>` and applies it.
+    ///
+    /// # Errors
+    /// Returns an error if the setting does not exist or the value has the wrong type.
+    pub(crate) fn set_words(&mut self, words: &[String]) -> Result<()> {
+        let Some((value, name)) = words.split_last() else {
+            return UnknownSettingSnafu { name: "" }.fail();
+        };
+        // `set history expansion` alone means `on` for booleans, like gdb.
+        let whole = words.join(" ");
+        if matches!(
+            self.get(&whole),
+            Ok(Setting {
+                value: Value::Bool(_),
+                ..
+            })
+        ) {
+            return self.set(&whole, "on");
+        }
+        self.set(&name.join(" "), value)
+    }
+
+    /// Sets `name` to `input`.
+    ///
+    /// # Errors
+    /// Returns an error if the setting does not exist or the value has the wrong type.
+    pub(crate) fn set(&mut self, name: &str, input: &str) -> Result<()> {
+        let setting = self
+            .settings
+            .iter_mut()
+            .find(|setting| setting.name == name)
+            .context(UnknownSettingSnafu { name })?;
+        setting.value = setting
+            .value
+            .parse(input)
+            .context(InvalidSettingValueSnafu {
+                name,
+                value: input,
+                expected: setting.value.expected(),
+            })?;
+        Ok(())
+    }
+
+    fn value(&self, name: &str) -> Option<&Value> {
+        self.get(name).ok().map(|setting| &setting.value)
+    }
+
+    fn bool(&self, name: &str) -> bool {
+        matches!(self.value(name), Some(Value::Bool(true)))
+    }
+
+    fn int(&self, name: &str) -> usize {
+        match self.value(name) {
+            Some(Value::Int(value)) => *value,
+            _ => 0,
+        }
+    }
+
+    /// `pagination`.
+    pub(crate) fn pagination(&self) -> bool {
+        self.bool("pagination")
+    }
+
+    /// `history size`.
+    pub(crate) fn history_size(&self) -> usize {
+        self.int("history size")
+    }
+
+    /// `history expansion`.
+    pub(crate) fn history_expansion(&self) -> bool {
+        self.bool("history expansion")
+    }
+
+    /// `history file`.
+    #[cfg_attr(
+        not(feature = "file-history"),
+        allow(dead_code, reason = "History is only saved with `file-history`.")
+    )]
+    pub(crate) fn history_file(&self) -> Option<&Path> {
+        match self.value("history file") {
+            Some(Value::Path(path)) => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// `disassembly-flavor`.
+    pub(crate) fn disassembly_flavor(&self) -> Syntax {
+        match self.value("disassembly-flavor") {
+            Some(Value::Enum { value: "att", .. }) => Syntax::Att,
+            _ => Syntax::Intel,
+        }
+    }
+
+    /// `context`.
+    pub(crate) fn context(&self) -> bool {
+        self.bool("context")
+    }
+
+    /// `telescope depth`.
+    pub(crate) fn telescope_depth(&self) -> usize {
+        self.int("telescope depth")
+    }
+
+    /// `catch seccomp`.
+    pub(crate) fn catch_seccomp(&self) -> bool {
+        self.bool("catch seccomp")
+    }
+
+    /// `print max-elements`.
+    pub(crate) fn print_max_elements(&self) -> usize {
+        self.int("print max-elements")
+    }
+
+    /// `hang-detector`.
+    pub(crate) fn hang_detector(&self) -> Option<Duration> {
+        match self.value("hang-detector") {
+            Some(Value::Duration(duration)) => *duration,
+            _ => None,
+        }
+    }
+
+    /// `show-timing`.
+    pub(crate) fn show_timing(&self) -> bool {
+        self.bool("show-timing")
+    }
+
+    /// `check-consistency`.
+    pub(crate) fn check_consistency(&self) -> bool {
+        self.bool("check-consistency")
+    }
+
+    /// `debug ptrace`.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn debug_ptrace(&self) -> bool {
+        self.bool("debug ptrace")
+    }
+
+    /// Applies `name = value` lines and `define <name>` ... `end` blocks from the config file
+    /// at `path`.
+    ///
+    /// # Returns
+    /// A warning for each line that could not be applied.
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but cannot be read.
+    pub(crate) fn load(&mut self, path: &Path) -> Result<Vec<String>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).context(IoErrWithPathSnafu { path }),
+        };
+        let mut warnings = Vec::new();
+        let mut lines = (1..).zip(contents.lines());
+        while let Some((number, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("define ") {
+                let body = lines
+                    .by_ref()
+                    .map(|(_, line)| line.trim())
+                    .take_while(|line| *line != "end")
+                    .map(str::to_string)
+                    .collect();
+                let name = name.trim();
+                match check_name(name) {
+                    Ok(()) => _ = self.user_commands.define(name.to_string(), body),
+                    Err(err) => warnings.push(format!("{}:{number}: {err}", path.display())),
+                }
+                continue;
+            }
+            let applied = line.split_once('=').map_or_else(
+                || UnknownSettingSnafu { name: line }.fail(),
+                |(name, value)| self.set(name.trim(), value.trim()),
+            );
+            if let Err(err) = applied {
+                warnings.push(format!("{}:{number}: {err}", path.display()));
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Writes every setting as `name = value`, then the user commands, to `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file or its directory cannot be written.
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let mut contents: String = self
+            .settings
+            .iter()
+            .map(|setting| format!("{} = {}\n", setting.name, setting.value))
+            .collect();
+        contents.push_str(&self.user_commands.render(None));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(IoErrWithPathSnafu { path: dir })?;
+        }
+        std::fs::write(path, contents).context(IoErrWithPathSnafu { path })
+    }
+}
+
+/// Parses a positive duration with a unit: `500ms`, `30s`, `2m` or `1h`.
+pub(crate) fn parse_duration(input: &str) -> Option<Duration> {
+    let split = input.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.checked_mul(60)?),
+        "h" => Duration::from_secs(number.checked_mul(60 * 60)?),
+        _ => return None,
+    };
+    (!duration.is_zero()).then_some(duration)
+}
+
+/// Returns `$XDG_CONFIG_HOME/sdb/config`, falling back to `~/.config/sdb/config`.
+pub(crate) fn config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("sdb").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_set_typed_values() -> Result<()> {
+        let mut settings = Settings::default();
+        let words = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+        settings.set_words(&words("print max-elements 16"))?;
+        settings.set_words(&words("disassembly-flavor att"))?;
+        settings.set_words(&words("pagination off"))?;
+        settings.set_words(&words("history expansion"))?;
+        settings.set_words(&words("hang-detector 1500ms"))?;
+        assert_eq!(settings.print_max_elements(), 16);
+        assert_eq!(settings.hang_detector(), Some(Duration::from_millis(1500)));
+        assert_eq!(settings.get("hang-detector")?.value.to_string(), "1500ms");
+        assert_eq!(settings.disassembly_flavor(), Syntax::Att);
+        assert!(!settings.pagination());
+        assert!(settings.history_expansion());
+
+        assert!(settings.set("pagination", "maybe").is_err());
+        assert!(settings.set("disassembly-flavor", "masm").is_err());
+        assert!(settings.set("hang-detector", "30").is_err());
+        assert!(settings.set("no such setting", "1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_config_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("sdb-settings-{}", std::process::id()));
+        let mut settings = Settings::default();
+        settings.set("history size", "42")?;
+        let body = vec!["bt --limit $arg0".to_string(), "info threads".to_string()];
+        settings.user_commands.define("where".into(), body);
+        settings.save(&path)?;
+
+        let mut loaded = Settings::default();
+        let warnings = loaded.load(&path)?;
+        let _ = std::fs::remove_file(&path);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(loaded, settings);
+        Ok(())
+    }
+}
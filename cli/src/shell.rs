@@ -0,0 +1,52 @@
+//! `shell`/`!` and `pipe`: running host commands from the REPL.
+
+use crate::error::{Result, ShellSnafu};
+use nix::unistd::dup2;
+use snafu::ResultExt as _;
+use std::io::{self, Write as _};
+use std::os::fd::{AsFd as _, AsRawFd as _};
+use std::process::{Command, Stdio};
+
+/// Runs `command` with `sh -c`, sharing the terminal.
+pub(crate) fn run(command: &str) -> Result<()> {
+    let status = Command::new("sh")
+        .args(["-c", command])
+        .status()
+        .context(ShellSnafu { command })?;
+    if !status.success() {
+        println!("Shell command {status}");
+    }
+    Ok(())
+}
+
+/// Runs `f` with stdout connected to the stdin of `sh -c command`.
+pub(crate) fn pipe_to(command: &str, f: impl FnOnce() -> Result<()>) -> Result<()> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context(ShellSnafu { command })?;
+    let result = child
+        .stdin
+        .take()
+        .map_or(Ok(Ok(())), |stdin| with_stdout_redirected(stdin.as_fd(), f));
+    // `stdin` is closed by now, so the shell sees EOF.
+    child.wait().context(ShellSnafu { command })?;
+    result.context(ShellSnafu { command })?
+}
+
+/// Points file descriptor 1 at `target` while `f` runs, then restores it.
+pub(crate) fn with_stdout_redirected<T>(
+    target: std::os::fd::BorrowedFd<'_>,
+    f: impl FnOnce() -> T,
+) -> io::Result<T> {
+    let mut stdout = io::stdout();
+    stdout.flush()?;
+    let saved = stdout.as_fd().try_clone_to_owned()?;
+    dup2(target.as_raw_fd(), stdout.as_raw_fd())?;
+    let value = f();
+    let flushed = stdout.flush();
+    dup2(saved.as_raw_fd(), stdout.as_raw_fd())?;
+    flushed?;
+    Ok(value)
+}
@@ -0,0 +1,80 @@
+//! `mem snapshot`/`mem diff`: named copies of inferior memory to compare across stops.
+
+use crate::error::Result;
+use sdb::process::Process;
+
+/// Bytes of a region as they were when `mem snapshot` ran.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    /// Start address.
+    pub addr: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Copies `len` bytes at `addr`, fewer if the range runs into unmapped memory.
+    pub(crate) fn take(process: &Process, addr: u64, len: u64) -> Result<Self> {
+        Ok(Self {
+            addr,
+            bytes: process.read_memory(addr, len as usize)?,
+        })
+    }
+}
+
+/// A run of consecutive bytes that differ between two snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Change {
+    /// Offset from the start of the snapshot.
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// Lists the byte runs that differ between `old` and `new`, by offset.
+///
+/// Only the part both snapshots cover is compared.
+pub(crate) fn diff(old: &[u8], new: &[u8]) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    for (offset, (&before, &after)) in old.iter().zip(new).enumerate() {
+        if before == after {
+            continue;
+        }
+        match changes.last_mut() {
+            Some(change) if change.offset + change.old.len() == offset => {
+                change.old.push(before);
+                change.new.push(after);
+            }
+            _ => changes.push(Change {
+                offset,
+                old: vec![before],
+                new: vec![after],
+            }),
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_group_consecutive_changes() {
+        let old = [0, 1, 2, 3, 4, 5, 6];
+        let new = [0, 9, 9, 3, 4, 8, 6, 7];
+        let expected = vec![
+            Change {
+                offset: 1,
+                old: vec![1, 2],
+                new: vec![9, 9],
+            },
+            Change {
+                offset: 5,
+                old: vec![5],
+                new: vec![8],
+            },
+        ];
+        assert_eq!(diff(&old, &new), expected);
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! `info sockets`: the socket descriptors of the inferior with their addresses and states.
+
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::read_fds;
+use sdb::sockets::{read_sockets, socket_inode, socket_protocol_name};
+
+/// Renders a header and one line per socket descriptor.
+///
+/// Sockets missing from the TCP, UDP and Unix tables, e.g. netlink or unbound UDP ones, only
+/// show the protocol name the kernel gives them.
+pub(crate) fn info(process: &Process) -> Result<Vec<String>> {
+    let fds: Vec<_> = read_fds(process.pid)?
+        .into_iter()
+        .filter_map(|fd| Some((fd.fd, socket_inode(&fd.target)?)))
+        .collect();
+    if fds.is_empty() {
+        return Ok(vec!["No socket is open.".to_string()]);
+    }
+    let sockets = read_sockets(process.pid)?;
+    let mut lines = vec![format!(
+        "{:>4}  {:<14} {:<12} {:<28} {:<28} Inode",
+        "Fd", "Protocol", "State", "Local", "Remote"
+    )];
+    lines.extend(fds.iter().map(|&(fd, inode)| {
+        let (protocol, state, local, remote) = sockets
+            .iter()
+            .find(|socket| socket.inode == inode)
+            .map_or_else(
+                || {
+                    let protocol =
+                        socket_protocol_name(process.pid, fd).unwrap_or_else(|| "?".to_string());
+                    (protocol, "-", "-".to_string(), "-".to_string())
+                },
+                |socket| {
+                    let local = if socket.local.is_empty() {
+                        "-".to_string()
+                    } else {
+                        socket.local.clone()
+                    };
+                    let remote = socket
+                        .remote
+                        .map_or_else(|| "-".to_string(), |remote| remote.to_string());
+                    (socket.protocol.to_string(), socket.state, local, remote)
+                },
+            );
+        format!("{fd:>4}  {protocol:<14} {state:<12} {local:<28} {remote:<28} {inode}")
+    }));
+    Ok(lines)
+}
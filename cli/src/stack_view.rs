@@ -0,0 +1,90 @@
+//! `stack view`: annotated dump of the stack from `rsp` upward.
+
+use crate::telescope::telescope;
+use sdb::annotate::Annotator;
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::{read_maps, MemoryRegion};
+use sdb::stack::{frame_pointer_chain, stack_canary, Frame, FrameKind};
+
+/// Deepest frame-pointer chain followed when attributing slots to frames.
+const MAX_FRAMES: usize = 256;
+
+/// Renders `nwords` stack slots starting at `rsp`, one per line, following pointers in them
+/// up to `depth` hops.
+pub(crate) fn render(process: &Process, nwords: usize, depth: usize) -> Result<Vec<String>> {
+    let regs = process.read_registers()?;
+    let words = process.read_words(regs.rsp, nwords)?;
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let frames = frame_pointer_chain(process, &regs, MAX_FRAMES);
+    let canary = stack_canary(process, regs.fs_base);
+    let pointees = telescope(process, &maps, &words, depth);
+
+    let lines = (0_u64..)
+        .zip(words)
+        .zip(pointees)
+        .map(|((index, value), pointee)| {
+            let addr = regs.rsp + index * 8;
+            let frame = frames
+                .iter()
+                .find(|frame| addr < frame.cfa())
+                .map_or_else(|| "   ".to_string(), |frame| format!("#{:<2}", frame.index));
+            let alignment = if addr.is_multiple_of(16) { '|' } else { ' ' };
+            let mut notes = Vec::new();
+            if addr == regs.rsp {
+                notes.push("<- rsp".to_string());
+            }
+            if addr == regs.rbp {
+                notes.push("<- rbp".to_string());
+            }
+            notes.extend(slot_role(&frames, addr, value, &maps));
+            if value != 0 && Some(value) == canary {
+                notes.push("stack canary".to_string());
+            } else if !is_frame_slot(&frames, addr) && !pointee.is_empty() {
+                notes.push(pointee);
+            }
+            format!(
+                "{frame} {alignment} {addr:#014x} (rsp+{:#06x}): {value:#018x}  {}",
+                index * 8,
+                notes.join(", ")
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect();
+    Ok(lines)
+}
+
+/// Names the role of the slot at `addr` holding `value` if a frame saved a register of its
+/// caller there, e.g. `rbp` or the return address.
+fn slot_role(frames: &[Frame], addr: u64, value: u64, maps: &[MemoryRegion]) -> Option<String> {
+    frames.iter().find_map(|frame| {
+        let caller = frame.index + 1;
+        let (name, _) = frame
+            .saved_registers()
+            .into_iter()
+            .find(|&(_, slot)| slot == addr)?;
+        if name != "rip" {
+            return Some(format!("saved {name} of frame #{caller}"));
+        }
+        let target = Annotator::new(maps)
+            .annotate(value)
+            .place()
+            .map(|place| format!(" to {place}"))
+            .unwrap_or_default();
+        let what = match frame.kind {
+            FrameKind::FramePointer => "return address",
+            FrameKind::Signal { .. } => "interrupted rip",
+        };
+        Some(format!("{what}{target} (frame #{caller})"))
+    })
+}
+
+fn is_frame_slot(frames: &[Frame], addr: u64) -> bool {
+    frames.iter().any(|frame| {
+        frame
+            .saved_registers()
+            .iter()
+            .any(|&(_, slot)| slot == addr)
+    })
+}
@@ -0,0 +1,88 @@
+//! `sym list`: the symbols of the program or a library, with their runtime addresses.
+
+use crate::compile_units::find_module;
+use crate::error::Result;
+use sdb::process::Process;
+use sdb::procfs::read_maps;
+use sdb::symbol_list::{self, ListedSymbol, SymbolQuery};
+use sdb::symbols::SymbolTable;
+use std::path::Path;
+
+/// Lists the symbols of `lib`, or of the program, matching `query`, skipping the first
+/// `skip` and stopping after `limit`.
+pub(crate) fn list(
+    process: &Process,
+    lib: Option<&str>,
+    query: &SymbolQuery,
+    skip: usize,
+    limit: Option<usize>,
+) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid)?;
+    let module = find_module(&maps, lib)?;
+    let path = Path::new(&module.path);
+    let bias = SymbolTable::cached(path)?
+        .load_bias(module)
+        .unwrap_or_default();
+    let listing = symbol_list::list(path, query)?;
+    let total = listing.symbols.len();
+    if total == 0 {
+        return Ok(vec![format!("No symbols of {} match.", module.path)]);
+    }
+    let name = path
+        .file_name()
+        .map_or_else(|| module.path.clone(), |name| name.to_string_lossy().into());
+    let shown = listing
+        .symbols
+        .iter()
+        .skip(skip)
+        .take(limit.unwrap_or(usize::MAX));
+    let mut lines: Vec<_> = shown
+        .map(|symbol| {
+            let place = format!("{name}+{:#x}", symbol.address - listing.image_base);
+            format_symbol(symbol, symbol.address.wrapping_add(bias), &place)
+        })
+        .collect();
+    let end = skip + lines.len();
+    if skip > 0 || end < total {
+        lines.push(format!(
+            "Symbols {}..{end} of {total}; see --skip and --limit.",
+            skip.min(total)
+        ));
+    }
+    Ok(lines)
+}
+
+/// Formats one line of `sym list`: runtime address, module+offset, size, kind, source,
+/// section and name.
+fn format_symbol(symbol: &ListedSymbol, addr: u64, place: &str) -> String {
+    format!(
+        "{addr:#018x}  {place:<28} {:>8}  {} {:<6} {:<12} {}",
+        symbol.size,
+        if symbol.is_function { 'F' } else { 'D' },
+        symbol.source,
+        symbol.section.as_deref().unwrap_or("?"),
+        symbol.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdb::symbol_list::SymbolSource;
+
+    #[test]
+    fn should_format_symbol() {
+        let symbol = ListedSymbol {
+            name: "main".into(),
+            address: 0x1149,
+            size: 35,
+            is_function: true,
+            source: SymbolSource::Static,
+            section: Some(".text".into()),
+        };
+        assert_eq!(
+            format_symbol(&symbol, 0x5555_5555_5149, "hello+0x1149"),
+            "0x0000555555555149  hello+0x1149                       35  F symtab .text        main"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Following chains of pointers, e.g. `[stack] -> 0x7ffd8a2c1f2e [stack] -> "/tmp/hello"`.
+
+use sdb::annotate::Annotator;
+use sdb::process::Process;
+use sdb::procfs::{find_region, MemoryRegion};
+use std::path::Path;
+
+/// Default `telescope depth`.
+pub(crate) const DEFAULT_TELESCOPE_DEPTH: usize = 3;
+/// Bytes read at each hop, enough for a short string.
+const PEEK_LEN: usize = 32;
+/// Shortest run of printable characters shown as a string rather than a number.
+const MIN_STRING_LEN: usize = 4;
+
+/// A chain being followed.
+struct Chain<'a> {
+    hops: Vec<String>,
+    /// Address to read next, with its mapping.
+    next: Option<(u64, &'a MemoryRegion)>,
+}
+
+/// Describes what each of `values` points to, following pointers up to `depth` hops.
+///
+/// Each hop names the mapping pointed into, then what is there: a symbol for code, a string,
+/// or the word stored there. A value not pointing to readable memory gets `""`. The memory of
+/// every chain's next hop is fetched with one batched read.
+pub(crate) fn telescope(
+    process: &Process,
+    maps: &[MemoryRegion],
+    values: &[u64],
+    depth: usize,
+) -> Vec<String> {
+    let mut chains: Vec<_> = values
+        .iter()
+        .map(|&value| Chain {
+            hops: Vec::new(),
+            next: readable(maps, value).map(|region| (value, region)),
+        })
+        .collect();
+    for level in 0..depth {
+        // Clamped to the mapping so one short range does not cut off the rest of the batch.
+        let ranges: Vec<_> = chains
+            .iter()
+            .map(|chain| match chain.next {
+                Some((addr, region)) if !region.execute => {
+                    (addr, PEEK_LEN.min((region.end - addr) as usize))
+                }
+                _ => (0, 0),
+            })
+            .collect();
+        let data = process.read_memory_batch(&ranges).unwrap_or_default();
+        for (index, chain) in chains.iter_mut().enumerate() {
+            let Some((addr, region)) = chain.next.take() else {
+                continue;
+            };
+            let name = region_name(region);
+            if region.execute {
+                chain
+                    .hops
+                    .push(Annotator::new(maps).annotate(addr).place().map_or_else(
+                        || format!("{name} code"),
+                        |place| format!("{name} -> {place}"),
+                    ));
+                continue;
+            }
+            let bytes = data.get(index).map(Vec::as_slice).unwrap_or_default();
+            chain.hops.push(match describe_data(bytes) {
+                Some(Pointee::Word(word)) => {
+                    if level + 1 < depth {
+                        chain.next = readable(maps, word).map(|region| (word, region));
+                    }
+                    format!("{name} -> {word:#x}")
+                }
+                Some(Pointee::String(text)) => format!("{name} -> {text}"),
+                None => name,
+            });
+        }
+    }
+    chains
+        .into_iter()
+        .map(|chain| chain.hops.join(" "))
+        .collect()
+}
+
+fn readable(maps: &[MemoryRegion], addr: u64) -> Option<&MemoryRegion> {
+    find_region(maps, addr).filter(|region| region.read)
+}
+
+/// Short name of a mapping: `[stack]`, `[heap]`, a file name, or `anon`.
+fn region_name(region: &MemoryRegion) -> String {
+    if region.path.is_empty() {
+        return "anon".to_string();
+    }
+    if region.path.starts_with('[') {
+        return region.path.clone();
+    }
+    Path::new(&region.path).file_name().map_or_else(
+        || region.path.clone(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// What memory holds, as far as telescoping is concerned.
+#[derive(Debug, PartialEq, Eq)]
+enum Pointee {
+    /// A quoted string, with `...` if it may continue past what was read.
+    String(String),
+    /// The first word, possibly another pointer.
+    Word(u64),
+}
+
+/// Reads memory as a string if it starts with one, otherwise as its first word.
+fn describe_data(bytes: &[u8]) -> Option<Pointee> {
+    let printable = bytes
+        .iter()
+        .take_while(|byte| byte.is_ascii_graphic() || **byte == b' ')
+        .count();
+    if printable >= MIN_STRING_LEN {
+        let text = String::from_utf8_lossy(&bytes[..printable]);
+        let ellipsis = if printable == bytes.len() { "..." } else { "" };
+        return Some(Pointee::String(format!("{text:?}{ellipsis}")));
+    }
+    let word: [u8; 8] = bytes.get(..8)?.try_into().ok()?;
+    Some(Pointee::Word(u64::from_le_bytes(word)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_strings_and_words() {
+        let string = |text: &str| Some(Pointee::String(text.to_string()));
+        assert_eq!(describe_data(b"hello\0world"), string("\"hello\""));
+        assert_eq!(describe_data(b"long"), string("\"long\"..."));
+        let word = 0x1234_u64.to_le_bytes();
+        assert_eq!(describe_data(&word), Some(Pointee::Word(0x1234)));
+        assert_eq!(describe_data(&[1, 2]), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! `thread list`: name, scheduler state, location and stop reason of every thread.
+//!
+//! Only the main thread is traced, so the others keep running while the program is stopped at
+//! the prompt. `/proc` tells where they are blocked in a syscall; where they run in user space
+//! is only known by sampling: interrupting them for the instant it takes to read `rip`.
+
+use nix::sys::wait::WaitStatus;
+use sdb::annotate::Annotator;
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::{read_maps, tids, MemoryRegion, TaskStat, TaskSyscall};
+use sdb::syscall::syscall_name;
+
+/// Renders one line per thread of the inferior; the traced thread is marked with `*`.
+///
+/// With `sample`, running threads are interrupted briefly to find their location.
+pub(crate) fn list(process: &Process, sample: bool) -> Result<Vec<String>> {
+    let maps = read_maps(process.pid).unwrap_or_default();
+    let lines = tids(process.pid)?
+        .into_iter()
+        .map(|tid| {
+            let is_traced = tid == process.pid;
+            let stat = TaskStat::read(process.pid, tid).ok();
+            let name = stat.as_ref().map_or("?", |stat| stat.comm.as_str());
+            let syscall = TaskSyscall::read(process.pid, tid).ok();
+            let state = match (syscall, &stat) {
+                (Some(TaskSyscall::Blocked { number, .. }), _) if !is_traced => {
+                    let name =
+                        syscall_name(number).map_or_else(|| number.to_string(), str::to_string);
+                    format!("blocked in {name}")
+                }
+                (_, Some(stat)) => stat.state_name().to_string(),
+                (_, None) => "unknown".to_string(),
+            };
+            let pc = if is_traced {
+                process.read_registers().ok().map(|regs| regs.rip)
+            } else {
+                syscall.and_then(|syscall| syscall.pc()).or_else(|| {
+                    sample
+                        .then(|| process.thread_registers(tid).ok())
+                        .flatten()
+                        .map(|regs| regs.rip)
+                })
+            };
+            let location = pc.map_or_else(|| "-".to_string(), |pc| describe_pc(&maps, pc));
+            let reason = if is_traced {
+                stop_reason(process)
+            } else {
+                "-".to_string()
+            };
+            let marker = if is_traced { '*' } else { ' ' };
+            format!("{marker} {tid:<7} {name:<16} {state:<26} {location:<44} {reason}")
+        })
+        .collect();
+    Ok(lines)
+}
+
+fn describe_pc(maps: &[MemoryRegion], pc: u64) -> String {
+    Annotator::new(maps).symbol(pc).map_or_else(
+        || format!("{pc:#x}"),
+        |location| format!("{pc:#x} <{location}>"),
+    )
+}
+
+/// Summarizes why the traced thread last stopped.
+fn stop_reason(process: &Process) -> String {
+    match process.state {
+        WaitStatus::Stopped(_, signal) => format!("signal {signal}"),
+        WaitStatus::PtraceSyscall(_) => process
+            .last_syscall
+            .as_ref()
+            .and_then(|event| event.name())
+            .map_or_else(|| "syscall".to_string(), |name| format!("syscall {name}")),
+        WaitStatus::PtraceEvent(_, signal, event) => format!("ptrace event {event} ({signal})"),
+        other => format!("{other:?}"),
+    }
+}
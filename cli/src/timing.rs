@@ -0,0 +1,62 @@
+//! `set show-timing on`: wall-clock and CPU time the inferior spent between two stops.
+
+use nix::unistd::Pid;
+use sdb::procfs::cpu_time;
+use std::time::{Duration, Instant};
+
+/// Times one `continue`.
+pub(crate) struct RunTimer {
+    pid: Pid,
+    wall: Instant,
+    /// CPU time of all threads at the start.
+    cpu: Option<Duration>,
+}
+
+impl RunTimer {
+    pub(crate) fn start(pid: Pid) -> Self {
+        Self {
+            pid,
+            wall: Instant::now(),
+            cpu: cpu_time(pid).ok(),
+        }
+    }
+
+    /// Describes the time spent since [`Self::start`]. CPU time is left out once the process
+    /// has been reaped, as `/proc` no longer has it.
+    pub(crate) fn report(&self) -> String {
+        let cpu = self
+            .cpu
+            .zip(cpu_time(self.pid).ok())
+            .map(|(start, end)| end.saturating_sub(start));
+        describe(self.wall.elapsed(), cpu)
+    }
+}
+
+/// Formats e.g. `Ran for 1.204s, 0.980s CPU (81%)`. Multithreaded programs can use more
+/// than 100%.
+fn describe(wall: Duration, cpu: Option<Duration>) -> String {
+    let mut text = format!("Ran for {:.3}s", wall.as_secs_f64());
+    if let Some(cpu) = cpu {
+        text.push_str(&format!(", {:.3}s CPU", cpu.as_secs_f64()));
+        if !wall.is_zero() {
+            let percent = cpu.as_secs_f64() / wall.as_secs_f64() * 100.0;
+            text.push_str(&format!(" ({percent:.0}%)"));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_timing() {
+        let wall = Duration::from_millis(2000);
+        assert_eq!(
+            describe(wall, Some(Duration::from_millis(500))),
+            "Ran for 2.000s, 0.500s CPU (25%)"
+        );
+        assert_eq!(describe(wall, None), "Ran for 2.000s");
+    }
+}
@@ -0,0 +1,178 @@
+//! Describing inferior addresses for output: the mapping and module they are in, the symbol
+//! containing them and the source line they were compiled from.
+//!
+//! Stop reports, backtraces, register and memory dumps and disassembly all go through
+//! [`Annotator`], so an address reads the same wherever it is printed.
+
+use crate::jit::JitObject;
+use crate::line_table::{LineTable, SourceLine};
+use crate::procfs::{find_region, MemoryRegion};
+use crate::symbols::{Symbol, SymbolTable};
+use serde::Serialize;
+use std::path::Path;
+
+/// The symbol containing an address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SymbolLocation {
+    /// Symbol name.
+    pub name: String,
+    /// Offset of the address into the symbol.
+    pub offset: u64,
+}
+
+impl std::fmt::Display for SymbolLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}+{:#x}", self.name, self.offset)
+    }
+}
+
+/// Everything known about an address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Annotation {
+    pub addr: u64,
+    /// Mapping containing the address.
+    pub region: Option<MemoryRegion>,
+    pub symbol: Option<SymbolLocation>,
+    pub line: Option<SourceLine>,
+}
+
+impl Annotation {
+    /// Returns the path of the mapped file or the pseudo name such as `[stack]`, if any.
+    pub fn module(&self) -> Option<&str> {
+        self.region
+            .as_ref()
+            .map(|region| region.path.as_str())
+            .filter(|path| !path.is_empty())
+    }
+
+    /// Returns the symbol and source line, e.g. `main+0x10 at main.c:12`, if known.
+    pub fn place(&self) -> Option<String> {
+        let symbol = self.symbol.as_ref()?;
+        Some(
+            self.line
+                .as_ref()
+                .map_or_else(|| symbol.to_string(), |line| format!("{symbol} at {line}")),
+        )
+    }
+}
+
+impl std::fmt::Display for Annotation {
+    /// Writes e.g. `0x401136 in main+0x10 at main.c:12 (/tmp/crash, r-xp)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}", self.addr)?;
+        let Some(region) = &self.region else {
+            return write!(f, " (not in any mapping)");
+        };
+        if let Some(place) = self.place() {
+            write!(f, " in {place}")?;
+        }
+        let name = self.module().unwrap_or("anonymous");
+        write!(f, " ({name}, {})", region.perms())
+    }
+}
+
+/// Annotates addresses of a process with the memory map `maps`.
+///
+/// Symbol and line tables are cached per file, so annotators are cheap to make for each
+/// printout.
+#[derive(Clone, Copy, Debug)]
+pub struct Annotator<'a> {
+    maps: &'a [MemoryRegion],
+    /// Describes code outside mapped files.
+    jit: &'a [JitObject],
+}
+
+impl<'a> Annotator<'a> {
+    pub const fn new(maps: &'a [MemoryRegion]) -> Self {
+        Self { maps, jit: &[] }
+    }
+
+    /// Also describes the JIT code of `jit`, e.g. [`crate::process::Process::jit_objects`].
+    #[must_use]
+    pub const fn with_jit(self, jit: &'a [JitObject]) -> Self {
+        Self { jit, ..self }
+    }
+
+    /// Returns the memory map addresses are annotated with.
+    pub const fn maps(&self) -> &'a [MemoryRegion] {
+        self.maps
+    }
+
+    /// Describes `addr`.
+    pub fn annotate(&self, addr: u64) -> Annotation {
+        Annotation {
+            addr,
+            region: find_region(self.maps, addr).cloned(),
+            symbol: self.symbol(addr),
+            line: self.line(addr),
+        }
+    }
+
+    /// Finds the symbol containing `addr` in the ELF file mapped there, or in the JIT object
+    /// describing it.
+    pub fn symbol(&self, addr: u64) -> Option<SymbolLocation> {
+        let location = |(symbol, offset): (&Symbol, u64)| SymbolLocation {
+            name: symbol.name.clone(),
+            offset,
+        };
+        let Some((region, path)) = self.file_region(addr) else {
+            return self
+                .jit
+                .iter()
+                .find_map(|object| object.symbol(addr).map(location));
+        };
+        let table = SymbolTable::cached(path).ok()?;
+        table.symbolize(region, addr).map(location)
+    }
+
+    /// Finds the source line of `addr` in the DWARF of the ELF file mapped there, or of the
+    /// JIT object describing it.
+    pub fn line(&self, addr: u64) -> Option<SourceLine> {
+        let Some((region, path)) = self.file_region(addr) else {
+            return self.jit.iter().find_map(|object| object.line(addr));
+        };
+        let bias = SymbolTable::cached(path).ok()?.load_bias(region)?;
+        LineTable::cached(path).ok()?.find(addr.wrapping_sub(bias))
+    }
+
+    /// Finds the mapping of a file containing `addr`.
+    fn file_region(&self, addr: u64) -> Option<(&'a MemoryRegion, &'a Path)> {
+        let region = find_region(self.maps, addr)?;
+        let path = Path::new(&region.path);
+        // Not anonymous or pseudo mappings like `[stack]`.
+        path.is_absolute().then_some((region, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_address() {
+        let region = MemoryRegion::parse("00401000-00402000 r-xp 00001000 08:01 42 /tmp/crash");
+        let annotation = Annotation {
+            addr: 0x40_1136,
+            region,
+            symbol: Some(SymbolLocation {
+                name: "main".into(),
+                offset: 0x10,
+            }),
+            line: Some(SourceLine {
+                file: "/src/app/main.c".into(),
+                line: 12,
+            }),
+        };
+        assert_eq!(
+            annotation.to_string(),
+            "0x401136 in main+0x10 at main.c:12 (/tmp/crash, r-xp)"
+        );
+        let annotation = Annotation {
+            region: None,
+            symbol: None,
+            line: None,
+            ..annotation
+        };
+        assert_eq!(annotation.to_string(), "0x401136 (not in any mapping)");
+    }
+}
@@ -0,0 +1,437 @@
+//! A small Intel syntax assembler for patching code.
+//!
+//! iced-x86 can encode but not parse assembly, so each line is parsed here into a mnemonic
+//! and operands, and every [`Code`] with that mnemonic is tried. A candidate is accepted if
+//! its encoding decodes back to the same operands; the shortest one wins.
+
+use crate::error::{AssembleSnafu, Result};
+use iced_x86::{
+    Code, Decoder, DecoderOptions, Encoder, Instruction, MemoryOperand, Mnemonic, OpKind, Register,
+};
+
+/// Conditional jump spellings that iced names differently.
+const MNEMONIC_ALIASES: [(&str, &str); 14] = [
+    ("jz", "je"),
+    ("jnz", "jne"),
+    ("jc", "jb"),
+    ("jnae", "jb"),
+    ("jnc", "jae"),
+    ("jnb", "jae"),
+    ("jna", "jbe"),
+    ("jnbe", "ja"),
+    ("jnge", "jl"),
+    ("jnl", "jge"),
+    ("jng", "jle"),
+    ("jnle", "jg"),
+    ("jpe", "jp"),
+    ("jpo", "jnp"),
+];
+
+/// Explicit operand sizes, as in `qword ptr [rsp]`.
+const MEMORY_SIZES: [(&str, usize); 7] = [
+    ("byte", 1),
+    ("word", 2),
+    ("dword", 4),
+    ("qword", 8),
+    ("tbyte", 10),
+    ("xmmword", 16),
+    ("ymmword", 32),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Register(Register),
+    Immediate(i64),
+    Memory {
+        operand: MemoryOperand,
+        /// Size in bytes, if spelled out.
+        size: Option<usize>,
+    },
+}
+
+/// One parsed instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Statement {
+    mnemonic: Mnemonic,
+    operands: Vec<Operand>,
+    lock: bool,
+    rep: bool,
+    repne: bool,
+}
+
+/// Assembles `text`, instructions separated by `;` or newlines, to run at `address`.
+///
+/// Branch and call targets are absolute addresses.
+///
+/// # Errors
+/// Returns an error naming the first instruction that cannot be parsed or encoded.
+///
+/// # Example
+/// ```
+/// use sdb::assembler::assemble;
+///
+/// let bytes = assemble("mov eax, 1; ret", 0x401000)?;
+/// assert_eq!(bytes, [0xb8, 0x01, 0x00, 0x00, 0x00, 0xc3]);
+/// # Ok::<(), sdb::error::SdbError>(())
+/// ```
+pub fn assemble(text: &str, address: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for line in text.split([';', '\n']).map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        let fail = |msg: &str| {
+            AssembleSnafu {
+                text: line,
+                msg: msg.to_string(),
+            }
+            .fail()
+        };
+        let statement = match parse_statement(line) {
+            Ok(statement) => statement,
+            Err(msg) => return fail(&msg),
+        };
+        match encode(&statement, address + bytes.len() as u64) {
+            Ok(encoded) => bytes.extend(encoded),
+            Err(msg) => return fail(&msg),
+        }
+    }
+    Ok(bytes)
+}
+
+fn parse_statement(line: &str) -> Result<Statement, String> {
+    let mut statement = Statement {
+        mnemonic: Mnemonic::INVALID,
+        operands: Vec::new(),
+        lock: false,
+        rep: false,
+        repne: false,
+    };
+    let mut rest = line;
+    loop {
+        let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let word = word.to_ascii_lowercase();
+        match word.as_str() {
+            "lock" => statement.lock = true,
+            "rep" | "repe" | "repz" => statement.rep = true,
+            "repne" | "repnz" => statement.repne = true,
+            _ => {
+                statement.mnemonic = parse_mnemonic(&word)?;
+                rest = tail;
+                break;
+            }
+        }
+        rest = tail.trim_start();
+    }
+    let rest = rest.trim();
+    if !rest.is_empty() {
+        statement.operands = rest
+            .split(',')
+            .map(|operand| parse_operand(operand.trim()))
+            .collect::<Result<_, _>>()?;
+    }
+    Ok(statement)
+}
+
+fn parse_mnemonic(word: &str) -> Result<Mnemonic, String> {
+    let word = MNEMONIC_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == word)
+        .map_or(word, |(_, name)| name);
+    Mnemonic::values()
+        .find(|mnemonic| format!("{mnemonic:?}").eq_ignore_ascii_case(word))
+        .ok_or_else(|| format!("unknown mnemonic `{word}`"))
+}
+
+fn parse_register(word: &str) -> Option<Register> {
+    Register::values()
+        .filter(|register| *register != Register::None)
+        .find(|register| format!("{register:?}").eq_ignore_ascii_case(word))
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let (negative, digits) = word
+        .strip_prefix('-')
+        .map_or((false, word), |digits| (true, digits));
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    } as i64;
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if let Some(register) = parse_register(text) {
+        return Ok(Operand::Register(register));
+    }
+    if let Some(value) = parse_number(text) {
+        return Ok(Operand::Immediate(value));
+    }
+    let lower = text.to_ascii_lowercase();
+    let mut rest = lower.as_str();
+    let mut size = None;
+    if let Some((word, tail)) = rest.split_once(" ptr") {
+        let bytes = MEMORY_SIZES
+            .iter()
+            .find(|(name, _)| *name == word.trim())
+            .map(|(_, bytes)| *bytes)
+            .ok_or_else(|| format!("unknown operand size `{}`", word.trim()))?;
+        size = Some(bytes);
+        rest = tail.trim_start();
+    }
+    let mut segment = Register::None;
+    if let Some((prefix, tail)) = rest.split_once(':') {
+        segment = parse_register(prefix.trim())
+            .filter(|register| register.is_segment_register())
+            .ok_or_else(|| format!("unknown segment `{prefix}`"))?;
+        rest = tail.trim_start();
+    }
+    let inner = rest
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(|| format!("cannot parse operand `{text}`"))?;
+    let operand = parse_address(inner, segment)?;
+    Ok(Operand::Memory { operand, size })
+}
+
+/// Parses `base + index*scale + displacement` in any order.
+fn parse_address(text: &str, segment: Register) -> Result<MemoryOperand, String> {
+    let (mut base, mut index, mut scale, mut displacement) = (Register::None, Register::None, 1, 0);
+    let compact: String = text.chars().filter(|char| !char.is_whitespace()).collect();
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (position, char) in compact.char_indices().skip(1) {
+        if char == '+' || char == '-' {
+            terms.push(&compact[start..position]);
+            start = position;
+        }
+    }
+    terms.push(&compact[start..]);
+
+    for term in terms {
+        let term = term.strip_prefix('+').unwrap_or(term);
+        if let Some(value) = parse_number(term) {
+            displacement += value;
+        } else if let Some((left, right)) = term.split_once('*') {
+            let (register, factor) = parse_register(left)
+                .map(|register| (register, right))
+                .or_else(|| parse_register(right).map(|register| (register, left)))
+                .ok_or_else(|| format!("cannot parse `{term}`"))?;
+            index = register;
+            scale = parse_number(factor)
+                .and_then(|scale| u32::try_from(scale).ok())
+                .filter(|scale| matches!(scale, 1 | 2 | 4 | 8))
+                .ok_or_else(|| format!("scale must be 1, 2, 4 or 8 in `{term}`"))?;
+        } else {
+            let register = parse_register(term).ok_or_else(|| format!("cannot parse `{term}`"))?;
+            if register == Register::RIP {
+                return Err(
+                    "rip relative operands are not supported; use the absolute address".to_string(),
+                );
+            }
+            if base == Register::None {
+                base = register;
+            } else if index == Register::None {
+                index = register;
+            } else {
+                return Err(format!("too many registers in `[{text}]`"));
+            }
+        }
+    }
+    let displ_size = u32::from(displacement != 0);
+    Ok(MemoryOperand::new(
+        base,
+        index,
+        scale,
+        displacement,
+        displ_size,
+        false,
+        segment,
+    ))
+}
+
+/// Returns the shortest encoding of `statement` at `ip`.
+fn encode(statement: &Statement, ip: u64) -> Result<Vec<u8>, String> {
+    let mut best: Option<(Vec<u8>, usize)> = None;
+    let mut memory_sizes = Vec::new();
+    for code in Code::values().filter(|code| {
+        code.mnemonic() == statement.mnemonic && op_count(*code) == statement.operands.len()
+    }) {
+        let Some(mut instruction) = create(code, &statement.operands) else {
+            continue;
+        };
+        instruction.set_has_lock_prefix(statement.lock);
+        instruction.set_has_rep_prefix(statement.rep);
+        instruction.set_has_repne_prefix(statement.repne);
+        let mut encoder = Encoder::new(64);
+        if encoder.encode(&instruction, ip).is_err() {
+            continue;
+        }
+        let bytes = encoder.take_buffer();
+        let decoded = Decoder::with_ip(64, &bytes, ip, DecoderOptions::NONE).decode();
+        if decoded.len() != bytes.len() || !round_trips(&decoded, statement) {
+            continue;
+        }
+        let memory_size = decoded.memory_size().size();
+        if !memory_sizes.contains(&memory_size) {
+            memory_sizes.push(memory_size);
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(best, _)| bytes.len() < best.len())
+        {
+            best = Some((bytes, memory_size));
+        }
+    }
+    let unsized_memory = statement
+        .operands
+        .iter()
+        .any(|operand| matches!(operand, Operand::Memory { size: None, .. }));
+    if unsized_memory && memory_sizes.len() > 1 {
+        return Err("ambiguous operand size; add e.g. `qword ptr`".to_string());
+    }
+    best.map(|(bytes, _)| bytes)
+        .ok_or_else(|| "no encoding with these operands".to_string())
+}
+
+/// Returns the number of operands `code` takes.
+fn op_count(code: Code) -> usize {
+    // `Instruction::with` asserts that `code` has no operands.
+    let mut instruction = Instruction::default();
+    instruction.set_code(code);
+    instruction.op_count() as usize
+}
+
+/// Builds `code` with `operands`, or `None` if the operand types do not fit.
+fn create(code: Code, operands: &[Operand]) -> Option<Instruction> {
+    use Operand::{Immediate as I, Memory as M, Register as R};
+
+    let imm32 = |value: i64| {
+        i32::try_from(value).or_else(|_| u32::try_from(value).map(|value| value as i32))
+    };
+    let instruction = match *operands {
+        [] => Ok(Instruction::with(code)),
+        // Branch targets are immediates in the syntax but not in the encoding.
+        [I(i)] => match Instruction::with_branch(code, i as u64) {
+            Ok(branch) => Ok(branch),
+            Err(_) => Instruction::with1(code, imm32(i).ok()?),
+        },
+        [R(r)] => Instruction::with1(code, r),
+        [M { operand: m, .. }] => Instruction::with1(code, m),
+        [R(r0), R(r1)] => Instruction::with2(code, r0, r1),
+        [R(r), I(i)] => Instruction::with2(code, r, i),
+        [R(r), M { operand: m, .. }] => Instruction::with2(code, r, m),
+        [M { operand: m, .. }, R(r)] => Instruction::with2(code, m, r),
+        [M { operand: m, .. }, I(i)] => Instruction::with2(code, m, imm32(i).ok()?),
+        [I(i), R(r)] => Instruction::with2(code, imm32(i).ok()?, r),
+        [I(i0), I(i1)] => Instruction::with2(code, imm32(i0).ok()?, imm32(i1).ok()?),
+        [R(r0), R(r1), R(r2)] => Instruction::with3(code, r0, r1, r2),
+        [R(r0), R(r1), I(i)] => Instruction::with3(code, r0, r1, imm32(i).ok()?),
+        [R(r0), R(r1), M { operand: m, .. }] => Instruction::with3(code, r0, r1, m),
+        [R(r0), M { operand: m, .. }, R(r1)] => Instruction::with3(code, r0, m, r1),
+        [R(r), M { operand: m, .. }, I(i)] => Instruction::with3(code, r, m, imm32(i).ok()?),
+        [M { operand: m, .. }, R(r0), R(r1)] => Instruction::with3(code, m, r0, r1),
+        [M { operand: m, .. }, R(r), I(i)] => Instruction::with3(code, m, r, imm32(i).ok()?),
+        _ => return None,
+    };
+    instruction.ok()
+}
+
+/// Checks that `decoded` has exactly the operands that were asked for.
+fn round_trips(decoded: &Instruction, statement: &Statement) -> bool {
+    decoded.mnemonic() == statement.mnemonic
+        && decoded.op_count() as usize == statement.operands.len()
+        && (0..).zip(&statement.operands).all(|(index, operand)| {
+            let kind = decoded.op_kind(index);
+            match *operand {
+                Operand::Register(register) => {
+                    kind == OpKind::Register && decoded.op_register(index) == register
+                }
+                Operand::Immediate(value) => match kind {
+                    OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => {
+                        decoded.near_branch_target() == value as u64
+                    }
+                    _ => immediate_bits(kind).is_some_and(|bits| {
+                        fits(value, bits)
+                            && truncate(decoded.immediate(index), bits)
+                                == truncate(value as u64, bits)
+                    }),
+                },
+                Operand::Memory { operand, size } => {
+                    kind == OpKind::Memory
+                        && decoded.memory_base() == operand.base
+                        && decoded.memory_index() == operand.index
+                        && (operand.index == Register::None
+                            || decoded.memory_index_scale() == operand.scale)
+                        && decoded.memory_displacement64() == operand.displacement as u64
+                        && decoded.segment_prefix() == operand.segment_prefix
+                        && size.is_none_or(|size| decoded.memory_size().size() == size)
+                }
+            }
+        })
+}
+
+/// Returns the width in bits of an immediate operand kind.
+const fn immediate_bits(kind: OpKind) -> Option<u32> {
+    match kind {
+        OpKind::Immediate8 | OpKind::Immediate8_2nd => Some(8),
+        OpKind::Immediate16 | OpKind::Immediate8to16 => Some(16),
+        OpKind::Immediate32 | OpKind::Immediate8to32 => Some(32),
+        OpKind::Immediate64 | OpKind::Immediate8to64 | OpKind::Immediate32to64 => Some(64),
+        _ => None,
+    }
+}
+
+/// Whether `value` can be written in `bits` bits, as a signed or an unsigned number.
+const fn fits(value: i64, bits: u32) -> bool {
+    bits >= 64 || (value >= -(1 << (bits - 1)) && value < (1 << bits))
+}
+
+const fn truncate(value: u64, bits: u32) -> u64 {
+    if bits >= 64 {
+        value
+    } else {
+        value & ((1 << bits) - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_assemble_intel_syntax() -> Result<()> {
+        let cases: [(&str, &[u8]); 7] = [
+            ("nop", &[0x90]),
+            ("mov eax, 1; ret", &[0xb8, 0x01, 0x00, 0x00, 0x00, 0xc3]),
+            ("xor rdi, rdi", &[0x48, 0x31, 0xff]),
+            (
+                "mov rax, qword ptr [rsp + 8]",
+                &[0x48, 0x8b, 0x44, 0x24, 0x08],
+            ),
+            (
+                "add dword ptr [rbx+rcx*4-0x10], 1",
+                &[0x83, 0x44, 0x8b, 0xf0, 0x01],
+            ),
+            ("mov al, -1", &[0xb0, 0xff]),
+            ("jmp 0x401000", &[0xeb, 0xfe]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(assemble(text, 0x40_1000)?, expected, "{text}");
+        }
+        assert!(assemble("inc [rax]", 0).is_err(), "size must be given");
+        assert!(
+            assemble("mov al, 0x100", 0).is_err(),
+            "immediate does not fit"
+        );
+        assert!(assemble("frobnicate rax", 0).is_err());
+        Ok(())
+    }
+}
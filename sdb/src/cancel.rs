@@ -0,0 +1,49 @@
+//! Cooperative cancellation of long operations such as memory scans.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Flag asking a long operation to stop early, typically set from a `SIGINT` handler.
+///
+/// Operations taking a token poll it between units of work and return what they found so
+/// far; callers check [`Self::is_cancelled`] afterwards to tell partial results apart.
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+}
+
+impl CancelToken {
+    pub const fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Asks the operation polling this token to stop. Async-signal-safe.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Clears the request, before starting the next operation.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_cancel_until_reset() {
+        static TOKEN: CancelToken = CancelToken::new();
+        assert!(!TOKEN.is_cancelled());
+        TOKEN.cancel();
+        assert!(TOKEN.is_cancelled());
+        TOKEN.reset();
+        assert!(!TOKEN.is_cancelled());
+    }
+}
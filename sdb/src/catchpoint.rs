@@ -0,0 +1,468 @@
+//! Catchpoints: stops triggered by events other than reaching an address.
+
+use crate::error::{InvalidRegexSnafu, Result, UnknownSyscallSnafu};
+use crate::rendezvous::{ModuleChange, ModuleEvent};
+use crate::syscall::{
+    syscall_name, syscall_number, syscalls, SyscallDirection, SyscallEvent, FD_SYSCALLS,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::hash::{Hash, Hasher};
+
+/// What a catchpoint reacts to.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum CatchpointKind {
+    /// Reads, writes and closes of a file descriptor (reported on syscall exit).
+    Fd(i32),
+    /// Syscalls selected by a [`SyscallFilter`].
+    Syscall(SyscallFilter),
+    /// The resident set size growing past this many bytes.
+    Rss(u64),
+    /// Shared objects whose path matches the pattern (any if `None`) being loaded.
+    Load(Option<ModulePattern>),
+    /// Shared objects whose path matches the pattern (any if `None`) being unloaded.
+    Unload(Option<ModulePattern>),
+    /// AddressSanitizer and ThreadSanitizer reports.
+    Sanitizer,
+}
+
+/// A regex matched against whole syscall names.
+#[derive(Clone, Debug)]
+pub struct SyscallPattern {
+    /// The pattern as written by the user.
+    source: String,
+    regex: Regex,
+}
+
+impl SyscallPattern {
+    /// Compiles `pattern`, anchored so that `open.*` does not match `fsopen`.
+    ///
+    /// # Errors
+    /// Returns an error if `pattern` is not a valid regex.
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(&format!("^(?:{pattern})$"))
+            .map(|regex| Self {
+                source: pattern.to_string(),
+                regex,
+            })
+            .map_err(|err| {
+                InvalidRegexSnafu {
+                    pattern,
+                    msg: err.to_string(),
+                }
+                .build()
+            })
+    }
+
+    /// Returns `true` if `name` matches.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+
+    /// Returns the pattern as written by the user.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl PartialEq for SyscallPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for SyscallPattern {}
+
+impl Hash for SyscallPattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+/// A regex searched for in shared object paths, so that `ssl` matches `libssl.so.3`.
+#[derive(Clone, Debug)]
+pub struct ModulePattern {
+    /// The pattern as written by the user.
+    source: String,
+    regex: Regex,
+}
+
+impl ModulePattern {
+    /// Compiles `pattern`.
+    ///
+    /// # Errors
+    /// Returns an error if `pattern` is not a valid regex.
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(|regex| Self {
+                source: pattern.to_string(),
+                regex,
+            })
+            .map_err(|err| {
+                InvalidRegexSnafu {
+                    pattern,
+                    msg: err.to_string(),
+                }
+                .build()
+            })
+    }
+
+    /// Returns `true` if `path` contains a match.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+
+    /// Returns the pattern as written by the user.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl PartialEq for ModulePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for ModulePattern {}
+
+impl Hash for ModulePattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+/// Selects syscall stops by name and direction.
+///
+/// A syscall passes when it is listed in `syscalls` or matches `include` (everything passes
+/// if neither is given), does not match `exclude`, and stops in an accepted direction.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SyscallFilter {
+    /// Explicit syscall numbers.
+    pub syscalls: Vec<u64>,
+    /// Positive name filter.
+    pub include: Option<SyscallPattern>,
+    /// Negative name filter.
+    pub exclude: Option<SyscallPattern>,
+    /// Report syscall-enter-stops.
+    pub on_entry: bool,
+    /// Report syscall-exit-stops.
+    pub on_exit: bool,
+}
+
+impl SyscallFilter {
+    /// Resolves syscall names (or numbers) for [`Self::syscalls`].
+    ///
+    /// # Errors
+    /// Returns an error if a name is not a known x86_64 syscall.
+    pub fn resolve_syscalls<S: AsRef<str>>(names: &[S]) -> Result<Vec<u64>> {
+        names
+            .iter()
+            .map(|name| {
+                let name = name.as_ref();
+                name.parse()
+                    .ok()
+                    .or_else(|| syscall_number(name))
+                    .ok_or_else(|| UnknownSyscallSnafu { name }.build())
+            })
+            .collect()
+    }
+
+    /// Returns `true` if `event` passes the filter.
+    pub fn matches(&self, event: &SyscallEvent) -> bool {
+        let direction = match event.direction {
+            SyscallDirection::Entry => self.on_entry,
+            SyscallDirection::Exit => self.on_exit,
+        };
+        direction && self.selects(event.number)
+    }
+
+    /// Returns `true` if syscall `number` passes the filter in some direction.
+    fn selects(&self, number: u64) -> bool {
+        let name = syscall_name(number).unwrap_or_default();
+        let selected = (self.include.is_none() && self.syscalls.is_empty())
+            || self.syscalls.contains(&number)
+            || self
+                .include
+                .as_ref()
+                .is_some_and(|include| include.is_match(name));
+        let excluded = self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(name));
+        selected && !excluded
+    }
+}
+
+/// A change of resident set size, in bytes, between two samples.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct RssCrossing {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl std::fmt::Display for RssCrossing {
+    /// Writes the sizes in MiB, e.g. `rss 510 MiB -> 530 MiB`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rss {} MiB -> {} MiB",
+            self.before >> 20,
+            self.after >> 20
+        )
+    }
+}
+
+/// What the front-end should do when a catchpoint is hit.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CatchAction {
+    /// Stop and return to the prompt.
+    #[default]
+    Stop,
+    /// Print the event and keep running.
+    Log,
+}
+
+/// A catchpoint registered on a process.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Catchpoint {
+    /// Identifier shown to the user.
+    pub id: usize,
+    /// Event filter.
+    pub kind: CatchpointKind,
+    /// Reaction on hit.
+    pub action: CatchAction,
+}
+
+impl Catchpoint {
+    /// Returns `true` if `event` triggers this catchpoint.
+    pub fn matches(&self, event: &SyscallEvent) -> bool {
+        match self.kind {
+            CatchpointKind::Fd(fd) => {
+                event.direction == SyscallDirection::Exit && event.fd() == Some(fd)
+            }
+            CatchpointKind::Syscall(ref filter) => filter.matches(event),
+            CatchpointKind::Rss(_)
+            | CatchpointKind::Load(_)
+            | CatchpointKind::Unload(_)
+            | CatchpointKind::Sanitizer => false,
+        }
+    }
+
+    /// Returns `true` if loading or unloading a module as in `event` triggers this catchpoint.
+    pub fn matches_module(&self, event: &ModuleEvent) -> bool {
+        let pattern = match (&self.kind, event.change) {
+            (CatchpointKind::Load(pattern), ModuleChange::Loaded)
+            | (CatchpointKind::Unload(pattern), ModuleChange::Unloaded) => pattern,
+            _ => return false,
+        };
+        pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(&event.module.path))
+    }
+
+    /// Returns `true` if this catchpoint needs the dynamic linker's module list tracked.
+    /// Sanitizer catchpoints need it to hook sanitizer runtimes as soon as they are loaded.
+    pub const fn tracks_modules(&self) -> bool {
+        matches!(
+            self.kind,
+            CatchpointKind::Load(_) | CatchpointKind::Unload(_) | CatchpointKind::Sanitizer
+        )
+    }
+
+    /// Returns `true` if this catchpoint stops at sanitizer reports.
+    pub const fn catches_sanitizers(&self) -> bool {
+        matches!(self.kind, CatchpointKind::Sanitizer)
+    }
+
+    /// Returns `true` if `crossing` grows the RSS past the threshold of this catchpoint.
+    pub const fn is_crossed_by(&self, crossing: &RssCrossing) -> bool {
+        match self.kind {
+            CatchpointKind::Rss(threshold) => {
+                crossing.before < threshold && threshold <= crossing.after
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if this catchpoint needs syscall stops.
+    pub const fn traces_syscalls(&self) -> bool {
+        matches!(
+            self.kind,
+            CatchpointKind::Fd(_) | CatchpointKind::Syscall(_)
+        )
+    }
+
+    /// Returns the numbers of the syscalls that can trigger this catchpoint.
+    ///
+    /// # Returns
+    /// `None` if any syscall can, including ones missing from sdb's table.
+    pub fn syscalls(&self) -> Option<Vec<u64>> {
+        match &self.kind {
+            CatchpointKind::Fd(_) => Some(
+                syscalls()
+                    .filter(|(_, name)| FD_SYSCALLS.contains(name))
+                    .map(|(number, _)| number)
+                    .collect(),
+            ),
+            CatchpointKind::Syscall(filter)
+                if filter.include.is_none() && filter.syscalls.is_empty() =>
+            {
+                None
+            }
+            CatchpointKind::Syscall(filter) => {
+                let mut numbers: Vec<_> = syscalls()
+                    .map(|(number, _)| number)
+                    .chain(filter.syscalls.iter().copied())
+                    .filter(|&number| filter.selects(number))
+                    .collect();
+                numbers.sort_unstable();
+                numbers.dedup();
+                Some(numbers)
+            }
+            CatchpointKind::Rss(_)
+            | CatchpointKind::Load(_)
+            | CatchpointKind::Unload(_)
+            | CatchpointKind::Sanitizer => Some(Vec::new()),
+        }
+    }
+}
+
+impl std::fmt::Display for CatchpointKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fd(fd) => write!(f, "fd {fd}"),
+            Self::Rss(threshold) => write!(f, "rss > {} MiB", threshold >> 20),
+            Self::Sanitizer => write!(f, "sanitizer"),
+            Self::Load(pattern) | Self::Unload(pattern) => {
+                let change = if matches!(self, Self::Load(_)) {
+                    "load"
+                } else {
+                    "unload"
+                };
+                match pattern {
+                    Some(pattern) => write!(f, "{change} '{}'", pattern.as_str()),
+                    None => write!(f, "{change}"),
+                }
+            }
+            Self::Syscall(filter) => {
+                write!(f, "syscall")?;
+                for &number in &filter.syscalls {
+                    match syscall_name(number) {
+                        Some(name) => write!(f, " {name}")?,
+                        None => write!(f, " {number}")?,
+                    }
+                }
+                if let Some(include) = &filter.include {
+                    write!(f, " --match '{}'", include.as_str())?;
+                }
+                if let Some(exclude) = &filter.exclude {
+                    write!(f, " --exclude '{}'", exclude.as_str())?;
+                }
+                match (filter.on_entry, filter.on_exit) {
+                    (true, false) => write!(f, " --enter-only"),
+                    (false, true) => write!(f, " --exit-only"),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, direction: SyscallDirection) -> SyscallEvent {
+        SyscallEvent {
+            number: syscall_number(name).unwrap_or(u64::MAX),
+            args: [0; 6],
+            direction,
+            ret: None,
+        }
+    }
+
+    #[test]
+    fn should_filter_syscalls() -> Result<()> {
+        let filter = SyscallFilter {
+            include: Some(SyscallPattern::new("open.*|stat")?),
+            exclude: Some(SyscallPattern::new("openat2")?),
+            on_entry: true,
+            ..Default::default()
+        };
+        assert!(filter.matches(&event("openat", SyscallDirection::Entry)));
+        assert!(filter.matches(&event("stat", SyscallDirection::Entry)));
+        assert!(!filter.matches(&event("openat", SyscallDirection::Exit)));
+        assert!(!filter.matches(&event("openat2", SyscallDirection::Entry)));
+        assert!(!filter.matches(&event("fstat", SyscallDirection::Entry)));
+        assert!(!filter.matches(&event("fsopen", SyscallDirection::Entry)));
+
+        let exclude_only = SyscallFilter {
+            exclude: Some(SyscallPattern::new("read|write")?),
+            on_entry: true,
+            on_exit: true,
+            ..Default::default()
+        };
+        assert!(exclude_only.matches(&event("close", SyscallDirection::Exit)));
+        assert!(!exclude_only.matches(&event("write", SyscallDirection::Exit)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_list_catchable_syscalls() -> Result<()> {
+        let catchpoint = |filter| Catchpoint {
+            id: 1,
+            kind: CatchpointKind::Syscall(filter),
+            action: CatchAction::Stop,
+        };
+        let open = catchpoint(SyscallFilter {
+            syscalls: vec![1],
+            include: Some(SyscallPattern::new("open.*")?),
+            exclude: Some(SyscallPattern::new("openat2")?),
+            ..Default::default()
+        });
+        assert_eq!(open.syscalls(), Some(vec![1, 2, 257, 304, 428]));
+        assert_eq!(catchpoint(SyscallFilter::default()).syscalls(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn should_catch_rss_growing_past_threshold() {
+        let catchpoint = Catchpoint {
+            id: 1,
+            kind: CatchpointKind::Rss(100 << 20),
+            action: CatchAction::Stop,
+        };
+        let crossing = |before: u64, after: u64| RssCrossing {
+            before: before << 20,
+            after: after << 20,
+        };
+        assert!(catchpoint.is_crossed_by(&crossing(99, 100)));
+        assert!(!catchpoint.is_crossed_by(&crossing(100, 200)));
+        assert!(!catchpoint.is_crossed_by(&crossing(200, 50)));
+        assert!(!catchpoint.traces_syscalls());
+        assert_eq!(catchpoint.kind.to_string(), "rss > 100 MiB");
+    }
+
+    #[test]
+    fn should_match_module_paths() -> Result<()> {
+        let catchpoint = Catchpoint {
+            id: 1,
+            kind: CatchpointKind::Load(Some(ModulePattern::new("ssl")?)),
+            action: CatchAction::Stop,
+        };
+        let event = |change, path: &str| ModuleEvent {
+            change,
+            module: crate::rendezvous::Module {
+                path: path.into(),
+                base: 0x7f00_0000_0000,
+            },
+        };
+        assert!(catchpoint.matches_module(&event(ModuleChange::Loaded, "/lib/libssl.so.3")));
+        assert!(!catchpoint.matches_module(&event(ModuleChange::Unloaded, "/lib/libssl.so.3")));
+        assert!(!catchpoint.matches_module(&event(ModuleChange::Loaded, "/lib/libc.so.6")));
+        assert!(catchpoint.tracks_modules() && !catchpoint.traces_syscalls());
+        assert_eq!(catchpoint.kind.to_string(), "load 'ssl'");
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Compile units recorded in an ELF file's DWARF: which compiler built each one, how, and
+//! which source files its line table covers.
+//!
+//! A source file only has line info if some unit's line table lists it, so browsing the units
+//! answers why a breakpoint or backtrace cannot show lines for it: the file was built without
+//! `-g`, its debug info lives in a split `.dwo` file, or it was stripped.
+
+use crate::error::{LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use gimli::{AttributeValue, EndianSlice, LittleEndian};
+use object::{Object, ObjectSection};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+pub(crate) type Slice<'a> = EndianSlice<'a, LittleEndian>;
+
+/// A compile unit (`DW_TAG_compile_unit`).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CompileUnit {
+    /// Offset of the unit in `.debug_info`.
+    pub offset: u64,
+    /// DWARF version.
+    pub version: u16,
+    /// Primary source file (`DW_AT_name`).
+    pub name: Option<String>,
+    /// Directory the compiler ran in (`DW_AT_comp_dir`).
+    pub comp_dir: Option<String>,
+    /// Compiler and, for GCC and clang with `-grecord-gcc-switches`, its flags
+    /// (`DW_AT_producer`).
+    pub producer: Option<String>,
+    /// Source language, e.g. `DW_LANG_C11`.
+    pub language: Option<String>,
+    /// Split DWARF file holding the rest of the unit (`DW_AT_dwo_name`).
+    pub dwo_name: Option<String>,
+    /// Link-time addresses of the unit's code.
+    pub ranges: Vec<Range<u64>>,
+    /// Source files in the unit's line table, or `None` if it has no line table.
+    pub files: Option<Vec<String>>,
+}
+
+impl CompileUnit {
+    /// Returns `true` if the unit has code at link-time address `addr`.
+    pub fn contains(&self, addr: u64) -> bool {
+        self.ranges.iter().any(|range| range.contains(&addr))
+    }
+
+    /// Returns the files of the unit's line table that are `file` or end with it as whole
+    /// path components, e.g. `src/main.c` for `main.c`.
+    pub fn matching_files(&self, file: &str) -> Vec<&str> {
+        self.files
+            .iter()
+            .flatten()
+            .filter(|path| Path::new(path).ends_with(file))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Reads the compile units of the ELF file at `path`.
+///
+/// # Returns
+/// No units if the file has no DWARF.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or its DWARF is malformed.
+pub fn load(path: &Path) -> Result<Vec<CompileUnit>> {
+    let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+    let data = MappedFile::open(path).map_err(|err| to_error(err.to_string()))?;
+    let file = object::File::parse(&*data).map_err(|err| to_error(err.to_string()))?;
+    let sections = dwarf_sections(&file).map_err(|err| to_error(err.to_string()))?;
+    let dwarf = sections.borrow(|section| EndianSlice::new(section, LittleEndian));
+    read_units(&dwarf).map_err(|err| to_error(format!("bad DWARF: {err}")))
+}
+
+/// Reads the DWARF sections of `file`, which are empty if it has none.
+pub(crate) fn dwarf_sections<'data>(
+    file: &object::File<'data>,
+) -> object::Result<gimli::DwarfSections<Cow<'data, [u8]>>> {
+    gimli::DwarfSections::load(|id| {
+        file.section_by_name(id.name())
+            .map_or(Ok(Cow::Borrowed(&[][..])), |section| {
+                section.uncompressed_data()
+            })
+    })
+}
+
+fn read_units(dwarf: &gimli::Dwarf<Slice<'_>>) -> gimli::Result<Vec<CompileUnit>> {
+    let mut units = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let offset = header
+            .offset()
+            .as_debug_info_offset()
+            .map_or(0, |offset| offset.0 as u64);
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        let Some((_, root)) = entries.next_dfs()? else {
+            continue;
+        };
+        let string = |name| -> gimli::Result<Option<String>> {
+            let Some(value) = root.attr_value(name)? else {
+                return Ok(None);
+            };
+            let string = dwarf.attr_string(&unit, value)?;
+            Ok(Some(string.to_string_lossy().into_owned()))
+        };
+        let language = match root.attr_value(gimli::DW_AT_language)? {
+            Some(AttributeValue::Language(language)) => Some(language.to_string()),
+            _ => None,
+        };
+        let dwo_name = match string(gimli::DW_AT_dwo_name)? {
+            Some(name) => Some(name),
+            None => string(gimli::DW_AT_GNU_dwo_name)?,
+        };
+        let mut ranges = Vec::new();
+        let mut unit_ranges = dwarf.unit_ranges(&unit)?;
+        while let Some(range) = unit_ranges.next()? {
+            ranges.push(range.begin..range.end);
+        }
+        units.push(CompileUnit {
+            offset,
+            version: unit.header.version(),
+            name: string(gimli::DW_AT_name)?,
+            comp_dir: string(gimli::DW_AT_comp_dir)?,
+            producer: string(gimli::DW_AT_producer)?,
+            language,
+            dwo_name,
+            ranges,
+            files: line_files(dwarf, &unit)?,
+        });
+    }
+    Ok(units)
+}
+
+/// Lists the files of the unit's line table, joined with their directories.
+fn line_files(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+) -> gimli::Result<Option<Vec<String>>> {
+    let Some(program) = &unit.line_program else {
+        return Ok(None);
+    };
+    let header = program.header();
+    let mut files = Vec::new();
+    for file in header.file_names() {
+        let path = file_path(dwarf, unit, header, file)?;
+        // DWARF 5 lists the primary file twice, as entries 0 and 1.
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    Ok(Some(files))
+}
+
+/// Joins a file of a line table with its directory.
+pub(crate) fn file_path(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    header: &gimli::LineProgramHeader<Slice<'_>>,
+    file: &gimli::FileEntry<Slice<'_>>,
+) -> gimli::Result<String> {
+    let name = dwarf.attr_string(unit, file.path_name())?;
+    let mut path = std::path::PathBuf::new();
+    if let Some(dir) = file.directory(header) {
+        path.push(&*dwarf.attr_string(unit, dir)?.to_string_lossy());
+    }
+    path.push(&*name.to_string_lossy());
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_load_own_units() -> Result<()> {
+        let exe = std::env::current_exe().map_err(|err| {
+            LoadElfSnafu {
+                path: "/proc/self/exe",
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        let units = load(&exe)?;
+        let unit = units
+            .iter()
+            .find(|unit| !unit.matching_files("src/compile_units.rs").is_empty());
+        assert!(unit.is_some(), "no unit covers this file");
+        if let Some(unit) = unit {
+            assert!(unit
+                .producer
+                .as_deref()
+                .is_some_and(|producer| producer.contains("rustc")));
+            assert_eq!(unit.language.as_deref(), Some("DW_LANG_Rust"));
+        }
+        Ok(())
+    }
+}
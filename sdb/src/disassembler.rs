@@ -0,0 +1,98 @@
+//! x86_64 disassembly of inferior memory.
+
+use crate::error::Result;
+use crate::process::Process;
+use iced_x86::{Decoder, DecoderOptions, Formatter, GasFormatter, IntelFormatter};
+
+/// Longest possible x86 instruction.
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// Assembly syntax used to format instructions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Syntax {
+    /// `mov eax, 1`
+    #[default]
+    Intel,
+    /// `mov $1, %eax`
+    Att,
+}
+
+/// A decoded instruction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    /// Runtime address.
+    pub address: u64,
+    /// Encoded bytes.
+    pub bytes: Vec<u8>,
+    /// Formatted text, e.g. `mov eax, 1`.
+    pub text: String,
+}
+
+/// Decodes up to `count` instructions from `bytes`, which start at `address`.
+///
+/// Decoding stops early at the end of `bytes` or at undecodable data.
+pub fn decode(bytes: &[u8], address: u64, count: usize, syntax: Syntax) -> Vec<Instruction> {
+    let mut decoder = Decoder::with_ip(64, bytes, address, DecoderOptions::NONE);
+    let mut formatter: Box<dyn Formatter> = match syntax {
+        Syntax::Intel => Box::new(IntelFormatter::new()),
+        Syntax::Att => Box::new(GasFormatter::new()),
+    };
+    formatter
+        .options_mut()
+        .set_space_after_operand_separator(true);
+    let mut instructions = Vec::new();
+    while decoder.can_decode() && instructions.len() < count {
+        let position = decoder.position();
+        let instruction = decoder.decode();
+        if instruction.is_invalid() {
+            break;
+        }
+        let mut text = String::new();
+        formatter.format(&instruction, &mut text);
+        instructions.push(Instruction {
+            address: instruction.ip(),
+            bytes: bytes[position..position + instruction.len()].to_vec(),
+            text,
+        });
+    }
+    instructions
+}
+
+/// Disassembles up to `count` instructions at `address` in the inferior.
+///
+/// # Errors
+/// Returns an error if the memory at `address` cannot be read.
+pub fn disassemble(
+    process: &Process,
+    address: u64,
+    count: usize,
+    syntax: Syntax,
+) -> Result<Vec<Instruction>> {
+    let bytes = process.read_memory(address, count * MAX_INSTRUCTION_LEN)?;
+    Ok(decode(&bytes, address, count, syntax))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_decode() {
+        // mov eax, 1; ret
+        let bytes = [0xb8, 0x01, 0x00, 0x00, 0x00, 0xc3];
+        let texts = |syntax| {
+            decode(&bytes, 0x1000, 10, syntax)
+                .into_iter()
+                .map(|instruction| (instruction.address, instruction.text))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts(Syntax::Intel),
+            vec![
+                (0x1000, "mov eax, 1".to_string()),
+                (0x1005, "ret".to_string())
+            ]
+        );
+        assert_eq!(texts(Syntax::Att)[0].1, "mov $1, %eax");
+    }
+}
@@ -1,8 +1,18 @@
 //! `sdb`(lib) errors
 
+use crate::process::AttachDenial;
 use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Size of the length prefix of an error sent through a pipe.
+pub(crate) const FRAME_HEADER_LEN: usize = size_of::<u32>();
+/// Longest serialized error [`SdbError::wait_read_from_fd`] accepts, so that a corrupt length
+/// prefix cannot make it allocate without bound.
+const MAX_FRAME_LEN: usize = 64 * 1024;
 
 /// Custom serializer for `nix::errno::Errno`
 fn serialize_errno<S>(errno: &Errno, serializer: S) -> Result<S::Ok, S::Error>
@@ -52,30 +62,66 @@ pub enum SdbError {
         source: Errno,
     },
 
-    /// [Launch Error: Execute failed] {source}
+    /// [Launch Error: Could not install the seccomp filter] {source}
     #[serde(
         serialize_with = "serialize_errno",
         deserialize_with = "deserialize_errno"
     )]
-    ExecFailed {
+    SeccompFailed {
         source: Errno,
     },
 
-    /// `waitpid` failed: {source}
+    /// [Launch Error: Execute failed] {source}
     #[serde(
         serialize_with = "serialize_errno",
         deserialize_with = "deserialize_errno"
     )]
-    WaitpidFailed {
+    ExecFailed {
         source: Errno,
     },
 
-    /// Could not resume: {source}
+    /// [Launch Error: Could not redirect the output to the debugger] {source}
     #[serde(
         serialize_with = "serialize_errno",
         deserialize_with = "deserialize_errno"
     )]
+    RedirectOutput {
+        source: Errno,
+    },
+
+    /// [Launch Error: Could not change directory to {}] {source}
+    #[snafu(display(
+        "[Launch Error: Could not change directory to {}] {source}",
+        path.display()
+    ))]
+    ChangeDirectory {
+        path: PathBuf,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// `waitpid` for {pid} failed: {source}
+    WaitpidFailed {
+        pid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not resume {pid} with {request}: {source}
     CouldNotResume {
+        /// `PTRACE_CONT`, `PTRACE_SYSCALL` or `PTRACE_SINGLESTEP`.
+        request: String,
+        pid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
         source: Errno,
     },
 
@@ -88,6 +134,278 @@ pub enum SdbError {
         source: Errno,
     },
 
+    /// Could not set ptrace options of {pid}: {source}
+    SetOptions {
+        pid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not read the registers of {tid}: {source}
+    ReadRegisters {
+        tid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not inspect thread {tid}: {source}
+    InspectThread {
+        tid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not read {len} bytes at {addr:#x} in {pid}: {source}
+    ReadMemory {
+        pid: i32,
+        addr: u64,
+        len: usize,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not get the signal info of {pid}: {source}
+    GetSigInfo {
+        pid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not write memory at {addr:#x} in {pid}: {source}
+    WriteMemory {
+        pid: i32,
+        addr: u64,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not write the registers of {tid}: {source}
+    WriteRegisters {
+        tid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Cannot run code in the inferior while it is stopped at a syscall; continue first
+    InjectAtSyscallStop,
+
+    /// Injected syscall did not complete: {status}
+    InjectInterrupted {
+        status: String,
+    },
+
+    /// Injected function call did not return: {status}
+    CallInterrupted {
+        status: String,
+    },
+
+    /// `{name}` failed in the inferior: {source}
+    RemoteSyscall {
+        name: String,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not attach to {pid}: {reason}
+    AttachNotPermitted {
+        pid: i32,
+        reason: AttachDenial,
+    },
+
+    /// Failed to read {}: {msg}
+    #[snafu(display("Failed to read {}: {msg}", path.display()))]
+    ReadProcFile {
+        path: PathBuf,
+        msg: String,
+    },
+
+    /// No process with pid {ns_pid} in the pid namespace of {ns_member}
+    NsPidNotFound {
+        ns_pid: i32,
+        ns_member: i32,
+    },
+
+    /// No process belongs to container {id}
+    ContainerNotFound {
+        id: String,
+    },
+
+    /// Invalid regex `{pattern}`: {msg}
+    InvalidRegex {
+        pattern: String,
+        msg: String,
+    },
+
+    /// Unknown syscall `{name}`
+    UnknownSyscall {
+        name: String,
+    },
+
+    /// Cannot assemble `{text}`: {msg}
+    Assemble {
+        text: String,
+        msg: String,
+    },
+
+    /// No patch number {id}.
+    NoPatch {
+        id: usize,
+    },
+
+    /// Patch {id} is overlapped by patch {later}; revert that first.
+    PatchOverlapped {
+        id: usize,
+        later: usize,
+    },
+
+    /// Could not write {addr:#x}, which is in a mapping ptrace cannot write ({perms}), nor
+    /// make it writable: {reason}
+    WriteProtected {
+        addr: u64,
+        perms: String,
+        reason: String,
+    },
+
+    /// Cannot watch {len} bytes at {addr:#x}: the length must be 1, 2, 4 or 8 (1 to watch
+    /// execution) and the address a multiple of it.
+    #[snafu(display(
+        "Cannot watch {len} bytes at {addr:#x}: the length must be 1, 2, 4 or 8 (1 to watch execution) and the address a multiple of it."
+    ))]
+    Unwatchable {
+        addr: u64,
+        len: usize,
+    },
+
+    /// All 4 debug registers are in use; delete a watchpoint first.
+    NoFreeDebugRegister,
+
+    /// No watchpoint number {id}.
+    NoWatchpoint {
+        id: usize,
+    },
+
+    /// No executable pages in {start:#x}..{end:#x}.
+    NoCodeInRange {
+        start: u64,
+        end: u64,
+    },
+
+    /// No writable pages in {start:#x}..{end:#x}.
+    NoWritableInRange {
+        start: u64,
+        end: u64,
+    },
+
+    /// The program is already executing at {addr:#x}, inside the range to watch.
+    ExecutingInRange {
+        addr: u64,
+    },
+
+    /// No function matches `{pattern}` in the files mapped so far.
+    NoFunctionsToTrace {
+        pattern: String,
+    },
+
+    /// Cannot trace functions of a program with {threads} threads: only the main thread is
+    /// traced, and the others would be killed by the breakpoints.
+    #[snafu(display(
+        "Cannot trace functions of a program with {threads} threads: only the main thread is traced, and the others would be killed by the breakpoints."
+    ))]
+    TraceThreads {
+        threads: usize,
+    },
+
+    /// A function returned to the trace trampoline with no traced call at stack {rsp:#x}.
+    UnknownTraceReturn {
+        rsp: u64,
+    },
+
+    /// Intel PT is not available: {msg}
+    #[cfg(feature = "intel-pt")]
+    NoIntelPt {
+        msg: String,
+    },
+
+    /// perf_event_open for {event} failed: {source}. ENOENT and EOPNOTSUPP mean the CPU or
+    /// VM lacks it; EACCES that /proc/sys/kernel/perf_event_paranoid must be 2 or less.
+    #[snafu(display(
+        "perf_event_open for {event} failed: {source}. ENOENT and EOPNOTSUPP mean the CPU or VM lacks it; EACCES that /proc/sys/kernel/perf_event_paranoid must be 2 or less."
+    ))]
+    PerfEventOpen {
+        event: String,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Failed to {action} the perf event: {source}
+    PerfEventControl {
+        action: String,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not {access} debug register DR{index} of {pid}: {source}
+    DebugRegister {
+        /// `read` or `write`.
+        access: String,
+        index: usize,
+        pid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Could not {action} thread {tid}: {source}
+    Scheduler {
+        /// E.g. `set the CPU affinity of`.
+        action: String,
+        tid: i32,
+        #[serde(
+            serialize_with = "serialize_errno",
+            deserialize_with = "deserialize_errno"
+        )]
+        source: Errno,
+    },
+
+    /// Failed to load {}: {msg}
+    #[snafu(display("Failed to load {}: {msg}", path.display()))]
+    LoadElf {
+        path: PathBuf,
+        msg: String,
+    },
+
     Null,
 
     /// Failed to serialize error
@@ -103,47 +421,339 @@ pub enum SdbError {
     },
     /// Failed to read fd.
     ReadFd,
+
+    /// The error report on the pipe was cut off after {got} of {expected} bytes.
+    TruncatedFrame {
+        expected: usize,
+        got: usize,
+    },
+
+    /// Nothing came through the pipe within {millis} ms.
+    PipeTimeout {
+        millis: u64,
+    },
 }
 
 impl SdbError {
+    /// Suggests what to do about the error, for front ends to show along with it.
+    ///
+    /// # Returns
+    /// `None` if there is nothing to add to the message.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::CouldNotResume {
+                pid,
+                source: Errno::ESRCH,
+                ..
+            }
+            | Self::SetOptions {
+                pid,
+                source: Errno::ESRCH,
+            }
+            | Self::ReadRegisters {
+                tid: pid,
+                source: Errno::ESRCH,
+            }
+            | Self::WriteRegisters {
+                tid: pid,
+                source: Errno::ESRCH,
+            }
+            | Self::GetSigInfo {
+                pid,
+                source: Errno::ESRCH,
+            }
+            | Self::DebugRegister {
+                pid,
+                source: Errno::ESRCH,
+                ..
+            }
+            | Self::ReadMemory {
+                pid,
+                source: Errno::ESRCH,
+                ..
+            }
+            | Self::WriteMemory {
+                pid,
+                source: Errno::ESRCH,
+                ..
+            } => Some(format!(
+                "{pid} has exited, or is running instead of stopped under the debugger."
+            )),
+            Self::ReadMemory {
+                pid,
+                addr,
+                source: Errno::EFAULT | Errno::EIO,
+                ..
+            }
+            | Self::WriteMemory {
+                pid,
+                addr,
+                source: Errno::EFAULT | Errno::EIO,
+            } => Some(format!(
+                "{addr:#x} is not mapped in {pid}; /proc/{pid}/maps lists the mapped ranges."
+            )),
+            Self::ReadMemory {
+                pid,
+                source: Errno::EPERM,
+                ..
+            } => Some(format!(
+                "Reading the memory of {pid} needs ptrace access to it; see /proc/sys/kernel/yama/ptrace_scope."
+            )),
+            Self::GetSigInfo {
+                source: Errno::EINVAL,
+                ..
+            } => Some("The process is not stopped by a signal.".into()),
+            Self::DebugRegister {
+                source: Errno::EINVAL,
+                ..
+            } => Some(
+                "The CPU rejected the watchpoint: its address must be in user space.".into(),
+            ),
+            Self::WaitpidFailed {
+                pid,
+                source: Errno::ECHILD,
+            } => Some(format!(
+                "{pid} is not traced by the debugger, or has already been reaped."
+            )),
+            Self::InspectThread {
+                tid,
+                source: Errno::ESRCH,
+            } => Some(format!("Thread {tid} has exited.")),
+            Self::Scheduler {
+                source: Errno::EINVAL,
+                ..
+            } => Some(
+                "None of the CPUs is online and allowed by the cpuset of the process; /sys/devices/system/cpu/online lists the online ones.".into(),
+            ),
+            _ => None,
+        }
+    }
+
     /// Writes the `SdbError` instance to a file descriptor.
     ///
-    /// This method serializes the `SdbError` enum using `bincode` and writes
-    /// the serialized bytes to the provided `OwnedFd`.
+    /// The error is serialized with `bincode` and sent as one frame: its length as a
+    /// little-endian `u32`, then the bytes, retrying partial writes.
     ///
     /// # Errors
-    /// Returns an `io::Result<()>` if writing to the file descriptor fails.
+    /// Returns an error if serializing or writing to the file descriptor fails.
     pub fn write_to_fd(&self, fd: impl AsFd) -> Result<()> {
         let encoded: Vec<u8> =
             bincode::serialize(self).map_err(|e| Self::SerializeErr { msg: e.to_string() })?;
-        nix::unistd::write(fd, &encoded).map_err(|_| Self::WriteFd)?;
-        Ok(())
+        let len = u32::try_from(encoded.len()).map_err(|_| Self::WriteFd)?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + encoded.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&encoded);
+        write_all(fd.as_fd(), &frame)
     }
 
-    /// Reads an `SdbError` instance from a file descriptor.
+    /// Reads an `SdbError` instance written by [`Self::write_to_fd`] from a file descriptor.
+    ///
+    /// Waits until a whole frame has arrived, the write side is closed, or `timeout` passes.
     ///
-    /// This method reads bytes from the provided file descriptor, deserializes
-    /// the bytes using `bincode`, and constructs an `SdbError` enum instance.
+    /// # Returns
+    /// `None` if the write side was closed without anything written.
     ///
     /// # Errors
-    /// Returns an `io::Result<SdbError>` if reading or deserialization fails.
-    pub fn wait_read_from_fd(fd: &OwnedFd) -> Result<Option<Self>> {
-        let mut buffer = [0; 1024]; // If vec is not cleared to 0, empty is always returned.
-        if let Err(err) = nix::unistd::read(fd.as_raw_fd(), &mut buffer).map_err(|_| Self::ReadFd) {
-            return Ok(Some(err));
+    /// Returns an error if reading fails, times out, the frame is cut off or cannot be
+    /// deserialized.
+    pub fn wait_read_from_fd(fd: &OwnedFd, timeout: Duration) -> Result<Option<Self>> {
+        let Some(encoded) = read_frame(fd.as_fd(), timeout)? else {
+            return Ok(None);
         };
+        bincode::deserialize(&encoded)
+            .map(Some)
+            .map_err(|e| Self::DeserializeErr { msg: e.to_string() })
+    }
+}
 
-        // is_empty
-        if buffer.iter().all(|&x| x == 0) {
-            return Ok(None);
+/// Reads a frame, a little-endian `u32` length then as many bytes, from `fd`.
+///
+/// # Returns
+/// `None` if the write side was closed without anything written.
+///
+/// # Errors
+/// Returns an error if reading fails, times out, or the frame is cut off or too long.
+pub(crate) fn read_frame(fd: BorrowedFd<'_>, timeout: Duration) -> Result<Option<Vec<u8>>> {
+    let deadline = Instant::now() + timeout;
+    let mut header = [0; FRAME_HEADER_LEN];
+    let got = read_until(fd, &mut header, deadline, timeout)?;
+    if got == 0 {
+        return Ok(None);
+    }
+    if got < header.len() {
+        return TruncatedFrameSnafu {
+            expected: header.len(),
+            got,
+        }
+        .fail();
+    }
+    let len = u32::from_le_bytes(header) as usize;
+    if len > MAX_FRAME_LEN {
+        return DeserializeErrSnafu {
+            msg: format!("frame of {len} bytes is too long"),
         }
+        .fail();
+    }
+    let mut payload = vec![0; len];
+    let got = read_until(fd, &mut payload, deadline, timeout)?;
+    if got < len {
+        return TruncatedFrameSnafu { expected: len, got }.fail();
+    }
+    Ok(Some(payload))
+}
 
-        match bincode::deserialize(&buffer).map_err(|e| Self::DeserializeErr { msg: e.to_string() })
-        {
-            Err(err) | Ok(err) => Ok(Some(err)),
+/// Writes all of `bytes` to `fd`, retrying partial and interrupted writes.
+pub(crate) fn write_all(fd: BorrowedFd<'_>, mut bytes: &[u8]) -> Result<()> {
+    while !bytes.is_empty() {
+        match nix::unistd::write(fd, bytes) {
+            Ok(0) => return Err(SdbError::WriteFd),
+            Ok(written) => bytes = &bytes[written..],
+            Err(Errno::EINTR) => {}
+            Err(_) => return Err(SdbError::WriteFd),
+        }
+    }
+    Ok(())
+}
+
+/// Reads from `fd` until `buffer` is full or the write side is closed.
+///
+/// # Returns
+/// The number of bytes read.
+///
+/// # Errors
+/// Returns [`SdbError::PipeTimeout`] if `deadline`, `timeout` from the start, passes first.
+fn read_until(
+    fd: BorrowedFd<'_>,
+    buffer: &mut [u8],
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let wait = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+        match poll(&mut [PollFd::new(fd, PollFlags::POLLIN)], wait) {
+            Ok(0) => {
+                return PipeTimeoutSnafu {
+                    millis: timeout.as_millis() as u64,
+                }
+                .fail()
+            }
+            Ok(_) => {}
+            // Check the deadline again: the pipe is blocking, so reading now could hang.
+            Err(Errno::EINTR) => continue,
+            Err(_) => return Err(SdbError::ReadFd),
+        }
+        match nix::unistd::read(fd.as_raw_fd(), &mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(Errno::EINTR | Errno::EAGAIN) => {}
+            Err(_) => return Err(SdbError::ReadFd),
         }
     }
+    Ok(filled)
 }
 
 /// `Result` for `sdb`(CLI) wrapper crate.
 pub type Result<T, E = SdbError> = core::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snafu::ResultExt as _;
+
+    #[test]
+    fn should_hint_at_unmapped_memory() {
+        let err = SdbError::ReadMemory {
+            pid: 42,
+            addr: 0x10,
+            len: 8,
+            source: Errno::EIO,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Could not read 8 bytes at 0x10 in 42: EIO: I/O error"
+        );
+        assert_eq!(
+            err.hint().as_deref(),
+            Some("0x10 is not mapped in 42; /proc/42/maps lists the mapped ranges.")
+        );
+        let err = SdbError::ReadMemory {
+            pid: 42,
+            addr: 0x10,
+            len: 8,
+            source: Errno::ESRCH,
+        };
+        assert_eq!(
+            err.hint().as_deref(),
+            Some("42 has exited, or is running instead of stopped under the debugger.")
+        );
+    }
+
+    #[test]
+    fn should_frame_errors_on_pipes() -> Result<()> {
+        let timeout = Duration::from_millis(50);
+        // Longer than a pipe write is guaranteed to be atomic.
+        let err = SdbError::InvalidRegex {
+            pattern: "(".repeat(8192),
+            msg: "unclosed group".into(),
+        };
+        let (read_fd, write_fd) = nix::unistd::pipe().context(CouldNotCreatePipeSnafu)?;
+        // Fits in the pipe buffer, so writing first does not block.
+        err.write_to_fd(&write_fd)?;
+        let read = SdbError::wait_read_from_fd(&read_fd, timeout)?;
+        assert!(
+            matches!(read, Some(SdbError::InvalidRegex { pattern, .. }) if pattern.len() == 8192)
+        );
+
+        let (read_fd, write_fd) = nix::unistd::pipe().context(CouldNotCreatePipeSnafu)?;
+        let read = SdbError::wait_read_from_fd(&read_fd, timeout);
+        assert!(matches!(read, Err(SdbError::PipeTimeout { millis: 50 })));
+
+        write_all(write_fd.as_fd(), &[8, 0, 0, 0, 1])?;
+        drop(write_fd);
+        let read = SdbError::wait_read_from_fd(&read_fd, timeout);
+        assert!(matches!(
+            read,
+            Err(SdbError::TruncatedFrame {
+                expected: 8,
+                got: 1
+            })
+        ));
+
+        let (read_fd, write_fd) = nix::unistd::pipe().context(CouldNotCreatePipeSnafu)?;
+        drop(write_fd);
+        assert!(SdbError::wait_read_from_fd(&read_fd, timeout)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn should_time_out_when_a_signal_interrupts_the_wait() -> Result<()> {
+        use nix::libc::{self, c_int};
+        use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+        extern "C" fn ignore(_: c_int) {}
+        let action = SigAction::new(
+            SigHandler::Handler(ignore),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        // SAFETY: The handler does nothing.
+        assert!(unsafe { sigaction(Signal::SIGUSR1, &action) }.is_ok());
+
+        let (read_fd, write_fd) = nix::unistd::pipe().context(CouldNotCreatePipeSnafu)?;
+        // SAFETY: Always safe to call.
+        let waiter = unsafe { libc::pthread_self() };
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            // SAFETY: `waiter` is the test thread, still waiting on the pipe by then.
+            unsafe { libc::pthread_kill(waiter, libc::SIGUSR1) };
+            // Unblocks a read that ignored the deadline, failing rather than hanging the test.
+            std::thread::sleep(Duration::from_secs(2));
+            drop(write_fd);
+        });
+        let read = SdbError::wait_read_from_fd(&read_fd, Duration::from_millis(200));
+        assert!(matches!(read, Err(SdbError::PipeTimeout { millis: 200 })));
+        Ok(())
+    }
+}
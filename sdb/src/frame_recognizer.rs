@@ -0,0 +1,141 @@
+//! Frame recognizers: plugins describing frames of runtimes the debugger knows nothing about,
+//! such as WebAssembly functions or a VM's trampolines, in backtraces.
+//!
+//! Embedders register a [`FrameRecognizer`] with [`register_frame_recognizer`], and every
+//! [`Backtrace`](crate::output::Backtrace) asks the recognizers about each of its frames.
+
+use crate::output::BacktraceFrame;
+use serde::Serialize;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Recognizers asked about each frame, in the order they were registered.
+static RECOGNIZERS: Mutex<Vec<Arc<dyn FrameRecognizer>>> = Mutex::new(Vec::new());
+
+/// Describes the frames of a runtime.
+pub trait FrameRecognizer: Send + Sync {
+    /// Name shown with the frames it recognizes, e.g. `wasmtime`.
+    fn name(&self) -> &str;
+
+    /// Describes `frame`, or returns `None` if it is not a frame of this runtime.
+    fn recognize(&self, frame: &BacktraceFrame) -> Option<String>;
+}
+
+/// What a recognizer said about a frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct FrameNote {
+    /// [`FrameRecognizer::name`] of the recognizer.
+    pub recognizer: String,
+    pub description: String,
+}
+
+impl std::fmt::Display for FrameNote {
+    /// Writes e.g. `wasmtime: wasm function 3 of module 0`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.recognizer, self.description)
+    }
+}
+
+/// Makes backtraces ask `recognizer` about their frames after the recognizers registered
+/// before it.
+pub fn register_frame_recognizer(recognizer: Arc<dyn FrameRecognizer>) {
+    RECOGNIZERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(recognizer);
+}
+
+/// Asks the registered recognizers about `frame`.
+///
+/// # Returns
+/// The description of the first recognizer that knows the frame.
+pub fn recognize(frame: &BacktraceFrame) -> Option<FrameNote> {
+    let recognizers = RECOGNIZERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    recognizers.iter().find_map(|recognizer| {
+        Some(FrameNote {
+            recognizer: recognizer.name().to_string(),
+            description: recognizer.recognize(frame)?,
+        })
+    })
+}
+
+/// Recognizes the code Wasmtime compiles from WebAssembly by its symbols.
+///
+/// Wasmtime registers them through the JIT interface when debug info is enabled, named e.g.
+/// `wasm[0]::function[3]::fib` or `wasm[0]::array_to_wasm_trampoline[3]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Wasmtime;
+
+impl FrameRecognizer for Wasmtime {
+    fn name(&self) -> &str {
+        "wasmtime"
+    }
+
+    fn recognize(&self, frame: &BacktraceFrame) -> Option<String> {
+        let symbol = frame.location.symbol.as_ref()?;
+        let mut parts = symbol.name.split("::");
+        let module = index(parts.next()?, "wasm")?;
+        let part = parts.next()?;
+        if let Some(function) = index(part, "function") {
+            return Some(parts.next().map_or_else(
+                || format!("wasm function {function} of module {module}"),
+                |name| format!("wasm function {function} ({name}) of module {module}"),
+            ));
+        }
+        let (kind, function) = part.split_once('[')?;
+        let function = function.strip_suffix(']')?;
+        kind.ends_with("trampoline").then(|| {
+            let kind = kind.replace('_', " ");
+            format!("{kind} of wasm function {function} of module {module}")
+        })
+    }
+}
+
+/// Parses `{name}[{index}]`.
+fn index(part: &str, name: &str) -> Option<u32> {
+    part.strip_prefix(name)?
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotate::{Annotation, SymbolLocation};
+
+    #[test]
+    fn should_recognize_wasmtime_frames() {
+        let frame = |name: &str| BacktraceFrame {
+            index: 0,
+            location: Annotation {
+                addr: 0x7000_1000,
+                region: None,
+                symbol: Some(SymbolLocation {
+                    name: name.into(),
+                    offset: 0,
+                }),
+                line: None,
+            },
+            note: None,
+        };
+        let describe = |name| Wasmtime.recognize(&frame(name));
+        assert_eq!(
+            describe("wasm[0]::function[3]::fib").as_deref(),
+            Some("wasm function 3 (fib) of module 0")
+        );
+        assert_eq!(
+            describe("wasm[1]::function[12]").as_deref(),
+            Some("wasm function 12 of module 1")
+        );
+        assert_eq!(
+            describe("wasm[0]::array_to_wasm_trampoline[3]").as_deref(),
+            Some("array to wasm trampoline of wasm function 3 of module 0")
+        );
+        assert_eq!(describe("main"), None);
+        assert_eq!(describe("wasm[0]::memory[0]"), None);
+    }
+}
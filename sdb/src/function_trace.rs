@@ -0,0 +1,366 @@
+//! Tracing function calls and returns, like a lightweight uftrace.
+//!
+//! Each traced function gets an `int3` on its first byte. At entry, the return address on
+//! the stack is replaced by the address of an `int3` in a page mapped for the trace, so the
+//! return traps too and is sent on to the real return address, kept on a shadow stack.
+//! Frames left by `longjmp` or exceptions are noticed when an outer frame returns.
+//!
+//! Only the main thread is traced, so other threads must not run traced code: its `int3`
+//! would kill them.
+
+use crate::error::{
+    InvalidRegexSnafu, NoFunctionsToTraceSnafu, Result, TraceThreadsSnafu, UnknownTraceReturnSnafu,
+};
+use crate::process::Process;
+use crate::procfs::{find_region, tids, MemoryRegion};
+use crate::symbols::SymbolTable;
+use nix::libc::user_regs_struct;
+use nix::sys::mman::ProtFlags;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use regex::Regex;
+use snafu::OptionExt as _;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Breakpoint instruction.
+const INT3: u8 = 0xcc;
+/// Size of the page holding the return trampoline.
+const TRAMPOLINE_LEN: u64 = 0x1000;
+/// Entry points of programs and of the dynamic linker, which are jumped to with `argc`
+/// rather than a return address on top of the stack.
+const ENTRY_POINTS: [&str; 2] = ["_start", "_dl_start_user"];
+/// Integer argument registers of the System V ABI.
+pub const MAX_ARGS: usize = 6;
+
+/// A function to instrument.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TracedFunction {
+    pub name: String,
+    /// Runtime address of the first instruction.
+    pub addr: u64,
+}
+
+/// A call or return seen by [`FunctionTracer::resume`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TraceEvent {
+    /// `name` was called with the first integer arguments `args`. `depth` is the number of
+    /// traced calls it is nested in.
+    Entry {
+        depth: usize,
+        name: String,
+        args: Vec<u64>,
+    },
+    /// The call to `name` at `depth` finished after `duration`, returning `value`, or `None`
+    /// if it was left without returning, e.g. by `longjmp`.
+    Return {
+        depth: usize,
+        name: String,
+        value: Option<u64>,
+        duration: Duration,
+    },
+}
+
+/// A traced call that has not returned yet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Frame {
+    name: String,
+    /// Stack address of the return address.
+    slot: u64,
+    /// Where the call returns to; the trampoline for a tail call from another traced frame.
+    return_address: u64,
+    start: Instant,
+}
+
+/// Breakpoints and shadow stack of a running trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionTracer {
+    /// Name and original first byte of each instrumented function, by address.
+    functions: BTreeMap<u64, (String, u8)>,
+    /// Address of the `int3` returns are sent to.
+    trampoline: u64,
+    frames: Vec<Frame>,
+    /// Number of arguments reported at entry.
+    arg_count: usize,
+}
+
+/// Finds the functions of the mapped files whose names contain a match of `pattern`, or
+/// without a pattern, all functions of the main `program`. Libraries not loaded yet are not
+/// searched.
+///
+/// # Errors
+/// Returns an error if `pattern` is not a valid regex or nothing matches.
+pub fn find_functions(
+    maps: &[MemoryRegion],
+    program: &Path,
+    pattern: Option<&str>,
+) -> Result<Vec<TracedFunction>> {
+    let regex = pattern
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|err| {
+                InvalidRegexSnafu {
+                    pattern,
+                    msg: err.to_string(),
+                }
+                .build()
+            })
+        })
+        .transpose()?;
+    let mut seen_paths = HashSet::new();
+    let mut functions = BTreeMap::new();
+    for region in maps {
+        let path = Path::new(&region.path);
+        if !path.is_absolute() || (regex.is_none() && path != program) || !seen_paths.insert(path) {
+            continue;
+        }
+        let Ok(table) = SymbolTable::cached(path) else {
+            continue;
+        };
+        let Some(bias) = table.load_bias(region) else {
+            continue;
+        };
+        for symbol in table.symbols() {
+            let addr = symbol.address.wrapping_add(bias);
+            let wanted = symbol.is_function
+                && symbol.size > 0
+                && !ENTRY_POINTS.contains(&symbol.name.as_str())
+                && regex
+                    .as_ref()
+                    .is_none_or(|regex| regex.is_match(&symbol.name))
+                && find_region(maps, addr).is_some_and(|region| region.perms().contains('x'));
+            if wanted {
+                // The first of several names for the same code.
+                functions.entry(addr).or_insert_with(|| symbol.name.clone());
+            }
+        }
+    }
+    if functions.is_empty() {
+        return NoFunctionsToTraceSnafu {
+            pattern: pattern.unwrap_or("(the main program)"),
+        }
+        .fail();
+    }
+    Ok(functions
+        .into_iter()
+        .map(|(addr, name)| TracedFunction { name, addr })
+        .collect())
+}
+
+impl FunctionTracer {
+    /// Instruments `functions` of the stopped `process`, reporting `arg_count` arguments
+    /// (at most [`MAX_ARGS`]) at each entry.
+    ///
+    /// # Errors
+    /// Returns an error if the program has several threads, or the trampoline page cannot be
+    /// mapped or the code written.
+    pub fn start(
+        process: &mut Process,
+        functions: &[TracedFunction],
+        arg_count: usize,
+    ) -> Result<Self> {
+        let threads = tids(process.pid)?.len();
+        if threads > 1 {
+            return TraceThreadsSnafu { threads }.fail();
+        }
+        let trampoline =
+            process.allocate_memory(TRAMPOLINE_LEN, ProtFlags::PROT_READ | ProtFlags::PROT_EXEC)?;
+        let mut tracer = Self {
+            functions: BTreeMap::new(),
+            trampoline,
+            frames: Vec::new(),
+            arg_count: arg_count.min(MAX_ARGS),
+        };
+        let armed = process
+            .write_memory(trampoline, &[INT3])
+            .and_then(|()| tracer.arm(process, functions));
+        if let Err(err) = armed {
+            // The first error says more than one while undoing.
+            let _ = tracer.finish(process);
+            return Err(err);
+        }
+        Ok(tracer)
+    }
+
+    /// Writes an `int3` over the first byte of each function.
+    fn arm(&mut self, process: &Process, functions: &[TracedFunction]) -> Result<()> {
+        for function in functions {
+            let original = process.read_memory(function.addr, 1)?;
+            let Some(&original) = original.first() else {
+                continue;
+            };
+            process.write_memory(function.addr, &[INT3])?;
+            self.functions
+                .insert(function.addr, (function.name.clone(), original));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of instrumented functions.
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// Returns `true` if no function is instrumented.
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Resumes the process like [`Process::resume`], passing each traced call and return to
+    /// `on_event`, until it stops for another reason, e.g. a catchpoint, a signal or its exit.
+    ///
+    /// # Errors
+    /// Returns an error if the process cannot be resumed, or a return cannot be matched to a
+    /// traced call.
+    pub fn resume(
+        &mut self,
+        process: &mut Process,
+        mut on_event: impl FnMut(TraceEvent),
+    ) -> Result<()> {
+        loop {
+            process.resume()?;
+            let trapped = matches!(process.state, WaitStatus::Stopped(_, Signal::SIGTRAP))
+                && process.last_watchpoint.is_none();
+            if !trapped || !self.handle_trap(process, &mut on_event)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handles a `SIGTRAP` stop from one of the trace's `int3`s.
+    ///
+    /// # Returns
+    /// `false` if the stop came from somewhere else.
+    fn handle_trap(
+        &mut self,
+        process: &mut Process,
+        on_event: &mut impl FnMut(TraceEvent),
+    ) -> Result<bool> {
+        let mut regs = process.read_registers()?;
+        let addr = regs.rip.wrapping_sub(1);
+        if addr == self.trampoline {
+            self.leave(process, regs, on_event)?;
+            return Ok(true);
+        }
+        let Some((name, original)) = self.functions.get(&addr).cloned() else {
+            return Ok(false);
+        };
+        regs.rip = addr;
+        let return_address = process
+            .read_words(regs.rsp, 1)?
+            .first()
+            .copied()
+            .unwrap_or_default();
+        if return_address != self.trampoline {
+            process.write_memory(regs.rsp, &self.trampoline.to_le_bytes())?;
+        }
+        on_event(TraceEvent::Entry {
+            depth: self.frames.len(),
+            name: name.clone(),
+            args: arguments(&regs)[..self.arg_count].to_vec(),
+        });
+        self.frames.push(Frame {
+            name,
+            slot: regs.rsp,
+            return_address,
+            start: Instant::now(),
+        });
+        // Run the original first instruction, then put the breakpoint back.
+        process.write_registers(&regs)?;
+        process.write_memory(addr, &[original])?;
+        let stepped = process.step_instruction();
+        if process.is_alive() {
+            process.write_memory(addr, &[INT3])?;
+        }
+        stepped.map(|()| true)
+    }
+
+    /// Reports the frames finished by a return to the trampoline, and sends the process on
+    /// to the real return address.
+    fn leave(
+        &mut self,
+        process: &Process,
+        mut regs: user_regs_struct,
+        on_event: &mut impl FnMut(TraceEvent),
+    ) -> Result<()> {
+        // `ret` popped the return address.
+        let slot = regs.rsp.wrapping_sub(8);
+        let mut return_address = None;
+        while return_address.is_none() {
+            let Some(frame) = self.frames.last() else {
+                break;
+            };
+            // Deeper frames than the returning one were left without returning.
+            let returned = match frame.slot.cmp(&slot) {
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal => true,
+                std::cmp::Ordering::Greater => break,
+            };
+            let Some(frame) = self.frames.pop() else {
+                break;
+            };
+            if returned && frame.return_address != self.trampoline {
+                return_address = Some(frame.return_address);
+            }
+            on_event(TraceEvent::Return {
+                depth: self.frames.len(),
+                name: frame.name,
+                value: returned.then_some(regs.rax),
+                duration: frame.start.elapsed(),
+            });
+        }
+        regs.rip = return_address.context(UnknownTraceReturnSnafu { rsp: regs.rsp })?;
+        process.write_registers(&regs)
+    }
+
+    /// Removes the instrumentation, and puts back the return addresses of the calls that are
+    /// still running.
+    ///
+    /// # Errors
+    /// Returns an error if the code or stack cannot be written, or the trampoline unmapped.
+    pub fn finish(self, process: &mut Process) -> Result<()> {
+        if !process.is_alive() {
+            return Ok(());
+        }
+        for (&addr, (_, original)) in &self.functions {
+            process.write_memory(addr, &[*original])?;
+        }
+        for frame in &self.frames {
+            let current = process.read_words(frame.slot, 1)?;
+            if current.first() == Some(&self.trampoline) && frame.return_address != self.trampoline
+            {
+                process.write_memory(frame.slot, &frame.return_address.to_le_bytes())?;
+            }
+        }
+        // Code cannot be injected at a syscall stop, so the unused page then stays mapped.
+        if matches!(process.state, WaitStatus::PtraceSyscall(_)) {
+            return Ok(());
+        }
+        process.free_memory(self.trampoline, TRAMPOLINE_LEN)
+    }
+}
+
+/// Returns the integer argument registers in order.
+const fn arguments(regs: &user_regs_struct) -> [u64; MAX_ARGS] {
+    [regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::read_maps;
+    use nix::unistd::getpid;
+
+    #[test]
+    fn should_find_functions_by_pattern() -> Result<()> {
+        let maps = read_maps(getpid())?;
+        let program = std::env::current_exe().unwrap_or_default();
+        let functions = find_functions(&maps, &program, Some("should_find_functions_by"))?;
+        assert!(functions
+            .iter()
+            .any(|function| function.name.ends_with("should_find_functions_by_pattern")));
+        assert!(find_functions(&maps, &program, Some("^no such function$")).is_err());
+        assert!(find_functions(&maps, &program, Some("(")).is_err());
+        Ok(())
+    }
+}
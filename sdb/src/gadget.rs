@@ -0,0 +1,177 @@
+//! Searching executable memory for short instruction sequences, e.g. ROP gadgets.
+
+use crate::cancel::CancelToken;
+use crate::process::Process;
+use crate::procfs::MemoryRegion;
+use iced_x86::{Decoder, DecoderOptions, Formatter, IntelFormatter, NumberBase};
+
+/// An instruction sequence found in memory.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Gadget {
+    /// Runtime address of the first instruction.
+    pub address: u64,
+    /// Path of the mapped file, or pseudo name such as `[vdso]`.
+    pub module: String,
+    /// Offset of the first instruction in `module`.
+    pub offset: u64,
+}
+
+/// Splits `query` into instructions separated by `;`, normalized for comparison with
+/// disassembly: `POP RDI ;ret` and `pop rdi; ret` are the same query, as are
+/// `qword ptr [rsp + 0x10]` and `[rsp+16]`.
+pub fn parse_query(query: &str) -> Vec<String> {
+    query
+        .split(';')
+        .map(normalize)
+        .filter(|instruction| !instruction.is_empty())
+        .collect()
+}
+
+/// Finds every address in the executable mappings where decoding yields the instructions of
+/// `pattern` (as returned by [`parse_query`]), unaligned starts included.
+///
+/// Once `cancel` is set, returns the gadgets found so far.
+pub fn search_gadgets(
+    process: &Process,
+    maps: &[MemoryRegion],
+    pattern: &[String],
+    cancel: &CancelToken,
+) -> Vec<Gadget> {
+    let mut gadgets = Vec::new();
+    for region in maps.iter().filter(|region| region.read && region.execute) {
+        let Ok(bytes) = process.read_memory(region.start, (region.end - region.start) as usize)
+        else {
+            continue;
+        };
+        gadgets.extend(
+            find_in(&bytes, region.start, pattern, cancel).map(|address| Gadget {
+                address,
+                module: region.path.clone(),
+                offset: address - region.start + region.offset,
+            }),
+        );
+    }
+    gadgets
+}
+
+/// Addresses in `bytes`, loaded at `base`, where `pattern` decodes.
+fn find_in<'a>(
+    bytes: &'a [u8],
+    base: u64,
+    pattern: &'a [String],
+    cancel: &'a CancelToken,
+) -> impl Iterator<Item = u64> + 'a {
+    let mut decoder = Decoder::with_ip(64, bytes, base, DecoderOptions::NONE);
+    let mut formatter = IntelFormatter::new();
+    let options = formatter.options_mut();
+    options.set_number_base(NumberBase::Decimal);
+    options.set_space_after_operand_separator(true);
+    let mut text = String::new();
+    (0..bytes.len())
+        .take_while(move |_| !cancel.is_cancelled())
+        .filter_map(move |position| {
+            if pattern.is_empty() {
+                return None;
+            }
+            decoder.set_position(position).ok()?;
+            decoder.set_ip(base + position as u64);
+            for expected in pattern {
+                let instruction = decoder.decode();
+                if instruction.is_invalid() {
+                    return None;
+                }
+                // Most candidates fail on the mnemonic, which is much cheaper to format.
+                text.clear();
+                formatter.format_mnemonic(&instruction, &mut text);
+                if !expected.starts_with(text.as_str()) {
+                    return None;
+                }
+                text.clear();
+                formatter.format(&instruction, &mut text);
+                if normalize(&text) != *expected {
+                    return None;
+                }
+            }
+            Some(base + position as u64)
+        })
+}
+
+/// Lowercases, writes numbers in decimal, drops memory sizes such as `qword ptr`, puts one
+/// space after commas and none around memory operand operators or inside brackets, and
+/// collapses other whitespace.
+fn normalize(instruction: &str) -> String {
+    let lower = instruction.to_lowercase();
+    let mut tokens = Vec::new();
+    let mut chars = lower.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char.is_alphanumeric() || char == '_' {
+            let mut word = char.to_string();
+            while let Some(&next) = chars
+                .peek()
+                .filter(|next| next.is_alphanumeric() || **next == '_')
+            {
+                word.push(next);
+                chars.next();
+            }
+            tokens.push(decimal(&word).unwrap_or(word));
+        } else if !char.is_whitespace() {
+            tokens.push(char.to_string());
+        }
+    }
+    if let Some(ptr) = tokens.iter().position(|token| token == "ptr") {
+        tokens.drain(ptr.saturating_sub(1)..=ptr);
+    }
+    let mut normalized = String::new();
+    let mut previous: Option<&str> = None;
+    for token in &tokens {
+        let word = |token: Option<&str>| {
+            token.is_some_and(|token| token.starts_with(|char: char| char.is_alphanumeric()))
+        };
+        // Words are separated by a space, e.g. `rep movsb`, but `[`, `+` and the like are not.
+        if word(previous) && word(Some(token)) || previous == Some(",") {
+            normalized.push(' ');
+        }
+        normalized.push_str(token);
+        previous = Some(token);
+    }
+    normalized
+}
+
+/// Converts a number written as `16`, `0x10` or `10h` to decimal. `None` for other words.
+fn decimal(word: &str) -> Option<String> {
+    if !word.starts_with(|char: char| char.is_ascii_digit()) {
+        return None;
+    }
+    let value = if let Some(hex) = word.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = word.strip_suffix('h') {
+        u64::from_str_radix(hex, 16).ok()?
+    } else {
+        word.parse().ok()?
+    };
+    Some(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_normalize_queries() {
+        assert_eq!(parse_query("POP RDI ;ret;"), ["pop rdi", "ret"]);
+        assert_eq!(
+            parse_query("mov rax,qword ptr [rsp + 0x10]"),
+            ["mov rax, [rsp+16]"]
+        );
+        assert_eq!(parse_query("add rsp, 18h"), ["add rsp, 24"]);
+    }
+
+    #[test]
+    fn should_find_unaligned_gadgets() {
+        // `mov eax, 0xc35f` hides `pop rdi; ret` in its immediate.
+        let bytes = [0xb8, 0x5f, 0xc3, 0x00, 0x00, 0x5f, 0xc3];
+        let pattern = parse_query("pop rdi; ret");
+        let found: Vec<_> = find_in(&bytes, 0x1000, &pattern, &CancelToken::new()).collect();
+        assert_eq!(found, [0x1001, 0x1005]);
+    }
+}
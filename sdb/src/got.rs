@@ -0,0 +1,123 @@
+//! Global offset table slots of an ELF file, from its dynamic relocations.
+
+use crate::error::{LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use object::elf::{R_X86_64_GLOB_DAT, R_X86_64_JUMP_SLOT};
+use object::RelocationTarget;
+use object::{
+    Object as _, ObjectSection as _, ObjectSymbol as _, ObjectSymbolTable as _, RelocationFlags,
+};
+use std::ops::Range;
+use std::path::Path;
+
+/// Sections holding PLT stubs, which unresolved lazy slots point back into.
+const PLT_SECTIONS: [&str; 2] = [".plt", ".plt.sec"];
+
+/// What a GOT slot is resolved to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GotKind {
+    /// A function called through the PLT (`R_X86_64_JUMP_SLOT`), possibly bound lazily.
+    Function,
+    /// A variable or function address (`R_X86_64_GLOB_DAT`), bound at load time.
+    Data,
+}
+
+/// One GOT slot.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GotEntry {
+    /// Link-time address of the slot.
+    pub slot: u64,
+    /// Symbol the slot is resolved to.
+    pub symbol: String,
+    pub kind: GotKind,
+}
+
+/// The GOT and PLT of one ELF file, at link-time addresses.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Got {
+    /// Slots sorted by address.
+    pub entries: Vec<GotEntry>,
+    /// Address ranges of the PLT stubs.
+    pub plt: Vec<Range<u64>>,
+}
+
+impl Got {
+    /// Reads the dynamic relocations and PLT sections of the ELF file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid object file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+        let data = MappedFile::open(path).map_err(|err| to_error(err.to_string()))?;
+        let file = object::File::parse(&*data).map_err(|err| to_error(err.to_string()))?;
+        let plt = file
+            .sections()
+            .filter(|section| {
+                section
+                    .name()
+                    .is_ok_and(|name| PLT_SECTIONS.contains(&name))
+            })
+            .map(|section| section.address()..section.address() + section.size())
+            .collect();
+        Ok(Self {
+            entries: entries(&file),
+            plt,
+        })
+    }
+}
+
+/// Lists the `JUMP_SLOT` and `GLOB_DAT` relocations of `file`, sorted by address.
+fn entries(file: &object::File<'_>) -> Vec<GotEntry> {
+    let (Some(relocations), Some(symbols)) =
+        (file.dynamic_relocations(), file.dynamic_symbol_table())
+    else {
+        return Vec::new(); // Statically linked.
+    };
+
+    let mut entries: Vec<_> = relocations
+        .filter_map(|(slot, relocation)| {
+            let kind = match relocation.flags() {
+                RelocationFlags::Elf {
+                    r_type: R_X86_64_JUMP_SLOT,
+                } => GotKind::Function,
+                RelocationFlags::Elf {
+                    r_type: R_X86_64_GLOB_DAT,
+                } => GotKind::Data,
+                _ => return None,
+            };
+            let RelocationTarget::Symbol(index) = relocation.target() else {
+                return None;
+            };
+            let symbol = symbols
+                .symbol_by_index(index)
+                .ok()?
+                .name()
+                .ok()?
+                .to_string();
+            Some(GotEntry { slot, symbol, kind })
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.slot);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_load_own_got() -> Result<()> {
+        let exe = std::env::current_exe().map_err(|err| {
+            LoadElfSnafu {
+                path: "/proc/self/exe",
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        let got = Got::load(&exe)?;
+        // Every Rust test binary calls into libc.
+        assert!(got.entries.iter().any(|entry| entry.symbol == "malloc"));
+        assert!(!got.plt.is_empty());
+        Ok(())
+    }
+}
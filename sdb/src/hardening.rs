@@ -0,0 +1,167 @@
+//! Checksec-style report of the exploit mitigations an ELF file was built with.
+
+use crate::error::{LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use object::elf::{
+    DF_1_NOW, DF_1_PIE, DF_BIND_NOW, DT_BIND_NOW, DT_FLAGS, DT_FLAGS_1, ET_DYN, PF_X, PT_GNU_RELRO,
+    PT_GNU_STACK, PT_INTERP,
+};
+use object::read::elf::{Dyn as _, ElfFile64, FileHeader as _, ProgramHeader as _};
+use object::{Endianness, Object as _, ObjectSymbol as _};
+use std::fmt;
+use std::path::Path;
+
+/// Symbols referenced by code built with `-fstack-protector`.
+const CANARY_SYMBOLS: [&str; 2] = ["__stack_chk_fail", "__stack_chk_guard"];
+
+/// How much of the relocation data is made read-only after loading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Relro {
+    None,
+    /// `PT_GNU_RELRO`, but the GOT stays writable for lazy binding.
+    Partial,
+    /// `PT_GNU_RELRO` with immediate binding, so the GOT is read-only too.
+    Full,
+}
+
+/// Whether the file is loaded at a random address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pie {
+    /// A fixed-address executable.
+    None,
+    /// A position independent executable.
+    Pie,
+    /// A shared library, which is always position independent.
+    SharedObject,
+}
+
+/// Mitigations found in one ELF file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Hardening {
+    pub relro: Relro,
+    /// Built with a stack protector.
+    pub canary: bool,
+    /// The stack is not executable.
+    pub nx: bool,
+    pub pie: Pie,
+    /// Number of `_FORTIFY_SOURCE` checked functions used, such as `__memcpy_chk`.
+    pub fortified: usize,
+}
+
+impl Hardening {
+    /// Inspects the program headers, dynamic section and symbols of the ELF file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a 64-bit ELF file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+        let data = MappedFile::open(path).map_err(|err| to_error(err.to_string()))?;
+        let file =
+            ElfFile64::<Endianness>::parse(&*data).map_err(|err| to_error(err.to_string()))?;
+        let endian = file.endian();
+        let headers = file.elf_program_headers();
+        let has_segment = |kind| headers.iter().any(|header| header.p_type(endian) == kind);
+
+        let mut bind_now = false;
+        let mut pie_flag = false;
+        for header in headers {
+            let Ok(Some(entries)) = header.dynamic(endian, &*data) else {
+                continue;
+            };
+            for entry in entries {
+                let (tag, value) = (entry.d_tag(endian), entry.d_val(endian));
+                match u32::try_from(tag) {
+                    Ok(DT_BIND_NOW) => bind_now = true,
+                    Ok(DT_FLAGS) => bind_now |= value & u64::from(DF_BIND_NOW) != 0,
+                    Ok(DT_FLAGS_1) => {
+                        bind_now |= value & u64::from(DF_1_NOW) != 0;
+                        pie_flag |= value & u64::from(DF_1_PIE) != 0;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let relro = match (has_segment(PT_GNU_RELRO), bind_now) {
+            (false, _) => Relro::None,
+            (true, false) => Relro::Partial,
+            (true, true) => Relro::Full,
+        };
+        // Without `PT_GNU_STACK` the kernel makes the stack executable.
+        let nx = headers.iter().any(|header| {
+            header.p_type(endian) == PT_GNU_STACK && header.p_flags(endian) & PF_X == 0
+        });
+        let pie = if file.elf_header().e_type(endian) != ET_DYN {
+            Pie::None
+        } else if pie_flag || has_segment(PT_INTERP) {
+            Pie::Pie
+        } else {
+            Pie::SharedObject
+        };
+
+        let mut canary = false;
+        let mut fortified: Vec<_> = Vec::new();
+        for symbol in file.symbols().chain(file.dynamic_symbols()) {
+            let Ok(name) = symbol.name() else {
+                continue;
+            };
+            // Versioned names such as `__stack_chk_fail@GLIBC_2.4` come from `.symtab`.
+            let name = name.split('@').next().unwrap_or(name);
+            if CANARY_SYMBOLS.contains(&name) {
+                canary = true;
+            } else if is_fortified(name) {
+                fortified.push(name.to_string());
+            }
+        }
+        fortified.sort_unstable();
+        fortified.dedup();
+
+        Ok(Self {
+            relro,
+            canary,
+            nx,
+            pie,
+            fortified: fortified.len(),
+        })
+    }
+}
+
+/// Whether `name` is a `_FORTIFY_SOURCE` checked function, e.g. `__printf_chk`.
+fn is_fortified(name: &str) -> bool {
+    name.starts_with("__") && name.ends_with("_chk") && !CANARY_SYMBOLS.contains(&name)
+}
+
+impl fmt::Display for Hardening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relro = match self.relro {
+            Relro::None => "No RELRO",
+            Relro::Partial => "Partial RELRO",
+            Relro::Full => "Full RELRO",
+        };
+        let canary = if self.canary { "Canary" } else { "No canary" };
+        let nx = if self.nx { "NX" } else { "NX disabled" };
+        let pie = match self.pie {
+            Pie::None => "No PIE",
+            Pie::Pie => "PIE",
+            Pie::SharedObject => "DSO",
+        };
+        write!(f, "{relro:<13}  {canary:<9}  {nx:<11}  {pie:<6}  ")?;
+        match self.fortified {
+            0 => write!(f, "No Fortify"),
+            count => write!(f, "Fortify ({count} checked)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_recognize_fortified_functions() {
+        assert!(is_fortified("__memcpy_chk"));
+        assert!(is_fortified("__printf_chk"));
+        assert!(!is_fortified("__stack_chk_fail"));
+        assert!(!is_fortified("memcpy"));
+    }
+}
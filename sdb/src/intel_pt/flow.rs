@@ -0,0 +1,304 @@
+//! Reconstructing the executed path from Intel PT packets and the program's code.
+//!
+//! The trace only records what the code cannot tell: whether conditional branches were
+//! taken, and where indirect branches and returns went. Everything in between is found by
+//! decoding the instructions from the stopped program's memory, so code changed since it ran,
+//! e.g. by a JIT or `patch`, is followed wrongly.
+
+use super::packet::{Packet, PacketDecoder};
+use crate::process::ReadMemory;
+use iced_x86::{Decoder, DecoderOptions, FlowControl, Instruction};
+
+/// Longest x86 instruction.
+const MAX_INSTRUCTION_LEN: usize = 15;
+/// Instructions walked without needing a packet before giving up, against endless loops of
+/// direct jumps.
+const MAX_WALK: usize = 100_000;
+
+/// A piece of the executed path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowEvent {
+    /// The instructions from `start` up to and including the one at `end` ran in a row.
+    Block { start: u64, end: u64 },
+    /// Tracing paused, e.g. in a syscall, until the next block.
+    Paused,
+    /// Packets were lost or did not match the code, so the path before is cut off.
+    Lost,
+}
+
+/// Where the walk through straight-line code stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Branch {
+    /// Address of the branch instruction.
+    ip: u64,
+    /// Address of the instruction after it.
+    next: u64,
+    kind: BranchKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum BranchKind {
+    /// A conditional branch, which takes a TNT bit.
+    Conditional { target: u64 },
+    /// An indirect branch or return, which takes a TIP.
+    Indirect,
+    /// A syscall, interrupt or exception, after which tracing pauses.
+    Far,
+}
+
+/// Reconstructs the path recorded in `trace`, reading the code from `memory`.
+pub fn reconstruct(trace: &[u8], memory: &impl ReadMemory) -> Vec<FlowEvent> {
+    let mut flow = Flow {
+        memory,
+        ip: None,
+        block_start: 0,
+        in_psb: false,
+        after_fup: false,
+        events: Vec::new(),
+    };
+    for packet in PacketDecoder::new(trace) {
+        if flow.packet(packet).is_none() {
+            flow.lose();
+        }
+    }
+    flow.events
+}
+
+/// State of the reconstruction.
+struct Flow<'m, M> {
+    memory: &'m M,
+    /// Address of the next instruction, or `None` while it is unknown.
+    ip: Option<u64>,
+    /// Start of the block being walked.
+    block_start: u64,
+    /// Between `PSB` and `PSBEND`.
+    in_psb: bool,
+    /// An asynchronous event was reported by a FUP, so the next TIP or TIP.PGD goes
+    /// elsewhere without a branch.
+    after_fup: bool,
+    events: Vec<FlowEvent>,
+}
+
+impl<M: ReadMemory> Flow<'_, M> {
+    /// Follows `packet`, or returns `None` if it does not match the code.
+    fn packet(&mut self, packet: Packet) -> Option<()> {
+        match packet {
+            Packet::Tnt(bits) => {
+                for taken in bits {
+                    if self.ip.is_none() {
+                        break;
+                    }
+                    let branch = self.walk()?;
+                    let BranchKind::Conditional { target } = branch.kind else {
+                        return None;
+                    };
+                    self.jump(branch.ip, if taken { target } else { branch.next });
+                }
+            }
+            Packet::Tip(target) => {
+                if self.ip.is_some() && !std::mem::take(&mut self.after_fup) {
+                    let branch = self.walk()?;
+                    if branch.kind != BranchKind::Indirect {
+                        return None;
+                    }
+                    self.end_block(branch.ip);
+                }
+                self.start_block(target);
+            }
+            Packet::TipPge(target) => {
+                self.after_fup = false;
+                self.start_block(target);
+            }
+            Packet::TipPgd(_) => {
+                if self.ip.is_some() && !std::mem::take(&mut self.after_fup) {
+                    let branch = self.walk()?;
+                    self.end_block(branch.ip);
+                }
+                if self
+                    .events
+                    .last()
+                    .is_some_and(|event| *event != FlowEvent::Paused)
+                {
+                    self.events.push(FlowEvent::Paused);
+                }
+                self.ip = None;
+            }
+            Packet::Fup(ip) => {
+                if self.in_psb {
+                    // Only a resync: the address is already known when in sync.
+                    if self.ip.is_none() {
+                        self.start_block(ip);
+                    }
+                } else if let (Some(ip), Some(_)) = (ip, self.ip) {
+                    self.walk_to(ip)?;
+                    self.after_fup = true;
+                }
+            }
+            Packet::Psb => self.in_psb = true,
+            Packet::PsbEnd => self.in_psb = false,
+            Packet::Lost => self.lose(),
+            Packet::Other => {}
+        }
+        Some(())
+    }
+
+    /// Ends the block at `last`, and starts the next one at `target`.
+    fn jump(&mut self, last: u64, target: u64) {
+        self.end_block(last);
+        self.start_block(Some(target));
+    }
+
+    fn start_block(&mut self, ip: Option<u64>) {
+        self.ip = ip;
+        self.block_start = ip.unwrap_or_default();
+    }
+
+    fn end_block(&mut self, last: u64) {
+        self.events.push(FlowEvent::Block {
+            start: self.block_start,
+            end: last,
+        });
+    }
+
+    /// Forgets the current address until the next sync point.
+    fn lose(&mut self) {
+        self.ip = None;
+        self.after_fup = false;
+        if self.events.last() != Some(&FlowEvent::Lost) {
+            self.events.push(FlowEvent::Lost);
+        }
+    }
+
+    /// Runs through the code from the current address up to the next branch a packet
+    /// decides, following direct jumps and calls.
+    fn walk(&mut self) -> Option<Branch> {
+        for _ in 0..MAX_WALK {
+            let ip = self.ip?;
+            let instruction = self.decode(ip)?;
+            let next = instruction.next_ip();
+            let kind = match instruction.flow_control() {
+                FlowControl::Next => {
+                    self.ip = Some(next);
+                    continue;
+                }
+                FlowControl::UnconditionalBranch | FlowControl::Call => {
+                    let target = instruction.near_branch_target();
+                    if target == 0 {
+                        // Far jumps and calls.
+                        BranchKind::Far
+                    } else {
+                        self.jump(ip, target);
+                        continue;
+                    }
+                }
+                FlowControl::ConditionalBranch => BranchKind::Conditional {
+                    target: instruction.near_branch_target(),
+                },
+                FlowControl::IndirectBranch | FlowControl::IndirectCall | FlowControl::Return => {
+                    BranchKind::Indirect
+                }
+                _ => BranchKind::Far,
+            };
+            return Some(Branch { ip, next, kind });
+        }
+        None
+    }
+
+    /// Runs through straight-line code up to `target`, where an asynchronous event came,
+    /// ending the block before it.
+    fn walk_to(&mut self, target: u64) -> Option<()> {
+        let mut last = None;
+        for _ in 0..MAX_WALK {
+            let ip = self.ip?;
+            if ip == target {
+                if let Some(last) = last {
+                    self.end_block(last);
+                }
+                return Some(());
+            }
+            let instruction = self.decode(ip)?;
+            let next = match instruction.flow_control() {
+                FlowControl::Next => instruction.next_ip(),
+                FlowControl::UnconditionalBranch | FlowControl::Call
+                    if instruction.near_branch_target() != 0 =>
+                {
+                    self.jump(ip, instruction.near_branch_target());
+                    last = None;
+                    continue;
+                }
+                _ => return None,
+            };
+            last = Some(ip);
+            self.ip = Some(next);
+        }
+        None
+    }
+
+    fn decode(&self, ip: u64) -> Option<Instruction> {
+        let bytes = self.memory.read_memory(ip, MAX_INSTRUCTION_LEN).ok()?;
+        let instruction = Decoder::with_ip(64, &bytes, ip, DecoderOptions::NONE).decode();
+        (!instruction.is_invalid()).then_some(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Code at 0x1000:
+    ///
+    /// ```text
+    /// 1000: nop
+    /// 1001: cmp edi, 0
+    /// 1004: je 1008
+    /// 1006: call rax
+    /// 1008: jmp 1010
+    /// 100a..1010: int3
+    /// 1010: ret
+    /// ```
+    struct Code;
+
+    impl ReadMemory for Code {
+        fn read_memory(&self, addr: u64, len: usize) -> crate::error::Result<Vec<u8>> {
+            let code = [
+                0x90, 0x83, 0xff, 0x00, 0x74, 0x02, 0xff, 0xd0, 0xeb, 0x06, 0xcc, 0xcc, 0xcc, 0xcc,
+                0xcc, 0xcc, 0xc3,
+            ];
+            let start = (addr - 0x1000) as usize;
+            Ok(code
+                .get(start..)
+                .unwrap_or_default()
+                .iter()
+                .copied()
+                .take(len)
+                .collect())
+        }
+    }
+
+    #[test]
+    fn should_follow_branches_through_code() {
+        let mut trace = [0x02, 0x82].repeat(8);
+        trace.extend([0x02, 0x23]); // PSBEND
+        trace.extend([0x71, 0x00, 0x10, 0, 0, 0, 0]); // TIP.PGE 0x1000
+        trace.extend([0x04]); // TNT: `je` not taken
+        trace.extend([0x2d, 0x10, 0x10]); // TIP 0x1010: `call rax`
+        trace.extend([0x2d, 0x08, 0x10]); // TIP 0x1008: `ret`
+        trace.extend([0x01]); // TIP.PGD at the `ret` after `jmp`
+        let block = |start, end| FlowEvent::Block { start, end };
+        assert_eq!(
+            reconstruct(&trace, &Code),
+            [
+                block(0x1000, 0x1004),
+                block(0x1006, 0x1006),
+                block(0x1010, 0x1010),
+                block(0x1008, 0x1008),
+                block(0x1010, 0x1010),
+                FlowEvent::Paused,
+            ]
+        );
+        // A TNT where the code has an indirect call loses sync.
+        trace.truncate(trace.len() - 7);
+        trace.extend([0x04]);
+        assert_eq!(reconstruct(&trace, &Code).last(), Some(&FlowEvent::Lost));
+    }
+}
@@ -0,0 +1,130 @@
+//! Recording the inferior's control flow with Intel PT (processor trace).
+//!
+//! The `intel_pt` perf PMU writes compressed branch packets into a buffer shared with the
+//! kernel. The buffer is mapped read-only, which makes it a ring that keeps the latest trace,
+//! so after a crash it holds the path leading up to it. Only the traced thread and its user
+//! space code are recorded.
+
+pub mod flow;
+pub mod packet;
+
+use crate::error::{NoIntelPtSnafu, Result};
+use crate::perf::{
+    Mapping, PerfEvent, PerfEventAttr, ATTR_DISABLED, ATTR_EXCLUDE_HV, ATTR_EXCLUDE_KERNEL,
+    PAGE_SIZE,
+};
+use nix::sys::mman::ProtFlags;
+use nix::unistd::Pid;
+use std::fs;
+use std::path::Path;
+
+/// Sysfs directory of the PMU.
+const PMU_DIR: &str = "/sys/bus/event_source/devices/intel_pt";
+/// Offsets of the AUX area fields in `struct perf_event_mmap_page`.
+const AUX_HEAD_OFFSET: usize = 1056;
+const AUX_OFFSET_OFFSET: usize = 1072;
+const AUX_SIZE_OFFSET: usize = 1080;
+
+/// A trace of one thread, stopped and unmapped on drop.
+#[derive(Debug)]
+pub struct PtRecorder {
+    /// The trace buffer.
+    aux: Mapping,
+    /// Metadata page and one data page of the perf buffer, which the trace buffer needs.
+    ring: Mapping,
+    event: PerfEvent,
+}
+
+impl PtRecorder {
+    /// Starts tracing the thread `tid` into a buffer of `size` bytes, rounded up to a power
+    /// of two pages.
+    ///
+    /// # Errors
+    /// Returns an error if the CPU or kernel has no Intel PT, the thread may not be traced, or
+    /// the buffer cannot be mapped.
+    pub fn start(tid: Pid, size: usize) -> Result<Self> {
+        let pmu_file = |name: &str| {
+            let path = Path::new(PMU_DIR).join(name);
+            fs::read_to_string(&path).map_err(|err| {
+                NoIntelPtSnafu {
+                    msg: format!("{}: {err}", path.display()),
+                }
+                .build()
+            })
+        };
+        let kind = pmu_file("type")?.trim().parse().map_err(|_| {
+            NoIntelPtSnafu {
+                msg: "unreadable PMU type",
+            }
+            .build()
+        })?;
+        // Branches on, and return compression off, so every `ret` gets its target.
+        let mut config = 0;
+        for format in ["format/branch", "format/noretcomp"] {
+            let bit = pmu_file(format)?
+                .trim()
+                .strip_prefix("config:")
+                .and_then(|bit| bit.parse::<u32>().ok());
+            config |= bit.map_or(0, |bit| 1 << bit);
+        }
+        let attr = PerfEventAttr {
+            kind,
+            config,
+            flags: ATTR_DISABLED | ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV,
+            ..PerfEventAttr::default()
+        };
+        let mut event = PerfEvent::open(attr, tid, "Intel PT")?;
+        let ring_len = 2 * PAGE_SIZE;
+        let aux_len = size.max(PAGE_SIZE).next_power_of_two();
+        let ring = event.map(0, ring_len, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)?;
+        ring.write_u64(AUX_OFFSET_OFFSET, ring_len as u64);
+        ring.write_u64(AUX_SIZE_OFFSET, aux_len as u64);
+        // Read-only: the kernel overwrites the oldest trace instead of stopping when full.
+        let aux = event.map(ring_len, aux_len, ProtFlags::PROT_READ)?;
+        event.enable()?;
+        Ok(Self { aux, ring, event })
+    }
+
+    /// Returns `true` if the trace is running.
+    pub const fn is_recording(&self) -> bool {
+        self.event.is_enabled()
+    }
+
+    /// Returns the buffer size.
+    pub const fn size(&self) -> usize {
+        self.aux.bytes().len()
+    }
+
+    /// Pauses the trace, keeping what was recorded.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel refuses.
+    pub fn disable(&mut self) -> Result<()> {
+        self.event.disable()
+    }
+
+    /// Returns the recorded packets, oldest first. The trace is paused meanwhile, which
+    /// flushes the processor's buffers.
+    ///
+    /// # Errors
+    /// Returns an error if the trace cannot be paused or resumed.
+    pub fn snapshot(&mut self) -> Result<Vec<u8>> {
+        let was_enabled = self.event.is_enabled();
+        if was_enabled {
+            self.event.disable()?;
+        }
+        let buffer = self.aux.bytes();
+        let head = usize::try_from(self.ring.read_u64(AUX_HEAD_OFFSET)).unwrap_or(usize::MAX);
+        let data = if head <= buffer.len() {
+            buffer[..head].to_vec()
+        } else {
+            // Wrapped: the oldest packets follow the newest.
+            let split = head % buffer.len();
+            [&buffer[split..], &buffer[..split]].concat()
+        };
+        if was_enabled {
+            self.event.enable()?;
+        }
+        Ok(data)
+    }
+}
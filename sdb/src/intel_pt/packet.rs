@@ -0,0 +1,232 @@
+//! Intel PT packets, as far as reconstructing control flow needs them.
+//!
+//! Timing and power packets are skipped. Undecodable bytes lose sync until the next `PSB`,
+//! which is reported like an overflow.
+
+/// Packet stream boundary: 8 times `02 82`.
+const PSB: [u8; 16] = [
+    0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82,
+];
+
+/// A decoded packet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Packet {
+    /// Taken (`true`) or not taken conditional branches, oldest first.
+    Tnt(Vec<bool>),
+    /// Target of an indirect branch or return; `None` if suppressed.
+    Tip(Option<u64>),
+    /// Tracing was enabled, continuing at the given address.
+    TipPge(Option<u64>),
+    /// Tracing was disabled, e.g. by a syscall; the target if not suppressed.
+    TipPgd(Option<u64>),
+    /// Where an asynchronous event such as an interrupt happened, or inside `PSB+`, the
+    /// current address.
+    Fup(Option<u64>),
+    /// Start of a sync point, which repeats the state in the packets up to [`Packet::PsbEnd`].
+    Psb,
+    PsbEnd,
+    /// Packets were lost, by an overflow of the processor's buffers or bytes that could not be
+    /// decoded.
+    Lost,
+    /// A packet that does not affect control flow.
+    Other,
+}
+
+/// Iterator over the packets of a trace, from its first sync point.
+#[derive(Clone, Debug)]
+pub struct PacketDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Last IP packet address, which compressed addresses update.
+    last_ip: u64,
+}
+
+impl<'a> PacketDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: find_psb(data, 0).unwrap_or(data.len()),
+            last_ip: 0,
+        }
+    }
+
+    /// Returns the `len` bytes at `offset` from the current packet.
+    fn bytes(&self, offset: usize, len: usize) -> Option<&'a [u8]> {
+        self.data.get(self.pos + offset..self.pos + offset + len)
+    }
+
+    /// Decodes the packet at the current position, returning it and its length, or `None` if
+    /// it cannot be decoded.
+    fn decode(&mut self) -> Option<(Packet, usize)> {
+        let header = *self.data.get(self.pos)?;
+        let packet = match header {
+            0x00 => (Packet::Other, 1),
+            0x02 => return self.decode_extended(),
+            _ if header & 1 == 0 => (Packet::Tnt(tnt_bits(u64::from(header >> 1))), 1),
+            _ => match header & 0x1f {
+                0x0d | 0x11 | 0x01 | 0x1d => {
+                    let (ip, len) = self.ip(header)?;
+                    let packet = match header & 0x1f {
+                        0x0d => Packet::Tip(ip),
+                        0x11 => Packet::TipPge(ip),
+                        0x01 => Packet::TipPgd(ip),
+                        _ => Packet::Fup(ip),
+                    };
+                    (packet, 1 + len)
+                }
+                // MODE, TSC and MTC.
+                _ if header == 0x99 => (Packet::Other, 2),
+                _ if header == 0x19 => (Packet::Other, 8),
+                _ if header == 0x59 => (Packet::Other, 2),
+                // CYC, continued while the lowest bit of the last byte is set.
+                _ if header & 3 == 3 => {
+                    let mut len = 1;
+                    if header & 4 != 0 {
+                        while self.bytes(len, 1)?[0] & 1 != 0 {
+                            len += 1;
+                        }
+                        len += 1;
+                    }
+                    (Packet::Other, len)
+                }
+                _ => return None,
+            },
+        };
+        self.bytes(0, packet.1)?;
+        Some(packet)
+    }
+
+    /// Decodes a packet starting with `02`.
+    fn decode_extended(&mut self) -> Option<(Packet, usize)> {
+        let kind = self.bytes(1, 1)?[0];
+        let packet = match kind {
+            0x82 => {
+                if self.bytes(0, PSB.len())? != PSB {
+                    return None;
+                }
+                self.last_ip = 0;
+                (Packet::Psb, PSB.len())
+            }
+            0x23 => (Packet::PsbEnd, 2),
+            0xf3 => (Packet::Lost, 2),
+            0xa3 => {
+                let mut payload = [0; 8];
+                payload[..6].copy_from_slice(self.bytes(2, 6)?);
+                (Packet::Tnt(tnt_bits(u64::from_le_bytes(payload))), 8)
+            }
+            // PIP, CBR, TraceStop, TMA, VMCS, MNT, EXSTOP, MWAIT, PWRE and PWRX.
+            0x43 => (Packet::Other, 8),
+            0x03 | 0x22 => (Packet::Other, 4),
+            0x83 | 0x62 | 0xe2 => (Packet::Other, 2),
+            0x73 | 0xc8 | 0xa2 => (Packet::Other, 7),
+            0xc3 => (Packet::Other, 11),
+            0xc2 => (Packet::Other, 10),
+            // PTW, with a 4 or 8 byte payload.
+            _ if kind & 0x1f == 0x12 => match (kind >> 5) & 3 {
+                0 => (Packet::Other, 6),
+                1 => (Packet::Other, 10),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.bytes(0, packet.1)?;
+        Some(packet)
+    }
+
+    /// Decodes the possibly compressed address after the IP packet `header`, returning it
+    /// and the length of its payload.
+    fn ip(&mut self, header: u8) -> Option<(Option<u64>, usize)> {
+        let len = match header >> 5 {
+            0 => return Some((None, 0)),
+            1 => 2,
+            2 => 4,
+            3 | 4 => 6,
+            6 => 8,
+            _ => return None,
+        };
+        let mut payload = [0; 8];
+        payload[..len].copy_from_slice(self.bytes(1, len)?);
+        let payload = u64::from_le_bytes(payload);
+        let ip = match header >> 5 {
+            1 => self.last_ip & !0xffff | payload,
+            2 => self.last_ip & !0xffff_ffff | payload,
+            // Sign-extended from 48 bits.
+            3 => (((payload << 16) as i64) >> 16) as u64,
+            4 => self.last_ip & !0xffff_ffff_ffff | payload,
+            _ => payload,
+        };
+        self.last_ip = ip;
+        Some((Some(ip), len))
+    }
+}
+
+impl Iterator for PacketDecoder<'_> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Packet> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        if let Some((packet, len)) = self.decode() {
+            self.pos += len;
+            return Some(packet);
+        }
+        // Truncated at the end, or garbage: go on from the next sync point.
+        self.pos = find_psb(self.data, self.pos + 1).unwrap_or(self.data.len());
+        Some(Packet::Lost)
+    }
+}
+
+/// Returns the offset of the first `PSB` at or after `from`.
+fn find_psb(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(PSB.len())
+        .position(|window| window == PSB)
+        .map(|offset| from + offset)
+}
+
+/// Returns the branch outcomes of a TNT payload: the bits below its highest set bit, which
+/// ends them, with the oldest branch in the highest bit.
+fn tnt_bits(payload: u64) -> Vec<bool> {
+    let count = 63 - payload.leading_zeros().min(63);
+    (0..count)
+        .rev()
+        .map(|bit| payload & (1 << bit) != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_decode_packets_from_sync_point() {
+        let mut data = vec![0xff, 0x0d]; // garbage before the first PSB
+        data.extend(PSB);
+        data.extend([0x7d, 0x00, 0x10, 0x40, 0x00, 0x00, 0x00]); // FUP sext48 0x401000
+        data.extend([0x02, 0x23]); // PSBEND
+        data.extend([0x0c]); // TNT: taken, not taken
+        data.extend([0x2d, 0x34, 0x12]); // TIP with 16 low bits 0x1234
+        data.extend([0x02, 0xa3, 0b1011, 0, 0, 0, 0, 0]); // long TNT: not taken, taken, taken
+        data.extend([0x19, 1, 2, 3, 4, 5, 6, 7]); // TSC
+        data.extend([0x01]); // TIP.PGD, suppressed
+        data.extend([0x02, 0xf3]); // OVF
+        data.extend([0x31, 0x00]); // TIP.PGE, truncated
+        let packets: Vec<_> = PacketDecoder::new(&data).collect();
+        assert_eq!(
+            packets,
+            [
+                Packet::Psb,
+                Packet::Fup(Some(0x40_1000)),
+                Packet::PsbEnd,
+                Packet::Tnt(vec![true, false]),
+                Packet::Tip(Some(0x40_1234)),
+                Packet::Tnt(vec![false, true, true]),
+                Packet::Other,
+                Packet::TipPgd(None),
+                Packet::Lost,
+                Packet::Lost,
+            ]
+        );
+    }
+}
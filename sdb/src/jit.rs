@@ -0,0 +1,212 @@
+//! The GDB JIT interface, through which JIT compilers describe the code they emit.
+//!
+//! A JIT compiler keeps an in-memory object file with the symbols and DWARF of each piece of
+//! code it emits in the `jit_code_entry` list of `__jit_debug_descriptor`, and calls the
+//! empty function `__jit_debug_register_code` after adding or removing one, so a breakpoint
+//! there sees every change of the list.
+
+use crate::error::{LoadElfSnafu, Result};
+use crate::line_table::{LineTable, SourceLine};
+use crate::process::ReadMemory;
+use crate::procfs::MemoryRegion;
+use crate::symbols::{Symbol, SymbolTable};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The only `version` of `struct jit_descriptor` there is.
+const VERSION: u64 = 1;
+/// Offset of `first_entry` in `struct jit_descriptor`.
+const FIRST_ENTRY: u64 = 16;
+/// Most `jit_code_entry` entries followed, in case the list is corrupt.
+const MAX_ENTRIES: usize = 1 << 16;
+/// Largest object file read from an entry.
+const MAX_SYMFILE_SIZE: u64 = 1 << 30;
+
+/// Addresses of the JIT interface in the inferior.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JitInterface {
+    /// Address of `__jit_debug_descriptor`.
+    pub descriptor: u64,
+    /// Address of `__jit_debug_register_code`, called after every change of the list.
+    pub breakpoint: u64,
+}
+
+/// An object file registered by a JIT compiler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct JitEntry {
+    /// Address of the `jit_code_entry`.
+    pub addr: u64,
+    /// Where the object file is in the inferior's memory.
+    pub symfile_addr: u64,
+    pub symfile_size: u64,
+}
+
+/// The symbols and source lines of a [`JitEntry`], at the addresses the code runs at.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JitObject {
+    pub entry: JitEntry,
+    pub symbols: SymbolTable,
+    /// Empty if the object has no DWARF.
+    pub lines: LineTable,
+}
+
+impl JitInterface {
+    /// Finds the interface in the first mapped file defining both of its symbols.
+    ///
+    /// # Returns
+    /// `None` unless the program links a JIT compiler supporting it.
+    pub fn locate(maps: &[MemoryRegion]) -> Option<Self> {
+        maps.iter()
+            .filter(|region| Path::new(&region.path).is_absolute())
+            .find_map(|region| {
+                let table = SymbolTable::cached(Path::new(&region.path)).ok()?;
+                let bias = table.load_bias(region)?;
+                let address = |name: &str| {
+                    table
+                        .symbols()
+                        .iter()
+                        .find(|symbol| symbol.name == name)
+                        .map(|symbol| symbol.address.wrapping_add(bias))
+                };
+                Some(Self {
+                    descriptor: address("__jit_debug_descriptor")?,
+                    breakpoint: address("__jit_debug_register_code")?,
+                })
+            })
+    }
+
+    /// Reads the `jit_code_entry` list.
+    ///
+    /// # Returns
+    /// No entries if the descriptor has an unknown version, e.g. before the JIT compiler set
+    /// it up.
+    ///
+    /// # Errors
+    /// Returns an error if the descriptor cannot be read.
+    pub fn entries(&self, memory: &impl ReadMemory) -> Result<Vec<JitEntry>> {
+        let header = memory.read_words(self.descriptor, 1)?;
+        // `version` is the lower half, `action_flag` the upper.
+        if header.first().map(|word| word & 0xffff_ffff) != Some(VERSION) {
+            return Ok(Vec::new());
+        }
+        let mut addr = memory
+            .read_words(self.descriptor + FIRST_ENTRY, 1)?
+            .first()
+            .copied()
+            .unwrap_or_default();
+        let mut entries = Vec::new();
+        while addr != 0 && entries.len() < MAX_ENTRIES {
+            // `next_entry`, `prev_entry`, `symfile_addr`, `symfile_size`.
+            let Ok(&[next, _, symfile_addr, symfile_size]) = memory.read_words(addr, 4).as_deref()
+            else {
+                break;
+            };
+            entries.push(JitEntry {
+                addr,
+                symfile_addr,
+                symfile_size,
+            });
+            addr = next;
+        }
+        Ok(entries)
+    }
+}
+
+impl JitObject {
+    /// Reads and parses the object file of `entry`.
+    ///
+    /// # Errors
+    /// Returns an error if the object file cannot be read whole or is not a valid ELF file.
+    pub fn read(memory: &impl ReadMemory, entry: JitEntry) -> Result<Self> {
+        let path = entry.path();
+        let to_error = |msg: String| LoadElfSnafu { path: &path, msg }.build();
+        if entry.symfile_size > MAX_SYMFILE_SIZE {
+            return Err(to_error(format!(
+                "{} bytes is too large",
+                entry.symfile_size
+            )));
+        }
+        let data = memory.read_memory(entry.symfile_addr, entry.symfile_size as usize)?;
+        if data.len() as u64 != entry.symfile_size {
+            return Err(to_error(format!("only {} bytes are mapped", data.len())));
+        }
+        Ok(Self {
+            entry,
+            symbols: SymbolTable::parse(&data, &path)?,
+            lines: LineTable::parse(&data, &path).unwrap_or_default(),
+        })
+    }
+
+    /// Finds the function containing `addr`.
+    ///
+    /// # Returns
+    /// The symbol and the offset of `addr` into it.
+    pub fn symbol(&self, addr: u64) -> Option<(&Symbol, u64)> {
+        // Unlike in a mapped file, a symbol of unknown size says nothing about what follows.
+        self.symbols
+            .find(addr)
+            .filter(|(symbol, offset)| symbol.is_function && (symbol.size > 0 || *offset == 0))
+    }
+
+    /// Finds the source line of `addr`.
+    pub fn line(&self, addr: u64) -> Option<SourceLine> {
+        self.lines.find(addr)
+    }
+}
+
+impl JitEntry {
+    /// Returns the name the object file goes by in errors, e.g. `<jit 0x7f0000001000>`.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(format!("<jit {:#x}>", self.symfile_addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Memory given as words by address.
+    struct Words(BTreeMap<u64, u64>);
+
+    impl ReadMemory for Words {
+        fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+            Ok((addr..addr + len as u64)
+                .step_by(size_of::<u64>())
+                .map_while(|addr| self.0.get(&addr))
+                .flat_map(|word| word.to_le_bytes())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn should_follow_entry_list() -> Result<()> {
+        let interface = JitInterface {
+            descriptor: 0x1000,
+            breakpoint: 0x2000,
+        };
+        let memory = Words(BTreeMap::from([
+            (0x1000, 1 | 1 << 32), // Version 1, `JIT_REGISTER_FN`.
+            (0x1008, 0x3020),
+            (0x1010, 0x3000),
+            (0x3000, 0x3020),
+            (0x3008, 0),
+            (0x3010, 0x5000),
+            (0x3018, 0x100),
+            (0x3020, 0),
+            (0x3028, 0x3000),
+            (0x3030, 0x6000),
+            (0x3038, 0x200),
+        ]));
+        let entries = interface.entries(&memory)?;
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.addr, entry.symfile_addr, entry.symfile_size))
+                .collect::<Vec<_>>(),
+            [(0x3000, 0x5000, 0x100), (0x3020, 0x6000, 0x200)]
+        );
+        assert!(JitObject::read(&memory, entries[0]).is_err());
+        Ok(())
+    }
+}
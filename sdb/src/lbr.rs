@@ -0,0 +1,193 @@
+//! Capturing the CPU's last branch records (LBR) of a thread with perf.
+//!
+//! LBR entries only reach user space in samples, so a hardware event counting the thread's
+//! branches takes a sample every `period` branches, each with the 8 to 32 branches before
+//! it. The newest sample shows how the thread got to where it stopped: exactly with a period
+//! of 1, or ending up to `period` branches before the stop. Samples may also be dropped when
+//! the kernel throttles a thread sampling too fast.
+
+use crate::error::Result;
+use crate::perf::{
+    Mapping, PerfEvent, PerfEventAttr, ATTR_DISABLED, ATTR_EXCLUDE_HV, ATTR_EXCLUDE_KERNEL,
+    ATTR_WRITE_BACKWARD, DATA_HEAD_OFFSET, PAGE_SIZE,
+};
+use nix::sys::mman::ProtFlags;
+use nix::unistd::Pid;
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_BRANCH_INSTRUCTIONS: u64 = 4;
+const PERF_SAMPLE_BRANCH_STACK: u64 = 1 << 11;
+/// `branch_sample_type`: user-space branches of any kind.
+const PERF_SAMPLE_BRANCH_USER: u64 = 1;
+const PERF_SAMPLE_BRANCH_ANY: u64 = 1 << 3;
+const PERF_RECORD_SAMPLE: u32 = 9;
+/// Data pages of the sample buffer, which keeps the newest samples.
+const DATA_PAGES: usize = 8;
+/// Size of `struct perf_branch_entry`.
+const BRANCH_ENTRY_LEN: usize = 24;
+
+/// A taken branch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Branch {
+    pub from: u64,
+    pub to: u64,
+    /// The CPU predicted it wrongly.
+    pub mispredicted: bool,
+}
+
+/// Branch sampling of one thread, stopped on drop.
+#[derive(Debug)]
+pub struct LbrRecorder {
+    ring: Mapping,
+    event: PerfEvent,
+    period: u64,
+}
+
+impl LbrRecorder {
+    /// Starts sampling the branches of the thread `tid` every `period` branches.
+    ///
+    /// # Errors
+    /// Returns an error if the CPU has no LBR, e.g. in most VMs, or the thread may not be
+    /// traced.
+    pub fn start(tid: Pid, period: u64) -> Result<Self> {
+        let attr = PerfEventAttr {
+            kind: PERF_TYPE_HARDWARE,
+            config: PERF_COUNT_HW_BRANCH_INSTRUCTIONS,
+            sample_period: period.max(1),
+            sample_type: PERF_SAMPLE_BRANCH_STACK,
+            branch_sample_type: PERF_SAMPLE_BRANCH_USER | PERF_SAMPLE_BRANCH_ANY,
+            // Written backward into a read-only buffer, the newest sample is at the head and
+            // overwrites the oldest.
+            flags: ATTR_DISABLED | ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV | ATTR_WRITE_BACKWARD,
+            ..PerfEventAttr::default()
+        };
+        let mut event = PerfEvent::open(attr, tid, "last branch records")?;
+        let ring = event.map(0, (1 + DATA_PAGES) * PAGE_SIZE, ProtFlags::PROT_READ)?;
+        event.enable()?;
+        Ok(Self {
+            ring,
+            event,
+            period: period.max(1),
+        })
+    }
+
+    /// Returns the number of branches between samples.
+    pub const fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Returns `true` if branches are being sampled.
+    pub const fn is_recording(&self) -> bool {
+        self.event.is_enabled()
+    }
+
+    /// Stops sampling, keeping the samples taken.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel refuses.
+    pub fn disable(&mut self) -> Result<()> {
+        self.event.disable()
+    }
+
+    /// Returns the branches of the newest sample, oldest first, or `None` if there is none
+    /// yet. Call it while the thread is stopped, so it takes no sample meanwhile.
+    pub fn latest(&self) -> Option<Vec<Branch>> {
+        let head = self.ring.read_u64(DATA_HEAD_OFFSET);
+        newest_sample(&self.ring.bytes()[PAGE_SIZE..], head)
+    }
+}
+
+/// Finds the newest sample in the data pages `data` written backward down to `head`, and
+/// returns its branches, oldest first.
+fn newest_sample(data: &[u8], head: u64) -> Option<Vec<Branch>> {
+    let len = data.len() as u64;
+    // `head` counts down from 0, so the bytes written are its negation.
+    let valid = head.wrapping_neg().min(len);
+    let read = |offset: u64, n: usize| -> Vec<u8> {
+        (0..n as u64)
+            .map(|i| data[(head.wrapping_add(offset + i) % len) as usize])
+            .collect()
+    };
+    let word = |offset: u64| {
+        let bytes = read(offset, 8);
+        u64::from_le_bytes(bytes.try_into().unwrap_or_default())
+    };
+    let mut offset = 0;
+    while offset + 8 <= valid {
+        // `struct perf_event_header`: type, misc and size.
+        let header = word(offset);
+        let kind = header as u32;
+        let size = header >> 48;
+        if size < 8 || offset + size > valid {
+            return None;
+        }
+        if kind == PERF_RECORD_SAMPLE && size >= 16 {
+            let count = word(offset + 8);
+            let entries = (size - 16) / BRANCH_ENTRY_LEN as u64;
+            let branches = (0..count.min(entries))
+                .map(|i| {
+                    let entry = offset + 16 + i * BRANCH_ENTRY_LEN as u64;
+                    Branch {
+                        from: word(entry),
+                        to: word(entry + 8),
+                        mispredicted: word(entry + 16) & 1 != 0,
+                    }
+                })
+                // The newest branch comes first.
+                .rev()
+                .collect();
+            return Some(branches);
+        }
+        offset += size;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `record` backward into `data` like the kernel, moving `head` down.
+    fn write_backward(data: &mut [u8], head: &mut u64, record: &[u8]) {
+        *head = head.wrapping_sub(record.len() as u64);
+        for (i, byte) in record.iter().enumerate() {
+            let pos = head.wrapping_add(i as u64) % data.len() as u64;
+            data[pos as usize] = *byte;
+        }
+    }
+
+    fn record(kind: u32, body: &[u64]) -> Vec<u8> {
+        let size = 8 + 8 * body.len() as u64;
+        let mut record = (u64::from(kind) | size << 48).to_le_bytes().to_vec();
+        record.extend(body.iter().flat_map(|word| word.to_le_bytes()));
+        record
+    }
+
+    #[test]
+    fn should_find_newest_sample() {
+        let mut data = vec![0; 128];
+        let mut head = 0;
+        assert_eq!(newest_sample(&data, head), None);
+        let old = record(PERF_RECORD_SAMPLE, &[1, 0x10, 0x20, 0]);
+        let new = record(PERF_RECORD_SAMPLE, &[2, 0x50, 0x60, 1, 0x30, 0x40, 0]);
+        write_backward(&mut data, &mut head, &old);
+        write_backward(&mut data, &mut head, &new);
+        // PERF_RECORD_LOST, newer than the samples but without branches.
+        write_backward(&mut data, &mut head, &record(2, &[0, 5]));
+        assert_eq!(
+            newest_sample(&data, head),
+            Some(vec![
+                Branch {
+                    from: 0x30,
+                    to: 0x40,
+                    mispredicted: false
+                },
+                Branch {
+                    from: 0x50,
+                    to: 0x60,
+                    mispredicted: true
+                },
+            ])
+        );
+    }
+}
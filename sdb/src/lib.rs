@@ -1,2 +1,37 @@
+pub mod annotate;
+pub mod assembler;
+pub mod cancel;
+pub mod catchpoint;
+pub mod compile_units;
+pub mod disassembler;
 pub mod error;
+pub mod frame_recognizer;
+pub mod function_trace;
+pub mod gadget;
+pub mod got;
+pub mod hardening;
+#[cfg(feature = "intel-pt")]
+pub mod intel_pt;
+pub mod jit;
+pub mod lbr;
+pub mod line_table;
+pub mod locks;
+pub mod mapped_file;
+pub mod output;
+pub mod patch;
+mod perf;
 pub mod process;
+pub mod procfs;
+pub mod rendezvous;
+pub mod sanitizer;
+pub mod scan;
+pub mod sched;
+pub mod seccomp;
+pub mod sockets;
+pub mod stack;
+pub mod symbol_cache;
+pub mod symbol_index;
+pub mod symbol_list;
+pub mod symbols;
+pub mod syscall;
+pub mod watchpoint;
@@ -0,0 +1,208 @@
+//! DWARF line tables, used to tell which source line an address was compiled from.
+
+use crate::compile_units::{dwarf_sections, file_path, Slice};
+use crate::error::{LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use crate::symbols::modified;
+use gimli::{EndianSlice, LittleEndian};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Tables loaded by [`LineTable::cached`], by path.
+static CACHE: Mutex<BTreeMap<PathBuf, CachedTable>> = Mutex::new(BTreeMap::new());
+
+struct CachedTable {
+    /// Modification time of the file the table was loaded from.
+    modified: Option<SystemTime>,
+    table: Arc<LineTable>,
+}
+
+/// A source file and line.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SourceLine {
+    /// Path as recorded by the compiler, joined with its directory.
+    pub file: String,
+    pub line: u64,
+}
+
+impl std::fmt::Display for SourceLine {
+    /// Writes the file name without its directories, e.g. `main.c:12`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = Path::new(&self.file)
+            .file_name()
+            .map_or(self.file.as_str(), |name| name.to_str().unwrap_or_default());
+        write!(f, "{name}:{}", self.line)
+    }
+}
+
+/// A row of a line table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Row {
+    /// Link-time address.
+    address: u64,
+    /// Index into [`LineTable::files`].
+    file: usize,
+    /// `0` for code with no line, such as compiler generated code.
+    line: u64,
+    /// Whether the row ends a sequence, the address being past its code.
+    end: bool,
+}
+
+/// The line tables of all compile units of an ELF file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineTable {
+    /// Sorted by address, ends of sequences before rows starting another at the same address.
+    rows: Vec<Row>,
+    files: Vec<String>,
+}
+
+impl LineTable {
+    /// Loads the line tables of the ELF file at `path`.
+    ///
+    /// # Returns
+    /// An empty table if the file has no DWARF.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or its DWARF is malformed.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = MappedFile::open(path).map_err(|err| {
+            LoadElfSnafu {
+                path,
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        Self::parse(&data, path)
+    }
+
+    /// Loads the line tables of the ELF file in `data`, which `path` names in errors.
+    ///
+    /// # Returns
+    /// An empty table if the file has no DWARF.
+    ///
+    /// # Errors
+    /// Returns an error if `data` is not a valid object file or its DWARF is malformed.
+    pub fn parse(data: &[u8], path: &Path) -> Result<Self> {
+        let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+        let file = object::File::parse(data).map_err(|err| to_error(err.to_string()))?;
+        let sections = dwarf_sections(&file).map_err(|err| to_error(err.to_string()))?;
+        let dwarf = sections.borrow(|section| EndianSlice::new(section, LittleEndian));
+        let mut table = Self::default();
+        table
+            .read_units(&dwarf)
+            .map_err(|err| to_error(format!("bad DWARF: {err}")))?;
+        table.rows.sort_by_key(|row| (row.address, !row.end));
+        Ok(table)
+    }
+
+    /// Like [`Self::load`], but each file is only loaded again once it changes.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or its DWARF is malformed.
+    pub fn cached(path: &Path) -> Result<Arc<Self>> {
+        let modified = modified(path);
+        let cached = CACHE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .filter(|cached| cached.modified == modified)
+            .map(|cached| Arc::clone(&cached.table));
+        if let Some(table) = cached {
+            return Ok(table);
+        }
+        let table = Arc::new(Self::load(path)?);
+        CACHE.lock().unwrap_or_else(PoisonError::into_inner).insert(
+            path.to_path_buf(),
+            CachedTable {
+                modified,
+                table: Arc::clone(&table),
+            },
+        );
+        Ok(table)
+    }
+
+    /// Finds the source line of the link-time address `address`.
+    pub fn find(&self, address: u64) -> Option<SourceLine> {
+        let index = self
+            .rows
+            .partition_point(|row| row.address <= address)
+            .checked_sub(1)?;
+        let row = self.rows[index];
+        if row.end || row.line == 0 {
+            return None;
+        }
+        Some(SourceLine {
+            file: self.files[row.file].clone(),
+            line: row.line,
+        })
+    }
+
+    fn read_units(&mut self, dwarf: &gimli::Dwarf<Slice<'_>>) -> gimli::Result<()> {
+        let mut file_indices = HashMap::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            // Indices into `self.files` by the unit's own file indices.
+            let mut unit_files = HashMap::new();
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                // Code discarded by the linker keeps address 0.
+                if row.address() == 0 {
+                    continue;
+                }
+                let file = if let Some(&file) = unit_files.get(&row.file_index()) {
+                    file
+                } else {
+                    let path = match row.file(header) {
+                        Some(file) => file_path(dwarf, &unit, header, file)?,
+                        None => String::new(),
+                    };
+                    let file = *file_indices.entry(path).or_insert_with_key(|path| {
+                        self.files.push(path.clone());
+                        self.files.len() - 1
+                    });
+                    unit_files.insert(row.file_index(), file);
+                    file
+                };
+                self.rows.push(Row {
+                    address: row.address(),
+                    file,
+                    line: row.line().map_or(0, |line| line.get()),
+                    end: row.end_sequence(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_own_lines() -> Result<()> {
+        let exe = std::env::current_exe().map_err(|err| {
+            LoadElfSnafu {
+                path: "/proc/self/exe",
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        let table = LineTable::load(&exe)?;
+        let row = table.rows.iter().find(|row| {
+            !row.end && row.line > 0 && table.files[row.file].ends_with("line_table.rs")
+        });
+        assert!(row.is_some(), "no rows for this file");
+        if let Some(row) = row {
+            assert!(table.find(row.address).is_some());
+        }
+        Ok(())
+    }
+}
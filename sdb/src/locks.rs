@@ -0,0 +1,153 @@
+//! Lock contention analysis: which thread waits on which futex, and who holds it.
+
+use crate::error::Result;
+use crate::process::Process;
+use crate::procfs::{tids, TaskSyscall};
+use crate::syscall::syscall_number;
+use nix::unistd::Pid;
+
+/// `FUTEX_CMD_MASK`: strips `FUTEX_PRIVATE_FLAG` and `FUTEX_CLOCK_REALTIME`.
+const FUTEX_CMD_MASK: u64 = !(128 | 256);
+/// Bits of a PI futex word holding the owner tid.
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+/// Offset of `__owner` in glibc's x86_64 `pthread_mutex_t`, relative to the `__lock` futex word.
+const MUTEX_OWNER_OFFSET: u64 = 8;
+
+/// A thread blocked in `futex(2)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FutexWait {
+    /// The waiting thread.
+    pub tid: Pid,
+    /// Address of the futex word.
+    pub addr: u64,
+    /// Futex operation without flags, e.g. `FUTEX_WAIT`.
+    pub op: u64,
+    /// Thread currently holding the lock at `addr`, if it looks like a mutex owned by
+    /// another thread of the process.
+    pub owner: Option<Pid>,
+}
+
+impl FutexWait {
+    /// Returns the name of the futex operation.
+    pub const fn op_name(&self) -> &'static str {
+        match self.op {
+            0 => "FUTEX_WAIT",
+            6 => "FUTEX_LOCK_PI",
+            9 => "FUTEX_WAIT_BITSET",
+            11 => "FUTEX_WAIT_REQUEUE_PI",
+            13 => "FUTEX_LOCK_PI2",
+            _ => "futex",
+        }
+    }
+
+    /// Whether the futex word itself holds the owner tid (priority-inheritance futexes).
+    const fn is_pi(&self) -> bool {
+        matches!(self.op, 6 | 11 | 13)
+    }
+}
+
+/// Lists the threads of `process` blocked in `futex(2)` and the owners of those futexes.
+///
+/// Owners are found by reading the tid stored next to the futex word in a glibc
+/// `pthread_mutex_t`, or in the word itself for PI futexes. Condition variables and other
+/// futex users have no owner; a value that is not a thread of the process is ignored.
+///
+/// # Errors
+/// Returns an error if the threads of the process cannot be listed.
+pub fn futex_waits(process: &Process) -> Result<Vec<FutexWait>> {
+    let Some(futex) = syscall_number("futex") else {
+        return Ok(Vec::new());
+    };
+    let threads = tids(process.pid)?;
+    Ok(threads
+        .iter()
+        .filter_map(|&tid| match TaskSyscall::read(process.pid, tid).ok()? {
+            TaskSyscall::Blocked { number, args, .. } if number == futex => {
+                let mut wait = FutexWait {
+                    tid,
+                    addr: args[0],
+                    op: args[1] & FUTEX_CMD_MASK,
+                    owner: None,
+                };
+                wait.owner = owner_of(process, &wait)
+                    .filter(|owner| *owner != tid && threads.contains(owner));
+                Some(wait)
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// Finds cycles in the wait-for graph formed by `waits`, where each waiting thread points to
+/// the owner of its futex.
+///
+/// # Returns
+/// Each cycle as the waits of its members, starting from the lowest tid.
+pub fn find_deadlocks(waits: &[FutexWait]) -> Vec<Vec<FutexWait>> {
+    let waiting_on = |tid: Pid| waits.iter().find(|wait| wait.tid == tid);
+    let mut cycles: Vec<Vec<FutexWait>> = Vec::new();
+    for start in waits {
+        // Every thread waits on at most one futex, so following owners either ends or loops.
+        let mut path = vec![*start];
+        while let Some(next) = path.last().and_then(|wait| waiting_on(wait.owner?)) {
+            if let Some(index) = path.iter().position(|wait| wait.tid == next.tid) {
+                let mut cycle = path.split_off(index);
+                let lowest = cycle
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, wait)| wait.tid)
+                    .map_or(0, |(index, _)| index);
+                cycle.rotate_left(lowest);
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+                break;
+            }
+            path.push(*next);
+        }
+    }
+    cycles
+}
+
+fn owner_of(process: &Process, wait: &FutexWait) -> Option<Pid> {
+    let (addr, mask) = if wait.is_pi() {
+        (wait.addr, FUTEX_TID_MASK)
+    } else {
+        (wait.addr + MUTEX_OWNER_OFFSET, u32::MAX)
+    };
+    let bytes = process.read_memory(addr, 4).ok()?;
+    let word = u32::from_le_bytes(bytes.try_into().ok()?) & mask;
+    i32::try_from(word)
+        .ok()
+        .filter(|&tid| tid > 0)
+        .map(Pid::from_raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn wait(tid: i32, addr: u64, owner: Option<i32>) -> FutexWait {
+        FutexWait {
+            tid: Pid::from_raw(tid),
+            addr,
+            op: 0,
+            owner: match owner {
+                Some(owner) => Some(Pid::from_raw(owner)),
+                None => None,
+            },
+        }
+    }
+
+    #[test]
+    fn should_find_deadlock_cycles() {
+        let waits = [
+            wait(12, 0xb, Some(11)),
+            wait(11, 0xa, Some(12)),
+            wait(13, 0xa, Some(12)), // Blocked behind the cycle, but not part of it.
+            wait(14, 0xc, None),
+        ];
+        assert_eq!(find_deadlocks(&waits), vec![vec![waits[1], waits[0]]]);
+        assert!(find_deadlocks(&waits[2..]).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Read-only memory mappings of files, so ELF parsing only pages in the parts it touches.
+//!
+//! `object` parses borrowed byte slices, so symbols, relocations and program headers are read
+//! straight out of the page cache instead of copying whole libraries, which can be hundreds of
+//! megabytes with debug info, into the heap first.
+
+use nix::libc::c_void;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use std::fs::File;
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
+use std::path::Path;
+use std::ptr::NonNull;
+
+/// A file mapped read-only into memory, unmapped on drop.
+#[derive(Debug)]
+pub struct MappedFile {
+    /// Start of the mapping, or dangling for an empty file, which cannot be mapped.
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl MappedFile {
+    /// Maps the whole file at `path`.
+    ///
+    /// The mapping is private, but still shows changes made to the file while it is mapped,
+    /// and touching it raises `SIGBUS` if the file is truncated meanwhile. Compilers and
+    /// linkers replace their output files rather than rewriting them, so binaries being
+    /// debugged are safe to map.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be opened or mapped.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        let Some(length) = NonZeroUsize::new(len) else {
+            return Ok(Self {
+                ptr: NonNull::dangling(),
+                len,
+            });
+        };
+        // SAFETY: A fresh read-only mapping does not alias any Rust object.
+        let ptr = unsafe {
+            mmap(
+                None,
+                length,
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_PRIVATE,
+                &file,
+                0,
+            )
+        }?;
+        Ok(Self { ptr, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` readable bytes that live as long as `self`; a dangling
+        // pointer is valid for an empty slice.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().cast(), self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: The mapping is only borrowed through `self`, so nothing uses it anymore.
+            let _ = unsafe { munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_file_contents() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("sdb-mapped-{}", std::process::id()));
+        std::fs::write(&path, b"\x7fELF")?;
+        let mapped = MappedFile::open(&path)?;
+        assert_eq!(&*mapped, b"\x7fELF");
+        std::fs::write(&path, b"")?;
+        // Empty files cannot be mmapped, yet they still open.
+        assert!(MappedFile::open(&path)?.is_empty());
+        std::fs::remove_file(path)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::annotate::{Annotation, Annotator};
+use crate::frame_recognizer::{recognize, FrameNote};
+use crate::process::ReadMemory;
+use crate::procfs::{find_region, MemoryRegion};
+use crate::stack::frame_pointer_chain;
+use nix::libc::user_regs_struct;
+use serde::Serialize;
+
+/// A frame of a [`Backtrace`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct BacktraceFrame {
+    /// `0` for the innermost frame.
+    pub index: usize,
+    /// Where the frame's code is: the program counter for frame 0, else the return address.
+    #[serde(flatten)]
+    pub location: Annotation,
+    /// What a [`FrameRecognizer`](crate::frame_recognizer::FrameRecognizer) said about it.
+    pub note: Option<FrameNote>,
+}
+
+impl std::fmt::Display for BacktraceFrame {
+    /// Writes e.g. `#1   0x0000000000401196 in main+0x40 at main.c:12 (/tmp/app)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:<3} {:#018x}", self.index, self.location.addr)?;
+        if let Some(place) = self.location.place() {
+            write!(f, " in {place}")?;
+        }
+        if let Some(module) = self.location.module() {
+            write!(f, " ({module})")?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " [{note}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// The call stack of a thread, followed through frame pointers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Backtrace {
+    /// Innermost first.
+    pub frames: Vec<BacktraceFrame>,
+}
+
+impl Backtrace {
+    /// Walks the stack of the thread with registers `regs`, at most `limit` frames deep.
+    pub fn capture(
+        memory: &impl ReadMemory,
+        annotator: Annotator<'_>,
+        regs: &user_regs_struct,
+        limit: usize,
+    ) -> Self {
+        Self::from_pcs(
+            annotator,
+            &Self::walk(memory, annotator.maps(), regs, limit),
+        )
+    }
+
+    /// Annotates the program counters of frames found by [`Self::walk`], and asks the
+    /// registered frame recognizers about them.
+    pub fn from_pcs(annotator: Annotator<'_>, pcs: &[u64]) -> Self {
+        let frames = pcs
+            .iter()
+            .enumerate()
+            .map(|(index, &pc)| {
+                let mut frame = BacktraceFrame {
+                    index,
+                    location: annotator.annotate(pc),
+                    note: None,
+                };
+                frame.note = recognize(&frame);
+                frame
+            })
+            .collect();
+        Self { frames }
+    }
+
+    /// Returns the program counters of at most `limit` frames of the thread with registers
+    /// `regs`, innermost first, without annotating them. Stacks can be compared this way
+    /// cheaply, e.g. to group threads.
+    pub fn walk(
+        memory: &impl ReadMemory,
+        maps: &[MemoryRegion],
+        regs: &user_regs_struct,
+        limit: usize,
+    ) -> Vec<u64> {
+        let frames = frame_pointer_chain(memory, regs, limit);
+        let mut pcs: Vec<_> = frames.iter().map(|frame| frame.pc).collect();
+        match frames.last() {
+            // The outermost caller may not keep a frame pointer, but its return address is
+            // still valid.
+            Some(outermost) if frames.len() < limit => {
+                let return_address = memory
+                    .read_words(outermost.return_address_slot(), 1)
+                    .ok()
+                    .and_then(|words| words.first().copied())
+                    .filter(|&addr| find_region(maps, addr).is_some_and(|region| region.execute));
+                pcs.extend(return_address);
+            }
+            // `rbp` is not a frame pointer here; at least show where we are.
+            None if limit > 0 => pcs.push(regs.rip),
+            _ => {}
+        }
+        pcs
+    }
+}
+
+impl std::fmt::Display for Backtrace {
+    /// Writes a frame per line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, frame) in self.frames.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{frame}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_frames() {
+        let maps: Vec<_> =
+            MemoryRegion::parse("00401000-00402000 r-xp 00001000 08:01 42 /nonexistent/app")
+                .into_iter()
+                .collect();
+        let backtrace = Backtrace::from_pcs(Annotator::new(&maps), &[0x40_1136, 0xdead_0000]);
+        assert_eq!(
+            backtrace.to_string(),
+            "#0   0x0000000000401136 (/nonexistent/app)\n#1   0x00000000dead0000"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::error::Result;
+use crate::process::ReadMemory;
+use serde::Serialize;
+
+/// Bytes shown per line of a [`MemoryDump`].
+const BYTES_PER_LINE: usize = 16;
+
+/// Bytes read from inferior memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct MemoryDump {
+    /// Address of the first byte.
+    pub addr: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl MemoryDump {
+    /// Reads up to `len` bytes at `addr`, stopping early at unreadable memory.
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte could be read.
+    pub fn read(memory: &impl ReadMemory, addr: u64, len: usize) -> Result<Self> {
+        Ok(Self {
+            addr,
+            bytes: memory.read_memory(addr, len)?,
+        })
+    }
+}
+
+impl std::fmt::Display for MemoryDump {
+    /// Writes a hex dump, e.g. `0x7ffd0000: 68 65 6c 6c 6f 00 ...  |hello.|`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, chunk) in self.bytes.chunks(BYTES_PER_LINE).enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:#x}:", self.addr + (index * BYTES_PER_LINE) as u64)?;
+            for byte in chunk {
+                write!(f, " {byte:02x}")?;
+            }
+            let padding = 3 * (BYTES_PER_LINE - chunk.len());
+            let text: String = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        char::from(byte)
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            write!(f, "{:padding$}  |{text}|", "")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_hex_dump() {
+        let dump = MemoryDump {
+            addr: 0x1000,
+            bytes: b"hello, world!\n\x00\x01\xffAB".to_vec(),
+        };
+        assert_eq!(
+            dump.to_string(),
+            "0x1000: 68 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 01  |hello, world!...|\n\
+             0x1010: ff 41 42                                         |.AB|"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! Structured results of inspecting a stopped inferior.
+//!
+//! Each type implements `Display`, giving the text the CLI prints, and `Serialize`, so other
+//! front ends and tests can work with the data instead of parsing that text.
+
+mod backtrace;
+mod memory;
+mod registers;
+mod stop;
+
+pub use backtrace::{Backtrace, BacktraceFrame};
+pub use memory::MemoryDump;
+pub use registers::{Register, RegisterDump};
+pub use stop::{StopEvent, WatchAccess};
+
+use std::time::Duration;
+
+/// Formats `duration` in whole seconds, or in milliseconds if it has a fraction of a second,
+/// e.g. `10s` or `1500ms`.
+pub fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
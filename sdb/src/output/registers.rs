@@ -0,0 +1,83 @@
+use crate::process::register_values;
+use nix::libc::user_regs_struct;
+use serde::Serialize;
+
+/// A general purpose register and its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Register {
+    pub name: &'static str,
+    pub value: u64,
+}
+
+impl std::fmt::Display for Register {
+    /// Writes e.g. `rax    0x0000000000000001`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<7}{:#018x}", self.name, self.value)
+    }
+}
+
+/// The general purpose registers of a thread.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct RegisterDump {
+    /// In `user_regs_struct` order, `rax` first.
+    pub registers: Vec<Register>,
+}
+
+impl RegisterDump {
+    pub fn new(regs: &user_regs_struct) -> Self {
+        let registers = register_values(regs)
+            .into_iter()
+            .map(|(name, value)| Register { name, value })
+            .collect();
+        Self { registers }
+    }
+
+    /// Keeps only the registers named in `names`.
+    #[must_use]
+    pub fn only(mut self, names: &[&str]) -> Self {
+        self.registers
+            .retain(|register| names.contains(&register.name));
+        self
+    }
+
+    /// Returns the value of the register `name`.
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.registers
+            .iter()
+            .find(|register| register.name == name)
+            .map(|register| register.value)
+    }
+}
+
+impl std::fmt::Display for RegisterDump {
+    /// Writes a register per line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, register) in self.registers.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{register}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_select_registers() {
+        // SAFETY: `user_regs_struct` is plain integers, valid when zeroed.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = 0x40_1136;
+        regs.rsp = 0x7ffd_0000;
+        let dump = RegisterDump::new(&regs).only(&["rsp", "rip"]);
+        assert_eq!(dump.get("rip"), Some(0x40_1136));
+        assert_eq!(dump.get("rax"), None);
+        assert_eq!(
+            dump.to_string(),
+            "rsp    0x000000007ffd0000\nrip    0x0000000000401136"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use super::{format_duration, Backtrace};
+use crate::annotate::{Annotation, Annotator};
+use crate::catchpoint::RssCrossing;
+use crate::process::{Backend, ExitStatus, Process, SignalInfo};
+use crate::procfs::{read_maps, MemoryRegion};
+use crate::rendezvous::ModuleEvent;
+use crate::sanitizer::SanitizerReport;
+use crate::stack::{detect_stack_overflow, StackOverflow};
+use crate::syscall::{describe_with_limit, SyscallDirection};
+use crate::watchpoint::{RangeWatchKind, WatchKind, WatchpointHit};
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use serde::Serialize;
+use std::time::Duration;
+
+/// What a watchpoint saw.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "access", rename_all = "kebab-case")]
+pub enum WatchAccess {
+    /// An instruction is about to write to a watched region at `addr`.
+    Write { addr: u64, at: Option<Annotation> },
+    /// Watched code is about to run.
+    Execute { at: Annotation },
+    /// A watched value changed.
+    Change { old: u64, new: u64 },
+}
+
+/// Why the inferior stopped, as reported after `continue` or `run`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum StopEvent {
+    /// The process is gone.
+    Exited { status: ExitStatus },
+    /// A syscall catchpoint stopped at a syscall.
+    Syscall {
+        catchpoints: Vec<usize>,
+        direction: SyscallDirection,
+        /// The call with its decoded arguments.
+        description: String,
+    },
+    /// The process ran for longer than allowed and was interrupted.
+    Interrupted {
+        after: Duration,
+        backtrace: Backtrace,
+    },
+    /// An RSS catchpoint saw the resident memory grow past its threshold.
+    Rss {
+        catchpoints: Vec<usize>,
+        crossing: RssCrossing,
+    },
+    /// A sanitizer catchpoint stopped at a report.
+    Sanitizer {
+        catchpoints: Vec<usize>,
+        report: SanitizerReport,
+        /// The code that made the bad access.
+        by: Option<Annotation>,
+    },
+    /// A load or unload catchpoint saw modules come or go.
+    Modules {
+        catchpoints: Vec<usize>,
+        events: Vec<ModuleEvent>,
+    },
+    /// A watchpoint or range watch triggered.
+    Watchpoint {
+        id: usize,
+        description: String,
+        #[serde(flatten)]
+        access: WatchAccess,
+    },
+    /// A fault such as `SIGSEGV`.
+    Fault {
+        info: SignalInfo,
+        /// The address that could not be accessed.
+        fault: Option<Annotation>,
+        /// Set if the fault hit the guard area below a stack.
+        overflow: Option<StackOverflow>,
+        pc: Option<Annotation>,
+        /// The main thread, the only one traced and so the only one whose faults stop.
+        tid: i32,
+    },
+    /// Any other signal.
+    Signal { signal: &'static str },
+    /// Any other stop, as reported by `waitpid`.
+    Other { status: String },
+}
+
+impl StopEvent {
+    /// Describes the current stop of `process`, decoding at most `max_buffer_len` bytes of
+    /// syscall buffers and `backtrace_limit` frames of an interrupted thread.
+    pub fn capture(
+        process: &Process<impl Backend>,
+        max_buffer_len: usize,
+        backtrace_limit: usize,
+    ) -> Self {
+        if let Some(status) = process.exit_status() {
+            return Self::Exited { status };
+        }
+        let maps = read_maps(process.pid).unwrap_or_default();
+        let annotator = |maps| Annotator::new(maps).with_jit(process.jit_objects());
+        let catchpoints = || {
+            process
+                .hit_catchpoints()
+                .iter()
+                .map(|catchpoint| catchpoint.id)
+                .collect()
+        };
+        if let Some(event) = &process.last_syscall {
+            return Self::Syscall {
+                catchpoints: catchpoints(),
+                direction: event.direction,
+                description: describe_with_limit(event, process, max_buffer_len),
+            };
+        }
+        if let Some(after) = process.last_watchdog {
+            let backtrace = process.read_registers().map_or_else(
+                |_| Backtrace::default(),
+                |regs| Backtrace::capture(process, annotator(&maps), &regs, backtrace_limit),
+            );
+            return Self::Interrupted { after, backtrace };
+        }
+        if let Some(crossing) = process.last_rss {
+            return Self::Rss {
+                catchpoints: catchpoints(),
+                crossing,
+            };
+        }
+        if let Some(report) = &process.last_sanitizer_report {
+            return Self::Sanitizer {
+                catchpoints: catchpoints(),
+                report: report.clone(),
+                by: report.pc.map(|pc| annotator(&maps).annotate(pc)),
+            };
+        }
+        if !process.last_modules.is_empty() {
+            return Self::Modules {
+                catchpoints: catchpoints(),
+                events: process.last_modules.clone(),
+            };
+        }
+        if let Some(hit) = process.last_watchpoint {
+            return watchpoint_stop(process, &maps, hit);
+        }
+        signal_stop(process, &maps)
+    }
+}
+
+/// Describes a stop at the watchpoint or range watch `hit`.
+fn watchpoint_stop(
+    process: &Process<impl Backend>,
+    maps: &[MemoryRegion],
+    hit: WatchpointHit,
+) -> StopEvent {
+    let annotator = Annotator::new(maps).with_jit(process.jit_objects());
+    let pc = process.read_registers().ok().map(|regs| regs.rip);
+    let watchpoint = process
+        .watchpoints()
+        .iter()
+        .find(|watchpoint| watchpoint.id == hit.id);
+    let range_watch = process
+        .range_watches()
+        .iter()
+        .find(|watch| watch.id == hit.id);
+    let description = watchpoint.map_or_else(
+        || range_watch.map_or_else(String::new, |watch| watch.description.clone()),
+        |watchpoint| watchpoint.description.clone(),
+    );
+    let access = match (watchpoint, range_watch) {
+        (None, Some(watch)) if watch.kind == RangeWatchKind::Write => WatchAccess::Write {
+            addr: hit.new,
+            at: pc.map(|pc| annotator.annotate(pc)),
+        },
+        (Some(watchpoint), _) if watchpoint.kind != WatchKind::Execute => WatchAccess::Change {
+            old: hit.old,
+            new: hit.new,
+        },
+        _ => WatchAccess::Execute {
+            at: annotator.annotate(pc.unwrap_or(hit.new)),
+        },
+    };
+    StopEvent::Watchpoint {
+        id: hit.id,
+        description,
+        access,
+    }
+}
+
+/// Describes a stop with a signal, or any other stop.
+fn signal_stop(process: &Process<impl Backend>, maps: &[MemoryRegion]) -> StopEvent {
+    let WaitStatus::Stopped(_, signal) = process.state else {
+        return StopEvent::Other {
+            status: format!("{:?}", process.state),
+        };
+    };
+    let info = match process.signal_info() {
+        Ok(info) if info.is_fault() => info,
+        _ => {
+            return StopEvent::Signal {
+                signal: signal.as_str(),
+            }
+        }
+    };
+    let annotator = Annotator::new(maps).with_jit(process.jit_objects());
+    let regs = process.read_registers().ok();
+    let overflow = regs.as_ref().and_then(|regs| {
+        if info.signal() != Some(Signal::SIGSEGV) {
+            return None;
+        }
+        detect_stack_overflow(maps, info.fault_addr?, regs.rsp)
+    });
+    StopEvent::Fault {
+        info,
+        fault: info.fault_addr.map(|addr| annotator.annotate(addr)),
+        overflow,
+        pc: regs.map(|regs| annotator.annotate(regs.rip)),
+        tid: process.pid.as_raw(),
+    }
+}
+
+impl std::fmt::Display for StopEvent {
+    /// Writes e.g. `stopped at watchpoint 1 (counter): 0x0 -> 0x1`, on several lines for
+    /// faults, module events and interrupts.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids = |catchpoints: &[usize]| {
+            catchpoints
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Self::Exited { status } => write!(f, "{status}"),
+            Self::Syscall {
+                catchpoints,
+                direction,
+                description,
+            } => {
+                let direction = match direction {
+                    SyscallDirection::Entry => "entry",
+                    SyscallDirection::Exit => "exit",
+                };
+                write!(
+                    f,
+                    "stopped at syscall {direction} (catchpoint {}): {description}",
+                    ids(catchpoints)
+                )
+            }
+            Self::Interrupted { after, backtrace } => {
+                write!(
+                    f,
+                    "interrupted after running for {}:",
+                    format_duration(*after)
+                )?;
+                if !backtrace.frames.is_empty() {
+                    write!(f, "\n{backtrace}")?;
+                }
+                Ok(())
+            }
+            Self::Rss {
+                catchpoints,
+                crossing,
+            } => write!(f, "stopped by catchpoint {}: {crossing}", ids(catchpoints)),
+            Self::Sanitizer {
+                catchpoints,
+                report,
+                by,
+            } => {
+                write!(f, "stopped by catchpoint {}: {report}", ids(catchpoints))?;
+                if let Some(by) = by {
+                    write!(f, " by {by}")?;
+                }
+                Ok(())
+            }
+            Self::Modules {
+                catchpoints,
+                events,
+            } => {
+                write!(f, "stopped by catchpoint {}:", ids(catchpoints))?;
+                events.iter().try_for_each(|event| write!(f, "\n  {event}"))
+            }
+            Self::Watchpoint {
+                id,
+                description,
+                access,
+            } => {
+                write!(f, "stopped at watchpoint {id} ({description}): ")?;
+                match access {
+                    WatchAccess::Write { addr, at } => {
+                        write!(f, "write to {addr:#x}")?;
+                        if let Some(at) = at {
+                            write!(f, " at {at}")?;
+                        }
+                        Ok(())
+                    }
+                    WatchAccess::Execute { at } => write!(f, "at {at}"),
+                    WatchAccess::Change { old, new } => write!(f, "{old:#x} -> {new:#x}"),
+                }
+            }
+            Self::Fault {
+                info,
+                fault,
+                overflow,
+                pc,
+                tid,
+            } => {
+                write!(f, "stopped with signal {info}")?;
+                if let Some(fault) = fault {
+                    write!(f, "\n  fault address {fault}")?;
+                }
+                if let Some(overflow) = overflow {
+                    write!(
+                        f,
+                        "\n  likely stack overflow in the main thread ({tid}): {overflow}"
+                    )?;
+                }
+                if let Some(pc) = pc {
+                    write!(f, "\n  pc {pc}")?;
+                }
+                Ok(())
+            }
+            Self::Signal { signal } => write!(f, "stopped with signal {signal}"),
+            Self::Other { status } => write!(f, "{status}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{ScriptedBackend, ScriptedStop};
+    use nix::libc::{self, user_regs_struct};
+    use nix::unistd::Pid;
+
+    #[test]
+    fn should_describe_stops() {
+        let stop = StopEvent::Watchpoint {
+            id: 2,
+            description: "counter".into(),
+            access: WatchAccess::Change { old: 0, new: 1 },
+        };
+        assert_eq!(
+            stop.to_string(),
+            "stopped at watchpoint 2 (counter): 0x0 -> 0x1"
+        );
+        let stop = StopEvent::Rss {
+            catchpoints: vec![1, 3],
+            crossing: RssCrossing {
+                before: 510 << 20,
+                after: 530 << 20,
+            },
+        };
+        assert_eq!(
+            stop.to_string(),
+            "stopped by catchpoint 1, 3: rss 510 MiB -> 530 MiB"
+        );
+    }
+
+    #[test]
+    fn should_classify_faults() -> crate::error::Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = 0x40_1136;
+        let backend = ScriptedBackend::new(regs);
+        let mut stop = ScriptedStop::new(WaitStatus::Stopped(pid, Signal::SIGSEGV));
+        stop.signal = Some(SignalInfo {
+            signo: libc::SIGSEGV,
+            code: 1,
+            fault_addr: Some(0),
+        });
+        backend.push_stop(stop);
+        let mut process =
+            Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+        process.resume()?;
+
+        let StopEvent::Fault {
+            info, overflow, pc, ..
+        } = StopEvent::capture(&process, 0, 0)
+        else {
+            panic!("not a fault");
+        };
+        assert_eq!(info.fault_addr, Some(0));
+        assert_eq!(overflow, None);
+        assert_eq!(pc.map(|pc| pc.addr), Some(0x40_1136));
+        Ok(())
+    }
+}
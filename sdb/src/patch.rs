@@ -0,0 +1,26 @@
+//! Code patches written into the inferior, remembered so they can be reverted.
+
+/// Bytes overwritten by `patch`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Patch {
+    /// Number shown to the user.
+    pub id: usize,
+    /// Start address.
+    pub addr: u64,
+    /// Bytes that were there before.
+    pub original: Vec<u8>,
+    /// Bytes written.
+    pub bytes: Vec<u8>,
+}
+
+impl Patch {
+    /// Returns the first address after the patch.
+    pub const fn end(&self) -> u64 {
+        self.addr + self.bytes.len() as u64
+    }
+
+    /// Returns `true` if both patches write to a common byte.
+    pub const fn overlaps(&self, other: &Self) -> bool {
+        self.addr < other.end() && other.addr < self.end()
+    }
+}
@@ -0,0 +1,201 @@
+//! `perf_event_open` plumbing shared by the hardware branch recorders.
+
+use crate::error::{PerfEventControlSnafu, PerfEventOpenSnafu, Result};
+use nix::errno::Errno;
+use nix::libc::{self, c_void};
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::unistd::Pid;
+use snafu::ResultExt as _;
+use std::num::NonZeroUsize;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
+use std::sync::atomic::{fence, Ordering};
+
+pub(crate) const PAGE_SIZE: usize = 0x1000;
+/// `perf_event_attr` flags: `disabled`, `exclude_kernel`, `exclude_hv` and `write_backward`.
+pub(crate) const ATTR_DISABLED: u64 = 1;
+pub(crate) const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+pub(crate) const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+pub(crate) const ATTR_WRITE_BACKWARD: u64 = 1 << 27;
+/// Offsets in `struct perf_event_mmap_page`.
+pub(crate) const DATA_HEAD_OFFSET: usize = 1024;
+/// `PERF_FLAG_FD_CLOEXEC`.
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+/// `PERF_EVENT_IOC_ENABLE` and `PERF_EVENT_IOC_DISABLE`.
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+
+/// `struct perf_event_attr` up to `PERF_ATTR_SIZE_VER5`.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub(crate) struct PerfEventAttr {
+    pub(crate) kind: u32,
+    pub(crate) size: u32,
+    pub(crate) config: u64,
+    pub(crate) sample_period: u64,
+    pub(crate) sample_type: u64,
+    pub(crate) read_format: u64,
+    pub(crate) flags: u64,
+    pub(crate) wakeup_events: u32,
+    pub(crate) bp_type: u32,
+    pub(crate) config1: u64,
+    pub(crate) config2: u64,
+    pub(crate) branch_sample_type: u64,
+    pub(crate) sample_regs_user: u64,
+    pub(crate) sample_stack_user: u32,
+    pub(crate) clockid: i32,
+    pub(crate) sample_regs_intr: u64,
+    pub(crate) aux_watermark: u32,
+    pub(crate) sample_max_stack: u16,
+    pub(crate) reserved: u16,
+}
+
+const _: () = assert!(size_of::<PerfEventAttr>() == 112);
+
+/// An open perf event counting one thread.
+#[derive(Debug)]
+pub(crate) struct PerfEvent {
+    fd: OwnedFd,
+    enabled: bool,
+}
+
+impl PerfEvent {
+    /// Opens the event `attr` for the thread `tid`, on any CPU. `name` says what the event is
+    /// for in errors.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel or CPU does not support the event, or the thread may
+    /// not be traced.
+    pub(crate) fn open(mut attr: PerfEventAttr, tid: Pid, name: &str) -> Result<Self> {
+        attr.size = size_of::<PerfEventAttr>() as u32;
+        let enabled = attr.flags & ATTR_DISABLED == 0;
+        // SAFETY: `attr` is a valid `perf_event_attr` of the size it states.
+        let fd = Errno::result(unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &raw const attr,
+                tid.as_raw(),
+                -1,
+                -1,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        })
+        .context(PerfEventOpenSnafu { event: name })?;
+        // SAFETY: `perf_event_open` returned a new descriptor that nothing else owns.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        Ok(Self { fd, enabled })
+    }
+
+    /// Maps `len` bytes of the event's buffers at `offset`: the metadata page and data pages
+    /// at 0, or the AUX area where the metadata page says.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel refuses the mapping.
+    pub(crate) fn map(&self, offset: usize, len: usize, prot: ProtFlags) -> Result<Mapping> {
+        let map_error = PerfEventControlSnafu {
+            action: "map the buffer of",
+        };
+        let length = NonZeroUsize::new(len)
+            .ok_or(Errno::EINVAL)
+            .context(map_error)?;
+        // SAFETY: A fresh shared mapping of a perf buffer does not alias any Rust object.
+        let ptr = unsafe {
+            mmap(
+                None,
+                length,
+                prot,
+                MapFlags::MAP_SHARED,
+                &self.fd,
+                offset as libc::off_t,
+            )
+        }
+        .context(map_error)?;
+        Ok(Mapping { ptr, len })
+    }
+
+    /// Returns `true` if the event is counting.
+    pub(crate) const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts or resumes counting.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel refuses.
+    pub(crate) fn enable(&mut self) -> Result<()> {
+        self.control(PERF_EVENT_IOC_ENABLE, "enable")?;
+        self.enabled = true;
+        Ok(())
+    }
+
+    /// Pauses counting, keeping what was recorded.
+    ///
+    /// # Errors
+    /// Returns an error if the kernel refuses.
+    pub(crate) fn disable(&mut self) -> Result<()> {
+        self.control(PERF_EVENT_IOC_DISABLE, "disable")?;
+        self.enabled = false;
+        Ok(())
+    }
+
+    fn control(&self, request: libc::c_ulong, action: &str) -> Result<()> {
+        // SAFETY: The perf ioctls used take no argument.
+        Errno::result(unsafe { libc::ioctl(self.fd.as_raw_fd(), request, 0) })
+            .context(PerfEventControlSnafu { action })?;
+        Ok(())
+    }
+}
+
+/// A shared mapping of a perf buffer, unmapped on drop.
+#[derive(Debug)]
+pub(crate) struct Mapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    /// Returns the mapped bytes, which the kernel may change while the event is enabled.
+    pub(crate) const fn bytes(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` readable bytes mapped for the life of `self`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().cast(), self.len) }
+    }
+
+    /// Reads the `u64` field at `offset` of a metadata page, which the kernel updates
+    /// atomically.
+    pub(crate) fn read_u64(&self, offset: usize) -> u64 {
+        assert!(offset + 8 <= self.len);
+        // SAFETY: Checked above to be in the mapping, and header fields are aligned.
+        let value = unsafe {
+            self.ptr
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset)
+                .cast::<u64>()
+                .read_volatile()
+        };
+        fence(Ordering::Acquire);
+        value
+    }
+
+    /// Writes the `u64` field at `offset` of a writable metadata page.
+    #[cfg(feature = "intel-pt")]
+    pub(crate) fn write_u64(&self, offset: usize, value: u64) {
+        assert!(offset + 8 <= self.len);
+        // SAFETY: Checked above to be in the mapping, and header fields are aligned.
+        unsafe {
+            self.ptr
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset)
+                .cast::<u64>()
+                .write_volatile(value);
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: The mapping is only borrowed through `self`, so nothing uses it anymore.
+        let _ = unsafe { munmap(self.ptr, self.len) };
+    }
+}
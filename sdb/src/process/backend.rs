@@ -0,0 +1,539 @@
+//! The system calls a [`Process`](super::Process) is controlled through.
+//!
+//! [`Ptrace`] makes them for real. [`ScriptedBackend`] plays back a script of stops against
+//! simulated registers and memory, so that the bookkeeping built on top, such as watchpoints,
+//! stepping and classifying stops, can be tested without a process to trace.
+
+use super::pidfd;
+use super::ptrace_log::logged;
+use super::siginfo::SignalInfo;
+use nix::errno::Errno;
+use nix::libc::user_regs_struct;
+use nix::sys::ptrace::{self, regset::NT_PRSTATUS, AddressType, Options};
+use nix::sys::signal::{kill, Signal};
+use nix::sys::uio::{process_vm_readv, RemoteIoVec};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::IoSliceMut;
+use std::os::fd::{BorrowedFd, OwnedFd};
+
+/// Granularity of a [`ScriptedBackend`]'s memory.
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Requests made to a traced process. Each corresponds to one system call, and fails with
+/// the `errno` it would set.
+#[allow(
+    clippy::missing_errors_doc,
+    reason = "Every request fails with the `errno` of its system call."
+)]
+pub trait Backend {
+    /// Resumes `pid`, delivering `signal` if any (`PTRACE_CONT`).
+    fn cont(&self, pid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    /// Resumes `pid` until the next syscall entry or exit, delivering `signal` if any
+    /// (`PTRACE_SYSCALL`).
+    fn syscall(&self, pid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    /// Executes one instruction of `pid` (`PTRACE_SINGLESTEP`).
+    fn step(&self, pid: Pid) -> nix::Result<()>;
+    /// Stops tracing `pid` (`PTRACE_DETACH`).
+    fn detach(&self, pid: Pid) -> nix::Result<()>;
+    /// `PTRACE_SETOPTIONS`.
+    fn set_options(&self, pid: Pid, options: Options) -> nix::Result<()>;
+    /// Traces `pid` without stopping it (`PTRACE_SEIZE`).
+    fn seize(&self, pid: Pid) -> nix::Result<()>;
+    /// Stops the seized `pid` (`PTRACE_INTERRUPT`).
+    fn interrupt(&self, pid: Pid) -> nix::Result<()>;
+    /// Waits for a state change of `pid` (`waitpid`).
+    fn wait(&self, pid: Pid, flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus>;
+    /// Sends `signal` to `pid` (`kill`).
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()>;
+    /// Opens a descriptor referring to `pid` for as long as it lives, even if the pid is
+    /// reused afterwards (`pidfd_open`).
+    fn pidfd_open(&self, pid: Pid) -> nix::Result<OwnedFd>;
+    /// Waits for a state change of `pid` through its `pidfd` (`waitid(P_PIDFD)`).
+    fn wait_pidfd(
+        &self,
+        pid: Pid,
+        pidfd: BorrowedFd<'_>,
+        flags: Option<WaitPidFlag>,
+    ) -> nix::Result<WaitStatus>;
+    /// Sends `signal` to `pid` through its `pidfd` (`pidfd_send_signal`).
+    fn pidfd_send_signal(&self, pid: Pid, pidfd: BorrowedFd<'_>, signal: Signal)
+        -> nix::Result<()>;
+    /// Reads the general purpose registers (`PTRACE_GETREGSET`).
+    fn registers(&self, pid: Pid) -> nix::Result<user_regs_struct>;
+    /// Writes the general purpose registers (`PTRACE_SETREGSET`).
+    fn set_registers(&self, pid: Pid, regs: user_regs_struct) -> nix::Result<()>;
+    /// Fetches the signal `pid` is stopped with (`PTRACE_GETSIGINFO`).
+    fn signal_info(&self, pid: Pid) -> nix::Result<SignalInfo>;
+    /// Reads the word at `addr` (`PTRACE_PEEKDATA`).
+    fn peek_data(&self, pid: Pid, addr: u64) -> nix::Result<i64>;
+    /// Writes the word at `addr`, even in read-only mappings (`PTRACE_POKEDATA`).
+    fn poke_data(&self, pid: Pid, addr: u64, word: i64) -> nix::Result<()>;
+    /// Reads the word at `offset` in the `user` area (`PTRACE_PEEKUSER`).
+    fn peek_user(&self, pid: Pid, offset: usize) -> nix::Result<i64>;
+    /// Writes the word at `offset` in the `user` area (`PTRACE_POKEUSER`).
+    fn poke_user(&self, pid: Pid, offset: usize, word: i64) -> nix::Result<()>;
+    /// Copies the `remote` ranges into the `local` buffers, stopping at the first unreadable
+    /// byte (`process_vm_readv`).
+    ///
+    /// # Returns
+    /// The number of bytes copied.
+    fn read_memory(
+        &self,
+        pid: Pid,
+        local: &mut [IoSliceMut<'_>],
+        remote: &[RemoteIoVec],
+    ) -> nix::Result<usize>;
+}
+
+/// The real thing. Requests are logged when [`ptrace_logging`](super::ptrace_logging) is on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Ptrace;
+
+impl Backend for Ptrace {
+    fn cont(&self, pid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        logged("PTRACE_CONT", pid, ptrace::cont(pid, signal))
+    }
+
+    fn syscall(&self, pid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        logged("PTRACE_SYSCALL", pid, ptrace::syscall(pid, signal))
+    }
+
+    fn step(&self, pid: Pid) -> nix::Result<()> {
+        logged("PTRACE_SINGLESTEP", pid, ptrace::step(pid, None))
+    }
+
+    fn detach(&self, pid: Pid) -> nix::Result<()> {
+        logged("PTRACE_DETACH", pid, ptrace::detach(pid, None))
+    }
+
+    fn set_options(&self, pid: Pid, options: Options) -> nix::Result<()> {
+        logged("PTRACE_SETOPTIONS", pid, ptrace::setoptions(pid, options))
+    }
+
+    fn seize(&self, pid: Pid) -> nix::Result<()> {
+        logged("PTRACE_SEIZE", pid, ptrace::seize(pid, Options::empty()))
+    }
+
+    fn interrupt(&self, pid: Pid) -> nix::Result<()> {
+        logged("PTRACE_INTERRUPT", pid, ptrace::interrupt(pid))
+    }
+
+    fn wait(&self, pid: Pid, flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus> {
+        let request = match flags {
+            Some(WaitPidFlag::WNOHANG) => "waitpid(WNOHANG)",
+            Some(WaitPidFlag::__WALL) => "waitpid(__WALL)",
+            _ => "waitpid",
+        };
+        logged(request, pid, waitpid(pid, flags))
+    }
+
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()> {
+        let request = match signal {
+            Signal::SIGSTOP => "kill(SIGSTOP)",
+            Signal::SIGCONT => "kill(SIGCONT)",
+            Signal::SIGKILL => "kill(SIGKILL)",
+            _ => "kill",
+        };
+        logged(request, pid, kill(pid, signal))
+    }
+
+    fn pidfd_open(&self, pid: Pid) -> nix::Result<OwnedFd> {
+        logged("pidfd_open", pid, pidfd::open(pid))
+    }
+
+    fn wait_pidfd(
+        &self,
+        pid: Pid,
+        pidfd: BorrowedFd<'_>,
+        flags: Option<WaitPidFlag>,
+    ) -> nix::Result<WaitStatus> {
+        let request = match flags {
+            Some(WaitPidFlag::WNOHANG) => "waitid(P_PIDFD, WNOHANG)",
+            _ => "waitid(P_PIDFD)",
+        };
+        logged(request, pid, pidfd::wait(pidfd, flags))
+    }
+
+    fn pidfd_send_signal(
+        &self,
+        pid: Pid,
+        pidfd: BorrowedFd<'_>,
+        signal: Signal,
+    ) -> nix::Result<()> {
+        logged("pidfd_send_signal", pid, pidfd::send_signal(pidfd, signal))
+    }
+
+    fn registers(&self, pid: Pid) -> nix::Result<user_regs_struct> {
+        logged(
+            "PTRACE_GETREGSET",
+            pid,
+            ptrace::getregset::<NT_PRSTATUS>(pid),
+        )
+    }
+
+    fn set_registers(&self, pid: Pid, regs: user_regs_struct) -> nix::Result<()> {
+        logged(
+            "PTRACE_SETREGSET",
+            pid,
+            ptrace::setregset::<NT_PRSTATUS>(pid, regs),
+        )
+    }
+
+    fn signal_info(&self, pid: Pid) -> nix::Result<SignalInfo> {
+        logged("PTRACE_GETSIGINFO", pid, ptrace::getsiginfo(pid))
+            .map(|info| SignalInfo::from_siginfo(&info))
+    }
+
+    fn peek_data(&self, pid: Pid, addr: u64) -> nix::Result<i64> {
+        logged(
+            "PTRACE_PEEKDATA",
+            pid,
+            ptrace::read(pid, addr as AddressType),
+        )
+    }
+
+    fn poke_data(&self, pid: Pid, addr: u64, word: i64) -> nix::Result<()> {
+        logged(
+            "PTRACE_POKEDATA",
+            pid,
+            ptrace::write(pid, addr as AddressType, word),
+        )
+    }
+
+    fn peek_user(&self, pid: Pid, offset: usize) -> nix::Result<i64> {
+        logged(
+            "PTRACE_PEEKUSER",
+            pid,
+            ptrace::read_user(pid, offset as AddressType),
+        )
+    }
+
+    fn poke_user(&self, pid: Pid, offset: usize, word: i64) -> nix::Result<()> {
+        logged(
+            "PTRACE_POKEUSER",
+            pid,
+            ptrace::write_user(pid, offset as AddressType, word),
+        )
+    }
+
+    fn read_memory(
+        &self,
+        pid: Pid,
+        local: &mut [IoSliceMut<'_>],
+        remote: &[RemoteIoVec],
+    ) -> nix::Result<usize> {
+        process_vm_readv(pid, local, remote)
+    }
+}
+
+/// A stop played back by a [`ScriptedBackend`], with the changes the process made while
+/// running up to it.
+#[derive(Clone, Debug)]
+pub struct ScriptedStop {
+    /// What `waitpid` reports.
+    pub status: WaitStatus,
+    /// Registers at the stop, if they changed.
+    pub registers: Option<user_regs_struct>,
+    /// Signal reported by `PTRACE_GETSIGINFO` from this stop on.
+    pub signal: Option<SignalInfo>,
+    /// Words of the `user` area written, such as the debug status register, by offset.
+    pub user: Vec<(usize, u64)>,
+    /// Memory written, by address.
+    pub memory: Vec<(u64, Vec<u8>)>,
+}
+
+impl ScriptedStop {
+    /// A stop that changes nothing but the state.
+    pub const fn new(status: WaitStatus) -> Self {
+        Self {
+            status,
+            registers: None,
+            signal: None,
+            user: Vec::new(),
+            memory: Vec::new(),
+        }
+    }
+}
+
+/// A [`Backend`] without a process: each resume or step moves on to the next scripted stop.
+///
+/// Only mapped pages can be read or written, and `waitpid` fails with `ECHILD` once the
+/// script has run out. Every request is recorded, see [`Self::requests`].
+///
+/// # Example
+/// ```
+/// use nix::sys::signal::Signal;
+/// use nix::sys::wait::WaitStatus;
+/// use nix::unistd::Pid;
+/// use sdb::process::{Process, ScriptedBackend, ScriptedStop};
+///
+/// let pid = Pid::from_raw(100);
+/// // SAFETY: `user_regs_struct` is plain integers.
+/// let backend = ScriptedBackend::new(unsafe { std::mem::zeroed() });
+/// backend.push_stop(ScriptedStop::new(WaitStatus::Exited(pid, 3)));
+/// let mut process = Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+/// process.resume()?;
+/// assert_eq!(process.exit_status().and_then(|status| status.code), Some(3));
+/// assert_eq!(process.backend().requests(), ["PTRACE_CONT", "waitpid"]);
+/// # Ok::<(), sdb::error::SdbError>(())
+/// ```
+#[derive(Debug)]
+pub struct ScriptedBackend {
+    stops: RefCell<VecDeque<ScriptedStop>>,
+    registers: Cell<user_regs_struct>,
+    signal: Cell<Option<SignalInfo>>,
+    user: RefCell<BTreeMap<usize, u64>>,
+    /// Mapped pages by address.
+    memory: RefCell<BTreeMap<u64, Vec<u8>>>,
+    requests: RefCell<Vec<&'static str>>,
+    delivered: RefCell<Vec<Signal>>,
+}
+
+impl ScriptedBackend {
+    /// A process stopped with `registers`, and no memory mapped.
+    pub const fn new(registers: user_regs_struct) -> Self {
+        Self {
+            stops: RefCell::new(VecDeque::new()),
+            registers: Cell::new(registers),
+            signal: Cell::new(None),
+            user: RefCell::new(BTreeMap::new()),
+            memory: RefCell::new(BTreeMap::new()),
+            requests: RefCell::new(Vec::new()),
+            delivered: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Appends `stop` to the script.
+    pub fn push_stop(&self, stop: ScriptedStop) {
+        self.stops.borrow_mut().push_back(stop);
+    }
+
+    /// Writes `bytes` at `addr`, mapping the pages they are on, zeroed, if needed.
+    pub fn map(&self, addr: u64, bytes: &[u8]) {
+        let mut memory = self.memory.borrow_mut();
+        for (addr, &byte) in (addr..).zip(bytes) {
+            let page = memory
+                .entry(addr & !(PAGE_SIZE - 1))
+                .or_insert_with(|| vec![0; PAGE_SIZE as usize]);
+            page[(addr % PAGE_SIZE) as usize] = byte;
+        }
+    }
+
+    /// Returns the current registers, including those written back by the debugger.
+    pub const fn current_registers(&self) -> user_regs_struct {
+        self.registers.get()
+    }
+
+    /// Returns the word at `offset` in the `user` area, `0` if never written.
+    pub fn user_word(&self, offset: usize) -> u64 {
+        self.user.borrow().get(&offset).copied().unwrap_or_default()
+    }
+
+    /// Returns the names of the requests made so far, e.g. `PTRACE_CONT`, oldest first.
+    pub fn requests(&self) -> Vec<&'static str> {
+        self.requests.borrow().clone()
+    }
+
+    /// Returns the signals delivered by resumes so far, oldest first.
+    pub fn delivered_signals(&self) -> Vec<Signal> {
+        self.delivered.borrow().clone()
+    }
+
+    fn record(&self, request: &'static str) {
+        self.requests.borrow_mut().push(request);
+    }
+
+    /// Reads `len` bytes at `addr`, stopping at the first unmapped page.
+    fn read_mapped(&self, addr: u64, len: usize) -> Vec<u8> {
+        let memory = self.memory.borrow();
+        (addr..addr.saturating_add(len as u64))
+            .map_while(|addr| {
+                let page = memory.get(&(addr & !(PAGE_SIZE - 1)))?;
+                Some(page[(addr % PAGE_SIZE) as usize])
+            })
+            .collect()
+    }
+
+    fn read_word(&self, addr: u64) -> nix::Result<i64> {
+        let bytes = self.read_mapped(addr, size_of::<i64>());
+        let word = bytes.try_into().map_err(|_| Errno::EIO)?;
+        Ok(i64::from_le_bytes(word))
+    }
+}
+
+impl Backend for ScriptedBackend {
+    fn cont(&self, _pid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record("PTRACE_CONT");
+        self.delivered.borrow_mut().extend(signal);
+        Ok(())
+    }
+
+    fn syscall(&self, _pid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record("PTRACE_SYSCALL");
+        self.delivered.borrow_mut().extend(signal);
+        Ok(())
+    }
+
+    fn step(&self, _pid: Pid) -> nix::Result<()> {
+        self.record("PTRACE_SINGLESTEP");
+        Ok(())
+    }
+
+    fn detach(&self, _pid: Pid) -> nix::Result<()> {
+        self.record("PTRACE_DETACH");
+        Ok(())
+    }
+
+    fn set_options(&self, _pid: Pid, _options: Options) -> nix::Result<()> {
+        self.record("PTRACE_SETOPTIONS");
+        Ok(())
+    }
+
+    fn seize(&self, _pid: Pid) -> nix::Result<()> {
+        self.record("PTRACE_SEIZE");
+        Err(Errno::ESRCH) // There are no other threads.
+    }
+
+    fn interrupt(&self, _pid: Pid) -> nix::Result<()> {
+        self.record("PTRACE_INTERRUPT");
+        Err(Errno::ESRCH)
+    }
+
+    fn wait(&self, _pid: Pid, _flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus> {
+        self.record("waitpid");
+        let stop = self.stops.borrow_mut().pop_front().ok_or(Errno::ECHILD)?;
+        if let Some(registers) = stop.registers {
+            self.registers.set(registers);
+        }
+        if stop.signal.is_some() {
+            self.signal.set(stop.signal);
+        }
+        self.user.borrow_mut().extend(stop.user);
+        for (addr, bytes) in &stop.memory {
+            self.map(*addr, bytes);
+        }
+        Ok(stop.status)
+    }
+
+    fn kill(&self, _pid: Pid, _signal: Signal) -> nix::Result<()> {
+        self.record("kill");
+        Ok(())
+    }
+
+    fn pidfd_open(&self, _pid: Pid) -> nix::Result<OwnedFd> {
+        self.record("pidfd_open");
+        Err(Errno::ENOSYS) // Keeps to pids, which the script is written for.
+    }
+
+    fn wait_pidfd(
+        &self,
+        pid: Pid,
+        _pidfd: BorrowedFd<'_>,
+        flags: Option<WaitPidFlag>,
+    ) -> nix::Result<WaitStatus> {
+        self.wait(pid, flags)
+    }
+
+    fn pidfd_send_signal(
+        &self,
+        pid: Pid,
+        _pidfd: BorrowedFd<'_>,
+        signal: Signal,
+    ) -> nix::Result<()> {
+        self.kill(pid, signal)
+    }
+
+    fn registers(&self, _pid: Pid) -> nix::Result<user_regs_struct> {
+        self.record("PTRACE_GETREGSET");
+        Ok(self.registers.get())
+    }
+
+    fn set_registers(&self, _pid: Pid, regs: user_regs_struct) -> nix::Result<()> {
+        self.record("PTRACE_SETREGSET");
+        self.registers.set(regs);
+        Ok(())
+    }
+
+    fn signal_info(&self, _pid: Pid) -> nix::Result<SignalInfo> {
+        self.record("PTRACE_GETSIGINFO");
+        self.signal.get().ok_or(Errno::EINVAL)
+    }
+
+    fn peek_data(&self, _pid: Pid, addr: u64) -> nix::Result<i64> {
+        self.record("PTRACE_PEEKDATA");
+        self.read_word(addr)
+    }
+
+    fn poke_data(&self, _pid: Pid, addr: u64, word: i64) -> nix::Result<()> {
+        self.record("PTRACE_POKEDATA");
+        self.read_word(addr)?;
+        self.map(addr, &word.to_le_bytes());
+        Ok(())
+    }
+
+    fn peek_user(&self, _pid: Pid, offset: usize) -> nix::Result<i64> {
+        self.record("PTRACE_PEEKUSER");
+        Ok(self.user_word(offset) as i64)
+    }
+
+    fn poke_user(&self, _pid: Pid, offset: usize, word: i64) -> nix::Result<()> {
+        self.record("PTRACE_POKEUSER");
+        self.user.borrow_mut().insert(offset, word as u64);
+        Ok(())
+    }
+
+    fn read_memory(
+        &self,
+        _pid: Pid,
+        local: &mut [IoSliceMut<'_>],
+        remote: &[RemoteIoVec],
+    ) -> nix::Result<usize> {
+        let mut bytes = Vec::new();
+        for range in remote {
+            let read = self.read_mapped(range.base as u64, range.len);
+            let complete = read.len() == range.len;
+            bytes.extend(read);
+            if !complete {
+                break;
+            }
+        }
+        if bytes.is_empty() && remote.iter().any(|range| range.len > 0) {
+            return Err(Errno::EFAULT);
+        }
+        let mut rest = bytes.as_slice();
+        for buffer in local {
+            let len = buffer.len().min(rest.len());
+            buffer[..len].copy_from_slice(&rest[..len]);
+            rest = &rest[len..];
+        }
+        Ok(bytes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_mapped_memory_only() {
+        // SAFETY: `user_regs_struct` is plain integers.
+        let backend = ScriptedBackend::new(unsafe { std::mem::zeroed() });
+        let pid = Pid::from_raw(1);
+        backend.map(0x1ffc, b"abcdef");
+        let mut buffer = [0; 16];
+        let remote = [RemoteIoVec {
+            base: 0x2000,
+            len: 16,
+        }];
+        let read = backend.read_memory(pid, &mut [IoSliceMut::new(&mut buffer)], &remote);
+        assert_eq!(read, Ok(16));
+        assert_eq!(&buffer[..4], b"ef\0\0");
+        assert_eq!(backend.peek_data(pid, 0x2ffc), Err(Errno::EIO));
+
+        assert_eq!(backend.poke_data(pid, 0x1ffc, 0x4142), Ok(()));
+        assert_eq!(backend.read_mapped(0x1ffc, 3), b"BA\0");
+    }
+}
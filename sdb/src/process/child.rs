@@ -0,0 +1,246 @@
+use super::launch_spec::LaunchSpec;
+use crate::error::{DeserializeErrSnafu, NullSnafu, Result, SdbError, FRAME_HEADER_LEN};
+use crate::seccomp::{install, trace_filter};
+use nix::errno::Errno;
+use nix::libc::{self, c_char, sock_filter};
+use nix::sys::ptrace;
+use nix::unistd::chdir;
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::path::PathBuf;
+use std::ptr;
+
+/// Size of a report from [`write_report`]: the failed [`Stage`] and the `errno`.
+const REPORT_LEN: usize = 2 * size_of::<u32>();
+
+/// A step the child takes between `fork` and `exec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum Stage {
+    ChangeDirectory = 1,
+    TraceMe = 2,
+    Seccomp = 3,
+    Exec = 4,
+    RedirectOutput = 5,
+}
+
+impl Stage {
+    const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::ChangeDirectory),
+            2 => Some(Self::TraceMe),
+            3 => Some(Self::Seccomp),
+            4 => Some(Self::Exec),
+            5 => Some(Self::RedirectOutput),
+            _ => None,
+        }
+    }
+}
+
+/// Everything the launched child needs, prepared before `fork`.
+///
+/// After `fork` in a multithreaded debugger, another thread may hold the allocator's lock
+/// forever, so [`Self::exec`] only makes syscalls on what was allocated here.
+pub(super) struct ChildSetup {
+    program: CString,
+    cwd: Option<CString>,
+    filter: Option<Vec<sock_filter>>,
+    /// What to make the child's stdout and stderr.
+    output: Option<[OwnedFd; 2]>,
+    /// Null-terminated, pointing into `program`.
+    argv: [*const c_char; 2],
+    /// Null-terminated, pointing into `_env`.
+    envp: Vec<*const c_char>,
+    _env: Vec<CString>,
+}
+
+impl ChildSetup {
+    /// Prepares to run `spec`, trapping `seccomp_syscalls` if set.
+    ///
+    /// # Errors
+    /// Returns [`SdbError::Null`] if the program, directory or environment contains a nul.
+    pub(super) fn new(spec: &LaunchSpec, seccomp_syscalls: Option<&[u64]>) -> Result<Self> {
+        let c_string = |bytes: Vec<u8>| CString::new(bytes).map_err(|_| NullSnafu.build());
+        let program = c_string(spec.program.as_os_str().as_encoded_bytes().to_vec())?;
+        let cwd = spec
+            .cwd
+            .as_ref()
+            .map(|cwd| c_string(cwd.as_os_str().as_encoded_bytes().to_vec()))
+            .transpose()?;
+        let env = spec
+            .env
+            .iter()
+            .map(|(name, value)| {
+                c_string([name.as_encoded_bytes(), b"=", value.as_encoded_bytes()].concat())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let envp = env
+            .iter()
+            .map(|variable| variable.as_ptr())
+            .chain([ptr::null()])
+            .collect();
+        Ok(Self {
+            argv: [program.as_ptr(), ptr::null()],
+            program,
+            cwd,
+            filter: seccomp_syscalls.map(trace_filter),
+            output: None,
+            envp,
+            _env: env,
+        })
+    }
+
+    /// Makes the child write its stdout and stderr to `stdout` and `stderr`.
+    pub(super) fn redirect_output(&mut self, [stdout, stderr]: [OwnedFd; 2]) {
+        self.output = Some([stdout, stderr]);
+    }
+
+    /// Sets up the forked child and runs the program, or reports why it cannot to `report`
+    /// and exits.
+    ///
+    /// Only makes async-signal-safe calls: glibc's `execvpe` searches `PATH` on the stack.
+    pub(super) fn exec(&mut self, report: BorrowedFd<'_>) -> ! {
+        let failure = self.prepare().err().unwrap_or_else(|| {
+            // SAFETY: `argv` and `envp` are null-terminated and point into `self`.
+            unsafe {
+                libc::execvpe(
+                    self.program.as_ptr(),
+                    self.argv.as_ptr(),
+                    self.envp.as_ptr(),
+                )
+            };
+            (Stage::Exec, Errno::last())
+        });
+        write_report(report, failure.0, failure.1);
+        // SAFETY: Leaves without running the parent's `atexit` handlers or destructors.
+        unsafe { libc::_exit(127) }
+    }
+
+    /// Redirects the output, changes directory, asks to be traced and installs the seccomp
+    /// filter.
+    fn prepare(&mut self) -> Result<(), (Stage, Errno)> {
+        if let Some(output) = &self.output {
+            for (fd, target) in output
+                .iter()
+                .zip([libc::STDOUT_FILENO, libc::STDERR_FILENO])
+            {
+                // SAFETY: `dup2` only replaces `target`; the copy is not close-on-exec.
+                Errno::result(unsafe { libc::dup2(fd.as_raw_fd(), target) })
+                    .map_err(|errno| (Stage::RedirectOutput, errno))?;
+            }
+        }
+        if let Some(cwd) = &self.cwd {
+            chdir(cwd.as_c_str()).map_err(|errno| (Stage::ChangeDirectory, errno))?;
+        }
+        // Allow tracing of branched processes.
+        ptrace::traceme().map_err(|errno| (Stage::TraceMe, errno))?;
+        if let Some(filter) = &mut self.filter {
+            install(filter).map_err(|errno| (Stage::Seccomp, errno))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a frame (see [`crate::error::read_frame`]) saying `stage` failed with `errno`.
+///
+/// It is shorter than `PIPE_BUF`, so it is written at once or not at all.
+fn write_report(fd: BorrowedFd<'_>, stage: Stage, errno: Errno) {
+    let mut frame = [0; FRAME_HEADER_LEN + REPORT_LEN];
+    let (header, report) = frame.split_at_mut(FRAME_HEADER_LEN);
+    header.copy_from_slice(&(REPORT_LEN as u32).to_le_bytes());
+    report[..4].copy_from_slice(&(stage as u32).to_le_bytes());
+    report[4..].copy_from_slice(&(errno as i32).to_le_bytes());
+    loop {
+        // SAFETY: Writes from a live buffer of the given length.
+        let written = unsafe { libc::write(fd.as_raw_fd(), frame.as_ptr().cast(), frame.len()) };
+        if written >= 0 || Errno::last() != Errno::EINTR {
+            break;
+        }
+    }
+}
+
+/// Turns a report written by [`ChildSetup::exec`] into the error it stands for.
+///
+/// `cwd` is the directory the child was to change to.
+///
+/// # Errors
+/// Returns [`SdbError::DeserializeErr`] if `report` is not a report.
+pub(super) fn decode_report(report: &[u8], cwd: Option<&PathBuf>) -> Result<SdbError> {
+    let (Ok(stage), Ok(errno)) = (
+        report
+            .get(..4)
+            .unwrap_or_default()
+            .try_into()
+            .map(u32::from_le_bytes),
+        report
+            .get(4..)
+            .unwrap_or_default()
+            .try_into()
+            .map(i32::from_le_bytes),
+    ) else {
+        return DeserializeErrSnafu {
+            msg: format!("launch report of {} bytes", report.len()),
+        }
+        .fail();
+    };
+    let source = Errno::from_raw(errno);
+    Ok(match Stage::from_raw(stage) {
+        Some(Stage::ChangeDirectory) => SdbError::ChangeDirectory {
+            path: cwd.cloned().unwrap_or_default(),
+            source,
+        },
+        Some(Stage::TraceMe) => SdbError::TracingFailed { source },
+        Some(Stage::Seccomp) => SdbError::SeccompFailed { source },
+        Some(Stage::Exec) => SdbError::ExecFailed { source },
+        Some(Stage::RedirectOutput) => SdbError::RedirectOutput { source },
+        None => {
+            return DeserializeErrSnafu {
+                msg: format!("unknown launch stage {stage}"),
+            }
+            .fail()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{read_frame, CouldNotCreatePipeSnafu};
+    use nix::unistd::pipe;
+    use snafu::ResultExt as _;
+    use std::ffi::{CStr, OsString};
+    use std::os::fd::AsFd;
+    use std::os::unix::ffi::OsStringExt as _;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn should_round_trip_reports() -> Result<()> {
+        let (read_fd, write_fd) = pipe().context(CouldNotCreatePipeSnafu)?;
+        write_report(write_fd.as_fd(), Stage::ChangeDirectory, Errno::ENOENT);
+        let report = read_frame(read_fd.as_fd(), Duration::from_millis(50))?.unwrap_or_default();
+        let cwd = PathBuf::from("/missing");
+        assert!(matches!(
+            decode_report(&report, Some(&cwd))?,
+            SdbError::ChangeDirectory { path, source: Errno::ENOENT } if path == cwd
+        ));
+        assert!(decode_report(&[1, 0], None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_pass_non_utf8_environment_as_is() -> Result<()> {
+        let mut spec = LaunchSpec::new(Path::new("/bin/true"));
+        spec.env = [(
+            OsString::from("NAME"),
+            OsString::from_vec(b"caf\xe9".to_vec()),
+        )]
+        .into();
+        let setup = ChildSetup::new(&spec, None)?;
+        // SAFETY: `envp` points into `_env`, which `setup` keeps alive.
+        let variable = unsafe { CStr::from_ptr(setup.envp[0]) };
+        assert_eq!(variable.to_bytes(), b"NAME=caf\xe9");
+        assert!(setup.envp[1].is_null());
+        Ok(())
+    }
+}
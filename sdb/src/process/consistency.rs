@@ -0,0 +1,161 @@
+//! Noticing when the process no longer is what the debugger thinks it is.
+//!
+//! A `SIGCONT` from elsewhere, another program poking its memory or its debug registers, or
+//! a tracer taking over after a detach can all change the process behind our back. Checking
+//! before each command turns such a silent desync into a warning, and repairs what can be.
+
+use super::memory::read_uncached;
+use super::{Backend, Process};
+use crate::error::Result;
+use crate::procfs::ProcStatus;
+use nix::sys::wait::WaitStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A difference between the process and the debugger's model of it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Desync {
+    /// The process is supposed to be stopped but runs, e.g. after an external `SIGCONT`.
+    NotStopped { state: String },
+    /// Another program traces the process.
+    Tracer { tracer: i32 },
+    /// The bytes of a patch were overwritten. They have been written again.
+    PatchOverwritten { id: usize, addr: u64 },
+    /// `DR7` does not enable exactly our watchpoints. It has been written again.
+    DebugControl { expected: u64, found: u64 },
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotStopped { state } => write!(
+                f,
+                "the process should be stopped but is `{state}`; something else resumed it"
+            ),
+            Self::Tracer { tracer } => write!(
+                f,
+                "the process is traced by pid {tracer} instead of sdb; its state may be stale"
+            ),
+            Self::PatchOverwritten { id, addr } => {
+                write!(f, "patch {id} at {addr:#x} was overwritten; wrote it again")
+            }
+            Self::DebugControl { expected, found } => write!(
+                f,
+                "DR7 was changed to {found:#x} by something else; restored {expected:#x}"
+            ),
+        }
+    }
+}
+
+impl<B: Backend> Process<B> {
+    /// Compares the stopped process with what the debugger knows about it: that it is
+    /// stopped and traced by us, that its patches are in place and that `DR7` enables our
+    /// watchpoints. Patches and `DR7` are written again if they differ.
+    ///
+    /// Only the traced thread is checked. The `/proc` checks are skipped if it cannot be
+    /// read, e.g. for a [`super::ScriptedBackend`].
+    ///
+    /// # Returns
+    /// What differed, nothing if the process runs or is gone.
+    ///
+    /// # Errors
+    /// Returns an error if patches or `DR7` cannot be read or written.
+    pub fn check_consistency(&mut self) -> Result<Vec<Desync>> {
+        if self.state == WaitStatus::StillAlive || !self.is_alive() {
+            return Ok(Vec::new());
+        }
+        let mut desyncs = Vec::new();
+        if let Ok(status) = ProcStatus::read(self.pid) {
+            // `t (tracing stop)`, or `T (stopped)` while a group stop is reported.
+            if let Some(state) = status
+                .get("State")
+                .filter(|state| !state.starts_with(['t', 'T']))
+            {
+                desyncs.push(Desync::NotStopped {
+                    state: state.to_string(),
+                });
+            }
+            if let Some(tracer) = status
+                .get_int("TracerPid")
+                .filter(|&tracer| tracer != i64::from(std::process::id()))
+            {
+                desyncs.push(Desync::Tracer {
+                    tracer: tracer as i32,
+                });
+            }
+        }
+        desyncs.extend(self.resync_patches()?);
+        if let Some((expected, found)) = self.resync_debug_control()? {
+            desyncs.push(Desync::DebugControl { expected, found });
+        }
+        Ok(desyncs)
+    }
+
+    /// Writes again the patches whose bytes are no longer in memory.
+    fn resync_patches(&mut self) -> Result<Vec<Desync>> {
+        // What memory should hold where patches overlap: the bytes of the latest.
+        let mut expected = BTreeMap::new();
+        for patch in &self.patches {
+            expected.extend((patch.addr..).zip(patch.bytes.iter().copied()));
+        }
+        let mut overwritten = Vec::new();
+        for patch in &self.patches {
+            let bytes: Vec<_> = expected
+                .range(patch.addr..patch.end())
+                .map(|(_, &byte)| byte)
+                .collect();
+            // The page cache may predate the change.
+            let found = read_uncached(&self.backend, self.pid, patch.addr, bytes.len())?;
+            if found != bytes {
+                overwritten.push((patch.id, patch.addr, bytes));
+            }
+        }
+        overwritten
+            .into_iter()
+            .map(|(id, addr, bytes)| {
+                self.write_code(addr, &bytes)?;
+                Ok(Desync::PatchOverwritten { id, addr })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::ScriptedBackend;
+    use nix::libc::user_regs_struct;
+    use nix::sys::signal::Signal;
+    use nix::unistd::Pid;
+
+    #[test]
+    fn should_repair_overwritten_patches() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        let backend = ScriptedBackend::new(regs);
+        backend.map(0x1000, &[0; 16]);
+        let mut process =
+            Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+        process.patch(0x1000, &[0x90; 4])?;
+        let later = process.patch(0x1002, &[0xcc; 4])?;
+        assert_eq!(process.check_consistency()?, []);
+
+        process.backend().map(0x1000, &[0x55]);
+        assert_eq!(
+            process.check_consistency()?,
+            [Desync::PatchOverwritten {
+                id: later - 1,
+                addr: 0x1000
+            }]
+        );
+        assert_eq!(
+            process.read_memory(0x1000, 6)?,
+            [0x90, 0x90, 0xcc, 0xcc, 0xcc, 0xcc]
+        );
+        assert_eq!(process.check_consistency()?, []);
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// How an inferior terminated.
+///
+/// Decoded from the `WIFEXITED`/`WIFSIGNALED` data reported by `waitpid`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct ExitStatus {
+    /// Exit code passed to `exit`, if the process exited normally.
+    pub code: Option<i32>,
+    /// Signal that terminated the process, if it was killed.
+    #[serde(serialize_with = "serialize_signal")]
+    pub signal: Option<Signal>,
+    /// Whether the kernel produced a core dump on termination.
+    pub core_dumped: bool,
+}
+
+impl ExitStatus {
+    /// Decodes the termination info from a `WaitStatus`.
+    ///
+    /// # Returns
+    /// `None` if `status` does not describe a terminated process.
+    pub const fn from_wait_status(status: &WaitStatus) -> Option<Self> {
+        match *status {
+            WaitStatus::Exited(_, code) => Some(Self {
+                code: Some(code),
+                signal: None,
+                core_dumped: false,
+            }),
+            WaitStatus::Signaled(_, signal, core_dumped) => Some(Self {
+                code: None,
+                signal: Some(signal),
+                core_dumped,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the process exited normally with code `0`.
+    pub const fn success(&self) -> bool {
+        matches!(self.code, Some(0))
+    }
+}
+
+/// Serializes a signal by name, e.g. `SIGSEGV`.
+fn serialize_signal<S>(signal: &Option<Signal>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    signal.map(Signal::as_str).serialize(serializer)
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(code) = self.code {
+            write!(f, "exited with status {code}")?;
+        }
+        if let Some(signal) = self.signal {
+            write!(f, "terminated with signal {signal}")?;
+        }
+        if self.core_dumped {
+            write!(f, " (core dumped)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::unistd::Pid;
+
+    #[test]
+    fn should_decode_wait_status() {
+        let pid = Pid::from_raw(1);
+
+        let exited = ExitStatus::from_wait_status(&WaitStatus::Exited(pid, 3));
+        assert_eq!(
+            exited,
+            Some(ExitStatus {
+                code: Some(3),
+                signal: None,
+                core_dumped: false,
+            })
+        );
+
+        let signaled =
+            ExitStatus::from_wait_status(&WaitStatus::Signaled(pid, Signal::SIGSEGV, true));
+        assert_eq!(
+            signaled.map(|status| status.to_string()),
+            Some("terminated with signal SIGSEGV (core dumped)".to_string())
+        );
+
+        let stopped = WaitStatus::Stopped(pid, Signal::SIGTRAP);
+        assert_eq!(ExitStatus::from_wait_status(&stopped), None);
+    }
+}
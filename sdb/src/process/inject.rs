@@ -0,0 +1,348 @@
+//! Making the stopped inferior run syscalls and functions on our behalf.
+
+use super::{Backend, Process};
+use crate::error::{
+    CallInterruptedSnafu, CouldNotResumeSnafu, InjectAtSyscallStopSnafu, InjectInterruptedSnafu,
+    RemoteSyscallSnafu, Result,
+};
+use nix::errno::Errno;
+use nix::libc::{self, user_regs_struct};
+use nix::sys::mman::{MapFlags, ProtFlags};
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use snafu::ResultExt;
+
+/// `syscall` instruction.
+const SYSCALL_INSTRUCTION: [u8; 2] = [0x0f, 0x05];
+/// Return values in `-MAX_ERRNO..0` are `-errno`.
+const MAX_ERRNO: u64 = 4095;
+/// `int3`, where called functions return to.
+const INT3: u8 = 0xcc;
+/// Stack below `rsp` that leaf functions may use without moving it (the System V red zone).
+const RED_ZONE: u64 = 128;
+
+impl<B: Backend> Process<B> {
+    /// Makes the inferior execute syscall `number` with `args`.
+    ///
+    /// A `syscall` instruction is written over the code at the program counter and single
+    /// stepped, then the original code and registers are put back. The process is then at the
+    /// stop it was at, which [`Self::state`] keeps describing.
+    ///
+    /// # Returns
+    /// The raw return value (`-errno` on failure).
+    ///
+    /// # Errors
+    /// Returns an error if the inferior is stopped at a syscall, including a seccomp stop, or
+    /// if it does not come back from the injected syscall (e.g. it received a fatal signal).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use nix::libc;
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// let pid = process.inject_syscall(libc::SYS_getpid as u64, [0; 6])?;
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn inject_syscall(&mut self, number: u64, args: [u64; 6]) -> Result<u64> {
+        // The kernel would resume the interrupted syscall with our registers.
+        if self.is_at_syscall_stop() {
+            return InjectAtSyscallStopSnafu.fail();
+        }
+        let saved = self.read_registers()?;
+        let code = self.read_memory(saved.rip, SYSCALL_INSTRUCTION.len())?;
+        self.write_memory(saved.rip, &SYSCALL_INSTRUCTION)?;
+
+        let result = self.step_syscall(&saved, number, args);
+
+        let restored_code = self.write_memory(saved.rip, &code);
+        let restored_registers = self.write_registers(&saved);
+        let value = result?;
+        restored_code?;
+        restored_registers?;
+        Ok(value)
+    }
+
+    /// Like [`Self::inject_syscall`], turning `-errno` into an error naming the syscall `name`.
+    ///
+    /// # Errors
+    /// Returns an error if injection fails or the syscall fails.
+    pub fn remote_syscall(&mut self, name: &str, number: i64, args: [u64; 6]) -> Result<u64> {
+        let value = self.inject_syscall(number as u64, args)?;
+        if value.wrapping_neg() <= MAX_ERRNO && value != 0 {
+            let source = Errno::from_raw(value.wrapping_neg() as i32);
+            return Err(source).context(RemoteSyscallSnafu { name });
+        }
+        Ok(value)
+    }
+
+    /// Changes the protection of `len` bytes at `addr` by injecting `mprotect`.
+    ///
+    /// # Errors
+    /// Returns an error if injection fails or `mprotect` fails in the inferior
+    /// (e.g. `addr` is not page aligned).
+    pub fn protect_memory(&mut self, addr: u64, len: u64, prot: ProtFlags) -> Result<()> {
+        let args = [addr, len, prot.bits() as u64, 0, 0, 0];
+        self.remote_syscall("mprotect", libc::SYS_mprotect, args)?;
+        Ok(())
+    }
+
+    /// Maps `len` bytes of fresh zeroed memory into the inferior by injecting `mmap`.
+    ///
+    /// # Returns
+    /// The page aligned address of the new mapping.
+    ///
+    /// # Errors
+    /// Returns an error if injection fails or `mmap` fails in the inferior.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use nix::sys::mman::{MapFlags, ProtFlags};
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// let addr = process.allocate_memory(4096, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)?;
+    /// process.write_memory(addr, b"scratch")?;
+    /// process.free_memory(addr, 4096)?;
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn allocate_memory(&mut self, len: u64, prot: ProtFlags) -> Result<u64> {
+        let flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS;
+        let args = [
+            0,
+            len,
+            prot.bits() as u64,
+            flags.bits() as u64,
+            u64::MAX, // fd -1
+            0,
+        ];
+        self.remote_syscall("mmap", libc::SYS_mmap, args)
+    }
+
+    /// Unmaps `len` bytes at `addr` from the inferior by injecting `munmap`.
+    ///
+    /// # Errors
+    /// Returns an error if injection fails or `munmap` fails in the inferior.
+    pub fn free_memory(&mut self, addr: u64, len: u64) -> Result<()> {
+        self.remote_syscall("munmap", libc::SYS_munmap, [addr, len, 0, 0, 0, 0])?;
+        Ok(())
+    }
+
+    /// Makes the inferior call the function at `addr` with integer `args`, following the
+    /// System V calling convention, and returns what it left in `rax`.
+    ///
+    /// The call gets a frame below the current stack and returns to an `int3` written over
+    /// the code at the program counter, then the original code and registers are put back.
+    /// The function runs with the other threads still stopped, so it must not wait for them.
+    ///
+    /// # Errors
+    /// Returns an error if the inferior is stopped at a syscall, or if the function does not
+    /// return (e.g. it crashed or hit a watchpoint).
+    pub fn call_function(&mut self, addr: u64, args: &[u64]) -> Result<u64> {
+        if self.is_at_syscall_stop() {
+            return InjectAtSyscallStopSnafu.fail();
+        }
+        let saved = self.read_registers()?;
+        let code = self.read_memory(saved.rip, 1)?;
+        self.write_memory(saved.rip, &[INT3])?;
+
+        let result = self.run_call(&saved, addr, args);
+
+        let restored_code = self.write_memory(saved.rip, &code);
+        let restored_registers = self.write_registers(&saved);
+        let value = result?;
+        restored_code?;
+        restored_registers?;
+        Ok(value)
+    }
+
+    fn run_call(&mut self, saved: &user_regs_struct, addr: u64, args: &[u64]) -> Result<u64> {
+        let (in_registers, on_stack) = args.split_at(args.len().min(6));
+        // `rsp` must be 16-byte aligned before the return address is pushed.
+        let mut rsp = (saved.rsp - RED_ZONE) & !0xf;
+        if on_stack.len() % 2 == 1 {
+            rsp -= 8;
+        }
+        let frame: Vec<u8> = std::iter::once(saved.rip)
+            .chain(on_stack.iter().copied())
+            .flat_map(u64::to_le_bytes)
+            .collect();
+        rsp -= frame.len() as u64;
+        self.write_memory(rsp, &frame)?;
+
+        let mut regs = *saved;
+        let mut registers = [0; 6];
+        registers[..in_registers.len()].copy_from_slice(in_registers);
+        [regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9] = registers;
+        // No vector registers are passed to variadic functions.
+        regs.rax = 0;
+        regs.rsp = rsp;
+        regs.rip = addr;
+        regs.orig_rax = u64::MAX;
+        self.write_registers(&regs)?;
+        self.flush_registers()?;
+
+        self.backend
+            .cont(self.pid, None)
+            .context(CouldNotResumeSnafu {
+                request: "PTRACE_CONT",
+                pid: self.pid.as_raw(),
+            })?;
+        self.invalidate_caches();
+        match self.wait_on_signal()? {
+            // An execute watchpoint at the return address stops before the `int3` does.
+            WaitStatus::Stopped(_, Signal::SIGTRAP)
+                if [saved.rip, saved.rip + 1].contains(&self.read_registers()?.rip) =>
+            {
+                self.clear_debug_status()?;
+                Ok(self.read_registers()?.rax)
+            }
+            status => {
+                self.end_injection(status);
+                CallInterruptedSnafu {
+                    status: format!("{status:?}"),
+                }
+                .fail()
+            }
+        }
+    }
+
+    fn step_syscall(
+        &mut self,
+        saved: &user_regs_struct,
+        number: u64,
+        args: [u64; 6],
+    ) -> Result<u64> {
+        let mut regs = *saved;
+        regs.rax = number;
+        [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9] = args;
+        // Not in a syscall, so the kernel does not try to restart one.
+        regs.orig_rax = u64::MAX;
+        self.write_registers(&regs)?;
+        self.flush_registers()?;
+
+        self.backend.step(self.pid).context(CouldNotResumeSnafu {
+            request: "PTRACE_SINGLESTEP",
+            pid: self.pid.as_raw(),
+        })?;
+        self.invalidate_caches();
+        match self.wait_on_signal()? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => Ok(self.read_registers()?.rax),
+            status => {
+                self.end_injection(status);
+                InjectInterruptedSnafu {
+                    status: format!("{status:?}"),
+                }
+                .fail()
+            }
+        }
+    }
+
+    /// Checks whether the process stopped in a syscall, at its entry or exit or for our
+    /// seccomp filter.
+    const fn is_at_syscall_stop(&self) -> bool {
+        match self.state {
+            WaitStatus::PtraceSyscall(_) => true,
+            WaitStatus::PtraceEvent(_, _, event) => {
+                event == ptrace::Event::PTRACE_EVENT_SECCOMP as i32
+            }
+            _ => false,
+        }
+    }
+
+    /// Handles injected code stopping somewhere else than where it should have.
+    ///
+    /// The registers are put back afterwards, so the process is again at the stop it was at
+    /// before the injection, and [`Self::state`] stays what it was, unless the process ended.
+    /// A signal other than our own `SIGTRAP` is delivered when the process is next resumed.
+    const fn end_injection(&mut self, status: WaitStatus) {
+        match status {
+            WaitStatus::Exited(..) | WaitStatus::Signaled(..) => self.state = status,
+            WaitStatus::Stopped(_, signal) if !matches!(signal, Signal::SIGTRAP) => {
+                self.pending_signal = Some(signal);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SdbError;
+    use crate::process::{ScriptedBackend, ScriptedStop};
+    use nix::unistd::Pid;
+
+    const RIP: u64 = 0x40_1000;
+
+    fn stopped_at(state: WaitStatus) -> Process<ScriptedBackend> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = RIP;
+        let backend = ScriptedBackend::new(regs);
+        backend.map(RIP, &[0x90; 16]);
+        Process::with_backend(backend, pid, state)
+    }
+
+    #[test]
+    fn should_refuse_to_inject_at_seccomp_stops() {
+        let pid = Pid::from_raw(i32::MAX);
+        let seccomp = ptrace::Event::PTRACE_EVENT_SECCOMP as i32;
+        let mut process = stopped_at(WaitStatus::PtraceEvent(pid, Signal::SIGTRAP, seccomp));
+        assert!(matches!(
+            process.inject_syscall(libc::SYS_getpid as u64, [0; 6]),
+            Err(SdbError::InjectAtSyscallStop)
+        ));
+        assert!(process.backend().requests().is_empty());
+    }
+
+    #[test]
+    fn should_keep_the_stop_after_injecting() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        let stop = WaitStatus::Stopped(pid, Signal::SIGTRAP);
+        let mut process = stopped_at(stop);
+        process
+            .backend()
+            .push_stop(ScriptedStop::new(WaitStatus::Stopped(pid, Signal::SIGTRAP)));
+        process.inject_syscall(libc::SYS_getpid as u64, [0; 6])?;
+        assert_eq!(process.state, stop);
+        assert_eq!(process.backend().current_registers().rip, RIP);
+        assert_eq!(process.read_memory(RIP, 2)?, [0x90, 0x90]);
+
+        process
+            .backend()
+            .push_stop(ScriptedStop::new(WaitStatus::Signaled(
+                pid,
+                Signal::SIGKILL,
+                false,
+            )));
+        assert!(process
+            .inject_syscall(libc::SYS_getpid as u64, [0; 6])
+            .is_err());
+        assert!(!process.is_alive());
+        Ok(())
+    }
+
+    #[test]
+    fn should_deliver_a_signal_that_interrupted_an_injection_on_resume() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        let stop = WaitStatus::Stopped(pid, Signal::SIGTRAP);
+        let mut process = stopped_at(stop);
+        process
+            .backend()
+            .push_stop(ScriptedStop::new(WaitStatus::Stopped(pid, Signal::SIGINT)));
+        assert!(process
+            .inject_syscall(libc::SYS_getpid as u64, [0; 6])
+            .is_err());
+        assert_eq!(process.state, stop);
+
+        process
+            .backend()
+            .push_stop(ScriptedStop::new(WaitStatus::Exited(pid, 0)));
+        process.resume()?;
+        assert_eq!(process.backend().delivered_signals(), [Signal::SIGINT]);
+        Ok(())
+    }
+}
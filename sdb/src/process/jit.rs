@@ -0,0 +1,83 @@
+//! Following the objects JIT compilers register through the GDB JIT interface.
+
+use super::{Backend, Process};
+use crate::error::Result;
+use crate::jit::{JitInterface, JitObject};
+use crate::procfs::read_maps;
+use crate::watchpoint::{WatchKind, Watchpoint};
+
+/// The JIT interface of the process, and the hidden watchpoint stopping at its changes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) struct JitTracker {
+    interface: JitInterface,
+    /// Execute watchpoint on `__jit_debug_register_code`, not listed with the user's.
+    pub(super) watchpoint: Watchpoint,
+}
+
+impl<B: Backend> Process<B> {
+    /// Returns the symbols and source lines of the JIT code registered so far, oldest first.
+    pub fn jit_objects(&self) -> &[JitObject] {
+        &self.jit_objects
+    }
+
+    /// Starts following the JIT interface once a file defining it is mapped, reading the
+    /// objects registered so far.
+    ///
+    /// JIT code stays without symbols while all debug registers are taken.
+    ///
+    /// # Errors
+    /// Returns an error if the debug registers cannot be written.
+    pub(super) fn track_jit(&mut self) -> Result<()> {
+        if self.jit_tracker.is_some() {
+            return Ok(());
+        }
+        let Some(interface) = read_maps(self.pid)
+            .ok()
+            .and_then(|maps| JitInterface::locate(&maps))
+        else {
+            return Ok(());
+        };
+        let Some(register) = self.free_debug_register() else {
+            return Ok(());
+        };
+        self.jit_tracker = Some(JitTracker {
+            interface,
+            watchpoint: Watchpoint {
+                id: 0,
+                addr: interface.breakpoint,
+                len: 1,
+                kind: WatchKind::Execute,
+                description: "JIT code registration".into(),
+                value: 0,
+                register,
+            },
+        });
+        if let Err(err) = self.arm_debug_register(register, interface.breakpoint) {
+            self.jit_tracker = None;
+            return Err(err);
+        }
+        self.check_jit()?;
+        self.at_jit_registration = false;
+        Ok(())
+    }
+
+    /// Brings [`Self::jit_objects`] up to date with the list of registered objects.
+    ///
+    /// Objects that cannot be read or parsed are left out.
+    pub(super) fn check_jit(&mut self) -> Result<()> {
+        self.at_jit_registration = true;
+        let Some(tracker) = &self.jit_tracker else {
+            return Ok(());
+        };
+        let entries = tracker.interface.entries(self)?;
+        let mut objects = std::mem::take(&mut self.jit_objects);
+        objects.retain(|object| entries.contains(&object.entry));
+        for &entry in &entries {
+            if !objects.iter().any(|object| object.entry == entry) {
+                objects.extend(JitObject::read(self, entry).ok());
+            }
+        }
+        self.jit_objects = objects;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// What to run and how: kept across `run`s so environment and directory changes stick.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LaunchSpec {
+    /// Executable to launch.
+    pub program: PathBuf,
+    /// Environment of the inferior, initially a copy of ours. Names and values may be any
+    /// bytes but `=` in names and nul.
+    pub env: BTreeMap<OsString, OsString>,
+    /// Working directory of the inferior. `None` keeps ours.
+    pub cwd: Option<PathBuf>,
+    /// Syscalls trapped by a seccomp filter installed before `exec`, so that catchpoints on
+    /// them stop the inferior without tracing every other syscall. `None` installs no filter.
+    pub traced_syscalls: Option<Vec<u64>>,
+    /// Whether the inferior dies with the debugger, see [`super::Process::set_kill_on_exit`].
+    pub kill_on_exit: bool,
+    /// Whether [`super::Process::launch_spec`] continues the inferior to its first stop,
+    /// rather than leaving it stopped before its first instruction.
+    pub run: bool,
+    /// Whether the inferior's stdout and stderr go to pipes read while it runs, see
+    /// [`super::Process::set_output_handler`], rather than to the debugger's terminal.
+    pub capture_output: bool,
+}
+
+impl LaunchSpec {
+    /// Creates a spec that runs `program` with the debugger's environment and directory.
+    pub fn new(program: &Path) -> Self {
+        Self {
+            program: program.to_path_buf(),
+            env: std::env::vars_os().collect(),
+            cwd: None,
+            traced_syscalls: None,
+            kill_on_exit: true,
+            run: false,
+            capture_output: false,
+        }
+    }
+
+    /// Makes the dynamic linker load `library` before the program's own libraries, through
+    /// `LD_PRELOAD`, e.g. a sanitizer runtime or a library interposing on libc.
+    ///
+    /// A path with a `/` is made absolute, so it still works after changing the directory.
+    pub fn preload(&mut self, library: &Path) {
+        let library = if library.components().count() > 1 {
+            std::path::absolute(library).unwrap_or_else(|_| library.to_path_buf())
+        } else {
+            library.to_path_buf()
+        };
+        self.prepend_env_list("LD_PRELOAD", &library);
+    }
+
+    /// Makes the dynamic linker search `dir` for libraries before the other directories,
+    /// through `LD_LIBRARY_PATH`. `dir` is made absolute like in [`Self::preload`].
+    pub fn add_library_path(&mut self, dir: &Path) {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.prepend_env_list("LD_LIBRARY_PATH", &dir);
+    }
+
+    /// Puts `entry` first in the `:`-separated list in the environment variable `name`.
+    fn prepend_env_list(&mut self, name: &str, entry: &Path) {
+        let mut list = entry.as_os_str().to_os_string();
+        if let Some(rest) = self
+            .env
+            .get(OsStr::new(name))
+            .filter(|rest| !rest.is_empty())
+        {
+            list.push(":");
+            list.push(rest);
+        }
+        self.env.insert(name.into(), list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prepend_libraries() {
+        let mut spec = LaunchSpec::new(Path::new("/bin/true"));
+        spec.env.remove(OsStr::new("LD_PRELOAD"));
+        spec.env.insert("LD_LIBRARY_PATH".into(), "/opt/lib".into());
+        spec.preload(Path::new("libasan.so.8"));
+        spec.preload(Path::new("/tmp/hook.so"));
+        spec.add_library_path(Path::new("/usr/local/lib"));
+        assert_eq!(
+            spec.env[OsStr::new("LD_PRELOAD")],
+            *"/tmp/hook.so:libasan.so.8"
+        );
+        assert_eq!(
+            spec.env[OsStr::new("LD_LIBRARY_PATH")],
+            *"/usr/local/lib:/opt/lib"
+        );
+    }
+}
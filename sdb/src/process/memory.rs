@@ -0,0 +1,348 @@
+use super::{Backend, Process, Ptrace};
+use crate::error::{ReadMemorySnafu, Result, WriteMemorySnafu};
+use nix::errno::Errno;
+use nix::sys::uio::RemoteIoVec;
+use nix::unistd::Pid;
+use snafu::ResultExt;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::io::IoSliceMut;
+
+const PAGE_SIZE: usize = 0x1000;
+/// Reads longer than this, such as scans and dumps, bypass the cache instead of filling it.
+const MAX_CACHED_READ: usize = 16 * PAGE_SIZE;
+/// `IOV_MAX`: most pages fetched by one `process_vm_readv`.
+const MAX_PAGES_PER_READ: usize = 1024;
+
+/// Contents of a page, or why it cannot be read.
+type Page = Result<Box<[u8]>, Errno>;
+
+/// Pages of inferior memory read during the current stop.
+///
+/// Disassembling, unwinding and telescoping read the same few stack and code pages over and
+/// over, so they are fetched once per stop. The cache is emptied whenever the process runs or
+/// memory is written.
+#[derive(Clone, Debug)]
+pub(super) struct PageCache {
+    /// Pages by address.
+    pages: RefCell<BTreeMap<u64, Page>>,
+}
+
+impl PageCache {
+    pub(super) const fn new() -> Self {
+        Self {
+            pages: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Fetches the pages of `ranges` not cached yet, batching them into as few
+    /// `process_vm_readv` calls as the kernel allows.
+    fn fill(&self, backend: &impl Backend, pid: Pid, ranges: &[(u64, usize)]) {
+        let mut pages = self.pages.borrow_mut();
+        let mut missing: Vec<_> = ranges
+            .iter()
+            .filter(|&&(_, len)| len > 0)
+            .flat_map(|&(addr, len)| page_range(addr, len))
+            .filter(|page| !pages.contains_key(page))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        while !missing.is_empty() {
+            let batch = &missing[..missing.len().min(MAX_PAGES_PER_READ)];
+            let mut buffers = vec![vec![0; PAGE_SIZE]; batch.len()];
+            let remote: Vec<_> = batch
+                .iter()
+                .map(|&page| RemoteIoVec {
+                    base: page as usize,
+                    len: PAGE_SIZE,
+                })
+                .collect();
+            let mut local: Vec<_> = buffers
+                .iter_mut()
+                .map(|buffer| IoSliceMut::new(buffer))
+                .collect();
+            let read = backend.read_memory(pid, &mut local, &remote);
+            drop(local);
+            // The kernel stops at the first page it cannot read.
+            let readable = read.map_or(0, |read| read / PAGE_SIZE);
+            for (page, buffer) in batch.iter().zip(buffers).take(readable) {
+                pages.insert(*page, Ok(buffer.into_boxed_slice()));
+            }
+            if readable < batch.len() {
+                pages.insert(batch[readable], Err(read.err().unwrap_or(Errno::EFAULT)));
+            }
+            missing.drain(..(readable + 1).min(batch.len()));
+        }
+    }
+
+    /// Copies `len` bytes at `addr` out of the cached pages, stopping at the first page that
+    /// cannot be read.
+    fn copy(&self, addr: u64, len: usize) -> Result<Vec<u8>, Errno> {
+        let pages = self.pages.borrow();
+        let end = addr.saturating_add(len as u64);
+        let mut buffer = Vec::with_capacity(len);
+        for page in page_range(addr, len) {
+            match pages.get(&page) {
+                Some(Ok(bytes)) => {
+                    let start = addr.max(page) - page;
+                    let stop = end.min(page + PAGE_SIZE as u64) - page;
+                    buffer.extend_from_slice(&bytes[start as usize..stop as usize]);
+                }
+                Some(Err(errno)) if buffer.is_empty() => {
+                    return Err(*errno);
+                }
+                _ => break,
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Drops the cached pages overlapping `len` bytes at `addr`.
+    fn forget(&self, addr: u64, len: usize) {
+        let mut pages = self.pages.borrow_mut();
+        for page in page_range(addr, len) {
+            pages.remove(&page);
+        }
+    }
+
+    /// Drops every cached page.
+    pub(super) fn clear(&self) {
+        self.pages.borrow_mut().clear();
+    }
+}
+
+// A cache is not part of what a process is.
+impl PartialEq for PageCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PageCache {}
+
+impl Hash for PageCache {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// Read access to inferior memory, so code like unwinding can run on a [`Process`] or, from
+/// other threads, on a [`RemoteMemory`].
+pub trait ReadMemory {
+    /// Reads up to `len` bytes starting at `addr`, see [`Process::read_memory`].
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte could be read.
+    fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>>;
+
+    /// Reads `count` little-endian 64-bit words starting at `addr`, see [`Process::read_words`].
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte could be read.
+    fn read_words(&self, addr: u64, count: usize) -> Result<Vec<u64>> {
+        Ok(words(&self.read_memory(addr, count * size_of::<u64>())?))
+    }
+}
+
+/// Uncached memory access to a process that, unlike [`Process`], can be shared between threads.
+///
+/// Reads do not need ptrace, so any thread can make them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteMemory {
+    pub pid: Pid,
+}
+
+impl ReadMemory for RemoteMemory {
+    fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+        read_uncached(&Ptrace, self.pid, addr, len)
+    }
+}
+
+impl<B: Backend> ReadMemory for Process<B> {
+    fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+        Self::read_memory(self, addr, len)
+    }
+
+    fn read_words(&self, addr: u64, count: usize) -> Result<Vec<u64>> {
+        Self::read_words(self, addr, count)
+    }
+}
+
+pub(super) fn read_uncached(
+    backend: &impl Backend,
+    pid: Pid,
+    addr: u64,
+    len: usize,
+) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    let remote = RemoteIoVec {
+        base: addr as usize,
+        len,
+    };
+    let read = backend
+        .read_memory(pid, &mut [IoSliceMut::new(&mut buffer)], &[remote])
+        .context(ReadMemorySnafu {
+            pid: pid.as_raw(),
+            addr,
+            len,
+        })?;
+    buffer.truncate(read);
+    Ok(buffer)
+}
+
+/// Decodes little-endian 64-bit words, dropping a trailing partial word.
+fn words(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks_exact(size_of::<u64>())
+        .map(|chunk| {
+            let mut word = [0; size_of::<u64>()];
+            word.copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect()
+}
+
+/// Addresses of the pages overlapping `len > 0` bytes at `addr`.
+fn page_range(addr: u64, len: usize) -> impl Iterator<Item = u64> {
+    let first = addr & !(PAGE_SIZE as u64 - 1);
+    let last = addr.saturating_add(len as u64 - 1) & !(PAGE_SIZE as u64 - 1);
+    (first..=last).step_by(PAGE_SIZE)
+}
+
+impl<B: Backend> Process<B> {
+    /// Reads up to `len` bytes of inferior memory starting at `addr`.
+    ///
+    /// The result is shorter than `len` if the range runs into unmapped memory. Short reads
+    /// are served from the pages cached since the last stop.
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte could be read.
+    pub fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        if len > MAX_CACHED_READ {
+            return read_uncached(&self.backend, self.pid, addr, len);
+        }
+        self.memory.fill(&self.backend, self.pid, &[(addr, len)]);
+        self.memory.copy(addr, len).context(ReadMemorySnafu {
+            pid: self.pid.as_raw(),
+            addr,
+            len,
+        })
+    }
+
+    /// Reads several ranges, given as `(addr, len)`, fetching the pages they need with as
+    /// few `process_vm_readv` calls as possible.
+    ///
+    /// Each range is truncated at its first unreadable byte, so an unreadable range comes back
+    /// empty.
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte of the first non-empty range could be read.
+    pub fn read_memory_batch(&self, ranges: &[(u64, usize)]) -> Result<Vec<Vec<u8>>> {
+        let cached: Vec<_> = ranges
+            .iter()
+            .copied()
+            .filter(|&(_, len)| len <= MAX_CACHED_READ)
+            .collect();
+        self.memory.fill(&self.backend, self.pid, &cached);
+        let first = ranges.iter().position(|&(_, len)| len > 0);
+        ranges
+            .iter()
+            .enumerate()
+            .map(|(index, &(addr, len))| match self.read_memory(addr, len) {
+                Err(err) if Some(index) == first => Err(err),
+                result => Ok(result.unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    /// Reads `count` little-endian 64-bit words starting at `addr`.
+    ///
+    /// The result is shorter than `count` if the range runs into unmapped memory.
+    ///
+    /// # Errors
+    /// Returns an error if not even the first byte could be read.
+    pub fn read_words(&self, addr: u64, count: usize) -> Result<Vec<u64>> {
+        Ok(words(&self.read_memory(addr, count * size_of::<u64>())?))
+    }
+
+    /// Writes `data` to inferior memory at `addr` a word at a time with `PTRACE_POKEDATA`.
+    ///
+    /// Unlike `process_vm_writev`, this also works on read-only mappings such as code.
+    ///
+    /// # Errors
+    /// Returns an error if part of the range is unmapped. Words before it are already written.
+    pub fn write_memory(&self, addr: u64, data: &[u8]) -> Result<()> {
+        if !data.is_empty() {
+            self.memory.forget(addr, data.len());
+        }
+        for (index, chunk) in data.chunks(size_of::<u64>()).enumerate() {
+            let word_addr = addr + (index * size_of::<u64>()) as u64;
+            let context = WriteMemorySnafu {
+                pid: self.pid.as_raw(),
+                addr: word_addr,
+            };
+            let mut word = [0; size_of::<u64>()];
+            if chunk.len() < word.len() {
+                // Keep the bytes after the end of `data`.
+                let old = self
+                    .backend
+                    .peek_data(self.pid, word_addr)
+                    .context(context)?;
+                word = old.to_le_bytes();
+            }
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.backend
+                .poke_data(self.pid, word_addr, i64::from_le_bytes(word))
+                .context(context)?;
+        }
+        Ok(())
+    }
+}
+
+impl Process {
+    /// Returns a handle reading this process's memory from other threads.
+    pub const fn remote_memory(&self) -> RemoteMemory {
+        RemoteMemory { pid: self.pid }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_copy_across_cached_pages() {
+        let cache = PageCache::new();
+        cache.pages.borrow_mut().extend([
+            (0x1000, Ok(vec![1; PAGE_SIZE].into_boxed_slice())),
+            (0x2000, Ok(vec![2; PAGE_SIZE].into_boxed_slice())),
+            (0x3000, Err(Errno::EFAULT)),
+        ]);
+        assert_eq!(cache.copy(0x1ffe, 4).ok(), Some(vec![1, 1, 2, 2]));
+        // Truncated at the unreadable page.
+        assert_eq!(cache.copy(0x2ffe, 4).ok(), Some(vec![2, 2]));
+        assert!(cache.copy(0x3000, 1).is_err());
+
+        cache.forget(0x1fff, 2);
+        assert_eq!(cache.pages.borrow().len(), 1);
+    }
+
+    #[test]
+    fn should_read_words_remotely() -> Result<()> {
+        let words = [1_u64, 2, 3];
+        let memory = RemoteMemory {
+            pid: nix::unistd::getpid(),
+        };
+        // Another thread sees the same memory.
+        let read = std::thread::scope(|scope| {
+            scope
+                .spawn(|| memory.read_words(words.as_ptr() as u64, words.len()))
+                .join()
+                .unwrap_or_else(|_| Ok(Vec::new()))
+        })?;
+        assert_eq!(read, words);
+        Ok(())
+    }
+}
@@ -1,16 +1,69 @@
+mod backend;
+mod child;
+mod consistency;
+mod exit_status;
+mod inject;
+mod jit;
+mod launch_spec;
+mod memory;
+mod modules;
+mod namespace;
+mod output;
+mod patches;
+mod permissions;
+mod pidfd;
+mod ptrace_log;
+mod registers;
+mod sanitizers;
+mod siginfo;
+mod watchdog;
+mod watchpoints;
+
+pub use self::backend::{Backend, Ptrace, ScriptedBackend, ScriptedStop};
+pub use self::consistency::Desync;
+pub use self::exit_status::ExitStatus;
+pub use self::launch_spec::LaunchSpec;
+pub use self::memory::{ReadMemory, RemoteMemory};
+pub use self::namespace::{find_container_process, host_path, ns_pids, translate_ns_pid};
+pub use self::output::{OutputHandler, OutputStream};
+pub use self::permissions::AttachDenial;
+pub use self::ptrace_log::{ptrace_logging, set_ptrace_logging, PTRACE_LOG_TARGET};
+pub use self::registers::{register_values, StoppedThreads};
+pub use self::siginfo::SignalInfo;
+
+use self::child::{decode_report, ChildSetup};
+use self::jit::JitTracker;
+use self::memory::PageCache;
+use self::modules::ModuleTracker;
+use self::output::OutputPipes;
+use self::permissions::diagnose_attach_denial;
+use self::pidfd::PidFd;
+use self::ptrace_log::logged;
+use self::registers::RegisterCache;
+use self::sanitizers::SanitizerHook;
+use self::watchdog::Watchdog;
+use crate::catchpoint::{CatchAction, Catchpoint, CatchpointKind, RssCrossing};
 use crate::error::{
-    CouldNotAttachSnafu, CouldNotCreatePipeSnafu, CouldNotResumeSnafu, NullSnafu, Result, SdbError,
-    TracingFailedSnafu, WaitpidFailedSnafu,
+    read_frame, CouldNotAttachSnafu, CouldNotCreatePipeSnafu, CouldNotResumeSnafu, ForkFailedSnafu,
+    Result, SdbError, SetOptionsSnafu, WaitpidFailedSnafu,
 };
+use crate::jit::JitObject;
+use crate::patch::Patch;
+use crate::procfs::resident_bytes;
+use crate::rendezvous::ModuleEvent;
+use crate::sanitizer::SanitizerReport;
+use crate::syscall::{syscall_number, SyscallDirection, SyscallEvent};
+use crate::watchpoint::{RangeWatch, Watchpoint, WatchpointHit};
+use nix::errno::Errno;
 use nix::fcntl::OFlag;
 use nix::sys::ptrace;
 use nix::sys::signal::{kill, Signal};
-use nix::sys::wait::{waitpid, WaitStatus};
-use nix::unistd::{execvp, fork, pipe2, ForkResult, Pid};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, pipe2, ForkResult, Pid};
 use snafu::ResultExt;
-use std::ffi::CString;
+use std::os::fd::AsFd;
 use std::path::Path;
-use std::process::exit;
+use std::time::Duration;
 
 /// Waits for a signal from the process with the given `pid`.
 ///
@@ -25,81 +78,209 @@ use std::process::exit;
 ///
 /// # Example
 /// ```no_run
+/// use nix::unistd::Pid;
+/// use sdb::process::wait_on_signal;
+///
 /// let pid = Pid::from_raw(12345);
 /// let status = wait_on_signal(pid);
 /// ```
 pub fn wait_on_signal(pid: Pid) -> Result<WaitStatus> {
-    waitpid(pid, None).context(WaitpidFailedSnafu)
+    logged("waitpid", pid, waitpid(pid, None)).context(WaitpidFailedSnafu { pid: pid.as_raw() })
 }
 
+/// Syscalls never put in a seccomp filter: the filter is installed before the `execve`
+/// launching the program, when the debugger cannot handle its stops yet.
+const UNFILTERED_SYSCALLS: [&str; 2] = ["execve", "execveat"];
+/// How often the process is checked for a stop while sampling its RSS for RSS catchpoints
+/// or reading its captured output.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long a launched child may take to reach `execve` or report why it cannot.
+const EXEC_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// A structure representing a managed process.
+///
+/// It is controlled through `B`, which only differs from [`Ptrace`] in tests.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct Process {
+pub struct Process<B: Backend = Ptrace> {
+    /// Makes the system calls.
+    backend: B,
     /// Process ID
     pub pid: Pid,
-    /// Whether the process should be terminated on drop
+    /// Whether the process is killed on drop and when the debugger dies, rather than
+    /// detached from.
     terminate_on_end: bool,
     /// Current state of the process
     pub state: WaitStatus,
+    /// Signal that stopped injected code, delivered by the next [`Self::resume`].
+    pending_signal: Option<Signal>,
+    /// Registers of the current stop.
+    registers: RegisterCache,
+    /// Memory read during the current stop.
+    memory: PageCache,
+    /// Registered catchpoints. Syscalls are traced while this is non-empty.
+    catchpoints: Vec<Catchpoint>,
+    /// Id given to the next catchpoint.
+    next_catchpoint_id: usize,
+    /// Syscalls trapped by the seccomp filter the process was launched with, if any.
+    seccomp_syscalls: Option<Vec<u64>>,
+    /// Whether the next syscall stop is a syscall-exit-stop.
+    expecting_syscall_exit: bool,
+    /// Syscall stop that triggered a catchpoint, if that is why the process last stopped.
+    pub last_syscall: Option<SyscallEvent>,
+    /// Applied code patches, oldest first.
+    patches: Vec<Patch>,
+    /// Id given to the next patch.
+    next_patch_id: usize,
+    /// Hardware watchpoints of the main thread.
+    watchpoints: Vec<Watchpoint>,
+    /// Watches on the first execution of code in a range of pages.
+    range_watches: Vec<RangeWatch>,
+    /// Id given to the next watchpoint or range watch.
+    next_watchpoint_id: usize,
+    /// Address of the write a write watch stopped before, let through by the next resume.
+    pending_write: Option<u64>,
+    /// Watchpoint that triggered, if that is why the process last stopped.
+    pub last_watchpoint: Option<WatchpointHit>,
+    /// Follows the dynamic linker's module list while load or unload catchpoints exist.
+    module_tracker: Option<ModuleTracker>,
+    /// Whether the process last stopped at the dynamic linker's rendezvous.
+    at_rendezvous: bool,
+    /// Modules whose loading or unloading triggered a catchpoint, if that is why the process
+    /// last stopped.
+    pub last_modules: Vec<ModuleEvent>,
+    /// Follows the objects JIT compilers register, once a program using one maps it.
+    jit_tracker: Option<JitTracker>,
+    /// Whether the process last stopped at a registration of JIT code.
+    at_jit_registration: bool,
+    /// Symbols and lines of the JIT code registered so far.
+    jit_objects: Vec<JitObject>,
+    /// Report hooks of the sanitizer runtimes loaded, while sanitizer catchpoints exist.
+    sanitizer_hooks: Vec<SanitizerHook>,
+    /// Sanitizer report being made, if that is why the process last stopped.
+    pub last_sanitizer_report: Option<SanitizerReport>,
+    /// Resident set size when last sampled for RSS catchpoints.
+    rss: u64,
+    /// RSS growth the process was sent a `SIGSTOP` for, until that stop is seen.
+    pending_rss: Option<RssCrossing>,
+    /// RSS growth that triggered a catchpoint, if that is why the process last stopped.
+    pub last_rss: Option<RssCrossing>,
+    /// How long [`Self::resume`] lets the process run before interrupting it.
+    watchdog: Option<Duration>,
+    /// Timeout of a watchdog that sent `SIGSTOP`, until that stop is seen.
+    pending_watchdog: Option<Duration>,
+    /// Timeout of the watchdog that interrupted the process, if that is why it last stopped.
+    pub last_watchdog: Option<Duration>,
+    /// Captured stdout and stderr, read while waiting.
+    output: Option<OutputPipes>,
+    /// Refers to the process even if its pid is reused. `None` on old kernels.
+    pidfd: Option<PidFd>,
 }
 
 impl Process {
     /// Launches a new process from the specified executable path.
     ///
-    /// This function forks the current process and attempts to execute the provided `path` in the
-    /// child process. The parent process will wait for the child process to start and return
-    /// a `Process` struct representing the launched process.
+    /// This is [`Process::launch_spec`] with the debugger's environment and working directory.
     ///
     /// # Errors
     /// Returns an error if the fork or exec fails, wrapping the underlying errors.
     ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    /// use std::path::Path;
+    ///
+    /// let process = Process::launch(Path::new("/bin/ls"), true)?;
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn launch(path: &Path, debug: bool) -> Result<Self> {
+        Self::launch_spec(&LaunchSpec::new(path), debug)
+    }
+
+    /// Launches a new process as described by `spec`.
+    ///
+    /// This function forks the current process and attempts to execute the program in the
+    /// child process. The parent process will wait for the child process to start and return
+    /// a `Process` struct representing the launched process, continued to its first stop
+    /// if [`LaunchSpec::run`] is set.
+    ///
+    /// # Errors
+    /// Returns an error if changing directory, the fork or exec fails, wrapping the underlying errors.
+    ///
     /// # Panics
     /// Failed write parent pipe fd.
     ///
     /// # Example
     /// ```no_run
-    /// let process = Process::launch(Path::new("/bin/ls"))?;
+    /// use sdb::process::{LaunchSpec, Process};
+    /// use std::path::Path;
+    ///
+    /// let mut spec = LaunchSpec::new(Path::new("/bin/ls"));
+    /// spec.env.insert("LC_ALL".into(), "C".into());
+    /// spec.cwd = Some("/tmp".into());
+    /// let process = Process::launch_spec(&spec, true)?;
+    /// # Ok::<(), sdb::error::SdbError>(())
     /// ```
-    pub fn launch(path: &Path, debug: bool) -> Result<Self> {
+    pub fn launch_spec(spec: &LaunchSpec, debug: bool) -> Result<Self> {
+        let seccomp_syscalls = spec.traced_syscalls.as_ref().map(|syscalls| {
+            let unfiltered = UNFILTERED_SYSCALLS.map(syscall_number);
+            let mut syscalls = syscalls.clone();
+            syscalls.retain(|&number| !unfiltered.contains(&Some(number)));
+            syscalls
+        });
+        let mut setup = ChildSetup::new(spec, seccomp_syscalls.as_deref())?;
+        let output = if spec.capture_output {
+            let (output, write_fds) = OutputPipes::open()?;
+            setup.redirect_output(write_fds);
+            Some(output)
+        } else {
+            None
+        };
         let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC).context(CouldNotCreatePipeSnafu)?;
 
-        let pid = unsafe { fork() }
-            .map_err(|err| SdbError::ForkFailed { source: err })
-            .and_then(|result| match result {
-                ForkResult::Parent { child } => Ok(child),
-                ForkResult::Child => {
-                    // Allow tracing of branched processes.
-                    if let Err(err) = ptrace::traceme().context(TracingFailedSnafu){
-                        err.write_to_fd(&write_fd)?;
-                        exit(-1);
-                     };
-                    let c_string = CString::new(path.to_string_lossy().to_string()).map_err(|_| NullSnafu.build())?;
-                    if let Err(e) = execvp(c_string.as_c_str(), &[c_string.as_c_str()]) {
-                        let error = SdbError::ExecFailed { source: e };
-                        error.write_to_fd(&write_fd)?;
-                        exit(-1);
-                    };
-                    unreachable!("The forking process will be asked to execute the specified program and will not return here.")
-                }
-            })?;
+        // SAFETY: The child only makes syscalls until `exec`, see `ChildSetup::exec`.
+        let pid = match unsafe { fork() }.context(ForkFailedSnafu)? {
+            ForkResult::Parent { child } => child,
+            ForkResult::Child => setup.exec(write_fd.as_fd()),
+        };
+        drop(setup); // Only the child may keep the write ends of the output pipes.
 
-        drop(write_fd); // When 1 byte is written or the `write` side is closed(drop), the wait for `read` is over.
-        if let Ok(Some(err)) = SdbError::wait_read_from_fd(&read_fd) {
-            let _ = wait_on_signal(pid); // wait child
-            return Err(err);
+        drop(write_fd); // The `write` side is closed by a successful `execve`, else gets the error.
+        match read_frame(read_fd.as_fd(), EXEC_TIMEOUT).and_then(|report| {
+            report
+                .map(|report| decode_report(&report, spec.cwd.as_ref()))
+                .transpose()
+        }) {
+            Ok(None) => {}
+            Ok(Some(err)) => {
+                let _ = wait_on_signal(pid); // wait child
+                return Err(err);
+            }
+            Err(err) => {
+                // Stuck or garbled: it is not running what was asked for.
+                let _ = logged("kill(SIGKILL)", pid, kill(pid, Signal::SIGKILL));
+                let _ = wait_on_signal(pid);
+                return Err(err);
+            }
         }
 
-        Ok(Self {
-            pid,
-            terminate_on_end: true,
-            state: {
-                if debug {
-                    wait_on_signal(pid)?
-                } else {
-                    WaitStatus::Stopped(pid, Signal::SIGSTOP)
-                }
-            },
-        })
+        let mut process = Self::new(Ptrace, pid, {
+            if debug {
+                wait_on_signal(pid)?
+            } else {
+                WaitStatus::Stopped(pid, Signal::SIGSTOP)
+            }
+        });
+        process.open_pidfd();
+        process.seccomp_syscalls = seccomp_syscalls;
+        process.terminate_on_end = spec.kill_on_exit;
+        process.output = output;
+        if debug {
+            process.set_ptrace_options()?;
+            if spec.run {
+                process.resume()?;
+            }
+        }
+        Ok(process)
     }
 
     /// Attaches to an existing process with the given PID.
@@ -109,22 +290,116 @@ impl Process {
     ///
     /// # Errors
     /// Returns an error if attaching to the process fails, wrapping the underlying `ptrace` error.
+    /// `EPERM` is diagnosed into [`SdbError::AttachNotPermitted`] explaining how to fix it.
     ///
     /// # Example
     /// ```no_run
+    /// use sdb::process::Process;
+    ///
     /// let process = Process::attach(12345)?;
+    /// # Ok::<(), sdb::error::SdbError>(())
     /// ```
     pub fn attach(pid: i32) -> Result<Self> {
         let pid = Pid::from_raw(pid);
-        ptrace::attach(pid).context(CouldNotAttachSnafu)?;
+        logged("PTRACE_ATTACH", pid, ptrace::attach(pid)).or_else(|errno| match errno {
+            Errno::EPERM => Err(SdbError::AttachNotPermitted {
+                pid: pid.as_raw(),
+                reason: diagnose_attach_denial(pid),
+            }),
+            errno => Err(errno).context(CouldNotAttachSnafu),
+        })?;
 
-        Ok(Self {
+        let mut process = Self::new(Ptrace, pid, wait_on_signal(pid)?);
+        process.open_pidfd();
+        process.terminate_on_end = false;
+        process.set_ptrace_options()?;
+        Ok(process)
+    }
+}
+
+impl<B: Backend> Process<B> {
+    /// Takes over the process `pid`, already traced and in `state`, controlling it with
+    /// `backend`, e.g. a [`ScriptedBackend`].
+    pub const fn with_backend(backend: B, pid: Pid, state: WaitStatus) -> Self {
+        Self::new(backend, pid, state)
+    }
+
+    const fn new(backend: B, pid: Pid, state: WaitStatus) -> Self {
+        Self {
+            backend,
             pid,
             terminate_on_end: true,
-            state: wait_on_signal(pid)?,
+            state,
+            pending_signal: None,
+            registers: RegisterCache::new(),
+            memory: PageCache::new(),
+            catchpoints: Vec::new(),
+            next_catchpoint_id: 1,
+            seccomp_syscalls: None,
+            expecting_syscall_exit: false,
+            last_syscall: None,
+            patches: Vec::new(),
+            next_patch_id: 1,
+            watchpoints: Vec::new(),
+            range_watches: Vec::new(),
+            next_watchpoint_id: 1,
+            pending_write: None,
+            last_watchpoint: None,
+            module_tracker: None,
+            at_rendezvous: false,
+            last_modules: Vec::new(),
+            jit_tracker: None,
+            at_jit_registration: false,
+            jit_objects: Vec::new(),
+            sanitizer_hooks: Vec::new(),
+            last_sanitizer_report: None,
+            rss: 0,
+            pending_rss: None,
+            last_rss: None,
+            watchdog: None,
+            pending_watchdog: None,
+            last_watchdog: None,
+            output: None,
+            pidfd: None,
+        }
+    }
+
+    /// Returns the backend the process is controlled through.
+    pub const fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Waits for the next stop of the process.
+    fn wait_on_signal(&self) -> Result<WaitStatus> {
+        self.wait_status(None).context(WaitpidFailedSnafu {
+            pid: self.pid.as_raw(),
         })
     }
 
+    /// Forgets registers and memory cached during the stop, as the process is about to run.
+    fn invalidate_caches(&self) {
+        self.invalidate_registers();
+        self.memory.clear();
+    }
+
+    /// Makes syscall stops distinguishable from real `SIGTRAP`s, reports the syscalls of
+    /// our seccomp filter, and has the kernel kill the process if we die while it is to be
+    /// killed on exit.
+    fn set_ptrace_options(&self) -> Result<()> {
+        let mut options = ptrace::Options::PTRACE_O_TRACESYSGOOD;
+        if self.seccomp_syscalls.is_some() {
+            options |= ptrace::Options::PTRACE_O_TRACESECCOMP;
+        }
+        if self.terminate_on_end {
+            options |= ptrace::Options::PTRACE_O_EXITKILL;
+        }
+        self.backend
+            .set_options(self.pid, options)
+            .context(SetOptionsSnafu {
+                pid: self.pid.as_raw(),
+            })
+    }
+
     /// Resumes execution of the attached process.
     ///
     /// This function uses `ptrace::cont` to continue the execution of the process
@@ -135,42 +410,438 @@ impl Process {
     ///
     /// # Example
     /// ```no_run
+    /// use sdb::process::Process;
+    ///
     /// let mut process = Process::attach(12345)?;
     /// process.resume()?;
+    /// # Ok::<(), sdb::error::SdbError>(())
     /// ```
     pub fn resume(&mut self) -> Result<()> {
-        ptrace::cont(self.pid, None).context(CouldNotResumeSnafu)?;
-        self.state = wait_on_signal(self.pid)?;
+        self.last_syscall = None;
+        self.last_rss = None;
+        self.last_modules.clear();
+        self.last_watchdog = None;
+        self.step_over_watched_write()?;
+        if !self.is_alive() {
+            return Ok(());
+        }
+        let watchdog = self
+            .watchdog
+            .map(|timeout| Watchdog::start(self.pid, timeout));
+        let result = self.run_until_stop();
+        if watchdog.is_some_and(Watchdog::stop) {
+            self.pending_watchdog = self.watchdog;
+        }
+        if matches!(self.state, WaitStatus::Stopped(_, Signal::SIGSTOP)) {
+            // If the process stopped for another reason first, the `SIGSTOP` stops it right
+            // after the next resume instead.
+            self.last_watchdog = self.pending_watchdog.take();
+        } else if !self.is_alive() {
+            self.pending_watchdog = None;
+        }
+        result
+    }
+
+    /// Sets how long [`Self::resume`] lets the process run before interrupting it with
+    /// `SIGSTOP`, which is reported in [`Self::last_watchdog`]. `None` lets it run forever.
+    pub const fn set_watchdog(&mut self, timeout: Option<Duration>) {
+        self.watchdog = timeout;
+    }
+
+    /// Executes one instruction of the stopped process.
+    ///
+    /// Unlike [`Self::resume`], this checks no catchpoints or watchpoints: the step ends
+    /// after the instruction or at a signal arriving before it.
+    ///
+    /// # Errors
+    /// Returns an error if the step cannot be started or waited for.
+    pub fn step_instruction(&mut self) -> Result<()> {
+        self.flush_registers()?;
+        self.backend.step(self.pid).context(CouldNotResumeSnafu {
+            request: "PTRACE_SINGLESTEP",
+            pid: self.pid.as_raw(),
+        })?;
+        self.invalidate_caches();
+        self.state = self.wait_on_signal()?;
+        Ok(())
+    }
+
+    /// Resumes the process until a stop to report.
+    fn run_until_stop(&mut self) -> Result<()> {
+        self.track_modules()?;
+        self.hook_sanitizers()?;
+        self.track_jit()?;
+        loop {
+            self.run_until_event()?;
+            // The dynamic linker stops at every change of its module list, but only changes
+            // matching a catchpoint are worth reporting. JIT registrations never are.
+            if (!self.at_rendezvous || !self.last_modules.is_empty()) && !self.at_jit_registration {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Resumes the process until it stops for any reason but an uncaught syscall.
+    fn run_until_event(&mut self) -> Result<()> {
+        if !self.catchpoints.iter().any(Catchpoint::traces_syscalls) {
+            self.expecting_syscall_exit = false;
+            self.flush_registers()?;
+            let signal = self.pending_signal.take();
+            self.backend
+                .cont(self.pid, signal)
+                .context(CouldNotResumeSnafu {
+                    request: "PTRACE_CONT",
+                    pid: self.pid.as_raw(),
+                })?;
+            self.invalidate_caches();
+            self.state = self.wait()?;
+            return self.check_watchpoints();
+        }
+
+        // Only syscalls in the seccomp filter stop with `PTRACE_CONT`, so it is only used when
+        // they are all the catchpoints can catch. Their exits still need `PTRACE_SYSCALL`.
+        let seccomp = self.seccomp_covers_catchpoints();
+        loop {
+            self.flush_registers()?;
+            let signal = self.pending_signal.take();
+            let (request, result) = if seccomp && !self.expecting_syscall_exit {
+                ("PTRACE_CONT", self.backend.cont(self.pid, signal))
+            } else {
+                ("PTRACE_SYSCALL", self.backend.syscall(self.pid, signal))
+            };
+            result.context(CouldNotResumeSnafu {
+                request,
+                pid: self.pid.as_raw(),
+            })?;
+            self.invalidate_caches();
+            self.state = self.wait()?;
+
+            let direction = match self.state {
+                WaitStatus::PtraceEvent(_, _, event)
+                    if event == ptrace::Event::PTRACE_EVENT_SECCOMP as i32 =>
+                {
+                    if !seccomp {
+                        continue; // Already reported by the syscall-enter-stop.
+                    }
+                    self.expecting_syscall_exit = true;
+                    SyscallDirection::Entry
+                }
+                WaitStatus::PtraceSyscall(_) => {
+                    self.expecting_syscall_exit = !self.expecting_syscall_exit;
+                    if self.expecting_syscall_exit {
+                        SyscallDirection::Entry
+                    } else {
+                        SyscallDirection::Exit
+                    }
+                }
+                _ => return self.check_watchpoints(),
+            };
+            let event = SyscallEvent::from_registers(&self.read_registers()?, direction);
+            if self
+                .catchpoints
+                .iter()
+                .any(|catchpoint| catchpoint.matches(&event))
+            {
+                self.last_syscall = Some(event);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Waits for the next stop of the running process.
+    ///
+    /// With RSS catchpoints or captured output, this polls every [`POLL_INTERVAL`] instead
+    /// of blocking, sampling the RSS and stopping the process with `SIGSTOP` once it grows
+    /// past a threshold, and handing over output as it comes.
+    fn wait(&mut self) -> Result<WaitStatus> {
+        let samples_rss = self
+            .catchpoints
+            .iter()
+            .any(|catchpoint| matches!(catchpoint.kind, CatchpointKind::Rss(_)));
+        let output = self.output.clone();
+        if !samples_rss && !output.as_ref().is_some_and(OutputPipes::is_open) {
+            let status = self.wait_on_signal();
+            if let Some(output) = &output {
+                output.flush();
+            }
+            return status;
+        }
+        loop {
+            if samples_rss && self.pending_rss.is_none() {
+                self.sample_rss();
+            }
+            let status =
+                self.wait_status(Some(WaitPidFlag::WNOHANG))
+                    .context(WaitpidFailedSnafu {
+                        pid: self.pid.as_raw(),
+                    })?;
+            match (status, &output) {
+                (WaitStatus::StillAlive, Some(output)) if output.is_open() => {
+                    output.wait(POLL_INTERVAL, self.pidfd());
+                    continue;
+                }
+                (WaitStatus::StillAlive, _) => {
+                    self.wait_exit(POLL_INTERVAL);
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some(output) = &output {
+                output.flush();
+            }
+            if matches!(status, WaitStatus::Stopped(_, Signal::SIGSTOP))
+                && self.pending_rss.is_some()
+            {
+                self.last_rss = self.pending_rss.take();
+            }
+            return Ok(status);
+        }
+    }
+
+    /// Reads the RSS, sending `SIGSTOP` if it grew past the threshold of an RSS catchpoint.
+    fn sample_rss(&mut self) {
+        let Ok(rss) = resident_bytes(self.pid) else {
+            return; // Exiting; `waitpid` tells.
+        };
+        let crossing = RssCrossing {
+            before: self.rss,
+            after: rss,
+        };
+        self.rss = rss;
+        if self
+            .catchpoints
+            .iter()
+            .any(|catchpoint| catchpoint.is_crossed_by(&crossing))
+            && self.send_signal(Signal::SIGSTOP).is_ok()
+        {
+            self.pending_rss = Some(crossing);
+        }
+    }
+
+    /// Returns `true` if every syscall the catchpoints can catch is in the seccomp filter.
+    fn seccomp_covers_catchpoints(&self) -> bool {
+        self.seccomp_syscalls.as_ref().is_some_and(|traced| {
+            self.catchpoints.iter().all(|catchpoint| {
+                catchpoint
+                    .syscalls()
+                    .is_some_and(|numbers| numbers.iter().all(|number| traced.contains(number)))
+            })
+        })
+    }
+
+    /// Registers a catchpoint and returns its id.
+    ///
+    /// Subsequent [`Self::resume`] calls trace syscalls and stop when it is hit.
+    pub fn add_catchpoint(&mut self, kind: CatchpointKind, action: CatchAction) -> usize {
+        if matches!(kind, CatchpointKind::Rss(_)) {
+            // Only growth from now on counts.
+            self.rss = resident_bytes(self.pid).unwrap_or_default();
+        }
+        let id = self.next_catchpoint_id;
+        self.next_catchpoint_id += 1;
+        self.catchpoints.push(Catchpoint { id, kind, action });
+        id
+    }
+
+    /// Removes the catchpoint with `id`.
+    ///
+    /// # Returns
+    /// `false` if there is no such catchpoint.
+    pub fn remove_catchpoint(&mut self, id: usize) -> bool {
+        let len = self.catchpoints.len();
+        self.catchpoints.retain(|catchpoint| catchpoint.id != id);
+        self.catchpoints.len() != len
+    }
+
+    /// Returns the registered catchpoints.
+    pub fn catchpoints(&self) -> &[Catchpoint] {
+        &self.catchpoints
+    }
+
+    /// Returns the catchpoints hit by the last stop.
+    pub fn hit_catchpoints(&self) -> Vec<&Catchpoint> {
+        self.catchpoints
+            .iter()
+            .filter(|catchpoint| {
+                self.last_syscall
+                    .as_ref()
+                    .is_some_and(|event| catchpoint.matches(event))
+                    || self
+                        .last_rss
+                        .as_ref()
+                        .is_some_and(|crossing| catchpoint.is_crossed_by(crossing))
+                    || self
+                        .last_modules
+                        .iter()
+                        .any(|event| catchpoint.matches_module(event))
+                    || (self.last_sanitizer_report.is_some() && catchpoint.catches_sanitizers())
+            })
+            .collect()
+    }
+
+    /// Returns how the process terminated.
+    ///
+    /// # Returns
+    /// `None` while the process has not exited or been killed by a signal.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// process.resume()?;
+    /// if let Some(status) = process.exit_status() {
+    ///     println!("{status}");
+    /// }
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub const fn exit_status(&self) -> Option<ExitStatus> {
+        ExitStatus::from_wait_status(&self.state)
+    }
+
+    /// Checks, without blocking, whether the process changed state behind our back.
+    ///
+    /// This polls `waitpid(WNOHANG)` so that a process killed externally while we were
+    /// waiting for user input is noticed before the next command is dispatched.
+    ///
+    /// # Returns
+    /// The exit status if the process has terminated.
+    ///
+    /// # Errors
+    /// Returns an error if `waitpid` fails.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// if let Some(status) = process.poll_exit()? {
+    ///     println!("inferior {status}");
+    /// }
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn poll_exit(&mut self) -> Result<Option<ExitStatus>> {
+        if self.is_alive() {
+            let status = self.wait_status(Some(WaitPidFlag::WNOHANG));
+            match status.context(WaitpidFailedSnafu {
+                pid: self.pid.as_raw(),
+            })? {
+                WaitStatus::StillAlive => {}
+                status => {
+                    self.invalidate_caches();
+                    self.state = status;
+                }
+            }
+        }
+        Ok(self.exit_status())
+    }
+
+    /// Returns `true` while the process has not terminated.
+    pub const fn is_alive(&self) -> bool {
+        self.exit_status().is_none()
+    }
+
+    /// Returns `true` if the process is killed when this is dropped or the debugger dies,
+    /// `false` if it is detached from and keeps running.
+    pub const fn kills_on_exit(&self) -> bool {
+        self.terminate_on_end
+    }
+
+    /// Chooses whether the process is killed when this is dropped or the debugger dies
+    /// (`PTRACE_O_EXITKILL`), or detached from and left running.
+    ///
+    /// Launched processes are killed by default, attached ones left running.
+    ///
+    /// # Errors
+    /// Returns an error if the ptrace options cannot be changed.
+    pub fn set_kill_on_exit(&mut self, kill: bool) -> Result<()> {
+        self.terminate_on_end = kill;
+        if self.is_alive() {
+            self.set_ptrace_options()?;
+        }
         Ok(())
     }
 }
 
-impl Drop for Process {
+impl<B: Backend> Drop for Process<B> {
+    /// Kills the process, or detaches from it and lets it run, see
+    /// [`Process::set_kill_on_exit`].
     fn drop(&mut self) {
-        if self.pid.as_raw() != 0 {
-            if self.state == WaitStatus::StillAlive {
-                if let Err(_errno) = kill(self.pid, Signal::SIGSTOP) {
-                    #[cfg(feature = "tracing")]
-                    tracing::error!("failed kill with SIGSTOP: {_errno}")
-                };
-                let _ = waitpid(self.pid, None);
-            }
-            if let Err(_errno) = ptrace::detach(self.pid, None) {
+        // Already reaped: the pid may belong to an unrelated process by now.
+        if self.pid.as_raw() == 0 || !self.is_alive() {
+            return;
+        }
+        if self.terminate_on_end {
+            if let Err(_errno) = self.send_signal(Signal::SIGKILL) {
                 #[cfg(feature = "tracing")]
-                tracing::error!("failed detach {_errno}")
+                tracing::error!("failed kill with SIGKILL: {_errno}");
             };
-            if let Err(_errno) = kill(self.pid, Signal::SIGCONT) {
+            let _ = self.wait_on_signal();
+            return;
+        }
+        if self.state == WaitStatus::StillAlive {
+            if let Err(_errno) = self.send_signal(Signal::SIGSTOP) {
                 #[cfg(feature = "tracing")]
-                tracing::error!("failed kill with SIGCONT: {_errno}")
+                tracing::error!("failed kill with SIGSTOP: {_errno}");
             };
+            let _ = self.wait_on_signal();
+        }
+        if let Err(_err) = self.disarm_watchpoints() {
+            #[cfg(feature = "tracing")]
+            tracing::error!("failed to disarm watchpoints: {_err}");
+        }
+        if let Err(_err) = self.flush_registers() {
+            #[cfg(feature = "tracing")]
+            tracing::error!("failed to write back registers: {_err}");
+        }
+        if let Err(_errno) = self.backend.detach(self.pid) {
+            #[cfg(feature = "tracing")]
+            tracing::error!("failed detach {_errno}");
+        };
+        if let Err(_errno) = self.send_signal(Signal::SIGCONT) {
+            #[cfg(feature = "tracing")]
+            tracing::error!("failed kill with SIGCONT: {_errno}");
+        };
+    }
+}
 
-            if self.terminate_on_end {
-                if let Err(_errno) = kill(self.pid, Signal::SIGKILL) {
-                    #[cfg(feature = "tracing")]
-                    tracing::error!("failed kill with SIGKILL: {_errno}")
-                };
-                let _ = waitpid(self.pid, None);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catchpoint::SyscallFilter;
+    use nix::libc::user_regs_struct;
+
+    #[test]
+    fn should_alternate_syscall_entry_and_exit() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.orig_rax = 1; // write
+        let backend = ScriptedBackend::new(regs);
+        backend.push_stop(ScriptedStop::new(WaitStatus::PtraceSyscall(pid)));
+        backend.push_stop(ScriptedStop::new(WaitStatus::PtraceSyscall(pid)));
+        backend.push_stop(ScriptedStop::new(WaitStatus::Exited(pid, 0)));
+        let mut process =
+            Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+        let filter = SyscallFilter {
+            syscalls: vec![1],
+            include: None,
+            exclude: None,
+            on_entry: true,
+            on_exit: true,
+        };
+        process.add_catchpoint(CatchpointKind::Syscall(filter), CatchAction::Stop);
+
+        let mut directions = Vec::new();
+        while process.is_alive() {
+            process.resume()?;
+            directions.extend(process.last_syscall.as_ref().map(|event| event.direction));
         }
+        assert_eq!(
+            directions,
+            [SyscallDirection::Entry, SyscallDirection::Exit]
+        );
+        assert!(!process.backend().requests().contains(&"PTRACE_CONT"));
+        Ok(())
     }
 }
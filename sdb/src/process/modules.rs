@@ -0,0 +1,102 @@
+//! Following the dynamic linker's module list for load and unload catchpoints.
+
+use super::{Backend, Process};
+use crate::catchpoint::Catchpoint;
+use crate::error::{NoFreeDebugRegisterSnafu, Result};
+use crate::procfs::read_maps;
+use crate::rendezvous::{changes, Module, ModuleChange, Rendezvous};
+use crate::watchpoint::{WatchKind, Watchpoint};
+use snafu::OptionExt as _;
+
+/// The module list as last seen, and the hidden watchpoint stopping at its changes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) struct ModuleTracker {
+    rendezvous: Rendezvous,
+    /// Execute watchpoint on the rendezvous breakpoint, not listed with the user's.
+    pub(super) watchpoint: Watchpoint,
+    /// The list when it was last consistent.
+    modules: Vec<Module>,
+}
+
+impl<B: Backend> Process<B> {
+    /// Starts following the module list once a catchpoint needs it.
+    ///
+    /// Statically linked programs have no dynamic linker, so their load and unload
+    /// catchpoints never trigger.
+    ///
+    /// # Errors
+    /// Returns an error if all debug registers are taken or cannot be written.
+    pub(super) fn track_modules(&mut self) -> Result<()> {
+        if self.module_tracker.is_some() || !self.catchpoints.iter().any(Catchpoint::tracks_modules)
+        {
+            return Ok(());
+        }
+        let Some(rendezvous) = Rendezvous::locate(&read_maps(self.pid)?) else {
+            return Ok(());
+        };
+        let register = self
+            .free_debug_register()
+            .context(NoFreeDebugRegisterSnafu)?;
+        // When attaching, the modules loaded so far are not news.
+        let modules = if rendezvous.is_consistent(self)? {
+            rendezvous.modules(self)?
+        } else {
+            Vec::new()
+        };
+        let watchpoint = Watchpoint {
+            id: 0,
+            addr: rendezvous.breakpoint,
+            len: 1,
+            kind: WatchKind::Execute,
+            description: "dynamic linker rendezvous".into(),
+            value: 0,
+            register,
+        };
+        self.module_tracker = Some(ModuleTracker {
+            rendezvous,
+            watchpoint,
+            modules,
+        });
+        let armed = self.arm_debug_register(register, rendezvous.breakpoint);
+        if armed.is_err() {
+            self.module_tracker = None;
+        }
+        armed
+    }
+
+    /// Records the modules loaded or unloaded since the last consistent list in
+    /// [`Self::last_modules`], keeping those matching a catchpoint.
+    pub(super) fn check_modules(&mut self) -> Result<()> {
+        self.at_rendezvous = true;
+        let Some(tracker) = &self.module_tracker else {
+            return Ok(());
+        };
+        let rendezvous = tracker.rendezvous;
+        // Stops before a change only announce it.
+        if !rendezvous.is_consistent(self)? {
+            return Ok(());
+        }
+        let modules = rendezvous.modules(self)?;
+        let Some(tracker) = &mut self.module_tracker else {
+            return Ok(());
+        };
+        let events = changes(&tracker.modules, &modules);
+        tracker.modules = modules;
+        if events
+            .iter()
+            .any(|event| event.change == ModuleChange::Loaded)
+        {
+            // Before the new libraries' initializers run, which may already report errors.
+            self.hook_sanitizers()?;
+        }
+        self.last_modules = events
+            .into_iter()
+            .filter(|event| {
+                self.catchpoints
+                    .iter()
+                    .any(|catchpoint| catchpoint.matches_module(event))
+            })
+            .collect();
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::{ContainerNotFoundSnafu, NsPidNotFoundSnafu, Result};
+use crate::procfs::{self, ProcStatus};
+use nix::unistd::Pid;
+use std::path::{Component, Path, PathBuf};
+
+/// Returns the identifier of the pid namespace `pid` lives in (e.g. `pid:[4026531836]`).
+fn pid_namespace(pid: Pid) -> Option<PathBuf> {
+    std::fs::read_link(procfs::pid_path(pid, "ns/pid")).ok()
+}
+
+/// Returns the pids of `pid` in each nested pid namespace, outermost first (`NSpid`).
+///
+/// # Errors
+/// Returns an error if `/proc/<pid>/status` cannot be read.
+pub fn ns_pids(pid: Pid) -> Result<Vec<i32>> {
+    let status = ProcStatus::read(pid)?;
+    Ok(status
+        .get_ints("NSpid")
+        .unwrap_or_else(|| vec![i64::from(pid.as_raw())])
+        .into_iter()
+        .map(|ns_pid| ns_pid as i32)
+        .collect())
+}
+
+/// Translates a pid as seen inside the pid namespace of `ns_member` into a host pid.
+///
+/// # Errors
+/// Returns an error if no process with `ns_pid` exists in that namespace.
+///
+/// # Example
+/// ```no_run
+/// use nix::unistd::Pid;
+/// use sdb::process::translate_ns_pid;
+///
+/// // pid 1 inside the namespace that host pid 4242 belongs to.
+/// let host_pid = translate_ns_pid(1, Pid::from_raw(4242))?;
+/// # Ok::<(), sdb::error::SdbError>(())
+/// ```
+pub fn translate_ns_pid(ns_pid: i32, ns_member: Pid) -> Result<Pid> {
+    let namespace = pid_namespace(ns_member);
+    procfs::pids()?
+        .into_iter()
+        .filter(|&pid| pid_namespace(pid) == namespace)
+        .find(|&pid| ns_pids(pid).is_ok_and(|pids| pids.last() == Some(&ns_pid)))
+        .ok_or_else(|| {
+            NsPidNotFoundSnafu {
+                ns_pid,
+                ns_member: ns_member.as_raw(),
+            }
+            .build()
+        })
+}
+
+/// Finds a host pid of a process running in the container with the given (possibly
+/// abbreviated) id, by looking for the id in each process' cgroup path.
+///
+/// # Errors
+/// Returns an error if no process belongs to such a container.
+pub fn find_container_process(container_id: &str) -> Result<Pid> {
+    procfs::pids()?
+        .into_iter()
+        .find(|&pid| {
+            procfs::read_to_string(procfs::pid_path(pid, "cgroup"))
+                .is_ok_and(|cgroup| cgroup.contains(container_id))
+        })
+        .ok_or_else(|| {
+            ContainerNotFoundSnafu {
+                id: container_id.to_string(),
+            }
+            .build()
+        })
+}
+
+/// Resolves `path` as seen by `pid` (e.g. a binary inside a container) to a host path
+/// through `/proc/<pid>/root`.
+pub fn host_path(pid: Pid, path: &Path) -> PathBuf {
+    let relative: PathBuf = path
+        .components()
+        .filter(|component| !matches!(component, Component::RootDir))
+        .collect();
+    procfs::pid_path(pid, "root").join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_translate_own_pid() -> Result<()> {
+        let me = Pid::this();
+        let ns_pid = *ns_pids(me)?.last().unwrap_or(&me.as_raw());
+        assert_eq!(translate_ns_pid(ns_pid, me)?, me);
+        Ok(())
+    }
+
+    #[test]
+    fn should_resolve_host_path() {
+        let path = host_path(Pid::from_raw(42), Path::new("/usr/bin/app"));
+        assert_eq!(path, PathBuf::from("/proc/42/root/usr/bin/app"));
+    }
+}
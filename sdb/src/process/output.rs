@@ -0,0 +1,241 @@
+//! The inferior's stdout and stderr, captured through pipes and handed over line by line.
+
+use super::{Backend, Process};
+use crate::error::{CouldNotCreatePipeSnafu, Result};
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::unistd::pipe2;
+use serde::Serialize;
+use snafu::ResultExt as _;
+use std::hash::{Hash, Hasher};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Bytes read from a pipe at once.
+const READ_CHUNK: usize = 4096;
+
+/// A captured stream of the inferior.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl std::fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        })
+    }
+}
+
+/// Called with each line the inferior writes to a captured stream, without the newline.
+pub type OutputHandler = Arc<dyn Fn(OutputStream, &str) + Send + Sync>;
+
+/// The read end of the pipe a stream is redirected to.
+struct Pipe {
+    stream: OutputStream,
+    fd: OwnedFd,
+    /// The last line read so far, and whether the write side was closed.
+    partial: Mutex<(Vec<u8>, bool)>,
+}
+
+/// Pipes the inferior's stdout and stderr are redirected to, and where their lines go.
+#[derive(Clone)]
+pub(super) struct OutputPipes {
+    pipes: Arc<[Pipe; 2]>,
+    handler: Option<OutputHandler>,
+}
+
+impl OutputPipes {
+    /// Creates the pipes.
+    ///
+    /// # Returns
+    /// The pipes and the write ends to make the child's stdout and stderr.
+    ///
+    /// # Errors
+    /// Returns an error if a pipe cannot be created.
+    pub(super) fn open() -> Result<(Self, [OwnedFd; 2])> {
+        let pipe = |stream| -> Result<(Pipe, OwnedFd)> {
+            let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC).context(CouldNotCreatePipeSnafu)?;
+            // Only our end: the inferior's writes must block on a full pipe.
+            fcntl(read_fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+                .context(CouldNotCreatePipeSnafu)?;
+            let pipe = Pipe {
+                stream,
+                fd: read_fd,
+                partial: Mutex::new((Vec::new(), false)),
+            };
+            Ok((pipe, write_fd))
+        };
+        let (stdout, stdout_write) = pipe(OutputStream::Stdout)?;
+        let (stderr, stderr_write) = pipe(OutputStream::Stderr)?;
+        let pipes = Self {
+            pipes: Arc::new([stdout, stderr]),
+            handler: None,
+        };
+        Ok((pipes, [stdout_write, stderr_write]))
+    }
+
+    /// Returns `true` while a stream may still get output.
+    pub(super) fn is_open(&self) -> bool {
+        self.pipes.iter().any(|pipe| !pipe.lock().1)
+    }
+
+    /// Waits up to `timeout` for output, or for `also` to become readable, then hands over
+    /// the complete lines read.
+    pub(super) fn wait(&self, timeout: Duration, also: Option<BorrowedFd<'_>>) {
+        let open: Vec<_> = self.pipes.iter().filter(|pipe| !pipe.lock().1).collect();
+        let mut fds: Vec<_> = open
+            .iter()
+            .map(|pipe| pipe.fd.as_fd())
+            .chain(also)
+            .map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+            .collect();
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        if matches!(poll(&mut fds, timeout), Ok(ready) if ready > 0) {
+            self.read();
+        }
+    }
+
+    /// Hands over all output written so far, including lines without a newline yet, e.g.
+    /// prompts, as the inferior stopped.
+    pub(super) fn flush(&self) {
+        self.read();
+        for pipe in self.pipes.iter() {
+            let line = std::mem::take(&mut pipe.lock().0);
+            if !line.is_empty() {
+                self.hand_over(pipe.stream, &line);
+            }
+        }
+    }
+
+    /// Reads what is available and hands over the complete lines.
+    fn read(&self) {
+        let mut buffer = [0; READ_CHUNK];
+        for pipe in self.pipes.iter() {
+            for line in pipe.read_lines(&mut buffer) {
+                self.hand_over(pipe.stream, &line);
+            }
+        }
+    }
+
+    fn hand_over(&self, stream: OutputStream, line: &[u8]) {
+        if let Some(handler) = &self.handler {
+            handler(stream, &String::from_utf8_lossy(line));
+        }
+    }
+}
+
+impl Pipe {
+    /// Reads what is available through `buffer`, returning the lines it completes without
+    /// their newlines.
+    fn read_lines(&self, buffer: &mut [u8]) -> Vec<Vec<u8>> {
+        let mut partial = self.lock();
+        while !partial.1 {
+            match nix::unistd::read(self.fd.as_raw_fd(), buffer) {
+                Ok(0) => partial.1 = true,
+                Ok(read) => partial.0.extend_from_slice(&buffer[..read]),
+                Err(Errno::EINTR) => {}
+                Err(_) => break, // `EAGAIN`: read everything.
+            }
+        }
+        let mut lines = Vec::new();
+        while let Some(end) = partial.0.iter().position(|&byte| byte == b'\n') {
+            let mut line: Vec<_> = partial.0.drain(..=end).collect();
+            line.pop();
+            lines.push(line);
+        }
+        drop(partial);
+        lines
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (Vec<u8>, bool)> {
+        self.partial
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for OutputPipes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputPipes")
+            .field("open", &self.is_open())
+            .field("handler", &self.handler.is_some())
+            .finish()
+    }
+}
+
+// Where output goes is not part of what a process is.
+impl PartialEq for OutputPipes {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for OutputPipes {}
+
+impl Hash for OutputPipes {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl<B: Backend> Process<B> {
+    /// Returns `true` if the stdout and stderr of the process are captured, see
+    /// [`super::LaunchSpec::capture_output`].
+    pub const fn captures_output(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Makes `handler` get each line the process writes to its captured stdout and stderr,
+    /// as soon as it is written while [`Self::resume`] waits, and the rest at the stop.
+    ///
+    /// Without a handler, the output is read and dropped so the process does not block.
+    pub fn set_output_handler(&mut self, handler: OutputHandler) {
+        if let Some(output) = &mut self.output {
+            output.handler = Some(handler);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::write_all;
+    use std::sync::PoisonError;
+
+    #[test]
+    fn should_split_output_into_lines() -> Result<()> {
+        let (mut pipes, [stdout, stderr]) = OutputPipes::open()?;
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        pipes.handler = Some(Arc::new({
+            let lines = Arc::clone(&lines);
+            move |stream, line: &str| {
+                lines
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(format!("[{stream}] {line}"));
+            }
+        }));
+        write_all(stdout.as_fd(), b"hello\nwor")?;
+        write_all(stderr.as_fd(), b"oops\n")?;
+        pipes.wait(Duration::from_millis(50), None);
+        write_all(stdout.as_fd(), b"ld\nPrompt: ")?;
+        drop((stdout, stderr));
+        pipes.flush();
+        assert!(!pipes.is_open());
+        assert_eq!(
+            *lines.lock().unwrap_or_else(PoisonError::into_inner),
+            [
+                "[stdout] hello",
+                "[stderr] oops",
+                "[stdout] world",
+                "[stdout] Prompt: "
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use super::watchpoints::{page_range, protection};
+use super::{Backend, Process};
+use crate::error::{NoPatchSnafu, PatchOverlappedSnafu, Result, SdbError, WriteProtectedSnafu};
+use crate::patch::Patch;
+use crate::procfs::read_maps;
+use nix::errno::Errno;
+use nix::sys::mman::ProtFlags;
+use snafu::OptionExt as _;
+
+impl<B: Backend> Process<B> {
+    /// Writes `bytes` at `addr`, remembering the original bytes for [`Self::revert_patch`].
+    ///
+    /// Pages ptrace cannot write, e.g. a shared mapping of a memfd without write permission,
+    /// are made writable with an injected `mprotect` for the write and protected again after.
+    ///
+    /// # Returns
+    /// The id of the new patch.
+    ///
+    /// # Errors
+    /// Returns an error if the memory cannot be read, or written even after `mprotect`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// let id = process.patch(0x401000, &[0x90, 0x90])?;
+    /// process.revert_patch(id)?;
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn patch(&mut self, addr: u64, bytes: &[u8]) -> Result<usize> {
+        let original = self.read_memory(addr, bytes.len())?;
+        self.write_code(addr, bytes)?;
+        let id = self.next_patch_id;
+        self.next_patch_id += 1;
+        self.patches.push(Patch {
+            id,
+            addr,
+            original,
+            bytes: bytes.to_vec(),
+        });
+        Ok(id)
+    }
+
+    /// Restores the bytes overwritten by patch `id`.
+    ///
+    /// # Errors
+    /// Returns an error if there is no such patch, if a later patch overlaps it (revert that
+    /// one first), or if the memory cannot be written.
+    pub fn revert_patch(&mut self, id: usize) -> Result<()> {
+        let index = self
+            .patches
+            .iter()
+            .position(|patch| patch.id == id)
+            .context(NoPatchSnafu { id })?;
+        let patch = &self.patches[index];
+        if let Some(later) = self.patches[index + 1..]
+            .iter()
+            .find(|later| later.overlaps(patch))
+        {
+            return PatchOverlappedSnafu {
+                id,
+                later: later.id,
+            }
+            .fail();
+        }
+        let (addr, original) = (patch.addr, patch.original.clone());
+        self.write_code(addr, &original)?;
+        self.patches.remove(index);
+        Ok(())
+    }
+
+    /// Returns the patches in the order they were applied.
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    /// Writes `bytes` at `addr`, making the pages writable for the write if ptrace cannot
+    /// write them as they are.
+    pub(super) fn write_code(&mut self, addr: u64, bytes: &[u8]) -> Result<()> {
+        let err = match self.write_memory(addr, bytes) {
+            Err(
+                err @ SdbError::WriteMemory {
+                    source: Errno::EFAULT | Errno::EIO,
+                    ..
+                },
+            ) => err,
+            result => return result,
+        };
+        let pages = page_range(&(addr..addr + bytes.len() as u64));
+        let maps = read_maps(self.pid)?;
+        let protected: Vec<_> = maps
+            .iter()
+            .filter(|region| !region.write && region.start < pages.end && pages.start < region.end)
+            .collect();
+        let Some(first) = protected.first() else {
+            return Err(err); // Unmapped, or already writable.
+        };
+        let perms = first.perms();
+        let ranges: Vec<_> = protected
+            .iter()
+            .map(|region| {
+                let range = region.start.max(pages.start)..region.end.min(pages.end);
+                (range, protection(region))
+            })
+            .collect();
+        let writable: Vec<_> = ranges
+            .iter()
+            .map(|(range, prot)| (range.clone(), *prot | ProtFlags::PROT_WRITE))
+            .collect();
+        let fail = |reason: String| {
+            WriteProtectedSnafu {
+                addr,
+                perms: &perms,
+                reason,
+            }
+            .build()
+        };
+        self.protect_pages(&writable)
+            .map_err(|source| fail(source.to_string()))?;
+        let written = self.write_memory(addr, bytes);
+        let restored = self.protect_pages(&ranges);
+        written.map_err(|source| fail(source.to_string()))?;
+        restored
+    }
+}
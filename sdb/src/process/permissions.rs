@@ -0,0 +1,108 @@
+use crate::procfs::{self, ProcStatus};
+use nix::unistd::{geteuid, Pid};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// `CAP_SYS_PTRACE` bit in the capability masks of `/proc/<pid>/status`.
+const CAP_SYS_PTRACE: u64 = 1 << 19;
+
+/// Why the kernel refused `PTRACE_ATTACH` with `EPERM`, and how to fix it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttachDenial {
+    /// Another debugger (or tracer like strace) is already attached.
+    AlreadyTraced { tracer: i32 },
+    /// Yama `ptrace_scope` = 1: only descendants may be traced.
+    YamaRestricted,
+    /// Yama `ptrace_scope` = 2: only processes with `CAP_SYS_PTRACE` may trace.
+    YamaAdminOnly,
+    /// Yama `ptrace_scope` = 3: attaching is disabled until reboot.
+    YamaDisabled,
+    /// The target runs as a different user.
+    DifferentUid { target: u32, ours: u32 },
+    /// None of the known causes applied (e.g. an LSM or a non-dumpable target).
+    Unknown,
+}
+
+impl fmt::Display for AttachDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyTraced { tracer } => {
+                write!(
+                    f,
+                    "the process is already traced by pid {tracer}; detach that tracer first"
+                )
+            }
+            Self::YamaRestricted => write!(
+                f,
+                "kernel.yama.ptrace_scope is 1, which only allows tracing descendants; \
+                 run as root/with CAP_SYS_PTRACE, launch the program from sdb instead, \
+                 or `sudo sysctl kernel.yama.ptrace_scope=0`"
+            ),
+            Self::YamaAdminOnly => write!(
+                f,
+                "kernel.yama.ptrace_scope is 2, which requires CAP_SYS_PTRACE; \
+                 run as root or `sudo setcap cap_sys_ptrace=eip <sdb>`"
+            ),
+            Self::YamaDisabled => write!(
+                f,
+                "kernel.yama.ptrace_scope is 3, which disables attaching until reboot"
+            ),
+            Self::DifferentUid { target, ours } => write!(
+                f,
+                "the process runs as uid {target} but sdb runs as uid {ours}; \
+                 run sdb as the same user or with CAP_SYS_PTRACE"
+            ),
+            Self::Unknown => write!(
+                f,
+                "permission denied (the target may be non-dumpable or protected by an LSM)"
+            ),
+        }
+    }
+}
+
+/// Inspects `ptrace_scope`, capabilities and the target's credentials to explain an `EPERM`.
+pub(crate) fn diagnose_attach_denial(pid: Pid) -> AttachDenial {
+    let target = ProcStatus::read(pid).ok();
+
+    if let Some(tracer) = target
+        .as_ref()
+        .and_then(|status| status.get_int("TracerPid"))
+    {
+        if tracer != 0 {
+            return AttachDenial::AlreadyTraced {
+                tracer: tracer as i32,
+            };
+        }
+    }
+
+    let has_cap_sys_ptrace = ProcStatus::read_self()
+        .ok()
+        .and_then(|status| status.get_hex("CapEff"))
+        .is_some_and(|caps| caps & CAP_SYS_PTRACE != 0);
+    if has_cap_sys_ptrace {
+        return AttachDenial::Unknown;
+    }
+
+    let ptrace_scope = procfs::read_to_string("/proc/sys/kernel/yama/ptrace_scope")
+        .ok()
+        .and_then(|scope| scope.trim().parse::<u8>().ok());
+    match ptrace_scope {
+        Some(1) => return AttachDenial::YamaRestricted,
+        Some(2) => return AttachDenial::YamaAdminOnly,
+        Some(3) => return AttachDenial::YamaDisabled,
+        _ => {}
+    }
+
+    let ours = geteuid().as_raw();
+    let target_uids = target.and_then(|status| status.get_ints("Uid"));
+    if let Some(target) =
+        target_uids.and_then(|uids| uids.into_iter().find(|&uid| uid != i64::from(ours)))
+    {
+        return AttachDenial::DifferentUid {
+            target: target as u32,
+            ours,
+        };
+    }
+
+    AttachDenial::Unknown
+}
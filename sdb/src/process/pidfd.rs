@@ -0,0 +1,117 @@
+//! Referring to the process by a pidfd rather than its pid, where the kernel supports it.
+//!
+//! A pid can be reused once its process is reaped, while a pidfd keeps referring to the
+//! process it was opened for. A pidfd also becomes readable when the process exits, so it can
+//! be polled along with other file descriptors.
+
+use super::{Backend, Process};
+use nix::errno::Errno;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitid, Id, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::hash::{Hash, Hasher};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An open pidfd, shared by clones of the process.
+#[derive(Clone, Debug)]
+pub(super) struct PidFd(Arc<OwnedFd>);
+
+impl PidFd {
+    pub(super) fn new(fd: OwnedFd) -> Self {
+        Self(Arc::new(fd))
+    }
+}
+
+// Which descriptor refers to the process is not part of what it is.
+impl PartialEq for PidFd {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PidFd {}
+
+impl Hash for PidFd {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// `pidfd_open(pid, 0)`.
+pub(super) fn open(pid: Pid) -> nix::Result<OwnedFd> {
+    // SAFETY: Takes no pointers; a returned descriptor is new and ours.
+    let fd = Errno::result(unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) })?;
+    // SAFETY: See above.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// `waitid(P_PIDFD)`, reporting like `waitpid` does.
+pub(super) fn wait(pidfd: BorrowedFd<'_>, flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus> {
+    let flags =
+        flags.unwrap_or_else(WaitPidFlag::empty) | WaitPidFlag::WEXITED | WaitPidFlag::WSTOPPED;
+    Ok(match waitid(Id::PIDFd(pidfd), flags)? {
+        // `waitid` reports signal-delivery stops as ptrace events without an event.
+        WaitStatus::PtraceEvent(pid, signal, 0) => WaitStatus::Stopped(pid, signal),
+        status => status,
+    })
+}
+
+/// `pidfd_send_signal(pidfd, signal, NULL, 0)`.
+pub(super) fn send_signal(pidfd: BorrowedFd<'_>, signal: Signal) -> nix::Result<()> {
+    // SAFETY: A null `siginfo` makes it send like `kill`.
+    Errno::result(unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            signal as i32,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    })
+    .map(drop)
+}
+
+impl<B: Backend> Process<B> {
+    /// Returns a pidfd of the process, unless the kernel is too old for them.
+    ///
+    /// It becomes readable when the process exits, so a caller can wait for that in a `poll`
+    /// loop together with, e.g., terminal input. Stops are only reported by `waitpid`.
+    pub fn pidfd(&self) -> Option<BorrowedFd<'_>> {
+        self.pidfd.as_ref().map(|pidfd| pidfd.0.as_fd())
+    }
+
+    /// Opens the pidfd the process is waited for and signalled through from now on, so that
+    /// a reused pid cannot be mistaken for it. Without kernel support, the pid is kept.
+    pub(super) fn open_pidfd(&mut self) {
+        self.pidfd = self.backend.pidfd_open(self.pid).ok().map(PidFd::new);
+    }
+
+    /// Waits for a state change of the process, through its pidfd if it has one.
+    pub(super) fn wait_status(&self, flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus> {
+        self.pidfd().map_or_else(
+            || self.backend.wait(self.pid, flags),
+            |pidfd| self.backend.wait_pidfd(self.pid, pidfd, flags),
+        )
+    }
+
+    /// Sends `signal` to the process, through its pidfd if it has one.
+    pub(super) fn send_signal(&self, signal: Signal) -> nix::Result<()> {
+        self.pidfd().map_or_else(
+            || self.backend.kill(self.pid, signal),
+            |pidfd| self.backend.pidfd_send_signal(self.pid, pidfd, signal),
+        )
+    }
+
+    /// Sleeps for `timeout`, or less if the process exits first.
+    pub(super) fn wait_exit(&self, timeout: Duration) {
+        match self.pidfd() {
+            Some(pidfd) => {
+                let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+                let _ = poll(&mut [PollFd::new(pidfd, PollFlags::POLLIN)], timeout);
+            }
+            None => std::thread::sleep(timeout),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Opt-in log of the ptrace conversation: every request, `waitpid` result and signal delivery.
+//!
+//! Events go to the `ptrace` tracing target, so they only reach the log file when built with
+//! the `tracing` feature. Without it logging is a no-op.
+
+use nix::unistd::Pid;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Target of the logged events, for filtering.
+pub const PTRACE_LOG_TARGET: &str = "ptrace";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns logging of ptrace requests and `waitpid` results on or off.
+pub fn set_ptrace_logging(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether ptrace requests are being logged.
+pub fn ptrace_logging() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Logs `request` made to `pid` and its `result`, passing the result through.
+#[cfg_attr(
+    not(feature = "tracing"),
+    allow(
+        unused_variables,
+        clippy::missing_const_for_fn,
+        reason = "Only logs with the tracing feature."
+    )
+)]
+pub(crate) fn logged<T: Debug, E: Debug>(
+    request: &str,
+    pid: Pid,
+    result: Result<T, E>,
+) -> Result<T, E> {
+    #[cfg(feature = "tracing")]
+    if ptrace_logging() {
+        tracing::info!(target: PTRACE_LOG_TARGET, "{request}(pid={pid}) -> {result:?}");
+    }
+    result
+}
@@ -0,0 +1,284 @@
+use super::{Backend, Process, Ptrace};
+use crate::error::{InspectThreadSnafu, ReadRegistersSnafu, Result, WriteRegistersSnafu};
+use nix::libc::user_regs_struct;
+use nix::sys::wait::WaitPidFlag;
+use nix::unistd::Pid;
+use snafu::ResultExt;
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+
+/// Registers of the current stop, fetched once and written back before the process resumes.
+///
+/// Reads after the first are served from memory, so showing `context` or stepping costs one
+/// `PTRACE_GETREGSET` per stop rather than one per read.
+#[derive(Clone, Debug)]
+pub(super) struct RegisterCache {
+    regs: Cell<Option<user_regs_struct>>,
+    /// Whether `regs` was written and differs from the process's registers.
+    dirty: Cell<bool>,
+}
+
+impl RegisterCache {
+    pub(super) const fn new() -> Self {
+        Self {
+            regs: Cell::new(None),
+            dirty: Cell::new(false),
+        }
+    }
+
+    /// Replaces the cached registers, to be written back by [`Self::take_dirty`].
+    fn write(&self, regs: &user_regs_struct) {
+        self.regs.set(Some(*regs));
+        self.dirty.set(true);
+    }
+
+    /// Returns the registers to write back, if any, marking them clean.
+    fn take_dirty(&self) -> Option<user_regs_struct> {
+        self.dirty.replace(false).then(|| self.regs.get()).flatten()
+    }
+
+    /// Forgets the cached registers, e.g. because the process ran.
+    fn clear(&self) {
+        self.regs.set(None);
+        self.dirty.set(false);
+    }
+}
+
+// A cache is not part of what a process is.
+impl PartialEq for RegisterCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for RegisterCache {}
+
+impl Hash for RegisterCache {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// Returns the general purpose registers as `(name, value)` pairs in display order.
+pub const fn register_values(regs: &user_regs_struct) -> [(&'static str, u64); 27] {
+    [
+        ("rax", regs.rax),
+        ("rbx", regs.rbx),
+        ("rcx", regs.rcx),
+        ("rdx", regs.rdx),
+        ("rsi", regs.rsi),
+        ("rdi", regs.rdi),
+        ("rbp", regs.rbp),
+        ("rsp", regs.rsp),
+        ("r8", regs.r8),
+        ("r9", regs.r9),
+        ("r10", regs.r10),
+        ("r11", regs.r11),
+        ("r12", regs.r12),
+        ("r13", regs.r13),
+        ("r14", regs.r14),
+        ("r15", regs.r15),
+        ("rip", regs.rip),
+        ("eflags", regs.eflags),
+        ("cs", regs.cs),
+        ("ss", regs.ss),
+        ("ds", regs.ds),
+        ("es", regs.es),
+        ("fs", regs.fs),
+        ("gs", regs.gs),
+        ("fs_base", regs.fs_base),
+        ("gs_base", regs.gs_base),
+        ("orig_rax", regs.orig_rax),
+    ]
+}
+
+impl<B: Backend> Process<B> {
+    /// Reads the general purpose registers of the stopped process.
+    ///
+    /// Only the first read of each stop asks the kernel.
+    ///
+    /// # Errors
+    /// Returns an error if the process is not stopped under ptrace.
+    pub fn read_registers(&self) -> Result<user_regs_struct> {
+        if let Some(regs) = self.registers.regs.get() {
+            return Ok(regs);
+        }
+        let regs = self
+            .backend
+            .registers(self.pid)
+            .context(ReadRegistersSnafu {
+                tid: self.pid.as_raw(),
+            })?;
+        self.registers.regs.set(Some(regs));
+        Ok(regs)
+    }
+
+    /// Overwrites the general purpose registers of the stopped process.
+    ///
+    /// The registers are written back when the process resumes.
+    ///
+    /// # Errors
+    /// Currently never fails: errors writing the registers back are reported by the resume.
+    pub fn write_registers(&self, regs: &user_regs_struct) -> Result<()> {
+        self.registers.write(regs);
+        Ok(())
+    }
+
+    /// Writes back registers changed by [`Self::write_registers`], as must be done before
+    /// the process runs.
+    ///
+    /// # Errors
+    /// Returns an error if the process is not stopped under ptrace.
+    pub(super) fn flush_registers(&self) -> Result<()> {
+        let Some(regs) = self.registers.take_dirty() else {
+            return Ok(());
+        };
+        self.backend
+            .set_registers(self.pid, regs)
+            .context(WriteRegistersSnafu {
+                tid: self.pid.as_raw(),
+            })
+    }
+
+    /// Forgets the cached registers after the process ran.
+    pub(super) fn invalidate_registers(&self) {
+        self.registers.clear();
+    }
+
+    /// Reads the registers of thread `tid` of the process.
+    ///
+    /// Only the initial thread is traced, so other threads are briefly seized and
+    /// interrupted with `PTRACE_INTERRUPT`, then detached again, which leaves them running.
+    /// Use [`Self::stop_threads`] to keep them stopped while their memory is read.
+    ///
+    /// # Errors
+    /// Returns an error if the thread cannot be seized or stopped (e.g. it already exited).
+    pub fn thread_registers(&self, tid: Pid) -> Result<user_regs_struct> {
+        if tid == self.pid {
+            return self.read_registers();
+        }
+        let context = InspectThreadSnafu { tid: tid.as_raw() };
+        self.backend.seize(tid).context(context)?;
+        let regs = self.interrupt_thread(tid);
+        let detached = self.backend.detach(tid);
+        let regs = regs.context(context)?;
+        detached.context(context)?;
+        Ok(regs)
+    }
+
+    /// Seizes and interrupts each thread of `tids`, keeping them stopped until the returned
+    /// [`StoppedThreads`] is dropped.
+    ///
+    /// Stacks walked while a thread runs change under the walk, so backtraces of other
+    /// threads have to be taken while they are held like this.
+    pub fn stop_threads(&self, tids: impl IntoIterator<Item = Pid>) -> StoppedThreads<'_, B> {
+        let mut seized = Vec::new();
+        let registers = tids
+            .into_iter()
+            .map(|tid| {
+                if tid == self.pid {
+                    return (tid, self.read_registers());
+                }
+                let context = InspectThreadSnafu { tid: tid.as_raw() };
+                if let Err(err) = self.backend.seize(tid).context(context) {
+                    return (tid, Err(err));
+                }
+                seized.push(tid);
+                (tid, self.interrupt_thread(tid).context(context))
+            })
+            .collect();
+        StoppedThreads {
+            process: self,
+            seized,
+            registers,
+        }
+    }
+
+    /// Stops the seized thread `tid` with `PTRACE_INTERRUPT` and reads its registers.
+    fn interrupt_thread(&self, tid: Pid) -> nix::Result<user_regs_struct> {
+        self.backend
+            .interrupt(tid)
+            .and_then(|()| self.backend.wait(tid, Some(WaitPidFlag::__WALL)))
+            .and_then(|_| self.backend.registers(tid))
+    }
+}
+
+/// Threads of a process held in a `PTRACE_INTERRUPT` stop, see [`Process::stop_threads`].
+///
+/// The threads are detached, and so run again, when this is dropped.
+#[derive(Debug)]
+pub struct StoppedThreads<'a, B: Backend = Ptrace> {
+    process: &'a Process<B>,
+    /// Threads other than the traced one, which have to be detached again.
+    seized: Vec<Pid>,
+    /// Registers of each thread, or why it could not be stopped.
+    registers: Vec<(Pid, Result<user_regs_struct>)>,
+}
+
+impl<B: Backend> StoppedThreads<'_, B> {
+    /// Returns the registers of each thread, in the order they were asked for.
+    pub fn registers(&self) -> &[(Pid, Result<user_regs_struct>)] {
+        &self.registers
+    }
+}
+
+impl<B: Backend> Drop for StoppedThreads<'_, B> {
+    fn drop(&mut self) {
+        for &tid in &self.seized {
+            if let Err(_errno) = self.process.backend.detach(tid) {
+                #[cfg(feature = "tracing")]
+                tracing::error!("failed to detach thread {tid}: {_errno}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::ScriptedBackend;
+    use nix::sys::signal::Signal;
+    use nix::sys::wait::WaitStatus;
+
+    #[test]
+    fn should_only_detach_threads_it_seized() {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = 0x401000;
+        let process = Process::with_backend(
+            ScriptedBackend::new(regs),
+            pid,
+            WaitStatus::Stopped(pid, Signal::SIGTRAP),
+        );
+
+        let stopped = process.stop_threads([pid, Pid::from_raw(i32::MAX - 1)]);
+        let rips: Vec<_> = stopped
+            .registers()
+            .iter()
+            .map(|(_, regs)| regs.as_ref().ok().map(|regs| regs.rip))
+            .collect();
+        assert_eq!(rips, [Some(0x401000), None]);
+        drop(stopped);
+        // The fake has no other threads, so seizing fails and nothing is detached.
+        assert_eq!(
+            process.backend().requests(),
+            ["PTRACE_GETREGSET", "PTRACE_SEIZE"]
+        );
+    }
+
+    #[test]
+    fn should_write_back_only_changed_registers() {
+        let cache = RegisterCache::new();
+        assert_eq!(cache.take_dirty().map(|regs| regs.rip), None);
+
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = 0x401000;
+        cache.write(&regs);
+        assert_eq!(cache.regs.get().map(|regs| regs.rip), Some(0x401000));
+        assert_eq!(cache.take_dirty().map(|regs| regs.rip), Some(0x401000));
+        assert_eq!(cache.take_dirty().map(|regs| regs.rip), None);
+
+        cache.clear();
+        assert_eq!(cache.regs.get().map(|regs| regs.rip), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Stopping at sanitizer reports for sanitizer catchpoints.
+
+use super::{Backend, Process};
+use crate::catchpoint::Catchpoint;
+use crate::error::{NoFreeDebugRegisterSnafu, Result};
+use crate::procfs::{read_maps, MemoryRegion};
+use crate::sanitizer::{BadAccess, Sanitizer, SanitizerReport};
+use crate::symbols::SymbolTable;
+use crate::watchpoint::{WatchKind, Watchpoint};
+use nix::sys::mman::ProtFlags;
+use snafu::OptionExt as _;
+use std::path::Path;
+
+/// Longest report description read.
+const DESCRIPTION_MAX: usize = 256;
+/// Size of the scratch memory the ThreadSanitizer getters write to.
+const SCRATCH_LEN: u64 = 0x1000;
+
+/// A hidden execute watchpoint on the report hook of a sanitizer runtime.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) struct SanitizerHook {
+    pub(super) sanitizer: Sanitizer,
+    pub(super) watchpoint: Watchpoint,
+}
+
+impl<B: Backend> Process<B> {
+    /// Hooks the sanitizer runtimes loaded so far while a sanitizer catchpoint exists, and
+    /// unhooks them once none does.
+    ///
+    /// # Errors
+    /// Returns an error if all debug registers are taken or cannot be written.
+    pub(super) fn hook_sanitizers(&mut self) -> Result<()> {
+        if !self.catchpoints.iter().any(Catchpoint::catches_sanitizers) {
+            if !self.sanitizer_hooks.is_empty() {
+                self.sanitizer_hooks.clear();
+                self.update_debug_control()?;
+            }
+            return Ok(());
+        }
+        let missing: Vec<_> = Sanitizer::ALL
+            .into_iter()
+            .filter(|&sanitizer| {
+                !self
+                    .sanitizer_hooks
+                    .iter()
+                    .any(|hook| hook.sanitizer == sanitizer)
+            })
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let maps = read_maps(self.pid)?;
+        for sanitizer in missing {
+            let Some(addr) = function_address(&maps, sanitizer.hook()) else {
+                continue;
+            };
+            let register = self
+                .free_debug_register()
+                .context(NoFreeDebugRegisterSnafu)?;
+            self.sanitizer_hooks.push(SanitizerHook {
+                sanitizer,
+                watchpoint: Watchpoint {
+                    id: 0,
+                    addr,
+                    len: 1,
+                    kind: WatchKind::Execute,
+                    description: sanitizer.hook().into(),
+                    value: 0,
+                    register,
+                },
+            });
+            if let Err(err) = self.arm_debug_register(register, addr) {
+                self.sanitizer_hooks.pop();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the report `sanitizer` is making in [`Self::last_sanitizer_report`],
+    /// asking its runtime for the details.
+    pub(super) fn check_sanitizer(&mut self, sanitizer: Sanitizer) -> Result<()> {
+        let maps = read_maps(self.pid)?;
+        let report = match sanitizer {
+            Sanitizer::Address => self.address_report(&maps)?,
+            Sanitizer::Thread => self.thread_report(&maps)?,
+        };
+        self.last_sanitizer_report = Some(report);
+        Ok(())
+    }
+
+    /// Reads the report from the `__asan_get_report_*` functions.
+    fn address_report(&mut self, maps: &[MemoryRegion]) -> Result<SanitizerReport> {
+        let description = self.call_runtime(maps, "__asan_get_report_description", &[])?;
+        let addr = self.call_runtime(maps, "__asan_get_report_address", &[])?;
+        let access = if addr == 0 {
+            None
+        } else {
+            let write = self.call_runtime(maps, "__asan_get_report_access_type", &[])?;
+            let size = self.call_runtime(maps, "__asan_get_report_access_size", &[])?;
+            Some(BadAccess {
+                addr,
+                size,
+                write: write as u32 != 0,
+            })
+        };
+        let pc = self.call_runtime(maps, "__asan_get_report_pc", &[])?;
+        Ok(SanitizerReport {
+            sanitizer: Sanitizer::Address,
+            description: self.read_description(description),
+            access,
+            pc: (pc != 0).then_some(pc),
+        })
+    }
+
+    /// Reads the report passed to `__tsan_on_report` with `__tsan_get_report_data` and
+    /// `__tsan_get_report_mop`, which write into scratch memory.
+    fn thread_report(&mut self, maps: &[MemoryRegion]) -> Result<SanitizerReport> {
+        let report = self.read_registers()?.rdi;
+        let scratch =
+            self.allocate_memory(SCRATCH_LEN, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)?;
+        let result = self.read_thread_report(maps, report, scratch);
+        self.free_memory(scratch, SCRATCH_LEN)?;
+        result
+    }
+
+    fn read_thread_report(
+        &mut self,
+        maps: &[MemoryRegion],
+        report: u64,
+        scratch: u64,
+    ) -> Result<SanitizerReport> {
+        // description, then the `int` counts: reports, stacks, memory operations, locations,
+        // mutexes, threads and unique thread ids. No sleep trace is asked for.
+        let counts: Vec<_> = (0..7).map(|i| scratch + 8 + 4 * i).collect();
+        let mut args = vec![report, scratch];
+        args.extend(&counts);
+        args.extend([scratch + 64, 0]);
+        self.call_runtime(maps, "__tsan_get_report_data", &args)?;
+        let description = self.read_memory(scratch, 8)?;
+        let description = u64::from_le_bytes(description.try_into().unwrap_or_default());
+        let mop_count = self.read_memory(counts[2], 4)?;
+        let mop_count = u32::from_le_bytes(mop_count.try_into().unwrap_or_default());
+
+        let mut access = None;
+        let mut pc = None;
+        if mop_count > 0 {
+            // Thread id, address, size, write, atomic, and the top frame of the stack.
+            let fields = scratch + 128;
+            let args = [
+                report,
+                0,
+                fields,
+                fields + 8,
+                fields + 16,
+                fields + 20,
+                fields + 24,
+                fields + 32,
+                1,
+            ];
+            self.call_runtime(maps, "__tsan_get_report_mop", &args)?;
+            let words = self.read_words(fields, 5)?;
+            if let &[_, addr, size_write, _, frame] = words.as_slice() {
+                access = Some(BadAccess {
+                    addr,
+                    size: size_write & 0xffff_ffff,
+                    write: size_write >> 32 != 0,
+                });
+                pc = (frame != 0).then_some(frame);
+            }
+        }
+        Ok(SanitizerReport {
+            sanitizer: Sanitizer::Thread,
+            description: self.read_description(description),
+            access,
+            pc,
+        })
+    }
+
+    /// Calls the runtime function `name`, or returns 0 if the runtime lacks it.
+    fn call_runtime(&mut self, maps: &[MemoryRegion], name: &str, args: &[u64]) -> Result<u64> {
+        function_address(maps, name).map_or(Ok(0), |addr| self.call_function(addr, args))
+    }
+
+    /// Reads the C string at `addr`, or `unknown` if there is none.
+    fn read_description(&self, addr: u64) -> String {
+        let bytes = if addr == 0 {
+            Vec::new()
+        } else {
+            self.read_memory(addr, DESCRIPTION_MAX).unwrap_or_default()
+        };
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        if end == 0 {
+            return "unknown".into();
+        }
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+}
+
+/// Finds the function `name` in the program or a loaded library.
+fn function_address(maps: &[MemoryRegion], name: &str) -> Option<u64> {
+    maps.iter()
+        .filter(|region| Path::new(&region.path).is_absolute())
+        .find_map(|region| {
+            let table = SymbolTable::cached(Path::new(&region.path)).ok()?;
+            let bias = table.load_bias(region)?;
+            table
+                .symbols()
+                .iter()
+                .find(|symbol| symbol.is_function && symbol.name == name)
+                .map(|symbol| symbol.address.wrapping_add(bias))
+        })
+}
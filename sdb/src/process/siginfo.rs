@@ -0,0 +1,153 @@
+use super::{Backend, Process};
+use crate::error::{GetSigInfoSnafu, Result};
+use nix::libc;
+use nix::sys::signal::Signal;
+use serde::Serialize;
+use snafu::ResultExt;
+use std::fmt;
+
+// Generic `si_code` values (not exported by `libc` for Linux).
+const SI_USER: i32 = 0;
+const SI_KERNEL: i32 = 0x80;
+const SI_QUEUE: i32 = -1;
+const SI_TKILL: i32 = -6;
+
+/// Decoded `siginfo_t` of the signal the process is stopped with.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct SignalInfo {
+    /// The signal number.
+    pub signo: i32,
+    /// Raw `si_code`.
+    pub code: i32,
+    /// Faulting address for `SIGSEGV`, `SIGBUS`, `SIGILL`, `SIGFPE` and `SIGTRAP`.
+    pub fault_addr: Option<u64>,
+}
+
+impl SignalInfo {
+    /// Decodes `info`, as fetched with `PTRACE_GETSIGINFO`.
+    pub(super) fn from_siginfo(info: &libc::siginfo_t) -> Self {
+        let mut signal_info = Self {
+            signo: info.si_signo,
+            code: info.si_code,
+            fault_addr: None,
+        };
+        if matches!(
+            info.si_signo,
+            libc::SIGSEGV | libc::SIGBUS | libc::SIGFPE | libc::SIGILL | libc::SIGTRAP
+        ) && info.si_code > 0
+        {
+            // SAFETY: `si_addr` is the active union member for these kernel generated signals.
+            signal_info.fault_addr = Some(unsafe { info.si_addr() } as u64);
+        }
+        signal_info
+    }
+
+    /// Returns the signal, if it is a known one.
+    pub fn signal(&self) -> Option<Signal> {
+        Signal::try_from(self.signo).ok()
+    }
+
+    /// Returns the symbolic name and description of `si_code`, e.g.
+    /// `("SEGV_MAPERR", "address not mapped to object")`.
+    pub const fn code_name(&self) -> Option<(&'static str, &'static str)> {
+        // Generic codes first: they apply to every signal.
+        let generic = match self.code {
+            SI_USER => Some(("SI_USER", "sent by kill")),
+            SI_KERNEL => Some(("SI_KERNEL", "sent by the kernel")),
+            SI_QUEUE => Some(("SI_QUEUE", "sent by sigqueue")),
+            SI_TKILL => Some(("SI_TKILL", "sent by tkill/tgkill")),
+            _ => None,
+        };
+        if generic.is_some() {
+            return generic;
+        }
+
+        match (self.signo, self.code) {
+            (libc::SIGSEGV, 1) => Some(("SEGV_MAPERR", "address not mapped to object")),
+            (libc::SIGSEGV, 2) => Some(("SEGV_ACCERR", "invalid permissions for mapped object")),
+            (libc::SIGSEGV, 3) => Some(("SEGV_BNDERR", "failed address bound checks")),
+            (libc::SIGSEGV, 4) => Some(("SEGV_PKUERR", "access denied by protection keys")),
+            (libc::SIGBUS, 1) => Some(("BUS_ADRALN", "invalid address alignment")),
+            (libc::SIGBUS, 2) => Some(("BUS_ADRERR", "nonexistent physical address")),
+            (libc::SIGBUS, 3) => Some(("BUS_OBJERR", "object-specific hardware error")),
+            (libc::SIGBUS, 4) => Some(("BUS_MCEERR_AR", "hardware memory error consumed")),
+            (libc::SIGBUS, 5) => Some(("BUS_MCEERR_AO", "hardware memory error detected")),
+            (libc::SIGFPE, 1) => Some(("FPE_INTDIV", "integer divide by zero")),
+            (libc::SIGFPE, 2) => Some(("FPE_INTOVF", "integer overflow")),
+            (libc::SIGFPE, 3) => Some(("FPE_FLTDIV", "floating-point divide by zero")),
+            (libc::SIGFPE, 4) => Some(("FPE_FLTOVF", "floating-point overflow")),
+            (libc::SIGFPE, 5) => Some(("FPE_FLTUND", "floating-point underflow")),
+            (libc::SIGFPE, 6) => Some(("FPE_FLTRES", "floating-point inexact result")),
+            (libc::SIGFPE, 7) => Some(("FPE_FLTINV", "invalid floating-point operation")),
+            (libc::SIGFPE, 8) => Some(("FPE_FLTSUB", "subscript out of range")),
+            (libc::SIGILL, 1) => Some(("ILL_ILLOPC", "illegal opcode")),
+            (libc::SIGILL, 2) => Some(("ILL_ILLOPN", "illegal operand")),
+            (libc::SIGILL, 3) => Some(("ILL_ILLADR", "illegal addressing mode")),
+            (libc::SIGILL, 4) => Some(("ILL_ILLTRP", "illegal trap")),
+            (libc::SIGILL, 5) => Some(("ILL_PRVOPC", "privileged opcode")),
+            (libc::SIGILL, 6) => Some(("ILL_PRVREG", "privileged register")),
+            (libc::SIGILL, 7) => Some(("ILL_COPROC", "coprocessor error")),
+            (libc::SIGILL, 8) => Some(("ILL_BADSTK", "internal stack error")),
+            (libc::SIGTRAP, 1) => Some(("TRAP_BRKPT", "process breakpoint")),
+            (libc::SIGTRAP, 2) => Some(("TRAP_TRACE", "process trace trap")),
+            (libc::SIGTRAP, 4) => Some(("TRAP_HWBKPT", "hardware breakpoint/watchpoint")),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` for signals raised by a faulting instruction.
+    pub const fn is_fault(&self) -> bool {
+        matches!(
+            self.signo,
+            libc::SIGSEGV | libc::SIGBUS | libc::SIGFPE | libc::SIGILL
+        ) && self.code > 0
+    }
+}
+
+impl fmt::Display for SignalInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.signal() {
+            Some(signal) => write!(f, "{signal}")?,
+            None => write!(f, "signal {}", self.signo)?,
+        }
+        match self.code_name() {
+            Some((name, description)) => write!(f, " ({name}: {description})")?,
+            None => write!(f, " (si_code {})", self.code)?,
+        }
+        if let Some(addr) = self.fault_addr {
+            write!(f, " at fault address {addr:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Backend> Process<B> {
+    /// Fetches the `siginfo_t` of the signal the process is stopped with (`PTRACE_GETSIGINFO`).
+    ///
+    /// # Errors
+    /// Returns an error if the process is not in a signal-delivery-stop.
+    pub fn signal_info(&self) -> Result<SignalInfo> {
+        self.backend.signal_info(self.pid).context(GetSigInfoSnafu {
+            pid: self.pid.as_raw(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_segfault() {
+        let info = SignalInfo {
+            signo: libc::SIGSEGV,
+            code: 1,
+            fault_addr: Some(0),
+        };
+        assert!(info.is_fault());
+        assert_eq!(
+            info.to_string(),
+            "SIGSEGV (SEGV_MAPERR: address not mapped to object) at fault address 0x0"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Interrupting the process when it runs for too long, to find out where it hangs.
+
+use super::ptrace_log::logged;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// A thread sending `SIGSTOP` to the process unless stopped within a timeout.
+///
+/// Sending a signal needs no ptrace, so unlike waiting this works from another thread, and
+/// the debugger can keep blocking in `waitpid`.
+pub(super) struct Watchdog {
+    /// Dropped to stop the thread.
+    stop: Sender<()>,
+    fired: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Watchdog {
+    pub(super) fn start(pid: Pid, timeout: Duration) -> Self {
+        let (stop, stopped) = channel();
+        let fired = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let fired = Arc::clone(&fired);
+            move || {
+                if stopped.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                    fired.store(true, Ordering::Relaxed);
+                    let _ = logged("kill(SIGSTOP)", pid, kill(pid, Signal::SIGSTOP));
+                }
+            }
+        });
+        Self {
+            stop,
+            fired,
+            thread,
+        }
+    }
+
+    /// Stops the thread.
+    ///
+    /// # Returns
+    /// `true` if the timeout expired first, so a `SIGSTOP` is on its way.
+    pub(super) fn stop(self) -> bool {
+        drop(self.stop);
+        let _ = self.thread.join();
+        self.fired.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_only_fire_after_timeout() {
+        // Pid 0 would signal our own process group, so use one that cannot exist.
+        let pid = Pid::from_raw(i32::MAX);
+        assert!(!Watchdog::start(pid, Duration::from_secs(60)).stop());
+        let watchdog = Watchdog::start(pid, Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(50));
+        assert!(watchdog.stop());
+    }
+}
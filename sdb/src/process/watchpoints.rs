@@ -0,0 +1,523 @@
+use super::{Backend, Process};
+use crate::error::{
+    DebugRegisterSnafu, ExecutingInRangeSnafu, NoCodeInRangeSnafu, NoFreeDebugRegisterSnafu,
+    NoWatchpointSnafu, NoWritableInRangeSnafu, Result, UnwatchableSnafu,
+};
+use crate::procfs::{read_maps, MemoryRegion};
+use crate::watchpoint::{
+    dr7, is_watchable, RangeWatch, RangeWatchKind, WatchKind, Watchpoint, WatchpointHit,
+    DEBUG_REGISTER_COUNT,
+};
+use nix::libc;
+use nix::sys::mman::ProtFlags;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use snafu::{OptionExt as _, ResultExt as _};
+use std::mem::offset_of;
+use std::ops::Range;
+
+/// Debug status register, telling which watchpoint triggered.
+const DR6: usize = 6;
+/// Debug control register, enabling the watchpoints.
+const DR7: usize = 7;
+/// Granularity of memory protection.
+const PAGE_SIZE: u64 = 0x1000;
+/// `si_code` of a `SIGSEGV` caused by accessing memory without the needed permission.
+const SEGV_ACCERR: i32 = 2;
+
+impl<B: Backend> Process<B> {
+    /// Watches `len` bytes at `addr` with a free debug register of the main thread.
+    ///
+    /// [`WatchKind::Execute`] watchpoints stop before the instruction at `addr` runs, and
+    /// need a `len` of 1.
+    ///
+    /// # Returns
+    /// The id of the new watchpoint.
+    ///
+    /// # Errors
+    /// Returns an error if `len` is not 1, 2, 4 or 8, `addr` is not aligned to it, all four
+    /// debug registers are taken, or the registers cannot be written.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    /// use sdb::watchpoint::WatchKind;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// let id = process.add_watchpoint(0x404018, 8, WatchKind::Write, "counter".into())?;
+    /// process.resume()?;
+    /// if let Some(hit) = process.last_watchpoint {
+    ///     println!("watchpoint {}: {:#x} -> {:#x}", hit.id, hit.old, hit.new);
+    /// }
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn add_watchpoint(
+        &mut self,
+        addr: u64,
+        len: usize,
+        kind: WatchKind,
+        description: String,
+    ) -> Result<usize> {
+        if !is_watchable(addr, len, kind) {
+            return UnwatchableSnafu { addr, len }.fail();
+        }
+        let register = self
+            .free_debug_register()
+            .context(NoFreeDebugRegisterSnafu)?;
+        let id = self.next_watchpoint_id;
+        let watchpoint = Watchpoint {
+            id,
+            addr,
+            len,
+            kind,
+            description,
+            value: self.read_watched(addr, len),
+            register,
+        };
+        self.watchpoints.push(watchpoint);
+        if let Err(err) = self.arm_debug_register(register, addr) {
+            self.watchpoints.pop();
+            return Err(err);
+        }
+        self.next_watchpoint_id += 1;
+        Ok(id)
+    }
+
+    /// Stops the next time any instruction in `range` is executed, e.g. to find out when a
+    /// library is first entered, by removing execute permission from its pages.
+    ///
+    /// The watch triggers once and restores the pages' permissions; the stop is reported in
+    /// [`Self::last_watchpoint`] with `new` set to the address executed.
+    ///
+    /// # Returns
+    /// The id of the new watch, numbered along with the watchpoints.
+    ///
+    /// # Errors
+    /// Returns an error if `range` has no executable pages, the program is executing inside
+    /// it, or the permissions cannot be changed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sdb::process::Process;
+    ///
+    /// let mut process = Process::attach(12345)?;
+    /// let id = process.add_range_watch(0x7f00_0000_0000..0x7f00_0001_0000, "libfoo".into())?;
+    /// process.resume()?;
+    /// if let Some(hit) = process.last_watchpoint {
+    ///     println!("entered at {:#x}", hit.new);
+    /// }
+    /// # Ok::<(), sdb::error::SdbError>(())
+    /// ```
+    pub fn add_range_watch(&mut self, range: Range<u64>, description: String) -> Result<usize> {
+        let range = page_range(&range);
+        let pc = self.read_registers()?.rip;
+        if range.contains(&pc) {
+            return ExecutingInRangeSnafu { addr: pc }.fail();
+        }
+        let pages = self.pages_in(&range, |region| region.execute)?;
+        if pages.is_empty() {
+            return NoCodeInRangeSnafu {
+                start: range.start,
+                end: range.end,
+            }
+            .fail();
+        }
+        self.arm_range_watch(range, RangeWatchKind::Enter, description, pages)
+    }
+
+    /// Stops at every write to `range`, e.g. a whole heap or data section too large for
+    /// hardware watchpoints, by removing write permission from its pages.
+    ///
+    /// The stop comes before the write and is reported in [`Self::last_watchpoint`] with `new`
+    /// set to the address written. The next resume lets the write through, by making its page
+    /// writable for one step. The kernel does not trap, so syscalls such as `read` into the
+    /// range fail with `EFAULT` instead.
+    ///
+    /// # Returns
+    /// The id of the new watch, numbered along with the watchpoints.
+    ///
+    /// # Errors
+    /// Returns an error if `range` has no writable pages, or the permissions cannot be changed.
+    pub fn add_write_watch(&mut self, range: Range<u64>, description: String) -> Result<usize> {
+        let range = page_range(&range);
+        let pages = self.pages_in(&range, |region| region.write)?;
+        if pages.is_empty() {
+            return NoWritableInRangeSnafu {
+                start: range.start,
+                end: range.end,
+            }
+            .fail();
+        }
+        self.arm_range_watch(range, RangeWatchKind::Write, description, pages)
+    }
+
+    /// Returns the parts of the mappings in `range` that pass `filter`, with their protection.
+    fn pages_in(
+        &self,
+        range: &Range<u64>,
+        filter: impl Fn(&MemoryRegion) -> bool,
+    ) -> Result<Vec<(Range<u64>, ProtFlags)>> {
+        Ok(read_maps(self.pid)?
+            .iter()
+            .filter(|region| filter(region))
+            .filter_map(|region| {
+                let start = region.start.max(range.start);
+                let end = region.end.min(range.end);
+                (start < end).then(|| (start..end, protection(region)))
+            })
+            .collect())
+    }
+
+    /// Removes the permission `kind` traps from `pages`, and adds the watch.
+    fn arm_range_watch(
+        &mut self,
+        range: Range<u64>,
+        kind: RangeWatchKind,
+        description: String,
+        pages: Vec<(Range<u64>, ProtFlags)>,
+    ) -> Result<usize> {
+        let armed: Vec<_> = pages
+            .iter()
+            .map(|(range, prot)| (range.clone(), *prot - kind.trapped()))
+            .collect();
+        self.protect_pages(&armed)?;
+        let id = self.next_watchpoint_id;
+        self.range_watches.push(RangeWatch {
+            id,
+            kind,
+            range,
+            description,
+            pages,
+        });
+        self.next_watchpoint_id += 1;
+        Ok(id)
+    }
+
+    /// Returns the range watches, oldest first, including triggered ones.
+    pub fn range_watches(&self) -> &[RangeWatch] {
+        &self.range_watches
+    }
+
+    /// Removes the watchpoint or range watch with `id`, freeing its debug register or
+    /// restoring its pages' permissions.
+    ///
+    /// # Errors
+    /// Returns an error if there is no such watchpoint or the registers cannot be written.
+    pub fn remove_watchpoint(&mut self, id: usize) -> Result<()> {
+        if let Some(index) = self.range_watches.iter().position(|watch| watch.id == id) {
+            let pages = std::mem::take(&mut self.range_watches[index].pages);
+            self.protect_pages(&pages)?;
+            self.range_watches.remove(index);
+            return Ok(());
+        }
+        let index = self
+            .watchpoints
+            .iter()
+            .position(|watchpoint| watchpoint.id == id)
+            .context(NoWatchpointSnafu { id })?;
+        let watchpoint = self.watchpoints.remove(index);
+        let result = self.update_debug_control();
+        if result.is_err() {
+            self.watchpoints.insert(index, watchpoint);
+        }
+        result
+    }
+
+    /// Returns the watchpoints, oldest first.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Finds which watchpoint, if any, caused the current `SIGTRAP` stop, or which range watch
+    /// caused the current `SIGSEGV` stop, and records it in [`Self::last_watchpoint`].
+    ///
+    /// A stop at the dynamic linker's rendezvous is recorded in [`Self::last_modules`]
+    /// instead, one at a registration of JIT code in [`Self::jit_objects`], and one at a
+    /// sanitizer's report in [`Self::last_sanitizer_report`].
+    pub(super) fn check_watchpoints(&mut self) -> Result<()> {
+        self.last_watchpoint = None;
+        self.last_modules.clear();
+        self.at_rendezvous = false;
+        self.at_jit_registration = false;
+        self.last_sanitizer_report = None;
+        if matches!(self.state, WaitStatus::Stopped(_, Signal::SIGSEGV)) {
+            return self.check_range_watches();
+        }
+        if (self.watchpoints.is_empty() && self.hidden_watchpoints().next().is_none())
+            || !matches!(self.state, WaitStatus::Stopped(_, Signal::SIGTRAP))
+        {
+            return Ok(());
+        }
+        let status = self.read_debug_register(DR6)?;
+        self.clear_debug_status()?;
+        let Some(index) = self
+            .watchpoints
+            .iter()
+            .position(|watchpoint| status & (1 << watchpoint.register) != 0)
+        else {
+            if let Some(tracker) = &self.module_tracker {
+                if status & (1 << tracker.watchpoint.register) != 0 {
+                    return self.check_modules();
+                }
+            }
+            if let Some(tracker) = &self.jit_tracker {
+                if status & (1 << tracker.watchpoint.register) != 0 {
+                    return self.check_jit();
+                }
+            }
+            let hook = self
+                .sanitizer_hooks
+                .iter()
+                .find(|hook| status & (1 << hook.watchpoint.register) != 0);
+            if let Some(sanitizer) = hook.map(|hook| hook.sanitizer) {
+                return self.check_sanitizer(sanitizer);
+            }
+            return Ok(());
+        };
+        let (addr, len) = (self.watchpoints[index].addr, self.watchpoints[index].len);
+        let new = self.read_watched(addr, len);
+        let watchpoint = &mut self.watchpoints[index];
+        self.last_watchpoint = Some(WatchpointHit {
+            id: watchpoint.id,
+            old: watchpoint.value,
+            new,
+        });
+        watchpoint.value = new;
+        Ok(())
+    }
+
+    /// Finds the range watch whose pages the `SIGSEGV` comes from. An entered watch is
+    /// disarmed, and a write is let through by the next resume. The signal is then dropped by
+    /// the next resume.
+    fn check_range_watches(&mut self) -> Result<()> {
+        if !self.range_watches.iter().any(RangeWatch::is_armed) {
+            return Ok(());
+        }
+        let info = self.signal_info()?;
+        let Some(addr) = info.fault_addr.filter(|_| info.code == SEGV_ACCERR) else {
+            return Ok(());
+        };
+        let pc = self.read_registers()?.rip;
+        let Some(index) = self.range_watches.iter().position(|watch| {
+            // Fetching an instruction faults at the program counter.
+            let caught = watch.kind == RangeWatchKind::Write || addr == pc;
+            caught && watch.pages.iter().any(|(pages, _)| pages.contains(&addr))
+        }) else {
+            return Ok(());
+        };
+        let watch = &mut self.range_watches[index];
+        let id = watch.id;
+        match watch.kind {
+            RangeWatchKind::Enter => {
+                let pages = std::mem::take(&mut watch.pages);
+                self.protect_pages(&pages)?;
+            }
+            RangeWatchKind::Write => self.pending_write = Some(addr),
+        }
+        self.last_watchpoint = Some(WatchpointHit {
+            id,
+            old: addr,
+            new: addr,
+        });
+        Ok(())
+    }
+
+    /// Runs the write a write watch stopped before, with its page writable for one step.
+    pub(super) fn step_over_watched_write(&mut self) -> Result<()> {
+        let Some(addr) = self.pending_write.take() else {
+            return Ok(());
+        };
+        let Some(prot) = self
+            .range_watches
+            .iter()
+            .filter(|watch| watch.kind == RangeWatchKind::Write)
+            .flat_map(|watch| &watch.pages)
+            .find(|(pages, _)| pages.contains(&addr))
+            .map(|(_, prot)| *prot)
+        else {
+            return Ok(()); // Deleted meanwhile.
+        };
+        let page = page_range(&(addr..addr + 1));
+        self.protect_pages(&[(page.clone(), prot)])?;
+        let stepped = self.step_instruction();
+        if self.is_alive() {
+            self.protect_pages(&[(page, prot - ProtFlags::PROT_WRITE)])?;
+        }
+        stepped
+    }
+
+    /// Sets the protection of each range of `pages`.
+    ///
+    /// The `mprotect` calls are injected at the start of some other executable mapping, as
+    /// the code at the program counter may be in a page without execute permission.
+    pub(super) fn protect_pages(&mut self, pages: &[(Range<u64>, ProtFlags)]) -> Result<()> {
+        let saved = self.read_registers()?;
+        let site = read_maps(self.pid)?
+            .iter()
+            // The kernel emulates `[vsyscall]`, whose code cannot be patched.
+            .find(|region| region.execute && region.path != "[vsyscall]")
+            .map_or(saved.rip, |region| region.start);
+        let mut moved = saved;
+        moved.rip = site;
+        self.write_registers(&moved)?;
+        let result = pages.iter().try_for_each(|(range, prot)| {
+            self.protect_memory(range.start, range.end - range.start, *prot)
+        });
+        self.write_registers(&saved)?;
+        result
+    }
+
+    /// Returns the watchpoints of the module and JIT trackers and sanitizer hooks, which are
+    /// not listed with the user's.
+    fn hidden_watchpoints(&self) -> impl Iterator<Item = &Watchpoint> + Clone {
+        self.module_tracker
+            .iter()
+            .map(|tracker| &tracker.watchpoint)
+            .chain(self.jit_tracker.iter().map(|tracker| &tracker.watchpoint))
+            .chain(self.sanitizer_hooks.iter().map(|hook| &hook.watchpoint))
+    }
+
+    /// Returns a debug register used neither by a watchpoint nor by a hidden one.
+    pub(super) fn free_debug_register(&self) -> Option<usize> {
+        let used = self
+            .watchpoints
+            .iter()
+            .chain(self.hidden_watchpoints())
+            .map(|watchpoint| watchpoint.register);
+        (0..DEBUG_REGISTER_COUNT).find(|&register| !used.clone().any(|used| used == register))
+    }
+
+    /// Points debug register `register` at `addr` and enables it along with the others.
+    pub(super) fn arm_debug_register(&self, register: usize, addr: u64) -> Result<()> {
+        self.write_debug_register(register, addr)?;
+        self.update_debug_control()
+    }
+
+    /// Enables exactly the debug registers of the watchpoints and hidden watchpoints.
+    pub(super) fn update_debug_control(&self) -> Result<()> {
+        self.write_debug_register(DR7, self.debug_control())
+    }
+
+    /// Rewrites `DR7` if it no longer enables exactly the watchpoints and hidden watchpoints,
+    /// e.g. because another program changed it.
+    ///
+    /// # Returns
+    /// The value expected and the value found, if they differed.
+    ///
+    /// # Errors
+    /// Returns an error if the register cannot be read or written.
+    pub(super) fn resync_debug_control(&self) -> Result<Option<(u64, u64)>> {
+        let expected = self.debug_control();
+        let found = self.read_debug_register(DR7)?;
+        if found == expected {
+            return Ok(None);
+        }
+        self.update_debug_control()?;
+        Ok(Some((expected, found)))
+    }
+
+    /// Disables the debug registers and restores the pages of the range watches, so that the
+    /// process does not trap once detached from.
+    pub(super) fn disarm_watchpoints(&mut self) -> Result<()> {
+        for watch in 0..self.range_watches.len() {
+            let pages = std::mem::take(&mut self.range_watches[watch].pages);
+            self.protect_pages(&pages)?;
+        }
+        self.write_debug_register(DR7, 0)
+    }
+
+    /// Clears the bits of `DR6` saying which debug registers triggered, which the CPU never
+    /// clears itself.
+    pub(super) fn clear_debug_status(&self) -> Result<()> {
+        self.write_debug_register(DR6, 0)
+    }
+
+    /// Computes `DR7` enabling the watchpoints and hidden watchpoints.
+    fn debug_control(&self) -> u64 {
+        dr7(self.watchpoints.iter().chain(self.hidden_watchpoints()))
+    }
+
+    /// Reads the watched value, or `0` if it is unmapped.
+    fn read_watched(&self, addr: u64, len: usize) -> u64 {
+        let mut word = [0; size_of::<u64>()];
+        if let Ok(bytes) = self.read_memory(addr, len) {
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        u64::from_le_bytes(word)
+    }
+
+    fn read_debug_register(&self, index: usize) -> Result<u64> {
+        let value = self
+            .backend
+            .peek_user(self.pid, debug_register_offset(index))
+            .context(DebugRegisterSnafu {
+                access: "read",
+                index,
+                pid: self.pid.as_raw(),
+            })?;
+        Ok(value as u64)
+    }
+
+    fn write_debug_register(&self, index: usize, value: u64) -> Result<()> {
+        self.backend
+            .poke_user(self.pid, debug_register_offset(index), value as i64)
+            .context(DebugRegisterSnafu {
+                access: "write",
+                index,
+                pid: self.pid.as_raw(),
+            })
+    }
+}
+
+/// Returns the offset of debug register `index` in the `user` area.
+const fn debug_register_offset(index: usize) -> usize {
+    offset_of!(libc::user, u_debugreg) + index * size_of::<u64>()
+}
+
+/// Rounds `range` out to whole pages.
+pub(super) const fn page_range(range: &Range<u64>) -> Range<u64> {
+    range.start & !(PAGE_SIZE - 1)..range.end.next_multiple_of(PAGE_SIZE)
+}
+
+/// Returns the permissions of `region`.
+pub(super) fn protection(region: &MemoryRegion) -> ProtFlags {
+    let mut prot = ProtFlags::PROT_NONE;
+    prot.set(ProtFlags::PROT_READ, region.read);
+    prot.set(ProtFlags::PROT_WRITE, region.write);
+    prot.set(ProtFlags::PROT_EXEC, region.execute);
+    prot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{ScriptedBackend, ScriptedStop};
+    use nix::unistd::Pid;
+
+    #[test]
+    fn should_report_watchpoint_hits() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let backend = ScriptedBackend::new(unsafe { std::mem::zeroed() });
+        backend.map(0x1000, &0_u64.to_le_bytes());
+        let mut process =
+            Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+        let id = process.add_watchpoint(0x1000, 8, WatchKind::Write, "counter".into())?;
+        let register = process.watchpoints()[0].register;
+        assert_eq!(
+            process.backend().user_word(debug_register_offset(register)),
+            0x1000
+        );
+
+        let mut stop = ScriptedStop::new(WaitStatus::Stopped(pid, Signal::SIGTRAP));
+        stop.user.push((debug_register_offset(DR6), 1 << register));
+        stop.memory.push((0x1000, 5_u64.to_le_bytes().to_vec()));
+        process.backend().push_stop(stop);
+        process.resume()?;
+        let hit = WatchpointHit { id, old: 0, new: 5 };
+        assert_eq!(process.last_watchpoint, Some(hit));
+        // The CPU never clears the status itself.
+        assert_eq!(process.backend().user_word(debug_register_offset(DR6)), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,648 @@
+//! Readers for the `/proc` pseudo filesystem.
+
+use crate::error::{ReadProcFileSnafu, Result};
+use nix::libc;
+use nix::unistd::{sysconf, Pid, SysconfVar};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Reads a file under `/proc` into a string.
+///
+/// # Errors
+/// Returns an error if the file cannot be read.
+pub fn read_to_string(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path).map_err(|err| {
+        ReadProcFileSnafu {
+            path,
+            msg: err.to_string(),
+        }
+        .build()
+    })
+}
+
+/// Returns `/proc/<pid>/<name>`.
+pub fn pid_path(pid: Pid, name: &str) -> PathBuf {
+    PathBuf::from(format!("/proc/{pid}/{name}"))
+}
+
+/// Lists the pids of all processes visible in `/proc`.
+///
+/// # Errors
+/// Returns an error if `/proc` cannot be listed.
+pub fn pids() -> Result<Vec<Pid>> {
+    let entries = std::fs::read_dir("/proc").map_err(|err| {
+        ReadProcFileSnafu {
+            path: "/proc",
+            msg: err.to_string(),
+        }
+        .build()
+    })?;
+    Ok(entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .map(Pid::from_raw)
+        .collect())
+}
+
+/// Parsed `/proc/<pid>/status`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcStatus {
+    fields: Vec<(String, String)>,
+}
+
+impl ProcStatus {
+    /// Reads `/proc/<pid>/status`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read (e.g. the process does not exist).
+    pub fn read(pid: Pid) -> Result<Self> {
+        read_to_string(pid_path(pid, "status")).map(|contents| Self::parse(&contents))
+    }
+
+    /// Reads `/proc/self/status`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read.
+    pub fn read_self() -> Result<Self> {
+        read_to_string("/proc/self/status").map(|contents| Self::parse(&contents))
+    }
+
+    /// Parses the `Key:\tvalue` lines of a status file.
+    pub fn parse(contents: &str) -> Self {
+        let fields = contents
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+            .collect();
+        Self { fields }
+    }
+
+    /// Returns the raw value of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find_map(|(k, v)| (k == key).then_some(v.as_str()))
+    }
+
+    /// Returns the whitespace separated integers of `key` (e.g. `Uid`, `NSpid`).
+    pub fn get_ints(&self, key: &str) -> Option<Vec<i64>> {
+        self.get(key)?
+            .split_whitespace()
+            .map(|value| value.parse().ok())
+            .collect()
+    }
+
+    /// Returns the first integer of `key`.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get_ints(key)?.first().copied()
+    }
+
+    /// Returns a hexadecimal bit mask such as `CapEff`.
+    pub fn get_hex(&self, key: &str) -> Option<u64> {
+        u64::from_str_radix(self.get(key)?, 16).ok()
+    }
+}
+
+/// One line of `/proc/<pid>/maps`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct MemoryRegion {
+    /// First address of the mapping.
+    pub start: u64,
+    /// One past the last address of the mapping.
+    pub end: u64,
+    /// Readable.
+    pub read: bool,
+    /// Writable.
+    pub write: bool,
+    /// Executable.
+    pub execute: bool,
+    /// Shared (`s`) rather than private (`p`).
+    pub shared: bool,
+    /// Offset into the mapped file.
+    pub offset: u64,
+    /// Inode of the mapped file (`0` for anonymous mappings).
+    pub inode: u64,
+    /// File path or pseudo name such as `[stack]`; empty for anonymous mappings.
+    pub path: String,
+}
+
+impl MemoryRegion {
+    /// Parses a single maps line.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(6, ' ');
+        let (start, end) = fields.next()?.split_once('-')?;
+        let perms = fields.next()?.as_bytes();
+        let offset = fields.next()?;
+        let _device = fields.next()?;
+        let inode = fields.next()?;
+        let path = fields.next().unwrap_or_default().trim_start();
+        Some(Self {
+            start: u64::from_str_radix(start, 16).ok()?,
+            end: u64::from_str_radix(end, 16).ok()?,
+            read: perms.first() == Some(&b'r'),
+            write: perms.get(1) == Some(&b'w'),
+            execute: perms.get(2) == Some(&b'x'),
+            shared: perms.get(3) == Some(&b's'),
+            offset: u64::from_str_radix(offset, 16).ok()?,
+            inode: inode.parse().ok()?,
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns `true` if `addr` lies inside the mapping.
+    pub const fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Size of the mapping in bytes.
+    pub const fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the mapping is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns `rwxp`-style permissions.
+    pub fn perms(&self) -> String {
+        [
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.execute { 'x' } else { '-' },
+            if self.shared { 's' } else { 'p' },
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+/// Reads `/proc/<pid>/maps`.
+///
+/// # Errors
+/// Returns an error if the file cannot be read.
+pub fn read_maps(pid: Pid) -> Result<Vec<MemoryRegion>> {
+    let contents = read_to_string(pid_path(pid, "maps"))?;
+    Ok(contents.lines().filter_map(MemoryRegion::parse).collect())
+}
+
+/// Reads the resident set size of `pid` in bytes from `/proc/<pid>/statm`, which is much
+/// cheaper to produce than `status`.
+///
+/// # Errors
+/// Returns an error if the file cannot be read.
+pub fn resident_bytes(pid: Pid) -> Result<u64> {
+    let path = pid_path(pid, "statm");
+    let contents = read_to_string(&path)?;
+    parse_statm_resident(&contents).ok_or_else(|| {
+        ReadProcFileSnafu {
+            path,
+            msg: "missing resident pages",
+        }
+        .build()
+    })
+}
+
+/// Parses the resident size, in bytes, out of a `statm` line.
+fn parse_statm_resident(contents: &str) -> Option<u64> {
+    /// `statm` counts x86_64 base pages.
+    const PAGE_SIZE: u64 = 4096;
+    let pages: u64 = contents.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * PAGE_SIZE)
+}
+
+/// Reads the CPU time used so far by all threads of `pid`, see [`TaskStat::cpu_time`].
+///
+/// # Errors
+/// Returns an error if the file cannot be read or parsed.
+pub fn cpu_time(pid: Pid) -> Result<Duration> {
+    let path = pid_path(pid, "stat");
+    let contents = read_to_string(&path)?;
+    TaskStat::parse(&contents)
+        .as_ref()
+        .and_then(TaskStat::cpu_time)
+        .ok_or_else(|| {
+            ReadProcFileSnafu {
+                path,
+                msg: "malformed stat line",
+            }
+            .build()
+        })
+}
+
+/// Returns the mapping containing `addr`.
+pub fn find_region(regions: &[MemoryRegion], addr: u64) -> Option<&MemoryRegion> {
+    regions.iter().find(|region| region.contains(addr))
+}
+
+/// Lists the thread ids of `pid` from `/proc/<pid>/task`, sorted.
+///
+/// # Errors
+/// Returns an error if the directory cannot be listed.
+pub fn tids(pid: Pid) -> Result<Vec<Pid>> {
+    let path = pid_path(pid, "task");
+    let entries = std::fs::read_dir(&path).map_err(|err| {
+        ReadProcFileSnafu {
+            path: &path,
+            msg: err.to_string(),
+        }
+        .build()
+    })?;
+    let mut tids: Vec<_> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .map(Pid::from_raw)
+        .collect();
+    tids.sort_unstable();
+    Ok(tids)
+}
+
+/// Returns `/proc/<pid>/task/<tid>/<name>`.
+pub fn task_path(pid: Pid, tid: Pid, name: &str) -> PathBuf {
+    PathBuf::from(format!("/proc/{pid}/task/{tid}/{name}"))
+}
+
+/// Parsed `/proc/<pid>/task/<tid>/stat`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaskStat {
+    /// Thread name (`comm`).
+    pub comm: String,
+    /// Scheduler state letter, e.g. `R`, `S`, `D`, `t`.
+    pub state: char,
+    /// Fields after the state, so that `fields[0]` is field 4 (`ppid`) of `proc(5)`.
+    fields: Vec<String>,
+}
+
+impl TaskStat {
+    /// Reads the `stat` file of thread `tid` of `pid`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn read(pid: Pid, tid: Pid) -> Result<Self> {
+        let path = task_path(pid, tid, "stat");
+        let contents = read_to_string(&path)?;
+        Self::parse(&contents).ok_or_else(|| {
+            ReadProcFileSnafu {
+                path,
+                msg: "malformed stat line",
+            }
+            .build()
+        })
+    }
+
+    /// Parses a stat line. `comm` may contain spaces and parentheses, so it spans to the last `)`.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let (_, rest) = contents.split_once(" (")?;
+        let (comm, rest) = rest.rsplit_once(") ")?;
+        let mut fields = rest.split_whitespace();
+        let state = fields.next()?.chars().next()?;
+        Some(Self {
+            comm: comm.to_string(),
+            state,
+            fields: fields.map(str::to_string).collect(),
+        })
+    }
+
+    /// Returns field `number` as numbered in `proc(5)` (`1` is the pid), for fields after the state.
+    pub fn field(&self, number: usize) -> Option<&str> {
+        self.fields.get(number.checked_sub(4)?).map(String::as_str)
+    }
+
+    /// Returns the user plus system CPU time (`utime` + `stime`).
+    ///
+    /// Of the thread for a task's `stat`, of all threads for `/proc/<pid>/stat`.
+    pub fn cpu_time(&self) -> Option<Duration> {
+        let ticks = self.field(14)?.parse::<u64>().ok()? + self.field(15)?.parse::<u64>().ok()?;
+        let ticks_per_second = sysconf(SysconfVar::CLK_TCK)
+            .ok()
+            .flatten()
+            .and_then(|ticks| u64::try_from(ticks).ok())
+            .filter(|&ticks| ticks > 0)
+            .unwrap_or(100);
+        Some(Duration::from_nanos(
+            ticks.saturating_mul(1_000_000_000) / ticks_per_second,
+        ))
+    }
+
+    /// Describes the state letter, e.g. `sleeping` for `S`.
+    pub const fn state_name(&self) -> &'static str {
+        match self.state {
+            'R' => "running",
+            'S' => "sleeping",
+            'D' => "disk sleep",
+            'T' => "stopped",
+            't' => "tracing stop",
+            'Z' => "zombie",
+            'X' => "dead",
+            'I' => "idle",
+            'P' => "parked",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Parsed `/proc/<pid>/task/<tid>/syscall`: what a thread is doing in the kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TaskSyscall {
+    /// The thread is on a CPU; no registers are reported.
+    Running,
+    /// The thread is blocked but not inside a syscall (e.g. stopped by a signal).
+    NotInSyscall {
+        /// Stack pointer.
+        sp: u64,
+        /// Program counter.
+        pc: u64,
+    },
+    /// The thread is blocked inside syscall `number`.
+    Blocked {
+        /// Syscall number.
+        number: u64,
+        /// Syscall arguments.
+        args: [u64; 6],
+        /// Stack pointer.
+        sp: u64,
+        /// Program counter.
+        pc: u64,
+    },
+}
+
+impl TaskSyscall {
+    /// Reads the `syscall` file of thread `tid` of `pid`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read (it requires ptrace access) or parsed.
+    pub fn read(pid: Pid, tid: Pid) -> Result<Self> {
+        let path = task_path(pid, tid, "syscall");
+        let contents = read_to_string(&path)?;
+        Self::parse(&contents).ok_or_else(|| {
+            ReadProcFileSnafu {
+                path,
+                msg: "malformed syscall line",
+            }
+            .build()
+        })
+    }
+
+    /// Parses `running`, `-1 sp pc` or `nr arg1 .. arg6 sp pc`.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let contents = contents.trim();
+        if contents == "running" {
+            return Some(Self::Running);
+        }
+        let fields: Vec<_> = contents.split_whitespace().collect();
+        let hex = |field: &str| u64::from_str_radix(field.trim_start_matches("0x"), 16).ok();
+        match fields.as_slice() {
+            ["-1", sp, pc] => Some(Self::NotInSyscall {
+                sp: hex(sp)?,
+                pc: hex(pc)?,
+            }),
+            [number, rest @ ..] if rest.len() == 8 => {
+                let mut args = [0; 6];
+                for (arg, field) in args.iter_mut().zip(rest) {
+                    *arg = hex(field)?;
+                }
+                Some(Self::Blocked {
+                    number: number.parse().ok()?,
+                    args,
+                    sp: hex(rest[6])?,
+                    pc: hex(rest[7])?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Program counter, unless the thread is running.
+    pub const fn pc(&self) -> Option<u64> {
+        match *self {
+            Self::Running => None,
+            Self::NotInSyscall { pc, .. } | Self::Blocked { pc, .. } => Some(pc),
+        }
+    }
+}
+
+/// An open file descriptor, from `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct OpenFd {
+    pub fd: i32,
+    /// What the descriptor refers to, e.g. `/etc/passwd`, `pipe:[1234]` or `socket:[5678]`.
+    pub target: String,
+    /// `None` if `fdinfo` could not be read.
+    pub info: Option<FdInfo>,
+}
+
+/// Parsed `/proc/<pid>/fdinfo/<fd>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct FdInfo {
+    /// File offset.
+    pub pos: u64,
+    /// `open` flags, e.g. `O_WRONLY | O_APPEND`.
+    pub flags: i32,
+}
+
+/// The kernel's `O_LARGEFILE`, which libc defines as 0 on 64-bit targets.
+const O_LARGEFILE: i32 = 0o100_000;
+
+/// `open` flags by name, a flag including another one before it.
+const OPEN_FLAGS: &[(i32, &str)] = &[
+    (libc::O_PATH, "O_PATH"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_SYNC, "O_SYNC"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_ASYNC, "O_ASYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    (libc::O_TMPFILE, "O_TMPFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_NOATIME, "O_NOATIME"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+];
+
+impl FdInfo {
+    /// Parses the `pos:` and `flags:` lines; the rest depends on the kind of file.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let field = |name: &str| {
+            contents.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key == name).then(|| value.trim())
+            })
+        };
+        Some(Self {
+            pos: field("pos")?.parse().ok()?,
+            flags: i32::from_str_radix(field("flags")?, 8).ok()?,
+        })
+    }
+
+    /// Names the access mode and the flags set, e.g. `["O_WRONLY", "O_APPEND"]`.
+    ///
+    /// `O_LARGEFILE`, which the kernel sets on every file of a 64-bit process, is left out, and
+    /// unknown bits are shown in octal.
+    pub fn flag_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut rest = self.flags & !O_LARGEFILE;
+        if rest & libc::O_PATH == 0 {
+            names.push(
+                match rest & libc::O_ACCMODE {
+                    libc::O_RDONLY => "O_RDONLY",
+                    libc::O_WRONLY => "O_WRONLY",
+                    _ => "O_RDWR",
+                }
+                .to_string(),
+            );
+            rest &= !libc::O_ACCMODE;
+        }
+        for &(bits, name) in OPEN_FLAGS {
+            if rest & bits == bits {
+                names.push(name.to_string());
+                rest &= !bits;
+            }
+        }
+        if rest != 0 {
+            names.push(format!("{rest:#o}"));
+        }
+        names
+    }
+}
+
+/// Lists the open file descriptors of `pid` in ascending order.
+///
+/// Descriptors closed while they are listed are left out.
+///
+/// # Errors
+/// Returns an error if `/proc/<pid>/fd` cannot be listed; it requires ptrace access.
+pub fn read_fds(pid: Pid) -> Result<Vec<OpenFd>> {
+    let dir = pid_path(pid, "fd");
+    let entries = std::fs::read_dir(&dir).map_err(|err| {
+        ReadProcFileSnafu {
+            path: &dir,
+            msg: err.to_string(),
+        }
+        .build()
+    })?;
+    let mut fds: Vec<_> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let fd = entry.file_name().to_str()?.parse().ok()?;
+            let target = std::fs::read_link(entry.path()).ok()?;
+            let info = read_to_string(pid_path(pid, &format!("fdinfo/{fd}")))
+                .ok()
+                .and_then(|contents| FdInfo::parse(&contents));
+            Some(OpenFd {
+                fd,
+                target: target.to_string_lossy().into_owned(),
+                info,
+            })
+        })
+        .collect();
+    fds.sort_unstable_by_key(|fd| fd.fd);
+    Ok(fds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_maps_line() {
+        let region = MemoryRegion::parse(
+            "55d4c3a4e000-55d4c3a50000 r-xp 00002000 08:01 1234                       /usr/bin/my app",
+        );
+        assert_eq!(
+            region,
+            Some(MemoryRegion {
+                start: 0x55d4_c3a4_e000,
+                end: 0x55d4_c3a5_0000,
+                read: true,
+                execute: true,
+                offset: 0x2000,
+                inode: 1234,
+                path: "/usr/bin/my app".to_string(),
+                ..Default::default()
+            })
+        );
+        let anonymous = MemoryRegion::parse("7ffd1000-7ffd2000 rw-p 00000000 00:00 0 ");
+        assert_eq!(anonymous.map(|region| region.path), Some(String::new()));
+    }
+
+    #[test]
+    fn should_parse_status() {
+        let status = ProcStatus::parse(
+            "Name:\tcat\nTracerPid:\t0\nUid:\t1000\t1000\t1000\t1000\nCapEff:\t0000000000080000\n",
+        );
+        assert_eq!(status.get("Name"), Some("cat"));
+        assert_eq!(status.get_int("TracerPid"), Some(0));
+        assert_eq!(status.get_ints("Uid"), Some(vec![1000; 4]));
+        assert_eq!(status.get_hex("CapEff"), Some(1 << 19));
+        assert_eq!(status.get("Missing"), None);
+    }
+
+    #[test]
+    fn should_parse_statm() {
+        assert_eq!(
+            parse_statm_resident("2496 350 302 5 0 137 0\n"),
+            Some(350 * 4096)
+        );
+        assert_eq!(parse_statm_resident("2496"), None);
+    }
+
+    #[test]
+    fn should_parse_task_files() {
+        let stat = TaskStat::parse("42 (a (b) c) S 1 42 42 0 -1 4194560");
+        assert_eq!(
+            stat.as_ref().map(|stat| stat.comm.as_str()),
+            Some("a (b) c")
+        );
+        assert_eq!(stat.as_ref().map(TaskStat::state_name), Some("sleeping"));
+        assert_eq!(stat.as_ref().and_then(|stat| stat.field(4)), Some("1"));
+        let stat = TaskStat::parse("42 (a) R 1 42 42 0 -1 4194560 10 0 0 0 150 50 0 0");
+        assert_eq!(
+            stat.as_ref().and_then(TaskStat::cpu_time),
+            Some(Duration::from_secs(2))
+        );
+
+        assert_eq!(TaskSyscall::parse("running\n"), Some(TaskSyscall::Running));
+        assert_eq!(
+            TaskSyscall::parse("-1 0x7ffd10 0x401000\n"),
+            Some(TaskSyscall::NotInSyscall {
+                sp: 0x7f_fd10,
+                pc: 0x40_1000
+            })
+        );
+        assert_eq!(
+            TaskSyscall::parse("202 0x4040 0x80 0x2 0x0 0x0 0x0 0x7ffd10 0x401000\n"),
+            Some(TaskSyscall::Blocked {
+                number: 202,
+                args: [0x4040, 0x80, 2, 0, 0, 0],
+                sp: 0x7f_fd10,
+                pc: 0x40_1000
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_fdinfo() {
+        let info = FdInfo::parse("pos:\t12\nflags:\t02102001\nmnt_id:\t25\nino:\t42\n");
+        assert_eq!(
+            info,
+            Some(FdInfo {
+                pos: 12,
+                flags: 0o2_102_001
+            })
+        );
+        assert_eq!(
+            info.map(|info| info.flag_names()),
+            Some(vec![
+                "O_WRONLY".to_string(),
+                "O_APPEND".to_string(),
+                "O_CLOEXEC".to_string()
+            ])
+        );
+        let path = FdInfo {
+            pos: 0,
+            flags: libc::O_PATH | libc::O_DIRECTORY,
+        };
+        assert_eq!(path.flag_names(), ["O_PATH", "O_DIRECTORY"]);
+        assert_eq!(FdInfo::parse("pos:\t0\n"), None);
+    }
+}
@@ -0,0 +1,191 @@
+//! The dynamic linker's rendezvous structure `r_debug`, through which debuggers learn which
+//! shared objects are loaded.
+//!
+//! The dynamic linker keeps the loaded objects in the `link_map` list of `_r_debug`, and
+//! calls the empty function `_dl_debug_state` before and after changing it, so a breakpoint
+//! there sees every `dlopen` and `dlclose`, and the libraries loaded at startup, before any of
+//! their initializers run.
+
+use crate::error::Result;
+use crate::process::ReadMemory;
+use crate::procfs::MemoryRegion;
+use crate::symbols::SymbolTable;
+use serde::Serialize;
+use std::path::Path;
+
+/// Offset of `r_map` in `struct r_debug`.
+const R_MAP: u64 = 8;
+/// Offset of `r_state` in `struct r_debug`.
+const R_STATE: u64 = 24;
+/// `r_state` once the `link_map` list is consistent again.
+const RT_CONSISTENT: u64 = 0;
+/// Longest path read from a `link_map`.
+const PATH_MAX: usize = 4096;
+/// Most `link_map` entries followed, in case the list is corrupt.
+const MAX_MODULES: usize = 4096;
+
+/// A shared object in the dynamic linker's `link_map` list.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Module {
+    /// Path as passed to the dynamic linker, e.g. `/lib/x86_64-linux-gnu/libc.so.6`.
+    pub path: String,
+    /// Load bias (`l_addr`): where the object is loaded relative to its link-time addresses.
+    pub base: u64,
+}
+
+/// Whether a [`ModuleEvent`] adds or removes a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModuleChange {
+    Loaded,
+    Unloaded,
+}
+
+/// A module appearing in or disappearing from the `link_map` list.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct ModuleEvent {
+    pub change: ModuleChange,
+    pub module: Module,
+}
+
+impl std::fmt::Display for ModuleEvent {
+    /// Writes e.g. `loaded /usr/lib/libm.so.6 at 0x7f0000000000`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let change = match self.change {
+            ModuleChange::Loaded => "loaded",
+            ModuleChange::Unloaded => "unloaded",
+        };
+        write!(
+            f,
+            "{change} {} at {:#x}",
+            self.module.path, self.module.base
+        )
+    }
+}
+
+/// Addresses of the dynamic linker's rendezvous.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rendezvous {
+    /// Address of `_r_debug`.
+    pub r_debug: u64,
+    /// Address of `_dl_debug_state`, called around every change of the list.
+    pub breakpoint: u64,
+}
+
+impl Rendezvous {
+    /// Finds the rendezvous in the mapped dynamic linker.
+    ///
+    /// # Returns
+    /// `None` for statically linked programs, which have no dynamic linker.
+    pub fn locate(maps: &[MemoryRegion]) -> Option<Self> {
+        maps.iter()
+            .filter(|region| Path::new(&region.path).is_absolute())
+            .find_map(|region| {
+                let table = SymbolTable::cached(Path::new(&region.path)).ok()?;
+                let bias = table.load_bias(region)?;
+                let address = |name: &str| {
+                    table
+                        .symbols()
+                        .iter()
+                        .find(|symbol| symbol.name == name)
+                        .map(|symbol| symbol.address.wrapping_add(bias))
+                };
+                Some(Self {
+                    r_debug: address("_r_debug")?,
+                    breakpoint: address("_dl_debug_state")?,
+                })
+            })
+    }
+
+    /// Returns `true` if the `link_map` list is not being changed.
+    ///
+    /// # Errors
+    /// Returns an error if `_r_debug` cannot be read.
+    pub fn is_consistent(&self, memory: &impl ReadMemory) -> Result<bool> {
+        let state = memory.read_words(self.r_debug + R_STATE, 1)?;
+        // `r_state` is an `int`; the upper half is padding.
+        Ok(state
+            .first()
+            .is_none_or(|&state| state & 0xffff_ffff == RT_CONSISTENT))
+    }
+
+    /// Reads the `link_map` list, skipping the main program, which has no name.
+    ///
+    /// # Errors
+    /// Returns an error if `_r_debug` cannot be read.
+    pub fn modules(&self, memory: &impl ReadMemory) -> Result<Vec<Module>> {
+        let mut link = memory
+            .read_words(self.r_debug + R_MAP, 1)?
+            .first()
+            .copied()
+            .unwrap_or_default();
+        let mut modules = Vec::new();
+        while link != 0 && modules.len() < MAX_MODULES {
+            // `l_addr`, `l_name`, `l_ld`, `l_next`.
+            let Ok(&[base, name, _, next]) = memory.read_words(link, 4).as_deref() else {
+                break;
+            };
+            let path = memory.read_memory(name, PATH_MAX).unwrap_or_default();
+            let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+            if end > 0 {
+                modules.push(Module {
+                    path: String::from_utf8_lossy(&path[..end]).into_owned(),
+                    base,
+                });
+            }
+            link = next;
+        }
+        Ok(modules)
+    }
+}
+
+/// Compares two snapshots of the `link_map` list.
+///
+/// # Returns
+/// The unloaded modules, then the loaded ones, in list order.
+pub fn changes(before: &[Module], after: &[Module]) -> Vec<ModuleEvent> {
+    let unloaded = before
+        .iter()
+        .filter(|module| !after.contains(module))
+        .map(|module| ModuleEvent {
+            change: ModuleChange::Unloaded,
+            module: module.clone(),
+        });
+    let loaded = after
+        .iter()
+        .filter(|module| !before.contains(module))
+        .map(|module| ModuleEvent {
+            change: ModuleChange::Loaded,
+            module: module.clone(),
+        });
+    unloaded.chain(loaded).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_diff_module_lists() {
+        let module = |path: &str, base| Module {
+            path: path.into(),
+            base,
+        };
+        let before = [module("libc.so.6", 0x1000), module("libfoo.so", 0x2000)];
+        let after = [module("libc.so.6", 0x1000), module("libbar.so", 0x3000)];
+        assert_eq!(
+            changes(&before, &after),
+            [
+                ModuleEvent {
+                    change: ModuleChange::Unloaded,
+                    module: module("libfoo.so", 0x2000),
+                },
+                ModuleEvent {
+                    change: ModuleChange::Loaded,
+                    module: module("libbar.so", 0x3000),
+                },
+            ]
+        );
+        assert!(changes(&after, &after).is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+//! AddressSanitizer and ThreadSanitizer reports, caught at the hooks their runtimes call
+//! for each one.
+//!
+//! Both runtimes export an empty function for debuggers, `__asan_on_error` and
+//! `__tsan_on_report`, and functions returning the details of the report being made, so a
+//! stop at the hook can tell what went wrong before the program aborts. ASan calls its hook
+//! before printing the report, and TSan after.
+
+use serde::Serialize;
+
+/// A sanitizer runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum Sanitizer {
+    Address,
+    Thread,
+}
+
+impl Sanitizer {
+    pub const ALL: [Self; 2] = [Self::Address, Self::Thread];
+
+    /// Returns the function the runtime calls for each report.
+    pub const fn hook(self) -> &'static str {
+        match self {
+            Self::Address => "__asan_on_error",
+            Self::Thread => "__tsan_on_report",
+        }
+    }
+}
+
+impl std::fmt::Display for Sanitizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Address => write!(f, "AddressSanitizer"),
+            Self::Thread => write!(f, "ThreadSanitizer"),
+        }
+    }
+}
+
+/// The memory access a report is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct BadAccess {
+    pub addr: u64,
+    pub size: u64,
+    pub write: bool,
+}
+
+/// An error reported by a sanitizer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SanitizerReport {
+    pub sanitizer: Sanitizer,
+    /// Kind of error, e.g. `heap-buffer-overflow` or `data-race`.
+    pub description: String,
+    /// The access that went wrong, if the error is about one.
+    pub access: Option<BadAccess>,
+    /// Address of the code making the access, if known.
+    pub pc: Option<u64>,
+}
+
+impl std::fmt::Display for SanitizerReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.sanitizer, self.description)?;
+        if let Some(access) = self.access {
+            let kind = if access.write { "WRITE" } else { "READ" };
+            write!(f, ", {kind} of size {} at {:#x}", access.size, access.addr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_report() {
+        let report = SanitizerReport {
+            sanitizer: Sanitizer::Address,
+            description: "heap-buffer-overflow".into(),
+            access: Some(BadAccess {
+                addr: 0x6020_0000_0014,
+                size: 4,
+                write: true,
+            }),
+            pc: Some(0x401196),
+        };
+        assert_eq!(
+            report.to_string(),
+            "AddressSanitizer: heap-buffer-overflow, WRITE of size 4 at 0x602000000014"
+        );
+        let report = SanitizerReport {
+            sanitizer: Sanitizer::Thread,
+            description: "data-race".into(),
+            access: None,
+            pc: None,
+        };
+        assert_eq!(report.to_string(), "ThreadSanitizer: data-race");
+    }
+}
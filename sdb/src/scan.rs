@@ -0,0 +1,405 @@
+//! Searching all of the inferior's readable memory.
+
+use crate::cancel::CancelToken;
+use crate::process::Process;
+use crate::procfs::MemoryRegion;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// Bytes read with one `process_vm_readv` while scanning.
+const CHUNK_SIZE: usize = 1 << 20;
+/// Pseudo mappings that cannot be read or are the same in every process.
+const SKIPPED_MAPPINGS: [&str; 2] = ["[vvar]", "[vsyscall]"];
+
+/// A word in memory holding an address inside the searched range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PointerRef {
+    /// Where the pointer is stored.
+    pub addr: u64,
+    /// The pointer.
+    pub value: u64,
+}
+
+/// Regions worth scanning: readable, and not kernel pseudo mappings.
+pub fn scannable_regions(maps: &[MemoryRegion]) -> impl Iterator<Item = &MemoryRegion> {
+    maps.iter()
+        .filter(|region| region.read && !SKIPPED_MAPPINGS.contains(&region.path.as_str()))
+}
+
+/// Calls `f` with the start address and contents of each chunk of the readable regions.
+///
+/// Chunks are at most 1 MiB and never span two regions. Parts that fail to read
+/// (e.g. `PROT_NONE` pages inside a region) are skipped. Stops early once `cancel` is set.
+pub fn for_each_chunk(
+    process: &Process,
+    maps: &[MemoryRegion],
+    cancel: &CancelToken,
+    mut f: impl FnMut(u64, &[u8]),
+) {
+    for region in scannable_regions(maps) {
+        let mut addr = region.start;
+        while addr < region.end {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let len = ((region.end - addr) as usize).min(CHUNK_SIZE);
+            if let Ok(bytes) = process.read_memory(addr, len) {
+                f(addr, &bytes);
+            }
+            addr += len as u64;
+        }
+    }
+}
+
+/// Finds every 8-byte aligned word in readable memory whose value lies in `target`, or those
+/// found before `cancel` was set.
+pub fn scan_pointers(
+    process: &Process,
+    maps: &[MemoryRegion],
+    target: &Range<u64>,
+    cancel: &CancelToken,
+) -> Vec<PointerRef> {
+    let mut found = Vec::new();
+    for_each_chunk(process, maps, cancel, |addr, bytes| {
+        found.extend(find_pointers(addr, bytes, target));
+    });
+    found
+}
+
+/// Finds the 8-byte aligned words of `bytes`, loaded at `base`, whose value lies in `target`.
+fn find_pointers<'a>(
+    base: u64,
+    bytes: &'a [u8],
+    target: &'a Range<u64>,
+) -> impl Iterator<Item = PointerRef> + 'a {
+    let skip = (base.next_multiple_of(8) - base) as usize;
+    bytes
+        .get(skip..)
+        .unwrap_or_default()
+        .chunks_exact(size_of::<u64>())
+        .enumerate()
+        .filter_map(move |(index, chunk)| {
+            let mut word = [0; size_of::<u64>()];
+            word.copy_from_slice(chunk);
+            let value = u64::from_le_bytes(word);
+            target.contains(&value).then_some(PointerRef {
+                addr: base + (skip + index * size_of::<u64>()) as u64,
+                value,
+            })
+        })
+}
+
+/// Type of the values a [`ValueScan`] looks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValueType {
+    /// Every type with its name, as accepted by [`Self::from_name`].
+    pub const ALL: [(&str, Self); 10] = [
+        ("u8", Self::U8),
+        ("u16", Self::U16),
+        ("u32", Self::U32),
+        ("u64", Self::U64),
+        ("i8", Self::I8),
+        ("i16", Self::I16),
+        ("i32", Self::I32),
+        ("i64", Self::I64),
+        ("f32", Self::F32),
+        ("f64", Self::F64),
+    ];
+
+    /// Looks up a type by name, e.g. `i32`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(type_name, _)| *type_name == name)
+            .map(|&(_, ty)| ty)
+    }
+
+    /// Name of the type, e.g. `i32`.
+    pub fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|&&(_, ty)| ty == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    /// Size in bytes, which is also the alignment values are searched at.
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    /// Parses `text` as a value of this type, returning its little-endian bytes zero-extended
+    /// to a `u64`. Integers may be given in hex with `0x`.
+    pub fn parse(self, text: &str) -> Option<u64> {
+        let (negative, digits) = text
+            .strip_prefix('-')
+            .map_or((false, text), |digits| (true, digits));
+        let magnitude = || {
+            digits.strip_prefix("0x").map_or_else(
+                || digits.parse::<u64>().ok(),
+                |hex| u64::from_str_radix(hex, 16).ok(),
+            )
+        };
+        let signed = || {
+            let magnitude = i64::try_from(magnitude()?).ok()?;
+            Some(if negative { -magnitude } else { magnitude })
+        };
+        let raw = match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 if negative => return None,
+            Self::U8 => u64::from(u8::try_from(magnitude()?).ok()?),
+            Self::U16 => u64::from(u16::try_from(magnitude()?).ok()?),
+            Self::U32 => u64::from(u32::try_from(magnitude()?).ok()?),
+            Self::U64 => magnitude()?,
+            Self::I8 => u64::from(i8::try_from(signed()?).ok()? as u8),
+            Self::I16 => u64::from(i16::try_from(signed()?).ok()? as u16),
+            Self::I32 => u64::from(i32::try_from(signed()?).ok()? as u32),
+            Self::I64 => signed()? as u64,
+            Self::F32 => u64::from(text.parse::<f32>().ok()?.to_bits()),
+            Self::F64 => text.parse::<f64>().ok()?.to_bits(),
+        };
+        Some(raw)
+    }
+
+    /// Formats raw bytes, as returned by [`Self::parse`], as a value of this type.
+    pub fn format(self, raw: u64) -> String {
+        match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => raw.to_string(),
+            Self::I8 => (raw as u8 as i8).to_string(),
+            Self::I16 => (raw as u16 as i16).to_string(),
+            Self::I32 => (raw as u32 as i32).to_string(),
+            Self::I64 => (raw as i64).to_string(),
+            Self::F32 => f32::from_bits(raw as u32).to_string(),
+            Self::F64 => f64::from_bits(raw).to_string(),
+        }
+    }
+
+    /// Compares two raw values numerically. `None` if either is a NaN.
+    fn compare(self, left: u64, right: u64) -> Option<Ordering> {
+        match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => Some(left.cmp(&right)),
+            Self::I8 => Some((left as u8 as i8).cmp(&(right as u8 as i8))),
+            Self::I16 => Some((left as u16 as i16).cmp(&(right as u16 as i16))),
+            Self::I32 => Some((left as u32 as i32).cmp(&(right as u32 as i32))),
+            Self::I64 => Some((left as i64).cmp(&(right as i64))),
+            Self::F32 => f32::from_bits(left as u32).partial_cmp(&f32::from_bits(right as u32)),
+            Self::F64 => f64::from_bits(left).partial_cmp(&f64::from_bits(right)),
+        }
+    }
+
+    /// Reads a raw value from the start of `bytes`.
+    fn load(self, bytes: &[u8]) -> Option<u64> {
+        let mut word = [0; size_of::<u64>()];
+        word[..self.size()].copy_from_slice(bytes.get(..self.size())?);
+        Some(u64::from_le_bytes(word))
+    }
+}
+
+/// How [`ValueScan::next`] narrows down the candidates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScanFilter {
+    /// The value differs from the last scan.
+    Changed,
+    /// The value is the same as in the last scan.
+    Unchanged,
+    /// The value is greater than in the last scan.
+    Increased,
+    /// The value is less than in the last scan.
+    Decreased,
+    /// The value equals the given raw value.
+    Equal(u64),
+}
+
+impl ScanFilter {
+    /// Parses `changed`, `unchanged`, `increased`, `decreased`, `==<value>` or a bare value.
+    pub fn parse(text: &str, ty: ValueType) -> Option<Self> {
+        let filter = match text {
+            "changed" => Self::Changed,
+            "unchanged" => Self::Unchanged,
+            "increased" => Self::Increased,
+            "decreased" => Self::Decreased,
+            _ => Self::Equal(ty.parse(text.strip_prefix("==").unwrap_or(text))?),
+        };
+        Some(filter)
+    }
+
+    fn matches(self, ty: ValueType, old: u64, new: u64) -> bool {
+        match self {
+            Self::Changed => old != new,
+            Self::Unchanged => old == new,
+            Self::Increased => ty.compare(new, old) == Some(Ordering::Greater),
+            Self::Decreased => ty.compare(new, old) == Some(Ordering::Less),
+            Self::Equal(value) => ty.compare(new, value) == Some(Ordering::Equal),
+        }
+    }
+}
+
+/// An address still matching every filter so far, with the value seen by the last scan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    pub addr: u64,
+    /// Raw value, see [`ValueType::parse`].
+    pub value: u64,
+}
+
+/// A search for the address of a value that is narrowed down across stops, by how the
+/// value changed in between.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueScan {
+    ty: ValueType,
+    candidates: Vec<Candidate>,
+}
+
+impl ValueScan {
+    /// Finds every naturally aligned `ty` in readable memory equal to the raw `value`.
+    ///
+    /// If `cancel` is set meanwhile, only the memory scanned so far is searched.
+    pub fn start(
+        process: &Process,
+        maps: &[MemoryRegion],
+        ty: ValueType,
+        value: u64,
+        cancel: &CancelToken,
+    ) -> Self {
+        let filter = ScanFilter::Equal(value);
+        let mut candidates = Vec::new();
+        for_each_chunk(process, maps, cancel, |addr, bytes| {
+            candidates.extend(
+                values(addr, bytes, ty).filter(|candidate| filter.matches(ty, 0, candidate.value)),
+            );
+        });
+        Self { ty, candidates }
+    }
+
+    /// Rereads the candidates and keeps those matching `filter`.
+    ///
+    /// Once `cancel` is set, the candidates not checked yet are kept as they are.
+    pub fn next(&mut self, process: &Process, filter: ScanFilter, cancel: &CancelToken) {
+        let ty = self.ty;
+        let mut window_addr = 0;
+        let mut window = Vec::new();
+        self.candidates.retain_mut(|candidate| {
+            let window_end = window_addr + window.len() as u64;
+            if candidate.addr < window_addr || candidate.addr + ty.size() as u64 > window_end {
+                if cancel.is_cancelled() {
+                    return true;
+                }
+                // Candidates are sorted, so one read serves the following ones too.
+                window_addr = candidate.addr;
+                window = process
+                    .read_memory(candidate.addr, CHUNK_SIZE)
+                    .unwrap_or_default();
+            }
+            let offset = (candidate.addr - window_addr) as usize;
+            let Some(value) = window.get(offset..).and_then(|bytes| ty.load(bytes)) else {
+                return false; // Unmapped since the last scan.
+            };
+            let keep = filter.matches(ty, candidate.value, value);
+            candidate.value = value;
+            keep
+        });
+    }
+
+    /// Type of the values searched for.
+    pub const fn value_type(&self) -> ValueType {
+        self.ty
+    }
+
+    /// Remaining candidates, by address.
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+}
+
+/// Every naturally aligned `ty` in `bytes`, loaded at `base`.
+fn values(base: u64, bytes: &[u8], ty: ValueType) -> impl Iterator<Item = Candidate> + '_ {
+    let size = ty.size();
+    let skip = (base.next_multiple_of(size as u64) - base) as usize;
+    bytes
+        .get(skip..)
+        .unwrap_or_default()
+        .chunks_exact(size)
+        .enumerate()
+        .filter_map(move |(index, chunk)| {
+            Some(Candidate {
+                addr: base + (skip + index * size) as u64,
+                value: ty.load(chunk)?,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_aligned_pointers_into_range() {
+        let mut bytes = vec![0xff; 4];
+        for value in [0x1000_u64, 0x2010, 0x2000, 0x3000] {
+            bytes.extend(value.to_le_bytes());
+        }
+        let found: Vec<_> = find_pointers(0x7ffc, &bytes, &(0x2000..0x3000)).collect();
+        let expected = [
+            PointerRef {
+                addr: 0x8008,
+                value: 0x2010,
+            },
+            PointerRef {
+                addr: 0x8010,
+                value: 0x2000,
+            },
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn should_parse_and_format_values() {
+        assert_eq!(ValueType::I16.parse("-2"), Some(0xfffe));
+        assert_eq!(ValueType::I16.format(0xfffe), "-2");
+        assert_eq!(ValueType::U8.parse("0x100"), None);
+        assert_eq!(ValueType::U32.parse("-1"), None);
+        assert_eq!(
+            ValueType::F32.parse("1.5"),
+            Some(u64::from(1.5_f32.to_bits()))
+        );
+    }
+
+    #[test]
+    fn should_filter_by_change() {
+        let ty = ValueType::I32;
+        let minus_one = u64::from(u32::MAX);
+        assert!(ScanFilter::Increased.matches(ty, minus_one, 0));
+        assert!(!ScanFilter::Decreased.matches(ty, minus_one, 0));
+        assert!(ScanFilter::Changed.matches(ty, 1, 2));
+        assert_eq!(
+            ScanFilter::parse("==-1", ty),
+            Some(ScanFilter::Equal(minus_one))
+        );
+        assert_eq!(ScanFilter::parse("5", ty), Some(ScanFilter::Equal(5)));
+    }
+
+    #[test]
+    fn should_load_aligned_values() {
+        let bytes = [0xaa, 1, 0, 2, 0, 3];
+        let found: Vec<_> = values(0x1001, &bytes, ValueType::U16).collect();
+        let expected = [(0x1002, 1), (0x1004, 2)];
+        let found: Vec<_> = found.iter().map(|c| (c.addr, c.value)).collect();
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,172 @@
+//! Scheduling of the inferior's threads: policy, priority, CPU affinity and context switches.
+//!
+//! Pinning all threads to one CPU serializes them, which makes many races far more
+//! reproducible under the debugger.
+
+use crate::error::{Result, SchedulerSnafu};
+use crate::procfs::{read_to_string, task_path, tids, ProcStatus, TaskStat};
+use nix::errno::Errno;
+use nix::libc;
+use nix::sched::{sched_getaffinity, sched_setaffinity, CpuSet};
+use nix::unistd::Pid;
+use serde::Serialize;
+use snafu::ResultExt as _;
+
+/// `SCHED_RESET_ON_FORK`, or'ed into the policy.
+const RESET_ON_FORK: i32 = 0x4000_0000;
+
+/// How a thread is scheduled.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SchedInfo {
+    /// `SCHED_OTHER`, `SCHED_FIFO`, ..., without `SCHED_RESET_ON_FORK`.
+    pub policy: i32,
+    /// Real-time priority, 0 for the other policies.
+    pub priority: i32,
+    pub nice: i32,
+    /// CPUs the thread may run on, ascending.
+    pub affinity: Vec<usize>,
+    /// Context switches because the thread blocked, `None` if unknown.
+    pub voluntary_switches: Option<u64>,
+    /// Context switches because the thread was preempted, `None` if unknown.
+    pub involuntary_switches: Option<u64>,
+}
+
+impl SchedInfo {
+    /// Reads the scheduling of thread `tid` of `pid`.
+    ///
+    /// # Errors
+    /// Returns an error if the policy, priority or affinity of the thread cannot be read, e.g.
+    /// it exited.
+    pub fn read(pid: Pid, tid: Pid) -> Result<Self> {
+        let to_error = |action: &'static str| SchedulerSnafu {
+            action,
+            tid: tid.as_raw(),
+        };
+        // SAFETY: Takes no pointers.
+        let policy = Errno::result(unsafe { libc::sched_getscheduler(tid.as_raw()) })
+            .context(to_error("read the policy of"))?;
+        let mut param = libc::sched_param { sched_priority: 0 };
+        // SAFETY: `param` is a valid `sched_param` to write to.
+        Errno::result(unsafe { libc::sched_getparam(tid.as_raw(), &raw mut param) })
+            .context(to_error("read the priority of"))?;
+        let cpus = sched_getaffinity(tid).context(to_error("read the CPU affinity of"))?;
+        let status = read_to_string(task_path(pid, tid, "status"))
+            .map(|contents| ProcStatus::parse(&contents))
+            .unwrap_or_default();
+        let switches = |key| {
+            status
+                .get_int(key)
+                .and_then(|count| u64::try_from(count).ok())
+        };
+        Ok(Self {
+            policy: policy & !RESET_ON_FORK,
+            priority: param.sched_priority,
+            nice: TaskStat::read(pid, tid)
+                .ok()
+                .and_then(|stat| stat.field(19)?.parse().ok())
+                .unwrap_or_default(),
+            affinity: (0..CpuSet::count())
+                .filter(|&cpu| cpus.is_set(cpu).unwrap_or_default())
+                .collect(),
+            voluntary_switches: switches("voluntary_ctxt_switches"),
+            involuntary_switches: switches("nonvoluntary_ctxt_switches"),
+        })
+    }
+
+    /// Returns the name of the policy, e.g. `SCHED_OTHER`.
+    pub const fn policy_name(&self) -> &'static str {
+        match self.policy {
+            libc::SCHED_OTHER => "SCHED_OTHER",
+            libc::SCHED_FIFO => "SCHED_FIFO",
+            libc::SCHED_RR => "SCHED_RR",
+            libc::SCHED_BATCH => "SCHED_BATCH",
+            libc::SCHED_IDLE => "SCHED_IDLE",
+            6 => "SCHED_DEADLINE",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Restricts every thread of `pid` to `cpus`. Threads created later inherit the affinity of
+/// the thread creating them.
+///
+/// # Returns
+/// How many threads were pinned.
+///
+/// # Errors
+/// Returns an error if the threads cannot be listed or a CPU does not exist, or the affinity
+/// of a thread cannot be set, e.g. none of `cpus` is online.
+pub fn set_affinity(pid: Pid, cpus: &[usize]) -> Result<usize> {
+    let mut set = CpuSet::new();
+    for &cpu in cpus {
+        set.set(cpu).context(SchedulerSnafu {
+            action: format!("add CPU {cpu} to the affinity of"),
+            tid: pid.as_raw(),
+        })?;
+    }
+    let tids = tids(pid)?;
+    for &tid in &tids {
+        sched_setaffinity(tid, &set).context(SchedulerSnafu {
+            action: "set the CPU affinity of",
+            tid: tid.as_raw(),
+        })?;
+    }
+    Ok(tids.len())
+}
+
+/// Parses a CPU list like `taskset -c` takes, e.g. `0-2,5`, or a hex mask, e.g. `0x3`.
+pub fn parse_cpu_list(input: &str) -> Option<Vec<usize>> {
+    if let Some(hex) = input.strip_prefix("0x") {
+        let mask = u128::from_str_radix(hex, 16).ok()?;
+        return Some((0..128).filter(|cpu| mask >> cpu & 1 == 1).collect());
+    }
+    let mut cpus = Vec::new();
+    for range in input.split(',') {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let (first, last): (usize, usize) = (first.parse().ok()?, last.parse().ok()?);
+        if first > last {
+            return None;
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Some(cpus)
+}
+
+/// Writes ascending `cpus` as a CPU list, e.g. `0-2,5`.
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-2,5,1"), Some(vec![0, 1, 2, 5]));
+        assert_eq!(parse_cpu_list("0x22"), Some(vec![1, 5]));
+        assert_eq!(parse_cpu_list("3-1"), None);
+        assert_eq!(parse_cpu_list("a"), None);
+        assert_eq!(format_cpu_list(&[0, 1, 2, 5, 7, 8]), "0-2,5,7-8");
+        assert_eq!(format_cpu_list(&[]), "");
+    }
+}
@@ -0,0 +1,99 @@
+//! Seccomp filters making only selected syscalls stop a traced process.
+//!
+//! Stopping on every syscall with `PTRACE_SYSCALL` costs two context switches per syscall.
+//! A filter returning `SECCOMP_RET_TRACE` lets the kernel run every other syscall without
+//! waking the debugger, which reports the traced ones as `PTRACE_EVENT_SECCOMP` stops.
+
+use nix::errno::Errno;
+use nix::libc::{
+    self, sock_filter, sock_fprog, BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W,
+    SECCOMP_RET_ALLOW, SECCOMP_RET_TRACE,
+};
+use std::mem::offset_of;
+
+/// `AUDIT_ARCH_X86_64` from `<linux/audit.h>`.
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+/// Offset of `nr` in `struct seccomp_data`.
+const NR_OFFSET: u32 = 0;
+/// Offset of `arch` in `struct seccomp_data`.
+const ARCH_OFFSET: u32 = 4;
+
+/// Compiles a filter tracing the x86_64 syscalls numbered `syscalls` and allowing all others,
+/// including those of other ABIs such as `int 0x80`.
+pub fn trace_filter(syscalls: &[u64]) -> Vec<sock_filter> {
+    let statement = |code: u32, k| sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let mut filter = vec![
+        statement(BPF_LD | BPF_W | BPF_ABS, ARCH_OFFSET),
+        sock_filter {
+            code: (BPF_JMP | BPF_JEQ | BPF_K) as u16,
+            jt: 1,
+            jf: 0,
+            k: AUDIT_ARCH_X86_64,
+        },
+        statement(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        statement(BPF_LD | BPF_W | BPF_ABS, NR_OFFSET),
+    ];
+    for number in syscalls
+        .iter()
+        .filter_map(|&number| u32::try_from(number).ok())
+    {
+        filter.push(sock_filter {
+            code: (BPF_JMP | BPF_JEQ | BPF_K) as u16,
+            jt: 0,
+            jf: 1,
+            k: number,
+        });
+        filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_TRACE));
+    }
+    filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    filter
+}
+
+/// Installs `filter` (from [`trace_filter`]) on the calling thread.
+///
+/// This sets `no_new_privs` first, as unprivileged processes must, so set-user-ID programs
+/// run afterwards do not gain privileges. Only makes syscalls, so it is safe to call between
+/// `fork` and `exec`.
+///
+/// # Errors
+/// Returns the `errno` of `prctl` or `seccomp`.
+pub(crate) fn install(filter: &mut [sock_filter]) -> Result<(), Errno> {
+    let program = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+    // SAFETY: `program` points to `filter`, which outlives both calls.
+    unsafe {
+        Errno::result(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+        Errno::result(libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            0,
+            &raw const program,
+        ))?;
+    }
+    Ok(())
+}
+
+const _: () = assert!(offset_of!(libc::seccomp_data, nr) == NR_OFFSET as usize);
+const _: () = assert!(offset_of!(libc::seccomp_data, arch) == ARCH_OFFSET as usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_trace_listed_syscalls() {
+        let raw = trace_filter(&[1, 257]);
+        // Arch check (3), load nr, two checks of 2 instructions each, default.
+        assert_eq!(raw.len(), 3 + 1 + 2 * 2 + 1);
+        assert_eq!((raw[4].k, raw[4].jt, raw[4].jf), (1, 0, 1));
+        assert_eq!(raw[7].k, SECCOMP_RET_TRACE);
+        assert_eq!(raw[8].k, SECCOMP_RET_ALLOW);
+    }
+}
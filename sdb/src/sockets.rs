@@ -0,0 +1,267 @@
+//! The sockets of a process, from the tables of its network namespace in `/proc/<pid>/net`.
+//!
+//! A socket descriptor only links to `socket:[<inode>]`; the tables tell the protocol,
+//! addresses and state of the socket with that inode.
+
+use crate::error::Result;
+use crate::procfs::{pid_path, read_to_string};
+use nix::libc;
+use nix::unistd::Pid;
+use serde::Serialize;
+use std::ffi::CString;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::ffi::OsStringExt as _;
+
+/// `__SO_ACCEPTCON`: the Unix socket listens.
+const UNIX_ACCEPTCON: u32 = 1 << 16;
+
+/// Protocol of a socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    UnixStream,
+    UnixDgram,
+    UnixSeqpacket,
+}
+
+impl Protocol {
+    /// The internet protocols, with their table under `/proc/<pid>/net`.
+    const INET: [(Self, &'static str); 4] = [
+        (Self::Tcp, "tcp"),
+        (Self::Tcp6, "tcp6"),
+        (Self::Udp, "udp"),
+        (Self::Udp6, "udp6"),
+    ];
+
+    const fn is_tcp(self) -> bool {
+        matches!(self, Self::Tcp | Self::Tcp6)
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "tcp",
+            Self::Tcp6 => "tcp6",
+            Self::Udp => "udp",
+            Self::Udp6 => "udp6",
+            Self::UnixStream => "unix stream",
+            Self::UnixDgram => "unix dgram",
+            Self::UnixSeqpacket => "unix seqpacket",
+        })
+    }
+}
+
+/// A socket of a network namespace.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Socket {
+    /// Inode the socket's descriptors link to, as `socket:[<inode>]`.
+    pub inode: u64,
+    pub protocol: Protocol,
+    /// Bound address, e.g. `127.0.0.1:8080`. For Unix sockets, the path, `@name` for an
+    /// abstract one, or empty if unbound.
+    pub local: String,
+    /// Peer address of an internet socket, `None` for Unix sockets.
+    pub remote: Option<SocketAddr>,
+    /// State like `ss` shows it, e.g. `LISTEN` or `ESTAB`.
+    pub state: &'static str,
+}
+
+impl Socket {
+    /// Parses a line of `/proc/net/{tcp,tcp6,udp,udp6}`:
+    /// `sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ..`.
+    pub fn parse_inet(protocol: Protocol, line: &str) -> Option<Self> {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+        Some(Self {
+            inode: fields.get(9)?.parse().ok()?,
+            protocol,
+            local: parse_inet_address(fields.get(1)?)?.to_string(),
+            remote: Some(parse_inet_address(fields.get(2)?)?),
+            state: match state {
+                // A UDP socket is `TCP_ESTABLISHED` once connected, `TCP_CLOSE` before.
+                7 if !protocol.is_tcp() => "UNCONN",
+                1 => "ESTAB",
+                2 => "SYN-SENT",
+                3 => "SYN-RECV",
+                4 => "FIN-WAIT-1",
+                5 => "FIN-WAIT-2",
+                6 => "TIME-WAIT",
+                7 => "CLOSE",
+                8 => "CLOSE-WAIT",
+                9 => "LAST-ACK",
+                10 => "LISTEN",
+                11 => "CLOSING",
+                _ => "UNKNOWN",
+            },
+        })
+    }
+
+    /// Parses a line of `/proc/net/unix`: `Num RefCount Protocol Flags Type St Inode Path`.
+    pub fn parse_unix(line: &str) -> Option<Self> {
+        // The path may contain spaces, and the inode is padded with them.
+        let mut fields = Vec::with_capacity(8);
+        let mut rest = line;
+        for _ in 0..7 {
+            let field = rest.trim_start();
+            let end = field.find(' ').unwrap_or(field.len());
+            fields.push(&field[..end]);
+            rest = &field[end..];
+        }
+        if let Some(path) = rest.strip_prefix(' ') {
+            fields.push(path);
+        }
+        let hex = |index: usize| u32::from_str_radix(fields.get(index)?, 16).ok();
+        let flags = hex(3)?;
+        let connected = hex(5)? == 3;
+        Some(Self {
+            inode: fields.get(6)?.parse().ok()?,
+            protocol: match hex(4)? {
+                1 => Protocol::UnixStream,
+                2 => Protocol::UnixDgram,
+                5 => Protocol::UnixSeqpacket,
+                _ => return None,
+            },
+            local: fields.get(7).map_or("", |path| path.trim_end()).to_string(),
+            remote: None,
+            state: if flags & UNIX_ACCEPTCON != 0 {
+                "LISTEN"
+            } else if connected {
+                "ESTAB"
+            } else {
+                "UNCONN"
+            },
+        })
+    }
+}
+
+/// Parses `0100007F:1F90`: an address in the kernel's byte order and a port.
+fn parse_inet_address(field: &str) -> Option<SocketAddr> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    // The address is printed as 32-bit words, each read from network order as a native one.
+    let mut bytes = Vec::with_capacity(16);
+    for start in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(start..start + 8)?, 16).ok()?;
+        bytes.extend(word.to_ne_bytes());
+    }
+    let ip = match <[u8; 4]>::try_from(bytes.as_slice()) {
+        Ok(bytes) => Ipv4Addr::from(bytes).into(),
+        Err(_) => Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).into(),
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Returns the inode of a descriptor linking to `socket:[<inode>]`.
+pub fn socket_inode(target: &str) -> Option<u64> {
+    target
+        .strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// Asks the kernel for the protocol name of socket descriptor `fd` of `pid`, e.g. `UDPv6`
+/// or `NETLINK`, for sockets missing from the tables.
+pub fn socket_protocol_name(pid: Pid, fd: i32) -> Option<String> {
+    let path = CString::new(
+        pid_path(pid, &format!("fd/{fd}"))
+            .into_os_string()
+            .into_vec(),
+    )
+    .ok()?;
+    let mut name = [0_u8; 32];
+    // SAFETY: Both strings are NUL-terminated and `name` is writable for its whole length.
+    let len = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            c"system.sockprotoname".as_ptr(),
+            name.as_mut_ptr().cast(),
+            name.len(),
+        )
+    };
+    let name = name.get(..usize::try_from(len).ok()?)?;
+    let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Reads the TCP, UDP and Unix sockets of the network namespace of `pid`.
+///
+/// Tables the kernel does not have, e.g. `tcp6` without IPv6, are skipped.
+///
+/// # Errors
+/// Returns an error if the Unix socket table cannot be read, e.g. the process does not exist.
+pub fn read_sockets(pid: Pid) -> Result<Vec<Socket>> {
+    let mut sockets: Vec<_> = read_to_string(pid_path(pid, "net/unix"))?
+        .lines()
+        .skip(1)
+        .filter_map(Socket::parse_unix)
+        .collect();
+    for (protocol, table) in Protocol::INET {
+        let Ok(contents) = read_to_string(pid_path(pid, &format!("net/{table}"))) else {
+            continue;
+        };
+        sockets.extend(
+            contents
+                .lines()
+                .skip(1)
+                .filter_map(|line| Socket::parse_inet(protocol, line)),
+        );
+    }
+    Ok(sockets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_socket_tables() {
+        let listen = Socket::parse_inet(
+            Protocol::Tcp,
+            "   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0",
+        );
+        assert_eq!(
+            listen,
+            Some(Socket {
+                inode: 41234,
+                protocol: Protocol::Tcp,
+                local: "127.0.0.1:8080".into(),
+                remote: "0.0.0.0:0".parse().ok(),
+                state: "LISTEN",
+            })
+        );
+        let udp6 = Socket::parse_inet(
+            Protocol::Udp6,
+            "  12: 00000000000000000000000001000000:0035 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 5678 2 0000000000000000 0",
+        );
+        assert_eq!(
+            udp6.map(|socket| (socket.local, socket.state)),
+            Some(("[::1]:53".to_string(), "UNCONN"))
+        );
+
+        let unix = Socket::parse_unix(
+            "0000000000000000: 00000002 00000000 00010000 0001 01 9012 /run/my app.sock",
+        );
+        assert_eq!(
+            unix.map(|socket| (socket.protocol, socket.local, socket.state)),
+            Some((
+                Protocol::UnixStream,
+                "/run/my app.sock".to_string(),
+                "LISTEN"
+            ))
+        );
+        assert_eq!(
+            Socket::parse_unix("0000000000000000: 00000003 00000000 00000000 0002 03  3456")
+                .map(|socket| (socket.protocol, socket.local, socket.state)),
+            Some((Protocol::UnixDgram, String::new(), "ESTAB"))
+        );
+        assert_eq!(socket_inode("socket:[9012]"), Some(9012));
+        assert_eq!(socket_inode("pipe:[9012]"), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! Stack inspection without debug info: frame-pointer chains and the stack protector canary.
+
+use crate::process::{Process, ReadMemory};
+use crate::procfs::{find_region, MemoryRegion};
+use nix::libc::user_regs_struct;
+use serde::Serialize;
+
+/// Offset of the stack protector canary in the x86_64 TCB (`%fs:0x28`).
+const CANARY_OFFSET: u64 = 0x28;
+
+/// Default `stack_guard_gap` the kernel keeps free below a growable stack (256 pages).
+const STACK_GUARD_GAP: u64 = 256 * 4096;
+/// How far from `rsp` a fault may be to still be blamed on a stack push or a large frame.
+const MAX_SP_DISTANCE: u64 = 64 * 1024;
+
+/// Code of `__restore_rt`, which signal handlers return to: `mov rax, 15` (`rt_sigreturn`),
+/// `syscall`. glibc and musl both have it.
+const RESTORE_RT: [u8; 9] = [0x48, 0xc7, 0xc0, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+/// Offset of `uc_mcontext` in `struct ucontext`.
+const MCONTEXT_OFFSET: u64 = 40;
+/// The registers at the start of `uc_mcontext` (`struct sigcontext`), in order.
+const SIGCONTEXT_REGISTERS: [&str; 18] = [
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rdi", "rsi", "rbp", "rbx", "rdx", "rax",
+    "rcx", "rsp", "rip", "eflags",
+];
+/// Index of `rip` in [`SIGCONTEXT_REGISTERS`].
+const RIP_INDEX: u64 = 16;
+
+/// How a [`Frame`] was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// From the saved `rbp` of the frame it calls, or from the registers for frame 0.
+    FramePointer,
+    /// The frame the kernel pushes to run a signal handler, which returns to `__restore_rt`.
+    /// Its `frame_pointer` is the `ucontext` holding the registers of the interrupted frame.
+    Signal {
+        /// `rsp` when the signal arrived.
+        interrupted_sp: u64,
+    },
+}
+
+impl std::fmt::Display for FrameKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::FramePointer => "frame pointer",
+            Self::Signal { .. } => "signal trampoline (__restore_rt)",
+        })
+    }
+}
+
+/// A frame recovered by following saved `rbp` values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// Frame number, `0` being the innermost.
+    pub index: usize,
+    /// Code address the frame is executing (`rip` for frame 0, a return address otherwise).
+    pub pc: u64,
+    /// `rbp` of this frame: the slot holding the caller's saved `rbp`. For a
+    /// [`FrameKind::Signal`] frame, the address of the `ucontext`.
+    pub frame_pointer: u64,
+    pub kind: FrameKind,
+}
+
+impl Frame {
+    /// Address of the slot holding this frame's return address, or the interrupted `rip`
+    /// for a signal frame.
+    pub const fn return_address_slot(&self) -> u64 {
+        match self.kind {
+            FrameKind::FramePointer => self.frame_pointer + 8,
+            FrameKind::Signal { .. } => self.frame_pointer + MCONTEXT_OFFSET + RIP_INDEX * 8,
+        }
+    }
+
+    /// Canonical frame address: the value of `rsp` before the call into this frame. For a
+    /// signal frame, `rsp` when the signal arrived, like glibc's CFI for `__restore_rt` has it.
+    pub const fn cfa(&self) -> u64 {
+        match self.kind {
+            FrameKind::FramePointer => self.frame_pointer + 16,
+            FrameKind::Signal { interrupted_sp } => interrupted_sp,
+        }
+    }
+
+    /// Lists the registers of the caller saved in this frame, with the slots holding them.
+    ///
+    /// Only `rbp` and the return address, as `rip`, are known without CFI; callee-saved
+    /// registers the function pushed, e.g. `rbx`, are not. A signal frame saves them all.
+    pub fn saved_registers(&self) -> Vec<(&'static str, u64)> {
+        match self.kind {
+            FrameKind::FramePointer => vec![
+                ("rbp", self.frame_pointer),
+                ("rip", self.return_address_slot()),
+            ],
+            FrameKind::Signal { .. } => (0..)
+                .zip(SIGCONTEXT_REGISTERS)
+                .map(|(index, name)| (name, self.frame_pointer + MCONTEXT_OFFSET + index * 8))
+                .collect(),
+        }
+    }
+}
+
+/// Walks the `rbp` chain from the current registers, returning at most `max_frames` frames.
+///
+/// `memory` is usually the [`Process`]; pass its
+/// [`RemoteMemory`](crate::process::RemoteMemory) to unwind threads in parallel.
+///
+/// This only works for code compiled with frame pointers; the walk stops at the first
+/// `rbp` that does not point further up the stack. A frame returning to `__restore_rt` is
+/// followed by the signal frame, and the walk goes on from the registers the signal
+/// interrupted.
+pub fn frame_pointer_chain(
+    memory: &impl ReadMemory,
+    regs: &user_regs_struct,
+    max_frames: usize,
+) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut frame = Frame {
+        index: 0,
+        pc: regs.rip,
+        frame_pointer: regs.rbp,
+        kind: FrameKind::FramePointer,
+    };
+    // Frame pointers are above the stack pointer of the innermost frame of their stack.
+    let mut sp = regs.rsp;
+    while frames.len() < max_frames && is_plausible_frame_pointer(frame.frame_pointer, sp) {
+        frames.push(frame);
+        let Ok(words) = memory.read_words(frame.frame_pointer, 2) else {
+            break;
+        };
+        let &[saved_rbp, return_address] = words.as_slice() else {
+            break;
+        };
+        if is_signal_trampoline(memory, return_address) {
+            if frames.len() == max_frames {
+                break;
+            }
+            // The `rt_sigframe` starts with the handler's return address, then the `ucontext`.
+            let context = frame.cfa();
+            let Ok(saved) =
+                memory.read_words(context + MCONTEXT_OFFSET, SIGCONTEXT_REGISTERS.len())
+            else {
+                break;
+            };
+            let saved = |name| {
+                let index = SIGCONTEXT_REGISTERS
+                    .iter()
+                    .position(|&saved| saved == name)?;
+                saved.get(index).copied()
+            };
+            let (Some(rbp), Some(rsp), Some(rip)) = (saved("rbp"), saved("rsp"), saved("rip"))
+            else {
+                break;
+            };
+            frames.push(Frame {
+                index: frame.index + 1,
+                pc: return_address,
+                frame_pointer: context,
+                kind: FrameKind::Signal {
+                    interrupted_sp: rsp,
+                },
+            });
+            // The handler may have run on an alternate stack.
+            sp = rsp;
+            frame = Frame {
+                index: frame.index + 2,
+                pc: rip,
+                frame_pointer: rbp,
+                kind: FrameKind::FramePointer,
+            };
+            continue;
+        }
+        if saved_rbp <= frame.frame_pointer || return_address == 0 {
+            break;
+        }
+        frame = Frame {
+            index: frame.index + 1,
+            pc: return_address,
+            frame_pointer: saved_rbp,
+            kind: FrameKind::FramePointer,
+        };
+    }
+    frames
+}
+
+/// Checks whether `pc` is at the start of `__restore_rt`.
+fn is_signal_trampoline(memory: &impl ReadMemory, pc: u64) -> bool {
+    pc != 0 && memory.read_memory(pc, RESTORE_RT.len()).ok().as_deref() == Some(&RESTORE_RT[..])
+}
+
+const fn is_plausible_frame_pointer(frame_pointer: u64, rsp: u64) -> bool {
+    frame_pointer >= rsp && frame_pointer.is_multiple_of(8)
+}
+
+/// Reads the stack protector canary of the thread whose `fs_base` is given.
+pub fn stack_canary(process: &Process, fs_base: u64) -> Option<u64> {
+    if fs_base == 0 {
+        return None;
+    }
+    process
+        .read_words(fs_base + CANARY_OFFSET, 1)
+        .ok()?
+        .first()
+        .copied()
+}
+
+/// A fault that hit the guard area right below a stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct StackOverflow {
+    /// Lowest address of the overflowed stack mapping.
+    pub stack_start: u64,
+    /// End of the overflowed stack mapping.
+    pub stack_end: u64,
+    /// Bytes between the fault address and the stack start.
+    pub distance: u64,
+}
+
+impl std::fmt::Display for StackOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the fault is {:#x} bytes below the stack at {:#x}-{:#x}",
+            self.distance, self.stack_start, self.stack_end
+        )
+    }
+}
+
+/// Checks whether a fault at `fault_addr` with stack pointer `sp` looks like a stack overflow.
+///
+/// That is the case when `sp` is close to the fault and the fault lies either in the gap below
+/// the main `[stack]` or in an inaccessible guard mapping right below a thread stack.
+pub fn detect_stack_overflow(
+    maps: &[MemoryRegion],
+    fault_addr: u64,
+    sp: u64,
+) -> Option<StackOverflow> {
+    if fault_addr.abs_diff(sp) > MAX_SP_DISTANCE {
+        return None;
+    }
+    let stack = match find_region(maps, fault_addr) {
+        // Thread stacks: glibc maps a `---p` guard directly below the anonymous stack.
+        Some(guard) if !guard.read && !guard.write && !guard.execute => maps
+            .iter()
+            .find(|region| region.start == guard.end && region.read && region.write)?,
+        Some(_) => return None,
+        // The main stack grows down into unmapped memory up to the guard gap.
+        None => maps.iter().find(|region| {
+            region.path == "[stack]"
+                && fault_addr < region.start
+                && region.start - fault_addr <= STACK_GUARD_GAP
+        })?,
+    };
+    Some(StackOverflow {
+        stack_start: stack.start,
+        stack_end: stack.end,
+        distance: stack.start - fault_addr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use crate::process::ScriptedBackend;
+    use nix::sys::signal::Signal;
+    use nix::sys::wait::WaitStatus;
+    use nix::unistd::Pid;
+
+    #[test]
+    fn should_unwind_through_signal_frames() -> Result<()> {
+        let pid = Pid::from_raw(i32::MAX);
+        // SAFETY: `user_regs_struct` is plain integers.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        (regs.rip, regs.rsp, regs.rbp) = (0x1111, 0x7000, 0x7010);
+        let words = |words: &[u64]| -> Vec<u8> {
+            words.iter().flat_map(|word| word.to_le_bytes()).collect()
+        };
+        let backend = ScriptedBackend::new(regs);
+        backend.map(0x5000, &RESTORE_RT);
+        // The handler's frame, returning to `__restore_rt`, then the `ucontext`.
+        let mut handler_stack = words(&[0, 0, 0xdead, 0x5000]);
+        handler_stack.extend([0; MCONTEXT_OFFSET as usize]);
+        let mut sigcontext = [0; SIGCONTEXT_REGISTERS.len()];
+        (sigcontext[10], sigcontext[15], sigcontext[16]) = (0x9010, 0x9000, 0x2222);
+        handler_stack.extend(words(&sigcontext));
+        backend.map(0x7000, &handler_stack);
+        // The interrupted frame and its caller, the outermost one.
+        backend.map(0x9000, &words(&[0, 0, 0x9030, 0x3333, 0, 0, 0, 0]));
+        let process =
+            Process::with_backend(backend, pid, WaitStatus::Stopped(pid, Signal::SIGTRAP));
+
+        let frames = frame_pointer_chain(&process, &regs, 16);
+        assert_eq!(
+            frames
+                .iter()
+                .map(|frame| (frame.index, frame.pc, frame.frame_pointer, frame.kind))
+                .collect::<Vec<_>>(),
+            [
+                (0, 0x1111, 0x7010, FrameKind::FramePointer),
+                (
+                    1,
+                    0x5000,
+                    0x7020,
+                    FrameKind::Signal {
+                        interrupted_sp: 0x9000
+                    }
+                ),
+                (2, 0x2222, 0x9010, FrameKind::FramePointer),
+                (3, 0x3333, 0x9030, FrameKind::FramePointer),
+            ]
+        );
+        assert_eq!(frames[1].cfa(), 0x9000);
+        assert_eq!(
+            process.read_words(frames[1].return_address_slot(), 1)?,
+            [0x2222]
+        );
+        assert_eq!(
+            frames[1].saved_registers().len(),
+            SIGCONTEXT_REGISTERS.len()
+        );
+        assert_eq!(frame_pointer_chain(&process, &regs, 1).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn should_detect_stack_overflow() {
+        let maps: Vec<_> = [
+            "7f0000000000-7f0000001000 ---p 00000000 00:00 0 ",
+            "7f0000001000-7f0000801000 rw-p 00000000 00:00 0 ",
+            "7ffd00000000-7ffd00021000 rw-p 00000000 00:00 0                          [stack]",
+        ]
+        .iter()
+        .filter_map(|line| MemoryRegion::parse(line))
+        .collect();
+
+        let main = detect_stack_overflow(&maps, 0x7ffc_ffff_fff8, 0x7ffd_0000_0000);
+        assert_eq!(main.map(|overflow| overflow.distance), Some(8));
+        let thread = detect_stack_overflow(&maps, 0x7f00_0000_0ff8, 0x7f00_0000_1000);
+        assert_eq!(
+            thread.map(|overflow| overflow.stack_start),
+            Some(0x7f00_0000_1000)
+        );
+        // A null dereference is not an overflow, even with the guard nearby.
+        assert_eq!(detect_stack_overflow(&maps, 0, 0x7ffd_0000_0000), None);
+    }
+}
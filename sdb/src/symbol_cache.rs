@@ -0,0 +1,92 @@
+//! Symbol tables saved on disk, so the next session debugging the same files starts faster.
+//!
+//! Tables live in `$XDG_CACHE_HOME/sdb/symbols` (`~/.cache/sdb/symbols` by default), one file
+//! per build ID. A table is only reused if the file it was loaded from has the same
+//! modification time, which catches binaries rebuilt without a new build ID. The cache is
+//! best-effort: any failure to read or write it just means loading the symbols from the ELF.
+
+use crate::symbols::SymbolTable;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Bumped whenever the layout of [`SymbolTable`] changes, invalidating older entries.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    version: u32,
+    /// Modification time of the file the table was loaded from.
+    modified: Option<SystemTime>,
+    table: SymbolTable,
+}
+
+/// Returns `$XDG_CACHE_HOME/sdb/symbols`, falling back to `~/.cache/sdb/symbols`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("sdb").join("symbols"))
+}
+
+/// Reads the table saved for `build_id`, if it was loaded from a file modified at `modified`.
+pub(crate) fn read(build_id: &[u8], modified: Option<SystemTime>) -> Option<SymbolTable> {
+    let file = File::open(entry_path(build_id)?).ok()?;
+    let entry: Entry = bincode::deserialize_from(BufReader::new(file)).ok()?;
+    (entry.version == FORMAT_VERSION && entry.modified == modified).then_some(entry.table)
+}
+
+/// Saves `table` for `build_id`, ignoring errors.
+pub(crate) fn write(build_id: &[u8], modified: Option<SystemTime>, table: &SymbolTable) {
+    let Some(path) = entry_path(build_id) else {
+        return;
+    };
+    let entry = Entry {
+        version: FORMAT_VERSION,
+        modified,
+        table: table.clone(),
+    };
+    // Write to a temporary file and rename it, so concurrent sessions never read half an entry.
+    let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| File::create(&temporary))
+        .is_ok_and(|file| bincode::serialize_into(BufWriter::new(file), &entry).is_ok());
+    if !written || fs::rename(&temporary, &path).is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+}
+
+fn entry_path(build_id: &[u8]) -> Option<PathBuf> {
+    let name = build_id.iter().fold(String::new(), |mut name, byte| {
+        let _ = write!(name, "{byte:02x}");
+        name
+    });
+    Some(cache_dir()?.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_entries() {
+        let dir = std::env::temp_dir().join(format!("sdb-symbol-cache-{}", std::process::id()));
+        std::env::set_var("XDG_CACHE_HOME", &dir);
+        let build_id = [0xde, 0xad, 0xbe, 0xef];
+        let modified = Some(SystemTime::UNIX_EPOCH);
+        let table = SymbolTable::default();
+
+        write(&build_id, modified, &table);
+        assert!(dir.join("sdb/symbols/deadbeef").exists());
+        assert_eq!(read(&build_id, modified), Some(table));
+        // A rebuilt file with the same build ID.
+        assert_eq!(read(&build_id, Some(SystemTime::now())), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,82 @@
+//! Loading the symbol tables of a process's files on a worker thread, so that the prompt is
+//! usable right after attaching to a program with many large libraries.
+
+use crate::procfs::MemoryRegion;
+use crate::symbols::SymbolTable;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// A worker filling the [`SymbolTable::cached`] cache.
+#[derive(Debug)]
+pub struct SymbolIndexer {
+    /// Files loaded (or failed) so far.
+    done: Arc<AtomicUsize>,
+    total: usize,
+    worker: JoinHandle<()>,
+}
+
+impl SymbolIndexer {
+    /// Starts loading the symbol tables of `paths`, one after the other.
+    pub fn spawn(paths: Vec<PathBuf>) -> Self {
+        let done = Arc::new(AtomicUsize::new(0));
+        let total = paths.len();
+        let worker = {
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                for path in paths {
+                    // Unreadable files are reported by whichever command needs them.
+                    let _ = SymbolTable::cached(&path);
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+        Self {
+            done,
+            total,
+            worker,
+        }
+    }
+
+    /// Returns how many of the files are loaded, and how many there are.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total)
+    }
+
+    /// Returns `true` once every file is loaded.
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+}
+
+/// Returns the files mapped in `maps` whose symbol tables are not cached yet.
+pub fn unindexed_files(maps: &[MemoryRegion]) -> Vec<PathBuf> {
+    maps.iter()
+        .map(|region| Path::new(&region.path))
+        .filter(|path| path.is_absolute())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|path| SymbolTable::try_cached(path).is_none())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_index_in_background() {
+        let Ok(exe) = std::env::current_exe() else {
+            return;
+        };
+        let indexer = SymbolIndexer::spawn(vec![exe.clone(), PathBuf::from("/nonexistent")]);
+        while !indexer.is_finished() {
+            std::thread::yield_now();
+        }
+        assert_eq!(indexer.progress(), (2, 2));
+        assert!(SymbolTable::try_cached(&exe).is_some());
+    }
+}
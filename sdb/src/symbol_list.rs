@@ -0,0 +1,309 @@
+//! Listing the symbols of an ELF file for `sym list`, by the table they come from.
+//!
+//! Unlike [`crate::symbols::SymbolTable`], which merges every source for symbolization, each
+//! listed symbol keeps its source and section, so one can tell an exported function from a
+//! local one, or see functions that only the debug info still knows after `strip`.
+
+use crate::compile_units::{dwarf_sections, Slice};
+use crate::error::{InvalidRegexSnafu, LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use crate::symbols::demangle;
+use gimli::{AttributeValue, EndianSlice, LittleEndian};
+use object::{Object, ObjectSection, ObjectSegment, ObjectSymbol, SectionKind, SymbolKind};
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+
+/// Where a symbol was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SymbolSource {
+    /// `.symtab`, removed by `strip`.
+    Static,
+    /// `.dynsym`, the symbols the dynamic linker resolves.
+    Dynamic,
+    /// A function with code in the DWARF (`DW_TAG_subprogram`).
+    Debug,
+}
+
+impl std::fmt::Display for SymbolSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Static => "symtab",
+            Self::Dynamic => "dynsym",
+            Self::Debug => "dwarf",
+        })
+    }
+}
+
+/// Order of [`list`]'s result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SymbolOrder {
+    #[default]
+    Address,
+    Name,
+}
+
+/// Which symbols [`list`] returns.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SymbolQuery {
+    /// Tables to read; `.symtab` and `.dynsym` if empty.
+    pub sources: Vec<SymbolSource>,
+    /// Only symbols whose name contains a match of this regex.
+    pub regex: Option<String>,
+    /// Only symbols in the section of this name, e.g. `.text`.
+    pub section: Option<String>,
+    pub order: SymbolOrder,
+}
+
+/// A symbol of one of the tables of an ELF file.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ListedSymbol {
+    /// Demangled name.
+    pub name: String,
+    /// Link-time virtual address.
+    pub address: u64,
+    /// Size in bytes (`0` if unknown).
+    pub size: u64,
+    /// Whether this is a code symbol.
+    pub is_function: bool,
+    pub source: SymbolSource,
+    /// Section containing the address.
+    pub section: Option<String>,
+}
+
+/// Symbols of an ELF file matching a [`SymbolQuery`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SymbolListing {
+    /// Link-time address of the first `PT_LOAD` segment, which module offsets are relative to.
+    pub image_base: u64,
+    pub symbols: Vec<ListedSymbol>,
+}
+
+/// Lists the function and data symbols of the ELF file at `path` matching `query`. A symbol
+/// found in several tables at the same address is listed once, from the first of them.
+///
+/// # Errors
+/// Returns an error if the regex is invalid, or the file cannot be read or has malformed
+/// DWARF.
+pub fn list(path: &Path, query: &SymbolQuery) -> Result<SymbolListing> {
+    let regex = query
+        .regex
+        .as_deref()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|err| {
+                InvalidRegexSnafu {
+                    pattern,
+                    msg: err.to_string(),
+                }
+                .build()
+            })
+        })
+        .transpose()?;
+    let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+    let data = MappedFile::open(path).map_err(|err| to_error(err.to_string()))?;
+    let file = object::File::parse(&*data).map_err(|err| to_error(err.to_string()))?;
+
+    let mut sources = query.sources.clone();
+    if sources.is_empty() {
+        sources = vec![SymbolSource::Static, SymbolSource::Dynamic];
+    }
+    sources.sort_unstable();
+    sources.dedup();
+    let sections = alloc_sections(&file);
+    let mut symbols = Vec::new();
+    for source in sources {
+        let found = match source {
+            SymbolSource::Static => table_symbols(file.symbols(), source),
+            SymbolSource::Dynamic => table_symbols(file.dynamic_symbols(), source),
+            SymbolSource::Debug => {
+                debug_functions(&file).map_err(|err| to_error(format!("bad DWARF: {err}")))?
+            }
+        };
+        symbols.extend(found.into_iter().map(|mut symbol| {
+            symbol.section = section_of(&sections, symbol.address);
+            symbol
+        }));
+    }
+    symbols.retain(|symbol| {
+        regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&symbol.name))
+            && query
+                .section
+                .as_ref()
+                .is_none_or(|section| symbol.section.as_ref() == Some(section))
+    });
+    // Stable, so that the first source of a duplicate comes first.
+    symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+    symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+    if query.order == SymbolOrder::Name {
+        symbols.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.address.cmp(&b.address)));
+    }
+
+    let image_base = file
+        .segments()
+        .map(|segment| segment.address())
+        .min()
+        .unwrap_or_default();
+    Ok(SymbolListing {
+        image_base,
+        symbols,
+    })
+}
+
+/// Collects the defined function and data symbols of a symbol table.
+fn table_symbols<'data, S: ObjectSymbol<'data>>(
+    symbols: impl Iterator<Item = S>,
+    source: SymbolSource,
+) -> Vec<ListedSymbol> {
+    symbols
+        .filter(|symbol| {
+            symbol.address() != 0 && matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data)
+        })
+        .filter_map(|symbol| {
+            Some(ListedSymbol {
+                name: demangle(symbol.name().ok()?),
+                address: symbol.address(),
+                size: symbol.size(),
+                is_function: symbol.kind() == SymbolKind::Text,
+                source,
+                section: None,
+            })
+        })
+        .collect()
+}
+
+/// Lists the subprograms of the DWARF that have code, named after their linkage name if
+/// they have one.
+fn debug_functions(file: &object::File<'_>) -> gimli::Result<Vec<ListedSymbol>> {
+    let Ok(sections) = dwarf_sections(file) else {
+        return Ok(Vec::new());
+    };
+    let dwarf = sections.borrow(|section| EndianSlice::new(section, LittleEndian));
+    let mut functions = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let low_pc = match entry.attr_value(gimli::DW_AT_low_pc)? {
+                Some(value) => dwarf.attr_address(&unit, value)?,
+                None => None,
+            };
+            let Some(low_pc) = low_pc.filter(|&low_pc| low_pc != 0) else {
+                continue;
+            };
+            let size = match entry.attr_value(gimli::DW_AT_high_pc)? {
+                Some(value @ (AttributeValue::Addr(_) | AttributeValue::DebugAddrIndex(_))) => {
+                    dwarf
+                        .attr_address(&unit, value)?
+                        .map_or(0, |high_pc| high_pc.saturating_sub(low_pc))
+                }
+                Some(value) => value.udata_value().unwrap_or_default(),
+                None => 0,
+            };
+            let Some(name) = subprogram_name(&dwarf, &unit, entry)? else {
+                continue;
+            };
+            functions.push(ListedSymbol {
+                name,
+                address: low_pc,
+                size,
+                is_function: true,
+                source: SymbolSource::Debug,
+                section: None,
+            });
+        }
+    }
+    Ok(functions)
+}
+
+/// Finds the name of a subprogram, following its declaration (`DW_AT_specification`) or
+/// abstract instance (`DW_AT_abstract_origin`) if the name is there.
+fn subprogram_name(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    entry: &gimli::DebuggingInformationEntry<'_, '_, Slice<'_>>,
+) -> gimli::Result<Option<String>> {
+    let string = |value| -> gimli::Result<String> {
+        Ok(dwarf
+            .attr_string(unit, value)?
+            .to_string_lossy()
+            .into_owned())
+    };
+    for name in [
+        gimli::DW_AT_linkage_name,
+        gimli::DW_AT_MIPS_linkage_name,
+        gimli::DW_AT_name,
+    ] {
+        if let Some(value) = entry.attr_value(name)? {
+            return Ok(Some(demangle(&string(value)?)));
+        }
+    }
+    for origin in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+        if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(origin)? {
+            let origin = unit.entry(offset)?;
+            return subprogram_name(dwarf, unit, &origin);
+        }
+    }
+    Ok(None)
+}
+
+/// Lists the address ranges of the sections loaded into memory, with their names.
+fn alloc_sections(file: &object::File<'_>) -> Vec<(Range<u64>, String)> {
+    file.sections()
+        .filter(|section| {
+            section.address() != 0
+                && !matches!(section.kind(), SectionKind::Metadata | SectionKind::Other)
+        })
+        .filter_map(|section| {
+            let start = section.address();
+            Some((
+                start..start + section.size(),
+                section.name().ok()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn section_of(sections: &[(Range<u64>, String)], addr: u64) -> Option<String> {
+    sections
+        .iter()
+        .find(|(range, _)| range.contains(&addr))
+        .map(|(_, name)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_list_own_functions() -> Result<()> {
+        let exe = std::env::current_exe().map_err(|err| {
+            LoadElfSnafu {
+                path: "/proc/self/exe",
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        let name = "should_list_own_functions";
+        for source in [SymbolSource::Static, SymbolSource::Debug] {
+            let query = SymbolQuery {
+                sources: vec![source],
+                regex: Some(format!("{name}$")),
+                section: Some(".text".into()),
+                order: SymbolOrder::Name,
+            };
+            let listing = list(&exe, &query)?;
+            assert_eq!(listing.symbols.len(), 1, "{source}: {:?}", listing.symbols);
+            assert!(listing
+                .symbols
+                .iter()
+                .all(|symbol| symbol.is_function && symbol.source == source));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! ELF symbol tables used to turn addresses into `symbol+offset`.
+
+use crate::error::{LoadElfSnafu, Result};
+use crate::mapped_file::MappedFile;
+use crate::procfs::MemoryRegion;
+use crate::symbol_cache;
+use object::{Object, ObjectKind, ObjectSection, ObjectSegment, ObjectSymbol, SymbolKind};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Tables loaded by [`SymbolTable::cached`], by path.
+static CACHE: Mutex<BTreeMap<PathBuf, CachedTable>> = Mutex::new(BTreeMap::new());
+
+struct CachedTable {
+    /// Modification time of the file the table was loaded from.
+    modified: Option<SystemTime>,
+    table: Arc<SymbolTable>,
+}
+
+/// A function or data symbol from `.symtab`/`.dynsym`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Symbol {
+    /// Demangled name.
+    pub name: String,
+    /// Link-time virtual address.
+    pub address: u64,
+    /// Size in bytes (`0` if unknown).
+    pub size: u64,
+    /// Whether this is a code symbol.
+    pub is_function: bool,
+}
+
+/// A `PT_LOAD` segment, used to translate file offsets into link-time addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Segment {
+    address: u64,
+    file_offset: u64,
+    file_size: u64,
+}
+
+/// Symbols of one ELF file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SymbolTable {
+    /// Sorted by address.
+    symbols: Vec<Symbol>,
+    segments: Vec<Segment>,
+}
+
+impl SymbolTable {
+    /// Loads the static and dynamic symbols of the ELF file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid object file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = MappedFile::open(path).map_err(|err| {
+            LoadElfSnafu {
+                path,
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        Self::parse(&data, path)
+    }
+
+    /// Loads the symbols of the ELF file in `data`, which `path` names in errors.
+    ///
+    /// # Errors
+    /// Returns an error if `data` is not a valid object file.
+    pub fn parse(data: &[u8], path: &Path) -> Result<Self> {
+        let file = object::File::parse(data).map_err(|err| {
+            LoadElfSnafu {
+                path,
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        Ok(Self::from_file(&file))
+    }
+
+    /// Like [`Self::load`], but reuses the table saved on disk by an earlier session for the
+    /// same build ID, or saves it for the next one.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid object file.
+    pub fn load_persistent(path: &Path) -> Result<Self> {
+        let to_error = |msg: String| LoadElfSnafu { path, msg }.build();
+        let data = MappedFile::open(path).map_err(|err| to_error(err.to_string()))?;
+        let file = object::File::parse(&*data).map_err(|err| to_error(err.to_string()))?;
+        let Some(build_id) = file.build_id().ok().flatten() else {
+            return Ok(Self::from_file(&file));
+        };
+        let modified = modified(path);
+        if let Some(table) = symbol_cache::read(build_id, modified) {
+            return Ok(table);
+        }
+        let table = Self::from_file(&file);
+        symbol_cache::write(build_id, modified, &table);
+        Ok(table)
+    }
+
+    fn from_file(file: &object::File<'_>) -> Self {
+        // Symbols of relocatable files, such as those JIT compilers register, are relative to
+        // their sections, which the compiler gave the addresses it placed them at.
+        let section_address = |symbol: &object::Symbol<'_, '_>| {
+            (file.kind() == ObjectKind::Relocatable)
+                .then(|| file.section_by_index(symbol.section_index()?).ok())
+                .flatten()
+                .map_or(0, |section| section.address())
+        };
+        let mut symbols: Vec<_> = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|symbol| matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data))
+            .filter_map(|symbol| {
+                let address = symbol.address() + section_address(&symbol);
+                Some(Symbol {
+                    name: demangle(symbol.name().ok()?),
+                    address: (address != 0).then_some(address)?,
+                    size: symbol.size(),
+                    is_function: symbol.kind() == SymbolKind::Text,
+                })
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+        symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+
+        let segments = file
+            .segments()
+            .map(|segment| {
+                let (file_offset, file_size) = segment.file_range();
+                Segment {
+                    address: segment.address(),
+                    file_offset,
+                    file_size,
+                }
+            })
+            .collect();
+
+        Self { symbols, segments }
+    }
+
+    /// Like [`Self::load_persistent`], but each file is only loaded again once it changes.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid object file.
+    pub fn cached(path: &Path) -> Result<Arc<Self>> {
+        if let Some(table) = Self::try_cached(path) {
+            return Ok(table);
+        }
+        let modified = modified(path);
+        let table = Arc::new(Self::load_persistent(path)?);
+        CACHE.lock().unwrap_or_else(PoisonError::into_inner).insert(
+            path.to_path_buf(),
+            CachedTable {
+                modified,
+                table: Arc::clone(&table),
+            },
+        );
+        Ok(table)
+    }
+
+    /// Returns the table of `path` if it is cached and the file has not changed since.
+    pub fn try_cached(path: &Path) -> Option<Arc<Self>> {
+        let (loaded, table) = CACHE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .map(|cached| (cached.modified, Arc::clone(&cached.table)))?;
+        (loaded == modified(path)).then_some(table)
+    }
+
+    /// Returns all symbols sorted by address.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Translates a file offset into the link-time address it is loaded at.
+    pub fn file_offset_to_address(&self, offset: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|segment| {
+                segment.file_offset <= offset && offset < segment.file_offset + segment.file_size
+            })
+            .map(|segment| segment.address + (offset - segment.file_offset))
+    }
+
+    /// Returns the difference between runtime and link-time addresses for `region`,
+    /// a mapping of this file.
+    pub fn load_bias(&self, region: &MemoryRegion) -> Option<u64> {
+        let address = self.file_offset_to_address(region.offset)?;
+        Some(region.start.wrapping_sub(address))
+    }
+
+    /// Finds the symbol containing the link-time address `address`.
+    ///
+    /// # Returns
+    /// The symbol and the offset of `address` into it.
+    pub fn find(&self, address: u64) -> Option<(&Symbol, u64)> {
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        let offset = address - symbol.address;
+        (symbol.size == 0 || offset < symbol.size).then_some((symbol, offset))
+    }
+
+    /// Finds the symbol containing the runtime address `addr` inside `region`.
+    pub fn symbolize(&self, region: &MemoryRegion, addr: u64) -> Option<(&Symbol, u64)> {
+        let bias = self.load_bias(region)?;
+        self.find(addr.wrapping_sub(bias))
+    }
+}
+
+pub(crate) fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Demangles Rust (legacy and v0) symbol names, leaving others untouched.
+pub(crate) fn demangle(name: &str) -> String {
+    rustc_demangle::try_demangle(name)
+        .map_or_else(|_| name.to_string(), |demangled| format!("{demangled:#}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_own_symbols() -> Result<()> {
+        let exe = std::env::current_exe().map_err(|err| {
+            LoadElfSnafu {
+                path: "/proc/self/exe",
+                msg: err.to_string(),
+            }
+            .build()
+        })?;
+        let table = SymbolTable::load(&exe)?;
+        let name = "should_find_own_symbols";
+        let symbol = table
+            .symbols()
+            .iter()
+            .find(|symbol| symbol.name.ends_with(name));
+        assert!(symbol.is_some(), "{name} not found");
+        if let Some(symbol) = symbol {
+            let found = table
+                .find(symbol.address + 1)
+                .map(|(symbol, _)| &symbol.name);
+            assert_eq!(found, Some(&symbol.name));
+        }
+        Ok(())
+    }
+}
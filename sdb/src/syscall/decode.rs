@@ -0,0 +1,114 @@
+use super::socket::{
+    address_family_name, format_socket_type, msghdr_at, sockaddr_at, sockaddr_with_len_ptr,
+    sockopt_name,
+};
+use super::{SyscallDirection, SyscallEvent};
+use crate::process::ReadMemory;
+
+/// Default maximum number of buffer bytes rendered per argument.
+pub const MAX_BUFFER_LEN: usize = 64;
+
+/// Renders a syscall like strace does, e.g. `write(1, "hi\n", 3) = 3`.
+///
+/// Buffers are read from the inferior's memory: outgoing data on entry and exit,
+/// incoming data only on exit once the kernel has filled it.
+pub fn describe(event: &SyscallEvent, process: &impl ReadMemory) -> String {
+    describe_with_limit(event, process, MAX_BUFFER_LEN)
+}
+
+/// [`describe`], rendering at most `max_buffer_len` bytes of each buffer.
+pub fn describe_with_limit(
+    event: &SyscallEvent,
+    process: &impl ReadMemory,
+    max_buffer_len: usize,
+) -> String {
+    let buffer = |addr, len| read_buffer(process, addr, len, max_buffer_len);
+    let name = event.name().unwrap_or("unknown");
+    let [a0, a1, a2, a3, a4, a5] = event.args;
+    let fd = a0 as i32;
+    let is_exit = event.direction == SyscallDirection::Exit;
+    let exit_len = event
+        .ret
+        .and_then(|ret| usize::try_from(ret).ok())
+        .unwrap_or_default();
+
+    let args = match name {
+        "write" | "pwrite64" => format!("{fd}, {}, {a2}", buffer(a1, a2 as usize)),
+        "read" | "pread64" => match event.direction {
+            SyscallDirection::Entry => format!("{fd}, {a1:#x}, {a2}"),
+            SyscallDirection::Exit => format!("{fd}, {}, {a2}", buffer(a1, exit_len)),
+        },
+        "readv" | "writev" | "preadv" | "pwritev" | "preadv2" | "pwritev2" => {
+            format!("{fd}, iov={a1:#x}, iovcnt={a2}")
+        }
+        "close" => fd.to_string(),
+        "shutdown" => format!("{fd}, {a1}"),
+        "socket" => format!(
+            "{}, {}, {a2}",
+            address_family_name(a0 as i32),
+            format_socket_type(a1 as i32)
+        ),
+        "connect" | "bind" => format!("{fd}, {}, {a2}", sockaddr_at(process, a1, a2 as usize)),
+        "accept" | "accept4" | "getsockname" | "getpeername" => {
+            let addr = if is_exit {
+                sockaddr_with_len_ptr(process, a1, a2)
+            } else {
+                format!("{a1:#x}, {a2:#x}")
+            };
+            match name {
+                "accept4" => format!("{fd}, {addr}, {a3:#x}"),
+                _ => format!("{fd}, {addr}"),
+            }
+        }
+        "sendto" => format!(
+            "{fd}, {}, {a2}, {a3:#x}, {}, {a5}",
+            buffer(a1, a2 as usize),
+            sockaddr_at(process, a4, a5 as usize)
+        ),
+        "recvfrom" if is_exit => format!(
+            "{fd}, {}, {a2}, {a3:#x}, {}",
+            buffer(a1, exit_len),
+            sockaddr_with_len_ptr(process, a4, a5)
+        ),
+        "recvfrom" => format!("{fd}, {a1:#x}, {a2}, {a3:#x}, {a4:#x}, {a5:#x}"),
+        "sendmsg" => format!(
+            "{fd}, {}, {a2:#x}",
+            msghdr_at(process, a1, usize::MAX, buffer)
+        ),
+        "recvmsg" if is_exit => format!(
+            "{fd}, {}, {a2:#x}",
+            msghdr_at(process, a1, exit_len, buffer)
+        ),
+        "recvmsg" | "sendmmsg" | "recvmmsg" => format!("{fd}, {a1:#x}, {a2:#x}"),
+        "setsockopt" | "getsockopt" => {
+            let (level, option) = sockopt_name(a1 as i32, a2 as i32);
+            format!("{fd}, {level}, {option}, {a3:#x}, {a4:#x}")
+        }
+        _ => event
+            .args
+            .iter()
+            .map(|arg| format!("{arg:#x}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    match event.ret {
+        Some(ret) if ret < 0 => match nix::errno::Errno::from_raw(-ret as i32) {
+            nix::errno::Errno::UnknownErrno => format!("{name}({args}) = {ret}"),
+            errno => format!("{name}({args}) = -1 {errno}"),
+        },
+        Some(ret) => format!("{name}({args}) = {ret}"),
+        None => format!("{name}({args})"),
+    }
+}
+
+/// Reads and escapes a buffer from the inferior, truncated to `max_len` bytes.
+fn read_buffer(process: &impl ReadMemory, addr: u64, len: usize, max_len: usize) -> String {
+    process.read_memory(addr, len.min(max_len)).map_or_else(
+        |_| format!("{addr:#x}"),
+        |bytes| {
+            let ellipsis = if len > max_len { "..." } else { "" };
+            format!("\"{}\"{ellipsis}", bytes.escape_ascii())
+        },
+    )
+}
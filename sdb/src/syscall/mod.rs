@@ -0,0 +1,102 @@
+//! Syscall tracing support: names, events and argument decoding.
+
+mod decode;
+mod socket;
+mod table;
+
+pub use self::decode::{describe, describe_with_limit, MAX_BUFFER_LEN};
+pub use self::socket::{address_family_name, format_sockaddr, format_socket_type, sockopt_name};
+
+use self::table::SYSCALLS;
+use nix::libc::user_regs_struct;
+use serde::Serialize;
+
+/// Returns the name of an x86_64 syscall number.
+pub fn syscall_name(number: u64) -> Option<&'static str> {
+    SYSCALLS
+        .binary_search_by_key(&number, |&(nr, _)| nr)
+        .ok()
+        .map(|index| SYSCALLS[index].1)
+}
+
+/// Returns the x86_64 syscall number of `name`.
+pub fn syscall_number(name: &str) -> Option<u64> {
+    SYSCALLS
+        .iter()
+        .find_map(|&(nr, syscall)| (syscall == name).then_some(nr))
+}
+
+/// Whether a syscall stop happened before or after the kernel ran the call.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyscallDirection {
+    /// syscall-enter-stop: the arguments are set up, nothing has run yet.
+    Entry,
+    /// syscall-exit-stop: the return value is available.
+    Exit,
+}
+
+/// Syscalls whose first argument is a file descriptor read, written or closed.
+pub(crate) const FD_SYSCALLS: &[&str] = &[
+    "read", "write", "pread64", "pwrite64", "readv", "writev", "preadv", "pwritev", "preadv2",
+    "pwritev2", "sendto", "recvfrom", "sendmsg", "recvmsg", "sendmmsg", "recvmmsg", "close",
+    "shutdown",
+];
+
+/// Returns every known syscall as `(number, name)`, by number.
+pub(crate) fn syscalls() -> impl Iterator<Item = (u64, &'static str)> {
+    SYSCALLS.iter().copied()
+}
+
+/// A syscall stop reported by `PTRACE_SYSCALL`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SyscallEvent {
+    /// Syscall number (`orig_rax`).
+    pub number: u64,
+    /// Arguments in ABI order (`rdi`, `rsi`, `rdx`, `r10`, `r8`, `r9`).
+    pub args: [u64; 6],
+    /// Entry or exit.
+    pub direction: SyscallDirection,
+    /// Return value (`rax`), only on exit.
+    pub ret: Option<i64>,
+}
+
+impl SyscallEvent {
+    /// Builds the event from the registers at a syscall stop.
+    pub const fn from_registers(regs: &user_regs_struct, direction: SyscallDirection) -> Self {
+        Self {
+            number: regs.orig_rax,
+            args: [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+            direction,
+            ret: match direction {
+                SyscallDirection::Entry => None,
+                SyscallDirection::Exit => Some(regs.rax as i64),
+            },
+        }
+    }
+
+    /// Returns the syscall name, if known.
+    pub fn name(&self) -> Option<&'static str> {
+        syscall_name(self.number)
+    }
+
+    /// Returns the file descriptor this syscall reads, writes or closes.
+    pub fn fd(&self) -> Option<i32> {
+        FD_SYSCALLS
+            .contains(&self.name()?)
+            .then_some(self.args[0] as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_look_up_syscalls() {
+        assert_eq!(syscall_name(0), Some("read"));
+        assert_eq!(syscall_name(231), Some("exit_group"));
+        assert_eq!(syscall_number("openat"), Some(257));
+        assert_eq!(syscall_name(100_000), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Decoders for socket related syscall arguments.
+
+use crate::process::ReadMemory;
+use nix::libc;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Size of `struct msghdr` on x86_64.
+const MSGHDR_LEN: usize = 56;
+/// Maximum number of iovecs rendered per call.
+const MAX_IOVECS: usize = 8;
+
+/// Renders a `struct sockaddr` held in `bytes`, e.g. `{AF_INET, 127.0.0.1:8080}`.
+pub fn format_sockaddr(bytes: &[u8]) -> String {
+    let Some(family) = read_u16_ne(bytes, 0) else {
+        return "{}".to_string();
+    };
+    match i32::from(family) {
+        libc::AF_UNIX => {
+            let path = bytes.get(2..).unwrap_or_default();
+            match path.split_first() {
+                // Abstract socket: leading NUL, not NUL terminated.
+                Some((0, name)) if !name.is_empty() => {
+                    format!("{{AF_UNIX, @\"{}\"}}", name.escape_ascii())
+                }
+                _ => {
+                    let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+                    format!("{{AF_UNIX, \"{}\"}}", path[..end].escape_ascii())
+                }
+            }
+        }
+        libc::AF_INET if bytes.len() >= 8 => {
+            let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+            let addr = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+            format!("{{AF_INET, {addr}:{port}}}")
+        }
+        libc::AF_INET6 if bytes.len() >= 24 => {
+            let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes[8..24]);
+            format!("{{AF_INET6, [{}]:{port}}}", Ipv6Addr::from(octets))
+        }
+        _ => format!("{{{}}}", address_family_name(i32::from(family))),
+    }
+}
+
+/// Returns the `AF_*` name of an address family.
+pub fn address_family_name(family: i32) -> String {
+    match family {
+        libc::AF_UNSPEC => "AF_UNSPEC".to_string(),
+        libc::AF_UNIX => "AF_UNIX".to_string(),
+        libc::AF_INET => "AF_INET".to_string(),
+        libc::AF_INET6 => "AF_INET6".to_string(),
+        libc::AF_NETLINK => "AF_NETLINK".to_string(),
+        libc::AF_PACKET => "AF_PACKET".to_string(),
+        other => format!("AF_{other}"),
+    }
+}
+
+/// Renders a `socket(2)` type argument, e.g. `SOCK_STREAM|SOCK_CLOEXEC`.
+pub fn format_socket_type(ty: i32) -> String {
+    let base = match ty & 0xf {
+        libc::SOCK_STREAM => "SOCK_STREAM".to_string(),
+        libc::SOCK_DGRAM => "SOCK_DGRAM".to_string(),
+        libc::SOCK_RAW => "SOCK_RAW".to_string(),
+        libc::SOCK_SEQPACKET => "SOCK_SEQPACKET".to_string(),
+        other => other.to_string(),
+    };
+    let mut flags = vec![base];
+    if ty & libc::SOCK_NONBLOCK != 0 {
+        flags.push("SOCK_NONBLOCK".to_string());
+    }
+    if ty & libc::SOCK_CLOEXEC != 0 {
+        flags.push("SOCK_CLOEXEC".to_string());
+    }
+    flags.join("|")
+}
+
+/// Returns the names of a `setsockopt`/`getsockopt` level and option.
+pub fn sockopt_name(level: i32, option: i32) -> (String, String) {
+    const SOL_SOCKET: &[(i32, &str)] = &[
+        (libc::SO_DEBUG, "SO_DEBUG"),
+        (libc::SO_REUSEADDR, "SO_REUSEADDR"),
+        (libc::SO_TYPE, "SO_TYPE"),
+        (libc::SO_ERROR, "SO_ERROR"),
+        (libc::SO_DONTROUTE, "SO_DONTROUTE"),
+        (libc::SO_BROADCAST, "SO_BROADCAST"),
+        (libc::SO_SNDBUF, "SO_SNDBUF"),
+        (libc::SO_RCVBUF, "SO_RCVBUF"),
+        (libc::SO_KEEPALIVE, "SO_KEEPALIVE"),
+        (libc::SO_OOBINLINE, "SO_OOBINLINE"),
+        (libc::SO_PRIORITY, "SO_PRIORITY"),
+        (libc::SO_LINGER, "SO_LINGER"),
+        (libc::SO_REUSEPORT, "SO_REUSEPORT"),
+        (libc::SO_PASSCRED, "SO_PASSCRED"),
+        (libc::SO_PEERCRED, "SO_PEERCRED"),
+        (libc::SO_RCVLOWAT, "SO_RCVLOWAT"),
+        (libc::SO_SNDLOWAT, "SO_SNDLOWAT"),
+        (libc::SO_RCVTIMEO, "SO_RCVTIMEO"),
+        (libc::SO_SNDTIMEO, "SO_SNDTIMEO"),
+        (libc::SO_BINDTODEVICE, "SO_BINDTODEVICE"),
+        (libc::SO_TIMESTAMP, "SO_TIMESTAMP"),
+        (libc::SO_ACCEPTCONN, "SO_ACCEPTCONN"),
+        (libc::SO_SNDBUFFORCE, "SO_SNDBUFFORCE"),
+        (libc::SO_RCVBUFFORCE, "SO_RCVBUFFORCE"),
+        (libc::SO_PROTOCOL, "SO_PROTOCOL"),
+        (libc::SO_DOMAIN, "SO_DOMAIN"),
+    ];
+    const IPPROTO_TCP: &[(i32, &str)] = &[
+        (libc::TCP_NODELAY, "TCP_NODELAY"),
+        (libc::TCP_MAXSEG, "TCP_MAXSEG"),
+        (libc::TCP_CORK, "TCP_CORK"),
+        (libc::TCP_KEEPIDLE, "TCP_KEEPIDLE"),
+        (libc::TCP_KEEPINTVL, "TCP_KEEPINTVL"),
+        (libc::TCP_KEEPCNT, "TCP_KEEPCNT"),
+        (libc::TCP_SYNCNT, "TCP_SYNCNT"),
+        (libc::TCP_LINGER2, "TCP_LINGER2"),
+        (libc::TCP_DEFER_ACCEPT, "TCP_DEFER_ACCEPT"),
+        (libc::TCP_WINDOW_CLAMP, "TCP_WINDOW_CLAMP"),
+        (libc::TCP_INFO, "TCP_INFO"),
+        (libc::TCP_QUICKACK, "TCP_QUICKACK"),
+        (libc::TCP_CONGESTION, "TCP_CONGESTION"),
+        (libc::TCP_USER_TIMEOUT, "TCP_USER_TIMEOUT"),
+        (libc::TCP_FASTOPEN, "TCP_FASTOPEN"),
+    ];
+    const IPPROTO_IP: &[(i32, &str)] = &[
+        (libc::IP_TOS, "IP_TOS"),
+        (libc::IP_TTL, "IP_TTL"),
+        (libc::IP_HDRINCL, "IP_HDRINCL"),
+        (libc::IP_OPTIONS, "IP_OPTIONS"),
+        (libc::IP_MTU_DISCOVER, "IP_MTU_DISCOVER"),
+        (libc::IP_RECVERR, "IP_RECVERR"),
+        (libc::IP_MULTICAST_IF, "IP_MULTICAST_IF"),
+        (libc::IP_MULTICAST_TTL, "IP_MULTICAST_TTL"),
+        (libc::IP_MULTICAST_LOOP, "IP_MULTICAST_LOOP"),
+        (libc::IP_ADD_MEMBERSHIP, "IP_ADD_MEMBERSHIP"),
+        (libc::IP_DROP_MEMBERSHIP, "IP_DROP_MEMBERSHIP"),
+    ];
+    const IPPROTO_IPV6: &[(i32, &str)] = &[
+        (libc::IPV6_UNICAST_HOPS, "IPV6_UNICAST_HOPS"),
+        (libc::IPV6_MULTICAST_IF, "IPV6_MULTICAST_IF"),
+        (libc::IPV6_MULTICAST_HOPS, "IPV6_MULTICAST_HOPS"),
+        (libc::IPV6_MULTICAST_LOOP, "IPV6_MULTICAST_LOOP"),
+        (libc::IPV6_RECVERR, "IPV6_RECVERR"),
+        (libc::IPV6_V6ONLY, "IPV6_V6ONLY"),
+    ];
+
+    let (level_name, options) = match level {
+        libc::SOL_SOCKET => ("SOL_SOCKET".to_string(), SOL_SOCKET),
+        libc::IPPROTO_TCP => ("IPPROTO_TCP".to_string(), IPPROTO_TCP),
+        libc::IPPROTO_IP => ("IPPROTO_IP".to_string(), IPPROTO_IP),
+        libc::IPPROTO_IPV6 => ("IPPROTO_IPV6".to_string(), IPPROTO_IPV6),
+        other => (other.to_string(), &[][..]),
+    };
+    let option_name = options
+        .iter()
+        .find_map(|&(value, name)| (value == option).then(|| name.to_string()))
+        .unwrap_or_else(|| option.to_string());
+    (level_name, option_name)
+}
+
+/// Reads and renders the sockaddr at `addr` of `len` bytes.
+pub(super) fn sockaddr_at(process: &impl ReadMemory, addr: u64, len: usize) -> String {
+    if addr == 0 {
+        return "NULL".to_string();
+    }
+    let len = len.min(std::mem::size_of::<libc::sockaddr_storage>());
+    process
+        .read_memory(addr, len)
+        .map_or_else(|_| format!("{addr:#x}"), |bytes| format_sockaddr(&bytes))
+}
+
+/// Reads and renders the sockaddr at `addr` whose length is stored at `len_ptr`
+/// (as filled in by `accept`, `recvfrom`, `getsockname`, ...).
+pub(super) fn sockaddr_with_len_ptr(process: &impl ReadMemory, addr: u64, len_ptr: u64) -> String {
+    if len_ptr == 0 {
+        return sockaddr_at(process, addr, 0);
+    }
+    read_u32(process, len_ptr).map_or_else(
+        || format!("{addr:#x}"),
+        |len| format!("{}, [{len}]", sockaddr_at(process, addr, len as usize)),
+    )
+}
+
+/// Renders a `struct msghdr` including its name and the contents of up to
+/// [`MAX_IOVECS`] iovecs, limited to `data_len` bytes in total.
+pub(super) fn msghdr_at(
+    process: &impl ReadMemory,
+    addr: u64,
+    data_len: usize,
+    render_buffer: impl Fn(u64, usize) -> String,
+) -> String {
+    let Some(msg) = process
+        .read_memory(addr, MSGHDR_LEN)
+        .ok()
+        .filter(|msg| msg.len() == MSGHDR_LEN)
+    else {
+        return format!("{addr:#x}");
+    };
+    let name_ptr = read_u64_ne(&msg, 0).unwrap_or_default();
+    let name_len = read_u32_ne(&msg, 8).unwrap_or_default() as usize;
+    let iov_ptr = read_u64_ne(&msg, 16).unwrap_or_default();
+    let iov_len = read_u64_ne(&msg, 24).unwrap_or_default() as usize;
+    let controllen = read_u64_ne(&msg, 40).unwrap_or_default();
+    let flags = read_u32_ne(&msg, 48).unwrap_or_default();
+
+    let name = if name_ptr == 0 {
+        "NULL".to_string()
+    } else {
+        sockaddr_at(process, name_ptr, name_len)
+    };
+
+    let mut remaining = data_len;
+    let mut iovecs = Vec::new();
+    let iov_bytes = process
+        .read_memory(iov_ptr, iov_len.min(MAX_IOVECS) * 16)
+        .unwrap_or_default();
+    for iov in iov_bytes.chunks_exact(16) {
+        let base = read_u64_ne(iov, 0).unwrap_or_default();
+        let len = read_u64_ne(iov, 8).unwrap_or_default() as usize;
+        let shown = len.min(remaining);
+        remaining -= shown;
+        iovecs.push(format!("{{{}, {len}}}", render_buffer(base, shown)));
+    }
+    if iov_len > MAX_IOVECS {
+        iovecs.push("...".to_string());
+    }
+
+    format!(
+        "{{msg_name={name}, msg_iov=[{}], msg_iovlen={iov_len}, msg_controllen={controllen}, msg_flags={flags:#x}}}",
+        iovecs.join(", ")
+    )
+}
+
+fn read_u32(process: &impl ReadMemory, addr: u64) -> Option<u32> {
+    read_u32_ne(&process.read_memory(addr, 4).ok()?, 0)
+}
+
+fn read_u16_ne(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_ne_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_ne(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64_ne(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_ne_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_format_sockaddr() {
+        let mut inet = vec![0; 16];
+        inet[..2].copy_from_slice(&(libc::AF_INET as u16).to_ne_bytes());
+        inet[2..4].copy_from_slice(&8080_u16.to_be_bytes());
+        inet[4..8].copy_from_slice(&[127, 0, 0, 1]);
+        assert_eq!(format_sockaddr(&inet), "{AF_INET, 127.0.0.1:8080}");
+
+        let mut unix = (libc::AF_UNIX as u16).to_ne_bytes().to_vec();
+        unix.extend(b"/run/sdb.sock\0");
+        assert_eq!(format_sockaddr(&unix), "{AF_UNIX, \"/run/sdb.sock\"}");
+
+        let mut abstract_unix = (libc::AF_UNIX as u16).to_ne_bytes().to_vec();
+        abstract_unix.extend(b"\0sdb");
+        assert_eq!(format_sockaddr(&abstract_unix), "{AF_UNIX, @\"sdb\"}");
+    }
+
+    #[test]
+    fn should_name_socket_options() {
+        assert_eq!(
+            sockopt_name(libc::SOL_SOCKET, libc::SO_REUSEADDR),
+            ("SOL_SOCKET".to_string(), "SO_REUSEADDR".to_string())
+        );
+        assert_eq!(
+            sockopt_name(libc::IPPROTO_TCP, libc::TCP_NODELAY),
+            ("IPPROTO_TCP".to_string(), "TCP_NODELAY".to_string())
+        );
+        assert_eq!(
+            format_socket_type(libc::SOCK_STREAM | libc::SOCK_CLOEXEC),
+            "SOCK_STREAM|SOCK_CLOEXEC"
+        );
+    }
+}
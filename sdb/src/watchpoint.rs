@@ -0,0 +1,159 @@
+//! Hardware watchpoints, set through the x86 debug registers `DR0`-`DR3` and `DR7`, and
+//! range watches, which catch the first instruction executed in a range of pages or every
+//! write to it.
+
+use nix::sys::mman::ProtFlags;
+use std::ops::Range;
+
+/// Number of address debug registers, and so of watchpoints.
+pub const DEBUG_REGISTER_COUNT: usize = 4;
+
+/// Access that triggers a watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    /// Writes to the watched bytes.
+    Write,
+    /// Reads or writes of the watched bytes.
+    ReadWrite,
+    /// Execution of the instruction starting at the watched byte.
+    Execute,
+}
+
+impl WatchKind {
+    /// `R/W` field of `DR7`.
+    const fn condition(self) -> u64 {
+        match self {
+            Self::Execute => 0b00,
+            Self::Write => 0b01,
+            Self::ReadWrite => 0b11,
+        }
+    }
+}
+
+/// A watchpoint occupying one debug register.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Watchpoint {
+    /// Number shown to the user.
+    pub id: usize,
+    /// Start address, aligned to `len`.
+    pub addr: u64,
+    /// Watched bytes: 1, 2, 4 or 8.
+    pub len: usize,
+    pub kind: WatchKind,
+    /// What is watched, e.g. `GOT slot of puts`.
+    pub description: String,
+    /// Value of the watched bytes when last checked.
+    pub value: u64,
+    /// Index of the debug register used.
+    pub(crate) register: usize,
+}
+
+/// What a [`RangeWatch`] catches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RangeWatchKind {
+    /// The first instruction executed in the range, once.
+    Enter,
+    /// Every write to the range.
+    Write,
+}
+
+impl RangeWatchKind {
+    /// Permission removed from the watched pages.
+    pub(crate) const fn trapped(self) -> ProtFlags {
+        match self {
+            Self::Enter => ProtFlags::PROT_EXEC,
+            Self::Write => ProtFlags::PROT_WRITE,
+        }
+    }
+}
+
+/// A watch on a range of pages, armed by removing execute or write permission from them so
+/// that entering or writing them raises `SIGSEGV`.
+///
+/// Only the main thread is traced, so another thread entering or writing the range crashes
+/// the program.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RangeWatch {
+    /// Number shown to the user, shared with [`Watchpoint`]s.
+    pub id: usize,
+    pub kind: RangeWatchKind,
+    /// Watched addresses, rounded out to whole pages.
+    pub range: Range<u64>,
+    /// What is watched, e.g. `libc.so.6`.
+    pub description: String,
+    /// Pages trapped, with the protection to restore; empty once an [`RangeWatchKind::Enter`]
+    /// watch triggered.
+    pub(crate) pages: Vec<(Range<u64>, ProtFlags)>,
+}
+
+impl RangeWatch {
+    /// Returns `true` until code in the range of an [`RangeWatchKind::Enter`] watch has been
+    /// executed.
+    pub const fn is_armed(&self) -> bool {
+        !self.pages.is_empty()
+    }
+}
+
+/// The watchpoint that stopped the process, with the watched value before and after.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatchpointHit {
+    pub id: usize,
+    pub old: u64,
+    pub new: u64,
+}
+
+/// Returns `true` if the CPU can watch `len` bytes at `addr` for `kind` accesses.
+pub const fn is_watchable(addr: u64, len: usize, kind: WatchKind) -> bool {
+    match kind {
+        // Instruction breakpoints must have a length of 1.
+        WatchKind::Execute => len == 1,
+        WatchKind::Write | WatchKind::ReadWrite => {
+            matches!(len, 1 | 2 | 4 | 8) && addr.is_multiple_of(len as u64)
+        }
+    }
+}
+
+/// Computes `DR7` enabling exactly `watchpoints`.
+pub(crate) fn dr7<'a>(watchpoints: impl IntoIterator<Item = &'a Watchpoint>) -> u64 {
+    watchpoints.into_iter().fold(0, |dr7, watchpoint| {
+        let length = match watchpoint.len {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        let control = watchpoint.kind.condition() | length << 2;
+        let register = watchpoint.register;
+        dr7 | 1 << (register * 2) | control << (16 + register * 4)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_dr7() {
+        let watchpoint = |register, len, kind| Watchpoint {
+            id: register + 1,
+            addr: 0x1000,
+            len,
+            kind,
+            description: String::new(),
+            value: 0,
+            register,
+        };
+        let watchpoints = [
+            watchpoint(0, 8, WatchKind::Write),
+            watchpoint(1, 1, WatchKind::Execute),
+            watchpoint(2, 1, WatchKind::ReadWrite),
+        ];
+        // L0 | L1 | L2, R/W0 = 01 LEN0 = 10, R/W1 = 00 LEN1 = 00, R/W2 = 11 LEN2 = 00.
+        assert_eq!(dr7(&watchpoints), 0b0011_0000_1001 << 16 | 0b01_01_01);
+        assert!(is_watchable(0x1008, 8, WatchKind::Write));
+        assert!(!is_watchable(0x1004, 8, WatchKind::Write));
+        assert!(!is_watchable(0x1000, 3, WatchKind::ReadWrite));
+        assert!(is_watchable(0x1003, 1, WatchKind::Execute));
+        assert!(!is_watchable(0x1000, 8, WatchKind::Execute));
+    }
+}
@@ -0,0 +1,98 @@
+//! Test inferiors, built from `tests/fixtures` when first needed.
+//!
+//! The fixtures are compiled with pinned flags (no optimization, frame pointers kept, debug
+//! info) so that the stops, registers and stacks tests look at do not depend on the
+//! compiler's defaults. Set `CC` to use another compiler than `cc`.
+
+// Each test binary uses only some of the helpers.
+#![allow(dead_code, reason = "shared by several test binaries")]
+
+use sdb::error::Result;
+use sdb::process::Process;
+use sdb::procfs::read_maps;
+use sdb::symbols::SymbolTable;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Flags every fixture is built with.
+const CFLAGS: [&str; 5] = ["-g", "-O0", "-fno-omit-frame-pointer", "-pthread", "-Wall"];
+
+/// Returns the path of the fixture built from `tests/fixtures/{name}.c`, compiling it unless
+/// it is up to date.
+///
+/// # Panics
+/// Panics if the fixture does not compile.
+pub fn fixture(name: &str) -> PathBuf {
+    build(name, &[])
+}
+
+/// Returns the path of the fixture built from `tests/fixtures/{name}.c` as an image without
+/// libc, linked to run at `0x70000000` from its function `entry`, for a JIT fixture to load.
+///
+/// # Panics
+/// Panics if the fixture does not compile.
+pub fn jit_image(name: &str, entry: &str) -> PathBuf {
+    let entry = format!("-Wl,-e,{entry}");
+    let flags = [
+        "-nostdlib",
+        "-static",
+        "-no-pie",
+        "-Wl,-Ttext-segment=0x70000000",
+    ];
+    build(name, &[&flags[..], &[entry.as_str()]].concat())
+}
+
+fn build(name: &str, flags: &[&str]) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{name}.c"));
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixtures");
+    let binary = dir.join(name);
+    if is_newer(&binary, &source) {
+        return binary;
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    // Test binaries run in parallel: build under a private name, then move it in place.
+    let partial = dir.join(format!("{name}.{}", std::process::id()));
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(CFLAGS)
+        .args(flags)
+        .arg("-o")
+        .arg(&partial)
+        .arg(&source)
+        .status()
+        .unwrap_or_else(|err| panic!("cannot run {compiler}: {err}"));
+    assert!(status.success(), "{} does not compile", source.display());
+    std::fs::rename(&partial, &binary).unwrap();
+    binary
+}
+
+/// Returns `true` if `path` exists and was modified after `than`.
+fn is_newer(path: &Path, than: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    matches!((modified(path), modified(than)), (Ok(path), Ok(than)) if path > than)
+}
+
+/// Launches the fixture `name` under the debugger, stopped before its first instruction.
+pub fn launch(name: &str) -> Result<Process> {
+    Process::launch(&fixture(name), true)
+}
+
+/// Returns the runtime address of the function or variable `name` in the main executable
+/// of `process`.
+///
+/// # Panics
+/// Panics if the executable is not mapped or has no such symbol.
+pub fn symbol_address(process: &Process, name: &str) -> u64 {
+    let maps = read_maps(process.pid).unwrap();
+    maps.iter()
+        .filter(|region| region.path.contains("/fixtures/"))
+        .find_map(|region| {
+            let table = SymbolTable::cached(Path::new(&region.path)).ok()?;
+            let bias = table.load_bias(region)?;
+            let symbol = table.symbols().iter().find(|symbol| symbol.name == name)?;
+            Some(symbol.address.wrapping_add(bias))
+        })
+        .unwrap_or_else(|| panic!("no symbol {name}"))
+}
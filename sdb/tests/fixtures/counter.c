@@ -0,0 +1,11 @@
+/* Counts to a known value in a global, then exits with a known status. */
+volatile unsigned long counter = 0;
+
+void tick(void) { counter++; }
+
+int main(void) {
+  for (int i = 0; i < 3; i++) {
+    tick();
+  }
+  return (int)counter + 39;
+}
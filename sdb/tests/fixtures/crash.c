@@ -0,0 +1,6 @@
+/* Writes through a null pointer. */
+int main(void) {
+  volatile int *pointer = 0;
+  *pointer = 42;
+  return 0;
+}
@@ -0,0 +1,14 @@
+/* Runs `/bin/true` in a child and exits with its status. */
+#include <sys/wait.h>
+#include <unistd.h>
+
+int main(void) {
+  pid_t child = fork();
+  if (child == 0) {
+    execl("/bin/true", "true", (char *)0);
+    _exit(127);
+  }
+  int status = 0;
+  waitpid(child, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : 1;
+}
@@ -0,0 +1,63 @@
+/* A "JIT compiler": maps the image named by $JIT_IMAGE at its link-time address, registers it
+ * through the GDB JIT interface and calls its entry point, which crashes. */
+#include <elf.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+
+struct jit_code_entry {
+  struct jit_code_entry *next_entry;
+  struct jit_code_entry *prev_entry;
+  const char *symfile_addr;
+  uint64_t symfile_size;
+};
+
+struct jit_descriptor {
+  uint32_t version;
+  uint32_t action_flag;
+  struct jit_code_entry *relevant_entry;
+  struct jit_code_entry *first_entry;
+};
+
+struct jit_descriptor __jit_debug_descriptor = {1, 0, 0, 0};
+
+void __attribute__((noinline)) __jit_debug_register_code(void) { __asm__ volatile(""); }
+
+int main(void) {
+  FILE *file = fopen(getenv("JIT_IMAGE"), "rb");
+  if (!file || fseek(file, 0, SEEK_END) != 0) {
+    return 1;
+  }
+  long size = ftell(file);
+  char *image = malloc(size);
+  rewind(file);
+  if (fread(image, 1, size, file) != (size_t)size) {
+    return 1;
+  }
+  const Elf64_Ehdr *header = (const Elf64_Ehdr *)image;
+  const Elf64_Phdr *segments = (const Elf64_Phdr *)(image + header->e_phoff);
+  for (int i = 0; i < header->e_phnum; i++) {
+    if (segments[i].p_type != PT_LOAD) {
+      continue;
+    }
+    uint64_t start = segments[i].p_vaddr & ~0xfffULL;
+    uint64_t len = segments[i].p_vaddr + segments[i].p_memsz - start;
+    if (mmap((void *)start, len, PROT_READ | PROT_WRITE | PROT_EXEC,
+             MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE, -1, 0) == MAP_FAILED) {
+      return 1;
+    }
+    memcpy((void *)segments[i].p_vaddr, image + segments[i].p_offset, segments[i].p_filesz);
+  }
+
+  static struct jit_code_entry entry;
+  entry.symfile_addr = image;
+  entry.symfile_size = size;
+  __jit_debug_descriptor.relevant_entry = &entry;
+  __jit_debug_descriptor.first_entry = &entry;
+  __jit_debug_descriptor.action_flag = 1; /* JIT_REGISTER_FN */
+  __jit_debug_register_code();
+
+  return ((int (*)(int))header->e_entry)(42);
+}
@@ -0,0 +1,6 @@
+/* Code a JIT compiler "emits": linked at a fixed address and copied there by `jit.c`. */
+int jitted_crash(int value) {
+  volatile int *pointer = 0;
+  *pointer = value;
+  return value;
+}
@@ -0,0 +1,10 @@
+/* Writes to stdout and stderr, ending without a newline. */
+#include <stdio.h>
+
+int main(void) {
+  printf("hello\n");
+  fflush(stdout);
+  fprintf(stderr, "warning\n");
+  printf("bye");
+  return 0;
+}
@@ -0,0 +1,22 @@
+/* Runs code from a shared mapping of a memfd, which ptrace cannot write as it is. */
+#define _GNU_SOURCE
+#include <signal.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+unsigned char *code;
+
+int main(void) {
+  static const unsigned char ret[] = {0xc3};
+  int fd = memfd_create("code", 0);
+  if (fd < 0 || ftruncate(fd, 4096) != 0 || write(fd, ret, sizeof ret) != sizeof ret) {
+    return 1;
+  }
+  code = mmap(NULL, 4096, PROT_READ | PROT_EXEC, MAP_SHARED, fd, 0);
+  if (code == MAP_FAILED) {
+    return 1;
+  }
+  raise(SIGSTOP);
+  ((void (*)(void))code)();
+  return 0;
+}
@@ -0,0 +1,28 @@
+/* Starts a few threads that all bump a shared counter. */
+#include <pthread.h>
+
+#define THREADS 4
+
+static unsigned long counter = 0;
+static pthread_mutex_t lock = PTHREAD_MUTEX_INITIALIZER;
+
+static void *work(void *arg) {
+  (void)arg;
+  for (int i = 0; i < 1000; i++) {
+    pthread_mutex_lock(&lock);
+    counter++;
+    pthread_mutex_unlock(&lock);
+  }
+  return 0;
+}
+
+int main(void) {
+  pthread_t threads[THREADS];
+  for (int i = 0; i < THREADS; i++) {
+    pthread_create(&threads[i], 0, work, 0);
+  }
+  for (int i = 0; i < THREADS; i++) {
+    pthread_join(threads[i], 0);
+  }
+  return counter == THREADS * 1000 ? 0 : 1;
+}
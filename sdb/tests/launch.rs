@@ -0,0 +1,136 @@
+//! Launching fixtures and inspecting them at their first stops.
+
+mod common;
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::wait::{waitpid, WaitStatus};
+use sdb::error::Result;
+use sdb::output::{MemoryDump, RegisterDump};
+use sdb::process::{LaunchSpec, OutputStream, Process};
+use sdb::procfs::{read_maps, MemoryRegion};
+use sdb::watchpoint::WatchKind;
+use std::sync::{Arc, Mutex, PoisonError};
+
+#[test]
+fn should_run_to_exit() -> Result<()> {
+    let mut process = common::launch("counter")?;
+    assert!(process.is_alive());
+    process.resume()?;
+    let status = process.exit_status().unwrap();
+    assert_eq!(status.code, Some(42));
+    assert!(!process.is_alive());
+    Ok(())
+}
+
+#[test]
+fn should_run_on_launch() -> Result<()> {
+    let mut spec = LaunchSpec::new(&common::fixture("counter"));
+    spec.run = true;
+    let process = Process::launch_spec(&spec, true)?;
+    assert_eq!(
+        process.exit_status().and_then(|status| status.code),
+        Some(42)
+    );
+    Ok(())
+}
+
+#[test]
+fn should_capture_output() -> Result<()> {
+    let mut spec = LaunchSpec::new(&common::fixture("output"));
+    spec.capture_output = true;
+    let mut process = Process::launch_spec(&spec, true)?;
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    process.set_output_handler(Arc::new({
+        let lines = Arc::clone(&lines);
+        move |stream, line: &str| lines.lock().unwrap().push((stream, line.to_string()))
+    }));
+    process.resume()?;
+    assert!(!process.is_alive());
+    let lines = std::mem::take(&mut *lines.lock().unwrap_or_else(PoisonError::into_inner));
+    assert!(lines.contains(&(OutputStream::Stdout, "hello".into())));
+    assert!(lines.contains(&(OutputStream::Stderr, "warning".into())));
+    assert!(lines.contains(&(OutputStream::Stdout, "bye".into())));
+    Ok(())
+}
+
+#[test]
+fn should_poll_pidfd_for_exit() -> Result<()> {
+    let mut process = common::launch("counter")?;
+    let readable = |process: &Process| {
+        let mut fds: Vec<_> = process
+            .pidfd()
+            .map(|pidfd| PollFd::new(pidfd, PollFlags::POLLIN))
+            .into_iter()
+            .collect();
+        assert_eq!(fds.len(), 1, "no pidfd");
+        poll(&mut fds, PollTimeout::ZERO) == Ok(1)
+    };
+    assert!(!readable(&process));
+    process.resume()?;
+    assert!(readable(&process));
+    Ok(())
+}
+
+#[test]
+fn should_stop_at_main() -> Result<()> {
+    let mut process = common::launch("counter")?;
+    let main = common::symbol_address(&process, "main");
+    let id = process.add_watchpoint(main, 1, WatchKind::Execute, "main".into())?;
+    process.resume()?;
+    assert_eq!(process.last_watchpoint.map(|hit| hit.id), Some(id));
+
+    let registers = RegisterDump::new(&process.read_registers()?);
+    assert_eq!(registers.get("rip"), Some(main));
+    Ok(())
+}
+
+#[test]
+fn should_read_and_write_memory() -> Result<()> {
+    let process = common::launch("counter")?;
+    let counter = common::symbol_address(&process, "counter");
+    let dump = MemoryDump::read(&process, counter, 8)?;
+    assert_eq!(dump.bytes, [0; 8]);
+
+    process.write_memory(counter, &40_u64.to_le_bytes())?;
+    assert_eq!(process.read_words(counter, 1)?, [40]);
+    Ok(())
+}
+
+#[test]
+fn should_patch_code_ptrace_cannot_write() -> Result<()> {
+    let mut process = common::launch("shared_code")?;
+    process.resume()?;
+    let code = process.read_words(common::symbol_address(&process, "code"), 1)?[0];
+    assert!(process.write_memory(code, &[0x90]).is_err());
+
+    process.patch(code, &[0x90, 0xc3])?; // nop; ret
+    assert_eq!(process.read_memory(code, 2)?, [0x90, 0xc3]);
+    let maps = read_maps(process.pid)?;
+    let region = maps.iter().find(|region| region.start == code);
+    assert_eq!(region.map(MemoryRegion::perms).as_deref(), Some("r-xs"));
+
+    process.resume()?;
+    assert!(process.exit_status().is_some_and(|status| status.success()));
+    Ok(())
+}
+
+#[test]
+fn should_kill_or_leave_running_on_drop() -> Result<()> {
+    let process = common::launch("counter")?;
+    let pid = process.pid;
+    assert!(process.kills_on_exit());
+    drop(process);
+    assert_eq!(waitpid(pid, None), Err(Errno::ECHILD)); // Killed and reaped.
+
+    let mut process = common::launch("counter")?;
+    let pid = process.pid;
+    let main = common::symbol_address(&process, "main");
+    process.add_watchpoint(main, 1, WatchKind::Execute, "main".into())?;
+    process.resume()?;
+    process.set_kill_on_exit(false)?;
+    drop(process);
+    // Detached with its watchpoint disarmed, it runs to the end.
+    assert_eq!(waitpid(pid, None), Ok(WaitStatus::Exited(pid, 42)));
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Single-stepping and watchpoints.
+
+mod common;
+
+use sdb::annotate::Annotator;
+use sdb::error::Result;
+use sdb::output::Backtrace;
+use sdb::procfs::read_maps;
+use sdb::watchpoint::WatchKind;
+
+#[test]
+fn should_step_instructions() -> Result<()> {
+    let mut process = common::launch("counter")?;
+    let main = common::symbol_address(&process, "main");
+    process.add_watchpoint(main, 1, WatchKind::Execute, "main".into())?;
+    process.resume()?;
+
+    // `main` starts with `push rbp`.
+    let before = process.read_registers()?;
+    process.step_instruction()?;
+    let after = process.read_registers()?;
+    assert_eq!(after.rip, before.rip + 1);
+    assert_eq!(after.rsp, before.rsp - 8);
+    assert_eq!(process.read_words(after.rsp, 1)?, [before.rbp]);
+    Ok(())
+}
+
+#[test]
+fn should_report_changed_values() -> Result<()> {
+    let mut process = common::launch("counter")?;
+    let counter = common::symbol_address(&process, "counter");
+    let id = process.add_watchpoint(counter, 8, WatchKind::Write, "counter".into())?;
+    for value in 1..=3 {
+        process.resume()?;
+        let hit = process.last_watchpoint.unwrap();
+        assert_eq!((hit.id, hit.old, hit.new), (id, value - 1, value));
+    }
+
+    // The write is made by `tick`, called from `main`.
+    let maps = read_maps(process.pid)?;
+    let annotator = Annotator::new(&maps);
+    let backtrace = Backtrace::capture(&process, annotator, &process.read_registers()?, 8);
+    let functions: Vec<_> = backtrace
+        .frames
+        .iter()
+        .filter_map(|frame| Some(frame.location.symbol.as_ref()?.name.as_str()))
+        .take(2)
+        .collect();
+    assert_eq!(functions, ["tick", "main"]);
+
+    process.remove_watchpoint(id)?;
+    process.resume()?;
+    assert_eq!(
+        process.exit_status().and_then(|status| status.code),
+        Some(42)
+    );
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Stops for signals, threads and child processes.
+
+mod common;
+
+use nix::sys::signal::Signal;
+use sdb::catchpoint::{CatchAction, CatchpointKind, SyscallFilter};
+use sdb::error::Result;
+use sdb::output::StopEvent;
+use sdb::process::{LaunchSpec, Process};
+use sdb::syscall::SyscallDirection;
+
+#[test]
+fn should_stop_at_faults() -> Result<()> {
+    let mut process = common::launch("crash")?;
+    process.resume()?;
+    let StopEvent::Fault { info, pc, .. } = StopEvent::capture(&process, 0, 0) else {
+        panic!("no fault");
+    };
+    assert_eq!(info.signal(), Some(Signal::SIGSEGV));
+    assert_eq!(info.fault_addr, Some(0));
+    let function = pc.and_then(|pc| pc.symbol).map(|symbol| symbol.name);
+    assert_eq!(function.as_deref(), Some("main"));
+    Ok(())
+}
+
+#[test]
+fn should_describe_jit_code() -> Result<()> {
+    let image = common::jit_image("jitted", "jitted_crash");
+    let mut spec = LaunchSpec::new(&common::fixture("jit"));
+    spec.env.insert("JIT_IMAGE".into(), image.into());
+    let mut process = Process::launch_spec(&spec, true)?;
+    process.resume()?;
+    let StopEvent::Fault { pc, .. } = StopEvent::capture(&process, 0, 0) else {
+        panic!("no fault");
+    };
+    assert_eq!(process.jit_objects().len(), 1);
+    let place = pc.and_then(|pc| pc.place()).unwrap_or_default();
+    assert!(place.starts_with("jitted_crash+"), "{place}");
+    assert!(place.ends_with(" at jitted.c:4"), "{place}");
+    Ok(())
+}
+
+#[test]
+fn should_run_threads_to_exit() -> Result<()> {
+    let mut process = common::launch("threads")?;
+    process.resume()?;
+    eprintln!("{:?}", process.state);
+    assert!(process.exit_status().is_some_and(|status| status.success()));
+    Ok(())
+}
+
+#[test]
+fn should_catch_syscalls_around_fork() -> Result<()> {
+    let mut process = common::launch("fork_exec")?;
+    let filter = SyscallFilter {
+        syscalls: SyscallFilter::resolve_syscalls(&["wait4"])?,
+        include: None,
+        exclude: None,
+        on_entry: true,
+        on_exit: true,
+    };
+    process.add_catchpoint(CatchpointKind::Syscall(filter), CatchAction::Stop);
+
+    // The child's `SIGCHLD` may stop the parent in between.
+    let mut stops = Vec::new();
+    while process.is_alive() {
+        process.resume()?;
+        stops.extend(process.last_syscall.take());
+    }
+    assert!(process.exit_status().is_some_and(|status| status.success()));
+    // An interrupted `wait4` is restarted, with stops of its own.
+    assert!(stops.len() >= 2 && stops.len() % 2 == 0);
+    assert_eq!(stops[0].direction, SyscallDirection::Entry);
+    let last = stops.last().unwrap();
+    assert_eq!(last.direction, SyscallDirection::Exit);
+    // `wait4` returns the pid of the child.
+    assert!(last.ret.is_some_and(|pid| pid > 0));
+    Ok(())
+}